@@ -17,10 +17,11 @@ def run_command(cmd, cwd=None):
         return False, "", str(e)
 
 def compile_examples():
-    """编译示例程序"""
+    """编译示例程序和标准库"""
     print("Compiling example programs...")
     
     examples_dir = Path("examples")
+    stdlib_dir = Path("stdlib")
     compiler_path = Path("compiler/aquac.py")
     
     if not compiler_path.exists():
@@ -28,7 +29,7 @@ def compile_examples():
         return False
     
     success = True
-    for aqua_file in examples_dir.glob("*.aqua"):
+    for aqua_file in [*examples_dir.glob("*.aqua"), *stdlib_dir.glob("*.aqua")]:
         print(f"  Compiling {aqua_file.name}...")
         
         cmd = f"python {compiler_path} {aqua_file}"
@@ -131,7 +132,7 @@ def clean():
     print("Cleaning generated files...")
     
     # 删除.acode文件
-    for acode_file in Path("examples").glob("*.acode"):
+    for acode_file in [*Path("examples").glob("*.acode"), *Path("stdlib").glob("*.acode")]:
         acode_file.unlink()
         print(f"  Removed {acode_file}")
     
@@ -148,7 +149,7 @@ def main():
         print("Usage: python build.py <command>")
         print("Commands:")
         print("  test      - Run tests")
-        print("  compile   - Compile examples")
+        print("  compile   - Compile examples and stdlib")
         print("  package   - Create packages")
         print("  run       - Run examples")
         print("  all       - Do everything")
@@ -4,7 +4,7 @@ AquaScript字节码生成器
 """
 
 from typing import List, Dict, Any, Optional
-from dataclasses import dataclass
+from dataclasses import dataclass, field
 import struct
 import json
 import sys
@@ -27,6 +27,15 @@ class Function:
     parameters: List[str]
     instructions: List[Instruction]
     local_vars: Dict[str, int]
+    cell_vars: List[int] = field(default_factory=list)   # 被内层函数捕获的局部变量槽位
+    free_vars: List[str] = field(default_factory=list)   # 从外层函数捕获的变量，按闭包单元顺序排列
+    kwonly_count: int = 0                                # 仅限关键字参数个数
+    varargs: bool = False                                # 是否有 *args
+    varkw: bool = False                                  # 是否有 **kwargs
+    defaults: List[int] = field(default_factory=list)    # 位置参数默认值的常量索引，对应最后几个位置参数
+    kwdefaults: Dict[str, int] = field(default_factory=dict)  # 仅限关键字参数默认值的常量索引
+    generator: bool = False                              # 函数体中含有 yield，调用时返回生成器
+    coroutine: bool = False                              # async func，调用时返回协程
 
 class CodeGenerator:
     def __init__(self):
@@ -35,6 +44,7 @@ class CodeGenerator:
         self.global_vars = {}        # 全局变量表
         self.instructions = []       # 主程序指令
         self.current_function = None # 当前编译的函数
+        self.enclosing = []          # 外层函数，内层在后
         
         # 用于控制流的标签
         self.label_counter = 0
@@ -84,7 +94,7 @@ class CodeGenerator:
             self.compile_expression(stmt.value)
             if self.current_function:
                 # 在函数内部，首先检查是否是已存在的全局变量
-                if stmt.target in self.global_vars:
+                if stmt.target in self.global_vars and stmt.target not in self.current_function.local_vars:
                     # 如果是已存在的全局变量，使用STORE_GLOBAL
                     var_index = self.global_vars[stmt.target]
                     self.emit(OpCode.STORE_GLOBAL, var_index)
@@ -168,6 +178,9 @@ class CodeGenerator:
         
         elif isinstance(stmt, ThrowStatement):
             self.compile_throw_statement(stmt)
+        
+        elif isinstance(stmt, NonlocalStatement):
+            self.compile_nonlocal_statement(stmt)
     
     def compile_expression(self, expr: Expression):
         """编译表达式"""
@@ -201,7 +214,13 @@ class CodeGenerator:
                     self.compile_expression(expr_ast)
                     
                     # 将结果转换为字符串 - 使用FORMAT_VALUE指令
-                    self.emit(OpCode.FORMAT_VALUE)
+                    # 操作数低两位为转换标志（1: !s, 2: !r），高位为格式说明常量索引加一
+                    conversion = {None: 0, 's': 1, 'r': 2}[part.get('conversion')]
+                    format_spec = part.get('format_spec')
+                    spec_operand = 0
+                    if format_spec:
+                        spec_operand = self.add_constant(format_spec) + 1
+                    self.emit(OpCode.FORMAT_VALUE, (spec_operand << 2) | conversion)
             
             # 连接所有部分
             if len(expr.parts) > 1:
@@ -222,6 +241,10 @@ class CodeGenerator:
                 if expr.name in self.current_function.local_vars:
                     var_index = self.current_function.local_vars[expr.name]
                     self.emit(OpCode.LOAD_LOCAL, var_index)
+                elif self.resolve_free_var(expr.name) is not None:
+                    # 外层函数的变量，函数编译完成后改写为 LOAD_DEREF
+                    var_index = self.resolve_free_var(expr.name)
+                    self.emit(OpCode.LOAD_LOCAL, var_index)
                 else:
                     # 不是局部变量，尝试作为全局变量
                     var_index = self.get_or_create_global_var(expr.name)
@@ -231,6 +254,17 @@ class CodeGenerator:
                 var_index = self.get_or_create_global_var(expr.name)
                 self.emit(OpCode.LOAD_GLOBAL, var_index)
         
+        elif isinstance(expr, BinaryOperation) and expr.operator in ('and', 'or'):
+            # 短路求值：左侧已决定结果时保留左值并跳过右侧
+            self.compile_expression(expr.left)
+            if expr.operator == 'and':
+                self.emit(OpCode.JUMP_IF_FALSE_OR_POP, 0)  # 占位符，稍后回填
+            else:
+                self.emit(OpCode.JUMP_IF_TRUE_OR_POP, 0)  # 占位符，稍后回填
+            short_circuit_jump = len(self.get_current_instructions()) - 1
+            self.compile_expression(expr.right)
+            self.get_current_instructions()[short_circuit_jump].operand = len(self.get_current_instructions())
+        
         elif isinstance(expr, BinaryOperation):
             self.compile_expression(expr.left)
             self.compile_expression(expr.right)
@@ -275,8 +309,8 @@ class CodeGenerator:
                 raise ValueError(f"Unknown unary operator: {expr.operator}")
         
         elif isinstance(expr, FunctionCall):
-            # 检查是否是方法调用
-            if isinstance(expr.function, AttributeAccess):
+            # 检查是否是方法调用，带关键字参数的方法调用先取绑定方法再调用
+            if isinstance(expr.function, AttributeAccess) and not expr.keywords:
                 # 方法调用：obj.method(args)
                 # 编译对象表达式
                 self.compile_expression(expr.function.object)
@@ -299,40 +333,35 @@ class CodeGenerator:
                     if expr.function.name in self.current_function.local_vars:
                         var_index = self.current_function.local_vars[expr.function.name]
                         self.emit(OpCode.LOAD_LOCAL, var_index)
+                    elif self.resolve_free_var(expr.function.name) is not None:
+                        var_index = self.resolve_free_var(expr.function.name)
+                        self.emit(OpCode.LOAD_LOCAL, var_index)
                     elif expr.function.name in self.global_vars:
                         var_index = self.global_vars[expr.function.name]
                         self.emit(OpCode.LOAD_GLOBAL, var_index)
                     else:
-                        # 假设是函数名
-                        func_name_index = self.add_constant(expr.function.name)
-                        self.emit(OpCode.LOAD_CONST, func_name_index)
+                        # 内置函数和之后才定义的函数同样通过全局变量引用
+                        var_index = self.get_or_create_global_var(expr.function.name)
+                        self.emit(OpCode.LOAD_GLOBAL, var_index)
                 else:
                     # 在全局作用域
                     if expr.function.name in self.global_vars:
                         var_index = self.global_vars[expr.function.name]
                         self.emit(OpCode.LOAD_GLOBAL, var_index)
                     else:
-                        # 假设是函数名
-                        func_name_index = self.add_constant(expr.function.name)
-                        self.emit(OpCode.LOAD_CONST, func_name_index)
-                
-                # 然后编译参数
-                for arg in expr.arguments:
-                    self.compile_expression(arg)
+                        # 内置函数和之后才定义的函数同样通过全局变量引用
+                        var_index = self.get_or_create_global_var(expr.function.name)
+                        self.emit(OpCode.LOAD_GLOBAL, var_index)
                 
-                # 调用函数（可能是类构造函数）
-                self.emit(OpCode.CALL, len(expr.arguments))
+                # 然后编译参数并调用函数（可能是类构造函数）
+                self.compile_call_arguments(expr)
             
             else:
                 # 对于其他表达式，正常编译
                 self.compile_expression(expr.function)
                 
-                # 编译参数
-                for arg in expr.arguments:
-                    self.compile_expression(arg)
-                
-                # 调用函数
-                self.emit(OpCode.CALL, len(expr.arguments))
+                # 编译参数并调用函数
+                self.compile_call_arguments(expr)
         
         elif isinstance(expr, ListLiteral):
             # 编译列表中的每个元素
@@ -408,6 +437,33 @@ class CodeGenerator:
             current_instructions = self.get_current_instructions()
             current_instructions[for_iter_index].operand = len(current_instructions)
         
+        elif isinstance(expr, YieldExpression):
+            if not self.current_function:
+                raise SyntaxError("'yield' outside function")
+            if self.current_function.coroutine:
+                raise SyntaxError("'yield' inside async function is not supported")
+            self.current_function.generator = True
+            if expr.value:
+                self.compile_expression(expr.value)
+            else:
+                self.emit(OpCode.LOAD_CONST, self.add_constant(None))
+            if expr.delegate:
+                # 栈布局 [子迭代器, 发送的值]，首次转发 None
+                self.emit(OpCode.GET_ITER)
+                self.emit(OpCode.LOAD_CONST, self.add_constant(None))
+                self.emit(OpCode.YIELD_FROM)
+            else:
+                self.emit(OpCode.YIELD_VALUE)
+        
+        elif isinstance(expr, AwaitExpression):
+            if not self.current_function or not self.current_function.coroutine:
+                raise SyntaxError("'await' outside async function")
+            # 与 yield from 相同：首次转发 None，等待的对象完成时得到它的结果
+            self.compile_expression(expr.expression)
+            self.emit(OpCode.GET_AWAITABLE)
+            self.emit(OpCode.LOAD_CONST, self.add_constant(None))
+            self.emit(OpCode.YIELD_FROM)
+        
         elif isinstance(expr, AttributeAccess):
             # 编译对象表达式
             self.compile_expression(expr.object)
@@ -423,25 +479,71 @@ class CodeGenerator:
             # 获取列表项
             self.emit(OpCode.GET_ITEM)
     
+    def compile_call_arguments(self, call: FunctionCall):
+        """编译实参并发出调用指令；有关键字参数时使用 CALL_KW，参数名元组最后压栈"""
+        for arg in call.arguments:
+            self.compile_expression(arg)
+        if not call.keywords:
+            self.emit(OpCode.CALL, len(call.arguments))
+            return
+        
+        for _, value in call.keywords:
+            self.compile_expression(value)
+        names_index = self.add_constant([name for name, _ in call.keywords])
+        self.emit(OpCode.LOAD_CONST, names_index)
+        self.emit(OpCode.CALL_KW, len(call.arguments) + len(call.keywords))
+    
+    def new_function(self, name: str, func_def: FunctionDef) -> Function:
+        """按函数定义创建函数对象，参数依次占据前几个局部变量槽位"""
+        function = Function(
+            name=name,
+            parameters=func_def.parameters,
+            instructions=[],
+            local_vars={param: i for i, param in enumerate(func_def.parameters)},
+            kwonly_count=func_def.kwonly_count,
+            varargs=func_def.varargs,
+            varkw=func_def.varkw,
+            coroutine=func_def.is_async,
+        )
+        positional = len(func_def.parameters) - func_def.kwonly_count - func_def.varargs - func_def.varkw
+        for i, param in enumerate(func_def.parameters):
+            if param not in func_def.defaults:
+                continue
+            const_index = self.add_constant(self.constant_value(name, param, func_def.defaults[param]))
+            if i < positional:
+                function.defaults.append(const_index)
+            else:
+                function.kwdefaults[param] = const_index
+        return function
+    
+    def constant_value(self, func_name: str, param: str, expr: Expression) -> Any:
+        """默认值作为常量保存在函数中，因此只支持字面量（数字、字符串、布尔值和 None），
+        其他表达式报告编译错误"""
+        if isinstance(expr, (NumberLiteral, StringLiteral, BooleanLiteral)):
+            return expr.value
+        if isinstance(expr, NoneLiteral):
+            return None
+        if isinstance(expr, UnaryOperation) and expr.operator == '-' and isinstance(expr.operand, NumberLiteral):
+            return -expr.operand.value
+        raise SyntaxError(
+            f"default value of parameter '{param}' in {func_name}() must be a literal "
+            "(number, string, boolean or None)"
+        )
+    
     def compile_function_def(self, func_def: FunctionDef):
         """编译函数定义"""
         # 保存当前状态
         old_function = self.current_function
         
         # 创建新函数
-        function = Function(
-            name=func_def.name,
-            parameters=func_def.parameters,
-            instructions=[],
-            local_vars={}
-        )
+        function = self.new_function(func_def.name, func_def)
         
+        if old_function:
+            # 先登记外层的局部变量，内层函数才能递归引用自己
+            self.get_or_create_local_var(func_def.name)
+            self.enclosing.append(old_function)
         self.current_function = function
         
-        # 将参数添加到局部变量表中，确保它们有正确的索引
-        for i, param in enumerate(func_def.parameters):
-            function.local_vars[param] = i
-        
         # 编译函数体
         for stmt in func_def.body:
             self.compile_statement(stmt)
@@ -452,16 +554,26 @@ class CodeGenerator:
             self.emit(OpCode.LOAD_CONST, const_index)
             self.emit(OpCode.RETURN)
         
+        self.convert_captured_vars(function)
+        
         # 保存函数
         self.functions[func_def.name] = function
         
         # 恢复状态
         self.current_function = old_function
+        if old_function:
+            self.enclosing.pop()
         
         # 在主程序中加载函数
         func_index = self.add_constant(func_def.name)
         self.emit(OpCode.LOAD_FUNC, func_index)
         
+        # 捕获了外层变量的函数创建为闭包，单元按自由变量的顺序压栈
+        if function.free_vars:
+            for name in function.free_vars:
+                self.emit(OpCode.LOAD_CLOSURE, self.current_function.local_vars[name])
+            self.emit(OpCode.MAKE_CLOSURE, len(function.free_vars))
+        
         # 根据上下文存储函数
         if self.current_function:
             var_index = self.get_or_create_local_var(func_def.name)
@@ -499,9 +611,16 @@ class CodeGenerator:
     
     def compile_class_def(self, class_def):
         """编译类定义"""
+        # 先登记类名，方法体中才能按全局变量引用这个类（如 return Vec(...)）
+        if not self.current_function:
+            self.get_or_create_global_var(class_def.name)
+        
         # 创建类字典来存储方法名
         class_methods = {}
         
+        # 方法不是闭包，不捕获外层函数的变量
+        old_enclosing, self.enclosing = self.enclosing, []
+        
         # 编译类体中的方法
         for stmt in class_def.body:
             if isinstance(stmt, FunctionDef):
@@ -509,19 +628,9 @@ class CodeGenerator:
                 old_function = self.current_function
                 
                 # 创建方法函数对象
-                method_func = Function(
-                    name=f"{class_def.name}.{stmt.name}",
-                    parameters=stmt.parameters,
-                    instructions=[],
-                    local_vars={}
-                )
-                
+                method_func = self.new_function(f"{class_def.name}.{stmt.name}", stmt)
                 self.current_function = method_func
                 
-                # 将参数添加到局部变量表中
-                for i, param in enumerate(stmt.parameters):
-                    method_func.local_vars[param] = i
-                
                 # 编译方法体
                 for body_stmt in stmt.body:
                     self.compile_statement(body_stmt)
@@ -541,13 +650,19 @@ class CodeGenerator:
                 # 将方法名添加到类方法字典（而不是函数对象）
                 class_methods[stmt.name] = method_func.name
         
+        self.enclosing = old_enclosing
+        
+        # 基类在方法字典之前压栈
+        for base in class_def.bases:
+            self.compile_expression(base)
+        
         # 将方法字典推入栈
         methods_index = self.add_constant(class_methods)
         self.emit(OpCode.LOAD_CONST, methods_index)
         
-        # 创建类
+        # 创建类：基类个数编码到操作数的高16位，类名索引到低16位
         class_name_index = self.add_constant(class_def.name)
-        self.emit(OpCode.CREATE_CLASS, class_name_index)
+        self.emit(OpCode.CREATE_CLASS, (len(class_def.bases) << 16) | class_name_index)
         
         # 存储类到变量
         if self.current_function:
@@ -825,6 +940,47 @@ class CodeGenerator:
             self.current_function.local_vars[name] = len(self.current_function.local_vars)
         return self.current_function.local_vars[name]
     
+    def resolve_free_var(self, name: str) -> Optional[int]:
+        """在外层函数中查找变量，找到时登记为当前函数的自由变量并返回其局部槽位"""
+        for depth in range(len(self.enclosing) - 1, -1, -1):
+            if name in self.enclosing[depth].local_vars:
+                break
+        else:
+            return None
+        
+        owner = self.enclosing[depth]
+        slot = owner.local_vars[name]
+        if name not in owner.free_vars and slot not in owner.cell_vars:
+            owner.cell_vars.append(slot)
+        
+        # 中间各层函数也要捕获这个变量，才能把单元逐层传给内层函数
+        for function in self.enclosing[depth + 1:] + [self.current_function]:
+            if name not in function.local_vars:
+                function.local_vars[name] = len(function.local_vars)
+                function.free_vars.append(name)
+        return self.current_function.local_vars[name]
+    
+    def compile_nonlocal_statement(self, stmt: NonlocalStatement):
+        """编译nonlocal声明：之后对这些名字的赋值写入外层函数的变量"""
+        if not self.current_function:
+            raise SyntaxError("nonlocal declaration not allowed at module level")
+        for name in stmt.names:
+            if name in self.current_function.local_vars and name not in self.current_function.free_vars:
+                raise SyntaxError(f"name '{name}' is assigned to before nonlocal declaration")
+            if self.resolve_free_var(name) is None:
+                raise SyntaxError(f"no binding for nonlocal '{name}' found")
+    
+    def convert_captured_vars(self, function: Function):
+        """函数体编译完成后，把对被捕获变量的局部访问改写为经由单元的访问"""
+        captured = set(function.cell_vars) | {function.local_vars[name] for name in function.free_vars}
+        for inst in function.instructions:
+            if inst.operand not in captured:
+                continue
+            if inst.opcode == OpCode.LOAD_LOCAL:
+                inst.opcode = OpCode.LOAD_DEREF
+            elif inst.opcode == OpCode.STORE_LOCAL:
+                inst.opcode = OpCode.STORE_DEREF
+    
     def serialize_bytecode(self) -> bytes:
         """序列化字节码为二进制格式"""
         # 文件头
@@ -845,6 +1001,15 @@ class CodeGenerator:
             functions_data[name] = {
                 'parameters': func.parameters,
                 'local_vars': func.local_vars,
+                'cell_vars': sorted(func.cell_vars),
+                'free_vars': [func.local_vars[name] for name in func.free_vars],
+                'kwonly_count': func.kwonly_count,
+                'varargs': func.varargs,
+                'varkw': func.varkw,
+                'generator': func.generator,
+                'coroutine': func.coroutine,
+                'defaults': func.defaults,
+                'kwdefaults': func.kwdefaults,
                 'instructions': [(inst.opcode.value, inst.operand) for inst in func.instructions]
             }
         functions_bytes = json.dumps(functions_data).encode('utf-8')
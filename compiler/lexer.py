@@ -290,7 +290,9 @@ class Lexer:
                         expr += self.current_char()
                     self.advance()
                 
-                parts.append({'type': 'expression', 'value': expr.strip()})
+                expr, conversion, format_spec = self.split_f_expression(expr)
+                parts.append({'type': 'expression', 'value': expr.strip(),
+                              'conversion': conversion, 'format_spec': format_spec})
             else:
                 current_text += self.current_char()
                 self.advance()
@@ -307,6 +309,35 @@ class Lexer:
         value = json.dumps(parts)
         return Token(TokenType.F_STRING, value, self.line, start_col)
     
+    def split_f_expression(self, expr: str):
+        """拆分f-string占位符，如 "x!r:>10" 得到 ("x", "r", ">10")
+
+        只识别不在括号和字符串内的 '!' 与 ':'，'!=' 仍属于表达式本身。
+        """
+        depth = 0
+        quote = None
+        conversion = None
+        for i, char in enumerate(expr):
+            if quote:
+                if char == quote:
+                    quote = None
+            elif char in '"\'':
+                quote = char
+            elif char in '([{':
+                depth += 1
+            elif char in ')]}':
+                depth -= 1
+            elif depth == 0 and char == '!' and expr[i + 1:i + 2] != '=':
+                rest = expr[i + 1:]
+                conversion, _, format_spec = rest.partition(':')
+                conversion = conversion.strip()
+                if conversion not in ('s', 'r'):
+                    raise SyntaxError(f"f-string: invalid conversion character '{conversion}'")
+                return expr[:i], conversion, format_spec if ':' in rest else None
+            elif depth == 0 and char == ':':
+                return expr[:i], conversion, expr[i + 1:]
+        return expr, None, None
+    
     def read_identifier(self) -> Token:
         start_pos = self.position
         start_col = self.column
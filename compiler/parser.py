@@ -3,7 +3,7 @@ AquaScript语法分析器
 负责将token流转换为抽象语法树(AST)
 """
 
-from typing import List, Optional, Union, Any
+from typing import Dict, List, Optional, Union, Any
 from dataclasses import dataclass, field
 from lexer import Token, TokenType, Lexer
 
@@ -52,6 +52,7 @@ class UnaryOperation(Expression):
 class FunctionCall(Expression):
     function: Expression
     arguments: List[Expression]
+    keywords: List[tuple[str, Expression]] = field(default_factory=list)  # 关键字参数，按出现顺序排列
 
 @dataclass
 class ListLiteral(Expression):
@@ -121,13 +122,19 @@ class IndexAssignment(Statement):
 @dataclass
 class FunctionDef(Statement):
     name: str
-    parameters: List[str]
+    parameters: List[str]  # 依次为位置参数、*args、仅限关键字参数和 **kwargs
     body: List[Statement]
+    defaults: Dict[str, Expression] = field(default_factory=dict)  # 参数名 -> 默认值表达式
+    kwonly_count: int = 0
+    varargs: bool = False
+    varkw: bool = False
+    is_async: bool = False  # async func，调用时返回协程
 
 @dataclass
 class ClassDef(Statement):
     name: str
     body: List[Statement]
+    bases: List[Expression] = field(default_factory=list)
 
 @dataclass
 class ElifClause(ASTNode):
@@ -210,6 +217,7 @@ class LambdaExpression(Expression):
 @dataclass
 class YieldExpression(Expression):
     value: Optional[Expression] = None
+    delegate: bool = False  # yield from
 
 @dataclass
 class AwaitExpression(Expression):
@@ -221,12 +229,6 @@ class WithStatement(Statement):
     optional_vars: Optional[str]
     body: List[Statement]
 
-@dataclass
-class AsyncFunctionDef(Statement):
-    name: str
-    parameters: List[str]
-    body: List[Statement]
-
 @dataclass
 class GlobalStatement(Statement):
     names: List[str]
@@ -421,42 +423,8 @@ class Parser:
         name = self.consume(TokenType.IDENTIFIER).value
         
         self.consume(TokenType.LPAREN)
-        parameters = []
-        
-        if not self.match(TokenType.RPAREN):
-            # 解析参数（可能包含类型注解）
-            if self.match(TokenType.SELF):
-                param_name = self.current_token.value
-                self.advance()
-            else:
-                param_name = self.consume(TokenType.IDENTIFIER).value
-            
-            if self.match(TokenType.COLON):
-                self.advance()
-                # 跳过参数类型注解
-                if self.current_token.type in [TokenType.INT, TokenType.FLOAT, TokenType.STR, TokenType.BOOL]:
-                    self.advance()
-                else:
-                    self.consume(TokenType.IDENTIFIER)
-            parameters.append(param_name)
-            
-            while self.match(TokenType.COMMA):
-                self.advance()
-                if self.match(TokenType.SELF):
-                    param_name = self.current_token.value
-                    self.advance()
-                else:
-                    param_name = self.consume(TokenType.IDENTIFIER).value
-                
-                if self.match(TokenType.COLON):
-                    self.advance()
-                    # 跳过参数类型注解
-                    if self.current_token.type in [TokenType.INT, TokenType.FLOAT, TokenType.STR, TokenType.BOOL]:
-                        self.advance()
-                    else:
-                        self.consume(TokenType.IDENTIFIER)
-                parameters.append(param_name)
-        
+        func_def = FunctionDef(name, [], [])
+        self.parse_parameters(func_def)
         self.consume(TokenType.RPAREN)
         
         # 可选的返回类型注解
@@ -494,7 +462,60 @@ class Parser:
             if self.match(TokenType.DEDENT):
                 self.advance()
         
-        return FunctionDef(name, parameters, body)
+        func_def.body = body
+        return func_def
+    
+    def parse_parameters(self, func_def: FunctionDef):
+        """解析参数表: a, b: int = 1, *args, c, d = 2, **kwargs"""
+        star = False  # 遇到 * 之后的参数只能按关键字传入
+        while not self.match(TokenType.RPAREN):
+            if func_def.varkw:
+                raise SyntaxError("arguments cannot follow var-keyword argument")
+            if self.match(TokenType.POWER):
+                self.advance()
+                func_def.parameters.append(self.consume(TokenType.IDENTIFIER).value)
+                func_def.varkw = True
+            elif self.match(TokenType.MULTIPLY):
+                if star:
+                    raise SyntaxError("* argument may appear only once")
+                self.advance()
+                star = True
+                if self.match(TokenType.IDENTIFIER):
+                    func_def.parameters.append(self.current_token.value)
+                    func_def.varargs = True
+                    self.advance()
+            else:
+                if self.match(TokenType.SELF):
+                    param_name = self.current_token.value
+                    self.advance()
+                else:
+                    param_name = self.consume(TokenType.IDENTIFIER).value
+                
+                if self.match(TokenType.COLON):
+                    self.advance()
+                    # 跳过参数类型注解
+                    if self.current_token.type in [TokenType.INT, TokenType.FLOAT, TokenType.STR, TokenType.BOOL]:
+                        self.advance()
+                    else:
+                        self.consume(TokenType.IDENTIFIER)
+                
+                if self.match(TokenType.ASSIGN):
+                    self.advance()
+                    func_def.defaults[param_name] = self.parse_expression()
+                elif func_def.defaults and not star:
+                    raise SyntaxError("non-default argument follows default argument")
+                if param_name in func_def.parameters:
+                    raise SyntaxError(f"duplicate argument '{param_name}' in function definition")
+                func_def.parameters.append(param_name)
+                if star:
+                    func_def.kwonly_count += 1
+            
+            if not self.match(TokenType.COMMA):
+                break
+            self.advance()
+        
+        if star and not func_def.varargs and func_def.kwonly_count == 0:
+            raise SyntaxError("named arguments must follow bare *")
     
     def parse_var_declaration(self) -> VarDeclaration:
         """解析变量声明: var name: type = value"""
@@ -524,6 +545,17 @@ class Parser:
         """解析类定义"""
         self.consume(TokenType.CLASS)
         name = self.consume(TokenType.IDENTIFIER).value
+        
+        # 基类列表：class Child(Parent, Mixin):
+        bases = []
+        if self.match(TokenType.LPAREN):
+            self.advance()
+            while not self.match(TokenType.RPAREN):
+                bases.append(self.parse_expression())
+                if not self.match(TokenType.RPAREN):
+                    self.consume(TokenType.COMMA)
+            self.consume(TokenType.RPAREN)
+        
         self.consume(TokenType.COLON)
         self.skip_newlines()
         
@@ -542,7 +574,7 @@ class Parser:
         if self.match(TokenType.DEDENT):
             self.advance()
         
-        return ClassDef(name, body)
+        return ClassDef(name, body, bases)
     
     def parse_repeat_loop(self) -> RepeatLoop:
         """解析repeat循环（do-while风格）"""
@@ -982,15 +1014,27 @@ class Parser:
                 # 函数调用
                 self.advance()
                 arguments = []
+                keywords = []
                 
-                if not self.match(TokenType.RPAREN):
-                    arguments.append(self.parse_expression())
-                    while self.match(TokenType.COMMA):
+                while not self.match(TokenType.RPAREN):
+                    # 关键字参数: name=value
+                    if self.match(TokenType.IDENTIFIER) and self.peek() and self.peek().type == TokenType.ASSIGN:
+                        name = self.current_token.value
                         self.advance()
+                        self.advance()
+                        if any(keyword == name for keyword, _ in keywords):
+                            raise SyntaxError(f"keyword argument repeated: {name}")
+                        keywords.append((name, self.parse_expression()))
+                    elif keywords:
+                        raise SyntaxError("positional argument follows keyword argument")
+                    else:
                         arguments.append(self.parse_expression())
+                    if not self.match(TokenType.COMMA):
+                        break
+                    self.advance()
                 
                 self.consume(TokenType.RPAREN)
-                expr = FunctionCall(expr, arguments)
+                expr = FunctionCall(expr, arguments, keywords)
             
             # 处理属性访问
             elif self.match(TokenType.DOT):
@@ -1263,45 +1307,12 @@ class Parser:
         function = self.parse_function_def()
         return DecoratedFunction(decorators, function)
     
-    def parse_async_function_def(self) -> AsyncFunctionDef:
-        """解析异步函数定义"""
+    def parse_async_function_def(self) -> FunctionDef:
+        """解析异步函数定义：async func name(...)，参数和函数体与普通函数相同"""
         self.consume(TokenType.ASYNC)
-        self.consume(TokenType.FUNC)
-        name = self.consume(TokenType.IDENTIFIER).value
-        
-        self.consume(TokenType.LPAREN)
-        parameters = []
-        
-        if not self.match(TokenType.RPAREN):
-            parameters.append(self.consume(TokenType.IDENTIFIER).value)
-            while self.match(TokenType.COMMA):
-                self.advance()
-                parameters.append(self.consume(TokenType.IDENTIFIER).value)
-        
-        self.consume(TokenType.RPAREN)
-        
-        # 支持两种语法
-        if self.match(TokenType.LBRACE):
-            body = self.parse_block()
-        else:
-            self.consume(TokenType.COLON)
-            self.skip_newlines()
-            self.consume(TokenType.INDENT)
-            body = []
-            
-            while not self.match(TokenType.DEDENT, TokenType.EOF):
-                if self.match(TokenType.NEWLINE, TokenType.COMMENT):
-                    self.advance()
-                    continue
-                stmt = self.parse_statement()
-                if stmt:
-                    body.append(stmt)
-                self.skip_newlines()
-            
-            if self.match(TokenType.DEDENT):
-                self.advance()
-        
-        return AsyncFunctionDef(name, parameters, body)
+        func_def = self.parse_function_def()
+        func_def.is_async = True
+        return func_def
     
     def parse_with_statement(self) -> WithStatement:
         """解析with语句"""
@@ -1404,17 +1415,22 @@ class Parser:
         """解析yield表达式"""
         self.consume(TokenType.YIELD)
         
+        # yield from 委托给子迭代器
+        if self.match(TokenType.FROM):
+            self.advance()
+            return YieldExpression(self.parse_expression(), delegate=True)
+        
         # yield可以没有值
         value = None
-        if not self.match(TokenType.NEWLINE, TokenType.SEMICOLON, TokenType.RBRACE, TokenType.RPAREN, TokenType.RBRACKET, TokenType.COMMA):
+        if not self.match(TokenType.NEWLINE, TokenType.RBRACE, TokenType.RPAREN, TokenType.RBRACKET, TokenType.COMMA):
             value = self.parse_expression()
         
         return YieldExpression(value)
     
     def parse_await_expression(self) -> AwaitExpression:
-        """解析await表达式"""
+        """解析await表达式：与 Python 相同，操作数是后缀表达式，await 比二元运算结合得更紧"""
         self.consume(TokenType.AWAIT)
-        expression = self.parse_expression()
+        expression = self.parse_postfix_expression()
         return AwaitExpression(expression)
 
 def main():
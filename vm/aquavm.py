@@ -49,11 +49,22 @@ class OpCode(Enum):
     JUMP = 0x40            # 无条件跳转
     JUMP_IF_FALSE = 0x41   # 条件跳转（假）
     JUMP_IF_TRUE = 0x42    # 条件跳转（真）
+    JUMP_IF_FALSE_OR_POP = 0x43  # 短路求值：为假时保留栈顶并跳转，否则弹出
+    JUMP_IF_TRUE_OR_POP = 0x44   # 短路求值：为真时保留栈顶并跳转，否则弹出
     
     # 函数操作
     CALL = 0x50            # 函数调用
     RETURN = 0x51          # 返回
     LOAD_FUNC = 0x52       # 加载函数
+    MAKE_CLOSURE = 0x53    # 由函数和捕获的单元创建闭包
+    LOAD_CLOSURE = 0x54    # 将局部变量的单元压栈
+    LOAD_DEREF = 0x55      # 读取单元中的变量
+    STORE_DEREF = 0x56     # 写入单元中的变量
+    CALL_KW = 0x57         # 带关键字参数的函数调用
+    TAIL_CALL = 0x58       # 尾调用，复用当前调用帧
+    YIELD_VALUE = 0x59     # 生成器产出值并挂起
+    YIELD_FROM = 0x5A      # 委托给子迭代器（yield from）
+    GET_AWAITABLE = 0x5B   # 检查 await 的对象（协程或 Future）
     
     # 类型操作
     TYPE_CHECK = 0x60      # 类型检查
@@ -112,11 +123,84 @@ class Instruction:
         return f"Instruction(opcode={self.opcode}, operand={self.operand})"
 
 class AquaFunction:
-    def __init__(self, name: str, parameters: List[str], instructions: List[Instruction], local_vars: Dict[str, int]):
+    def __init__(self, name: str, parameters: List[str], instructions: List[Instruction], local_vars: Dict[str, int],
+                 cell_vars: Optional[List[int]] = None, free_vars: Optional[List[int]] = None,
+                 kwonly_count: int = 0, varargs: bool = False, varkw: bool = False,
+                 defaults: Optional[List[Any]] = None, kwdefaults: Optional[Dict[str, Any]] = None,
+                 generator: bool = False, coroutine: bool = False):
         self.name = name
-        self.parameters = parameters
+        self.parameters = parameters       # 依次为位置参数、*args、仅限关键字参数、**kwargs
         self.instructions = instructions
         self.local_vars = local_vars
+        self.cell_vars = cell_vars or []   # 被内层函数捕获的局部变量槽位
+        self.free_vars = free_vars or []   # 从外层函数捕获的变量槽位，按闭包单元顺序排列
+        self.kwonly_count = kwonly_count
+        self.varargs = varargs
+        self.varkw = varkw
+        self.defaults = defaults or []     # 最后几个位置参数的默认值
+        self.kwdefaults = kwdefaults or {} # 仅限关键字参数的默认值
+        self.generator = generator         # 调用时返回生成器
+        self.coroutine = coroutine         # async func，调用时返回协程
+    
+    def __repr__(self):
+        return f"<function {self.name}>"
+    
+    def bind(self, args: List[Any], kwargs: Dict[str, Any]) -> List[Any]:
+        """把实参绑定到参数，返回按参数顺序排列的值"""
+        positional = len(self.parameters) - self.kwonly_count - self.varargs - self.varkw
+        kwonly_start = positional + self.varargs
+        named = kwonly_start + self.kwonly_count
+        unbound = object()
+        bound = [unbound] * len(self.parameters)
+        
+        if len(args) > positional and not self.varargs:
+            raise TypeError(f"{self.name}() takes {positional} positional arguments but {len(args)} were given")
+        bound[:min(len(args), positional)] = args[:positional]
+        if self.varargs:
+            bound[positional] = tuple(args[positional:])
+        
+        extra = {}
+        for name, value in kwargs.items():
+            slots = [slot for slot in range(named) if not (self.varargs and slot == positional)]
+            slot = next((slot for slot in slots if self.parameters[slot] == name), None)
+            if slot is None:
+                if not self.varkw:
+                    raise TypeError(f"{self.name}() got an unexpected keyword argument '{name}'")
+                extra[name] = value
+            elif bound[slot] is not unbound:
+                raise TypeError(f"{self.name}() got multiple values for argument '{name}'")
+            else:
+                bound[slot] = value
+        if self.varkw:
+            bound[named] = extra
+        
+        first_default = positional - len(self.defaults)
+        for slot in range(positional):
+            if bound[slot] is unbound:
+                if slot < first_default:
+                    raise TypeError(f"{self.name}() missing 1 required positional argument: '{self.parameters[slot]}'")
+                bound[slot] = self.defaults[slot - first_default]
+        for slot in range(kwonly_start, named):
+            if bound[slot] is unbound:
+                name = self.parameters[slot]
+                if name not in self.kwdefaults:
+                    raise TypeError(f"{self.name}() missing 1 required keyword-only argument: '{name}'")
+                bound[slot] = self.kwdefaults[name]
+        return bound
+
+class Cell:
+    """被内层函数捕获的变量，外层函数和闭包共享同一个单元"""
+    def __init__(self, value: Any = None):
+        self.value = value
+
+class AquaClosure:
+    """函数与它捕获的单元"""
+    def __init__(self, function: AquaFunction, cells: List[Cell]):
+        self.function = function
+        self.cells = cells
+    
+    def __repr__(self):
+        return f"<function {self.function.name}>"
 
 class CallFrame:
     def __init__(self, function: AquaFunction, return_address: int):
@@ -124,6 +208,80 @@ class CallFrame:
         self.return_address = return_address
         self.pc = 0  # 程序计数器
         self.locals = [None] * len(function.local_vars)  # 局部变量
+        self.generator = None  # 生成器和协程的帧指向所属的生成器或协程
+
+class AquaGenerator:
+    """生成器：调用生成器函数时创建，挂起时保存调用帧和它在操作数栈上的值"""
+    def __init__(self, vm: 'AquaVM', frame: CallFrame):
+        self.vm = vm
+        self.frame = frame
+        self.saved_stack = []    # 挂起时帧在操作数栈上的值
+        self.stack_base = 0      # 运行时帧的值在操作数栈上的起始位置
+        self.started = False
+        self.running = False
+        self.suspended = False   # 最近一次运行以产出值结束
+        self.finished = False
+    
+    def __repr__(self):
+        return f"<generator object {self.frame.function.name}>"
+    
+    def __iter__(self):
+        return self
+    
+    def __next__(self):
+        return self.send(None)
+    
+    def send(self, value: Any) -> Any:
+        """恢复生成器，value 作为挂起处 yield 表达式的值；生成器返回时抛出 StopIteration"""
+        if self.running:
+            raise ValueError("generator already executing")
+        if self.finished:
+            raise StopIteration
+        if not self.started and value is not None:
+            raise TypeError("can't send non-None value to a just-started generator")
+        return self.vm.resume(self, value)
+    
+    def throw(self, typ, val=None, tb=None):
+        """在挂起处抛出异常：正在 yield from 时先交给子迭代器处理，
+        否则生成器结束并把异常抛给调用者（函数体内的 try 只捕获 AquaScript 异常）"""
+        exception = typ if isinstance(typ, BaseException) else typ(*([] if val is None else [val]))
+        if self.running:
+            raise ValueError("generator already executing")
+        if self.finished or not self.started:
+            self.close()
+            raise exception
+        
+        frame = self.frame
+        delegating = frame.function.instructions[frame.pc].opcode == OpCode.YIELD_FROM
+        iterator = self.saved_stack[-1] if delegating else None
+        if not hasattr(iterator, 'throw'):
+            self.close()
+            raise exception
+        try:
+            return iterator.throw(exception)
+        except StopIteration as e:
+            # 子迭代器处理了异常并结束，从 yield from 之后继续执行
+            self.saved_stack.pop()
+            frame.pc += 1
+            return self.vm.resume(self, e.value)
+        except BaseException:
+            self.close()
+            raise
+    
+    def close(self):
+        """结束生成器，丢弃挂起的帧"""
+        if self.running:
+            raise ValueError("generator already executing")
+        self.finished = True
+        self.saved_stack = []
+
+class AquaCoroutine(AquaGenerator):
+    """协程：以生成器的方式执行 async func，实现 Python 的协程协议，可以交给 asyncio 调度"""
+    def __repr__(self):
+        return f"<coroutine object {self.frame.function.name}>"
+    
+    def __await__(self):
+        return self
 
 class AquaException(Exception):
     """AquaScript异常基类"""
@@ -198,6 +356,10 @@ class AquaVM:
         global_vars_map = json.loads(globals_data.decode('utf-8'))
         self.globals = [None] * len(global_vars_map)
         self.global_vars = global_vars_map
+        # 内置函数通过全局变量引用，与内置函数同名的全局变量绑定到内置函数本身
+        for name, index in global_vars_map.items():
+            if name in self.builtins:
+                self.globals[index] = self.builtins[name]
         offset += globals_size
         
         # 读取函数表
@@ -212,7 +374,17 @@ class AquaVM:
                 name=name,
                 parameters=func_data['parameters'],
                 instructions=instructions,
-                local_vars=func_data['local_vars']
+                local_vars=func_data['local_vars'],
+                cell_vars=func_data.get('cell_vars'),
+                free_vars=func_data.get('free_vars'),
+                kwonly_count=func_data.get('kwonly_count', 0),
+                varargs=func_data.get('varargs', False),
+                varkw=func_data.get('varkw', False),
+                # 默认值以常量索引保存
+                defaults=[self.constants[index] for index in func_data.get('defaults', [])],
+                kwdefaults={name: self.constants[index] for name, index in func_data.get('kwdefaults', {}).items()},
+                generator=func_data.get('generator', False),
+                coroutine=func_data.get('coroutine', False)
             )
         offset += functions_size
         
@@ -412,6 +584,16 @@ class AquaVM:
                 else:
                     self.pc = operand
         
+        elif opcode in (OpCode.JUMP_IF_FALSE_OR_POP, OpCode.JUMP_IF_TRUE_OR_POP):
+            condition = bool(self.stack[-1])
+            if condition == (opcode == OpCode.JUMP_IF_TRUE_OR_POP):
+                if self.call_stack:
+                    self.call_stack[-1].pc = operand
+                else:
+                    self.pc = operand
+            else:
+                self.stack.pop()
+        
         elif opcode == OpCode.CALL:
             argc = operand
             args = []
@@ -419,18 +601,17 @@ class AquaVM:
                 args.insert(0, self.stack.pop())
             
             func = self.stack.pop()
-            
-            if isinstance(func, str):
-                # 内置函数或用户定义函数
-                if func in self.builtins:
-                    result = self.builtins[func](*args)
-                    self.stack.append(result)
-                elif func in self.functions:
-                    self.call_function(self.functions[func], args)
-                else:
-                    raise NameError(f"Function '{func}' not found")
-            else:
-                raise TypeError(f"'{type(func).__name__}' object is not callable")
+            self.call_value(func, args, {})
+        
+        elif opcode == OpCode.CALL_KW:
+            # 栈布局 [函数, 位置参数..., 关键字参数值..., 参数名列表]，operand为实参总数
+            names = self.stack.pop()
+            values = self.stack[len(self.stack) - operand:]
+            del self.stack[len(self.stack) - operand:]
+            positional = len(values) - len(names)
+            kwargs = dict(zip(names, values[positional:]))
+            func = self.stack.pop()
+            self.call_value(func, values[:positional], kwargs)
         
         elif opcode == OpCode.RETURN:
             return_value = self.stack.pop()
@@ -440,8 +621,51 @@ class AquaVM:
             self.stack.append(return_value)
         
         elif opcode == OpCode.LOAD_FUNC:
+            # 函数是一等值：压入函数对象，可以保存到变量、作为参数传递
             func_name = self.constants[operand]
-            self.stack.append(func_name)
+            self.stack.append(self.functions[func_name])
+        
+        elif opcode == OpCode.MAKE_CLOSURE:
+            # 栈布局 [函数, 单元1, ..., 单元n]
+            cells = self.stack[len(self.stack) - operand:]
+            del self.stack[len(self.stack) - operand:]
+            func = self.stack.pop()
+            self.stack.append(AquaClosure(func, cells))
+        
+        elif opcode == OpCode.LOAD_CLOSURE:
+            # 压入局部变量的单元本身，供 MAKE_CLOSURE 使用
+            self.stack.append(self.call_stack[-1].locals[operand])
+        
+        elif opcode == OpCode.LOAD_DEREF:
+            self.stack.append(self.call_stack[-1].locals[operand].value)
+        
+        elif opcode == OpCode.STORE_DEREF:
+            self.call_stack[-1].locals[operand].value = self.stack.pop()
+        
+        elif opcode == OpCode.YIELD_VALUE:
+            self.suspend(self.stack.pop())
+        
+        elif opcode == OpCode.YIELD_FROM:
+            # 栈布局 [子迭代器, 发送的值]；子迭代器产出值时挂起，恢复后重新执行本指令转发发送的值
+            sent = self.stack.pop()
+            iterator = self.stack[-1]
+            try:
+                value = next(iterator) if sent is None else iterator.send(sent)
+            except StopIteration as e:
+                # 子迭代器结束，它的返回值作为 yield from 表达式的值
+                self.stack.pop()
+                self.stack.append(e.value)
+            else:
+                self.call_stack[-1].pc -= 1
+                self.suspend(value)
+        
+        elif opcode == OpCode.GET_AWAITABLE:
+            # 取得 await 的对象的迭代器：AquaScript 协程就是它自己，
+            # asyncio 的协程和 Future 等 Python 对象取 __await__() 的结果
+            awaitable = self.stack.pop()
+            if not hasattr(awaitable, '__await__'):
+                raise TypeError(f"object {type(awaitable).__name__} can't be used in 'await' expression")
+            self.stack.append(awaitable.__await__())
         
         elif opcode == OpCode.POP:
             self.stack.pop()
@@ -549,9 +773,19 @@ class AquaVM:
             self.stack.append(tuple(elements))
         
         elif opcode == OpCode.FORMAT_VALUE:
-            # 格式化值为字符串
+            # 格式化值为字符串，operand低两位为转换标志，高位为格式说明常量索引加一
             value = self.stack.pop()
-            formatted = str(value)
+            operand = operand or 0
+            conversion = operand & 0b11
+            if conversion == 1:
+                value = str(value)
+            elif conversion == 2:
+                value = repr(value)
+            spec_index = operand >> 2
+            if spec_index:
+                formatted = format(value, self.constants[spec_index - 1])
+            else:
+                formatted = str(value)
             self.stack.append(formatted)
         
         elif opcode == OpCode.GET_ITEM:
@@ -641,6 +875,16 @@ class AquaVM:
             self.stack.append(a)
             self.stack.append(b)
         
+        elif opcode == OpCode.CALL_METHOD:
+            # 操作数高16位为参数个数，低16位为方法名的常量索引；
+            # 调用宿主对象的方法，如生成器的 send() 和 close()
+            argc = operand >> 16
+            method_name = self.constants[operand & 0xFFFF]
+            args = self.stack[len(self.stack) - argc:]
+            del self.stack[len(self.stack) - argc:]
+            obj = self.stack.pop()
+            self.call_value(getattr(obj, method_name), args, {})
+        
         elif opcode == OpCode.IMPORT_MODULE:
             # 导入模块
             if not self.stack:
@@ -674,20 +918,93 @@ class AquaVM:
         else:
             raise ValueError(f"Unknown opcode: {opcode}")
     
-    def call_function(self, function: AquaFunction, args: List[Any]):
-        """调用函数"""
-        if len(args) != len(function.parameters):
-            raise TypeError(f"Function '{function.name}' takes {len(function.parameters)} arguments but {len(args)} were given")
+    def call_value(self, func: Any, args: List[Any], kwargs: Dict[str, Any]):
+        """调用函数值：用户函数进入新的调用帧，其余调用的结果直接压栈"""
+        if isinstance(func, AquaFunction):
+            self.call_function(func, args, kwargs=kwargs)
+        elif isinstance(func, AquaClosure):
+            self.call_function(func.function, args, func.cells, kwargs)
+        elif isinstance(func, str):
+            # 旧版编译器以函数名字符串调用内置函数或用户定义函数
+            if func in self.builtins:
+                self.stack.append(self.builtins[func](*args, **kwargs))
+            elif func in self.functions:
+                self.call_function(self.functions[func], args, kwargs=kwargs)
+            else:
+                raise NameError(f"Function '{func}' not found")
+        elif callable(func):
+            # 内置函数，以及从 Python 模块导入的函数
+            self.stack.append(func(*args, **kwargs))
+        else:
+            raise TypeError(f"'{type(func).__name__}' object is not callable")
+    
+    def call_function(self, function: AquaFunction, args: List[Any], cells: List[Cell] = (),
+                      kwargs: Optional[Dict[str, Any]] = None):
+        """调用函数，cells 为闭包捕获的单元"""
+        values = function.bind(args, kwargs or {})
         
         # 创建调用帧
         return_address = self.pc
         frame = CallFrame(function, return_address)
         
         # 设置参数
-        for i, arg in enumerate(args):
-            frame.locals[i] = arg
+        for i, value in enumerate(values):
+            frame.locals[i] = value
+        
+        # 被捕获的局部变量放入新单元，自由变量使用闭包的单元
+        for slot in function.cell_vars:
+            frame.locals[slot] = Cell(frame.locals[slot])
+        for slot, cell in zip(function.free_vars, cells):
+            frame.locals[slot] = cell
         
+        # 生成器函数和 async func 不立即执行，返回挂起在第一条指令之前的生成器或协程
+        if function.generator or function.coroutine:
+            frame.generator = (AquaCoroutine if function.coroutine else AquaGenerator)(self, frame)
+            self.stack.append(frame.generator)
+            return
+        
+        self.call_stack.append(frame)
+    
+    def resume(self, generator: AquaGenerator, value: Any) -> Any:
+        """在嵌套的执行循环中运行生成器的帧，直到它产出值（返回该值）或返回（抛出 StopIteration）"""
+        frame = generator.frame
+        frame.return_address = self.pc
+        base = len(self.call_stack)
+        generator.stack_base = len(self.stack)
+        self.stack.extend(generator.saved_stack)
+        generator.saved_stack = []
+        if generator.started:
+            self.stack.append(value)
+        generator.started = True
+        generator.running = True
+        generator.suspended = False
         self.call_stack.append(frame)
+        try:
+            while len(self.call_stack) > base:
+                self.execute_instruction()
+        except BaseException:
+            # 异常离开生成器时丢弃它的帧和栈上的值，生成器随之结束
+            del self.call_stack[base:]
+            del self.stack[generator.stack_base:]
+            generator.finished = True
+            raise
+        finally:
+            generator.running = False
+        
+        result = self.stack.pop()
+        if generator.suspended:
+            return result
+        generator.finished = True
+        raise StopIteration(result)
+    
+    def suspend(self, value: Any):
+        """挂起当前生成器的帧并保存它在操作数栈上的值，产出的值留在栈顶交给 resume"""
+        frame = self.call_stack.pop()
+        generator = frame.generator
+        generator.saved_stack = self.stack[generator.stack_base:]
+        del self.stack[generator.stack_base:]
+        generator.suspended = True
+        self.stack.append(value)
     
     def print_stack_trace(self):
         """打印调用栈"""
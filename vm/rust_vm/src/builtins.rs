@@ -0,0 +1,324 @@
+/*!
+内置函数
+
+与 `vm/aquavm.py` 中 `_builtin_*` 系列函数的行为保持一致。
+内置函数与用户函数一样是函数值（[`Value::Builtin`]），在虚拟机函数表中的编号即 [`BuiltinFunction::id`]。
+`asyncio` 模块的函数（[`BuiltinFunction::ASYNCIO`]）也在这里登记，但不作为全局名字绑定。
+*/
+
+use crate::iter::Range;
+use crate::object::Class;
+use crate::value::Value;
+use crate::{Result, VMError};
+use std::rc::Rc;
+
+/// 内置函数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinFunction {
+    Print,
+    Str,
+    Repr,
+    Int,
+    Float,
+    Len,
+    Range,
+    Iter,
+    Next,
+    Hash,
+    IsInstance,
+    IsSubclass,
+    /// 需要读取调用帧，由虚拟机直接处理
+    Super,
+    /// `asyncio` 模块的函数，需要正在运行的事件循环，由虚拟机直接处理
+    Sleep,
+    CreateTask,
+    Gather,
+    WaitFor,
+    Run,
+}
+
+impl BuiltinFunction {
+    /// 全部内置函数，顺序与编号一致
+    pub const ALL: [BuiltinFunction; 18] = [
+        BuiltinFunction::Print,
+        BuiltinFunction::Str,
+        BuiltinFunction::Repr,
+        BuiltinFunction::Int,
+        BuiltinFunction::Float,
+        BuiltinFunction::Len,
+        BuiltinFunction::Range,
+        BuiltinFunction::Iter,
+        BuiltinFunction::Next,
+        BuiltinFunction::Hash,
+        BuiltinFunction::IsInstance,
+        BuiltinFunction::IsSubclass,
+        BuiltinFunction::Super,
+        BuiltinFunction::Sleep,
+        BuiltinFunction::CreateTask,
+        BuiltinFunction::Gather,
+        BuiltinFunction::WaitFor,
+        BuiltinFunction::Run,
+    ];
+
+    /// `asyncio` 模块的函数
+    pub const ASYNCIO: [BuiltinFunction; 5] = [
+        BuiltinFunction::Sleep,
+        BuiltinFunction::CreateTask,
+        BuiltinFunction::Gather,
+        BuiltinFunction::WaitFor,
+        BuiltinFunction::Run,
+    ];
+
+    /// 按名字查找全局内置函数（不包括 `asyncio` 模块的函数）
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .filter(|builtin| !Self::ASYNCIO.contains(builtin))
+            .find(|builtin| builtin.name() == name)
+    }
+
+    /// 在虚拟机函数表中的编号
+    pub fn id(self) -> usize {
+        self as usize
+    }
+
+    /// 函数名
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuiltinFunction::Print => "print",
+            BuiltinFunction::Str => "str",
+            BuiltinFunction::Repr => "repr",
+            BuiltinFunction::Int => "int",
+            BuiltinFunction::Float => "float",
+            BuiltinFunction::Len => "len",
+            BuiltinFunction::Range => "range",
+            BuiltinFunction::Iter => "iter",
+            BuiltinFunction::Next => "next",
+            BuiltinFunction::Hash => "hash",
+            BuiltinFunction::IsInstance => "isinstance",
+            BuiltinFunction::IsSubclass => "issubclass",
+            BuiltinFunction::Super => "super",
+            BuiltinFunction::Sleep => "sleep",
+            BuiltinFunction::CreateTask => "create_task",
+            BuiltinFunction::Gather => "gather",
+            BuiltinFunction::WaitFor => "wait_for",
+            BuiltinFunction::Run => "run",
+        }
+    }
+
+    /// 调用内置函数
+    pub fn call(&self, args: &[Value]) -> Result<Value> {
+        match self {
+            BuiltinFunction::Print => {
+                let output: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                println!("{}", output.join(" "));
+                Ok(Value::Null)
+            }
+
+            BuiltinFunction::Str => {
+                let arg = self.single_arg(args)?;
+                Ok(Value::String(arg.to_string().into()))
+            }
+
+            BuiltinFunction::Repr => {
+                let arg = self.single_arg(args)?;
+                Ok(Value::String(arg.repr().into()))
+            }
+
+            BuiltinFunction::Int => match self.single_arg(args)? {
+                Value::Int(i) => Ok(Value::Int(*i)),
+                Value::Bool(b) => Ok(Value::Int(*b as i64)),
+                Value::Float(f) if f.is_finite() => Ok(Value::Int(f.trunc() as i64)),
+                Value::Float(f) => Err(VMError::RuntimeError(format!(
+                    "cannot convert float {} to integer",
+                    Value::Float(*f)
+                ))),
+                Value::String(s) => s
+                    .trim()
+                    .replace('_', "")
+                    .parse::<i64>()
+                    .map(Value::Int)
+                    .map_err(|_| {
+                        VMError::RuntimeError(format!(
+                            "invalid literal for int() with base 10: '{}'",
+                            s
+                        ))
+                    }),
+                other => Err(VMError::TypeError(format!(
+                    "int() argument must be a string or a number, not '{}'",
+                    other.type_name()
+                ))),
+            },
+
+            BuiltinFunction::Float => match self.single_arg(args)? {
+                Value::Int(i) => Ok(Value::Float(*i as f64)),
+                Value::Bool(b) => Ok(Value::Float(*b as i64 as f64)),
+                Value::Float(f) => Ok(Value::Float(*f)),
+                Value::String(s) => parse_float(s).map(Value::Float).ok_or_else(|| {
+                    VMError::RuntimeError(format!("could not convert string to float: '{}'", s))
+                }),
+                other => Err(VMError::TypeError(format!(
+                    "float() argument must be a string or a number, not '{}'",
+                    other.type_name()
+                ))),
+            },
+
+            BuiltinFunction::Len => {
+                let arg = self.single_arg(args)?;
+                Ok(Value::Int(arg.length()? as i64))
+            }
+
+            BuiltinFunction::Range => {
+                let bounds = args
+                    .iter()
+                    .map(|arg| match arg {
+                        Value::Int(i) => Ok(*i),
+                        Value::Bool(b) => Ok(*b as i64),
+                        other => Err(VMError::TypeError(format!(
+                            "'{}' object cannot be interpreted as an integer",
+                            other.type_name()
+                        ))),
+                    })
+                    .collect::<Result<Vec<i64>>>()?;
+                let range = match bounds[..] {
+                    [stop] => Range::new(0, stop, 1)?,
+                    [start, stop] => Range::new(start, stop, 1)?,
+                    [start, stop, step] => Range::new(start, stop, step)?,
+                    [] => {
+                        return Err(VMError::TypeError(
+                            "range expected at least 1 argument, got 0".to_string(),
+                        ))
+                    }
+                    _ => {
+                        return Err(VMError::TypeError(format!(
+                            "range expected at most 3 arguments, got {}",
+                            args.len()
+                        )))
+                    }
+                };
+                Ok(Value::Range(Rc::new(range)))
+            }
+
+            BuiltinFunction::Iter => self.single_arg(args)?.iter(),
+
+            BuiltinFunction::Next => {
+                let (iterator, default) = match args {
+                    [iterator] => (iterator, None),
+                    [iterator, default] => (iterator, Some(default)),
+                    _ => {
+                        return Err(VMError::TypeError(format!(
+                            "next expected 1 or 2 arguments, got {}",
+                            args.len()
+                        )))
+                    }
+                };
+                let Value::Iterator(iter) = iterator else {
+                    return Err(VMError::TypeError(format!(
+                        "'{}' object is not an iterator",
+                        iterator.type_name()
+                    )));
+                };
+                let next = iter.borrow_mut().next_item()?;
+                match (next, default) {
+                    (Some(value), _) => Ok(value),
+                    (None, Some(default)) => Ok(default.clone()),
+                    (None, None) => Err(VMError::StopIteration),
+                }
+            }
+
+            BuiltinFunction::Hash => {
+                let hash = self.single_arg(args)?.hash_key()?;
+                Ok(Value::Int(hash as i64))
+            }
+
+            BuiltinFunction::IsInstance => {
+                let [object, classinfo] = self.two_args(args)?;
+                // 内置类型的值不是任何用户类的实例，但仍要检查第二个参数
+                let class = match object {
+                    Value::Instance(instance) => Some(&instance.class),
+                    _ => None,
+                };
+                Ok(Value::Bool(is_subclass(self, class, classinfo)?))
+            }
+
+            BuiltinFunction::IsSubclass => {
+                let [class, classinfo] = self.two_args(args)?;
+                let Value::Class(class) = class else {
+                    return Err(VMError::TypeError(
+                        "issubclass() arg 1 must be a class".to_string(),
+                    ));
+                };
+                Ok(Value::Bool(is_subclass(self, Some(class), classinfo)?))
+            }
+
+            // 不在方法中调用时没有可用的类和实例
+            BuiltinFunction::Super => {
+                Err(VMError::RuntimeError("super(): no arguments".to_string()))
+            }
+
+            // 事件循环由虚拟机持有
+            BuiltinFunction::Sleep
+            | BuiltinFunction::CreateTask
+            | BuiltinFunction::Gather
+            | BuiltinFunction::WaitFor
+            | BuiltinFunction::Run => {
+                Err(VMError::RuntimeError("no running event loop".to_string()))
+            }
+        }
+    }
+
+    fn two_args<'a>(&self, args: &'a [Value]) -> Result<&'a [Value; 2]> {
+        args.try_into().map_err(|_| {
+            VMError::TypeError(format!(
+                "{} expected 2 arguments, got {}",
+                self.name(),
+                args.len()
+            ))
+        })
+    }
+
+    fn single_arg<'a>(&self, args: &'a [Value]) -> Result<&'a Value> {
+        match args {
+            [arg] => Ok(arg),
+            _ => Err(VMError::TypeError(format!(
+                "{}() takes exactly one argument ({} given)",
+                self.name(),
+                args.len()
+            ))),
+        }
+    }
+}
+
+/// `class` 是否为 `classinfo`（类或由类组成的元组，可以嵌套）的子类
+fn is_subclass(
+    builtin: &BuiltinFunction,
+    class: Option<&Rc<Class>>,
+    classinfo: &Value,
+) -> Result<bool> {
+    match classinfo {
+        Value::Class(other) => Ok(class.is_some_and(|class| class.is_subclass(other))),
+        Value::Tuple(items) => {
+            let mut found = false;
+            for item in items.iter() {
+                found |= is_subclass(builtin, class, item)?;
+            }
+            Ok(found)
+        }
+        _ => {
+            let kind = match builtin {
+                BuiltinFunction::IsInstance => "a type, a tuple of types",
+                _ => "a class, a tuple of classes",
+            };
+            Err(VMError::TypeError(format!(
+                "{}() arg 2 must be {}, or a union",
+                builtin.name(),
+                kind
+            )))
+        }
+    }
+}
+
+/// 解析浮点数字面量（Rust 的解析同样接受 `inf` / `nan`）
+fn parse_float(s: &str) -> Option<f64> {
+    s.trim().replace('_', "").parse().ok()
+}
@@ -0,0 +1,765 @@
+/*!
+文本汇编（`.aasm`）
+
+不经过 Python 编译器直接编写字节码，用于虚拟机回归测试和手工优化热点函数。
+
+```text
+; 注释以 ';' 开头，整行注释也可以用 '#'
+.const greeting "hello"      ; 具名常量
+.const (1, 2)                ; 不带名字的常量只加入常量池
+.global total, count         ; 声明全局变量槽位，名字之间的逗号可省略
+
+.func add(a, b)              ; 函数，参数依次占据局部变量槽位
+.local tmp                   ; 额外的局部变量
+    LOAD_LOCAL a
+    LOAD_LOCAL b
+    ADD
+    RETURN
+.end
+
+    LOAD_FUNC add
+    LOAD_CONST 1             ; 字面量常量会自动加入常量池
+    LOAD_CONST 2
+    CALL 2
+    STORE_GLOBAL total
+loop:
+    JUMP loop
+```
+
+操作数规则：
+- 常量类指令（`LOAD_CONST`、`GET_ATTR`、`HAS_ATTR`、`CREATE_CLASS`）接受字面量、
+  `.const` 定义的名字或 `#索引`
+- `LOAD_CONST` 和 `.const` 还接受元组字面量，如 `IMPORT_FROM` 的导入名 `("area", "PI")`
+- `LOAD_FUNC` 接受函数名
+- 变量类指令（包括 `LOAD_DEREF`、`STORE_DEREF`、`LOAD_CLOSURE`）接受变量名或槽位编号
+- 跳转类指令接受当前代码段内的标签名或指令编号
+- `CALL_METHOD` 写作 `CALL_METHOD 方法名 参数个数`
+- `CREATE_CLASS` 写作 `CREATE_CLASS 类名 [基类个数]`，基类在方法表之前压栈
+- `CATCH_BEGIN` 写作 `CATCH_BEGIN [异常类型名]`，省略类型名时捕获所有异常
+- `FORMAT_VALUE` 写作 `FORMAT_VALUE [!s|!r] ["格式说明"]`，两部分都可省略
+- `.func` 的参数表与 Python 相同：`f(a, b=1, *args, c, d=2, **kwargs)`，
+  默认值为字面量，单独的 `*` 之后为仅限关键字参数
+- `CALL_KW` 之前用 `LOAD_CONST ("x", "y")` 压入关键字参数名
+- 函数体中含有 `YIELD_VALUE` 或 `YIELD_FROM` 的函数是生成器函数
+- `.async` 与 `.func` 写法相同，定义协程函数（`async func`）
+- 闭包：外层函数用 `.cell 变量名` 声明被内层函数捕获的变量（可以是参数），
+  内层函数用 `.free 变量名` 按 `MAKE_CLOSURE` 收到单元的顺序声明自由变量
+- 其余指令接受整数
+*/
+
+use super::{
+    format_operand, Bytecode, ExceptionTable, Instruction, OpCode, CATCH_ALL, FORMAT_REPR,
+    FORMAT_STR,
+};
+use crate::function::Function;
+use crate::value::Value;
+use crate::{Result, VMError};
+use std::collections::HashMap;
+
+/// 汇编源码
+pub fn assemble(source: &str) -> Result<Bytecode> {
+    let mut assembler = Assembler::default();
+    for (index, line) in source.lines().enumerate() {
+        assembler.line = index + 1;
+        let tokens = tokenize(line).map_err(|message| assembler.error(message))?;
+        if !tokens.is_empty() {
+            assembler.statement(&tokens)?;
+        }
+    }
+    assembler.finish()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bytes(Vec<u8>),
+    Hash,
+    Bang,
+    Star,
+    Equals,
+    Colon,
+    LParen,
+    RParen,
+    Comma,
+}
+
+/// 把一行源码切分为记号，注释在此处被丢弃
+fn tokenize(line: &str) -> std::result::Result<Vec<Token>, String> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            _ if c.is_whitespace() => i += 1,
+            ';' => break,
+            '#' if tokens.is_empty() => break,
+            '#' => {
+                tokens.push(Token::Hash);
+                i += 1;
+            }
+            '!' => {
+                tokens.push(Token::Bang);
+                i += 1;
+            }
+            '*' => {
+                tokens.push(Token::Star);
+                i += 1;
+            }
+            '=' => {
+                tokens.push(Token::Equals);
+                i += 1;
+            }
+            ':' => {
+                tokens.push(Token::Colon);
+                i += 1;
+            }
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            }
+            '"' | '\'' => {
+                let (text, next) = read_string(&chars, i)?;
+                tokens.push(Token::Str(text));
+                i = next;
+            }
+            'b' if matches!(chars.get(i + 1), Some('"') | Some('\'')) => {
+                let (text, next) = read_string(&chars, i + 1)?;
+                let bytes = text
+                    .chars()
+                    .map(|c| {
+                        u8::try_from(c as u32)
+                            .map_err(|_| format!("non-byte character {:?} in bytes literal", c))
+                    })
+                    .collect::<std::result::Result<Vec<u8>, String>>()?;
+                tokens.push(Token::Bytes(bytes));
+                i = next;
+            }
+            _ if c.is_ascii_digit()
+                || ((c == '-' || c == '+')
+                    && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit())) =>
+            {
+                let start = i;
+                i += 1;
+                while i < chars.len()
+                    && (chars[i].is_ascii_alphanumeric()
+                        || chars[i] == '_'
+                        || chars[i] == '.'
+                        || ((chars[i] == '-' || chars[i] == '+')
+                            && matches!(chars[i - 1], 'e' | 'E')
+                            && !chars[start..i].iter().any(|c| *c == 'x' || *c == 'X')))
+                {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().filter(|c| **c != '_').collect();
+                tokens.push(parse_number(&text)?);
+            }
+            _ if c.is_alphabetic() || c == '_' || c == '.' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '.'))
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            _ => return Err(format!("unexpected character {:?}", c)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_number(text: &str) -> std::result::Result<Token, String> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        let value = i64::from_str_radix(hex, 16).map_err(|_| format!("invalid number {}", text))?;
+        return Ok(Token::Int(if negative { -value } else { value }));
+    }
+    if let Ok(value) = text.parse::<i64>() {
+        return Ok(Token::Int(value));
+    }
+    text.parse::<f64>()
+        .map(Token::Float)
+        .map_err(|_| format!("invalid number {}", text))
+}
+
+/// 读取带引号的字符串，返回内容和结束位置
+fn read_string(chars: &[char], start: usize) -> std::result::Result<(String, usize), String> {
+    let quote = chars[start];
+    let mut out = String::new();
+    let mut i = start + 1;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == quote {
+            return Ok((out, i + 1));
+        }
+        if c == '\\' {
+            i += 1;
+            let escaped = *chars.get(i).ok_or("unterminated escape sequence")?;
+            match escaped {
+                'n' => out.push('\n'),
+                't' => out.push('\t'),
+                'r' => out.push('\r'),
+                '0' => out.push('\0'),
+                '\\' | '"' | '\'' => out.push(escaped),
+                'x' => {
+                    let hex: String = chars
+                        .get(i + 1..i + 3)
+                        .ok_or("truncated \\x escape")?
+                        .iter()
+                        .collect();
+                    let code = u8::from_str_radix(&hex, 16)
+                        .map_err(|_| format!("invalid escape \\x{}", hex))?;
+                    out.push(code as char);
+                    i += 2;
+                }
+                other => return Err(format!("unknown escape sequence \\{}", other)),
+            }
+        } else {
+            out.push(c);
+        }
+        i += 1;
+    }
+    Err("unterminated string literal".to_string())
+}
+
+/// 尚未解析标签的指令
+struct PendingInstruction {
+    line: usize,
+    opcode: OpCode,
+    operand: Operand,
+}
+
+enum Operand {
+    Resolved(u32),
+    Label(String),
+}
+
+/// 当前正在汇编的代码段（主程序或函数）
+#[derive(Default)]
+struct Section {
+    instructions: Vec<PendingInstruction>,
+    labels: HashMap<String, usize>,
+}
+
+/// 函数的参数表
+#[derive(Default)]
+struct Signature {
+    parameters: Vec<String>,
+    kwonly_count: usize,
+    varargs: bool,
+    varkw: bool,
+    defaults: Vec<Value>,
+    kwdefaults: HashMap<String, Value>,
+}
+
+/// 函数定义的头部信息
+struct FunctionHeader {
+    name: String,
+    signature: Signature,
+    coroutine: bool,
+    local_vars: HashMap<String, usize>,
+    cell_vars: Vec<usize>,
+    free_vars: Vec<usize>,
+    section: Section,
+}
+
+#[derive(Default)]
+struct Assembler {
+    line: usize,
+    bytecode: Bytecode,
+    named_constants: HashMap<String, u32>,
+    main: Section,
+    function: Option<FunctionHeader>,
+}
+
+impl Assembler {
+    fn error(&self, message: impl Into<String>) -> VMError {
+        VMError::Assembly {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn section(&mut self) -> &mut Section {
+        match &mut self.function {
+            Some(function) => &mut function.section,
+            None => &mut self.main,
+        }
+    }
+
+    fn statement(&mut self, tokens: &[Token]) -> Result<()> {
+        match tokens {
+            [Token::Ident(label), Token::Colon] => {
+                let line = self.line;
+                let section = self.section();
+                let pc = section.instructions.len();
+                if section.labels.insert(label.clone(), pc).is_some() {
+                    return Err(VMError::Assembly {
+                        line,
+                        message: format!("duplicate label '{}'", label),
+                    });
+                }
+                Ok(())
+            }
+            [Token::Ident(directive), rest @ ..] if directive.starts_with('.') => {
+                self.directive(directive, rest)
+            }
+            [Token::Ident(mnemonic), rest @ ..] => self.instruction(mnemonic, rest),
+            _ => Err(self.error("expected a label, directive or instruction")),
+        }
+    }
+
+    fn directive(&mut self, directive: &str, args: &[Token]) -> Result<()> {
+        match directive {
+            ".const" => {
+                let (name, value) = match args {
+                    [Token::Ident(name), literal @ ..] if self.literal(&args[0]).is_err() => {
+                        (Some(name), self.constant(literal))
+                    }
+                    _ => (None, self.constant(args)),
+                };
+                let value = value.map_err(|_| self.error(".const expects [name] literal"))?;
+                let index = self.intern(value);
+                if let Some(name) = name {
+                    self.named_constants.insert(name.clone(), index);
+                }
+                Ok(())
+            }
+
+            ".global" => {
+                if args.is_empty() {
+                    return Err(self.error(".global expects at least one name"));
+                }
+                for arg in args.iter().filter(|t| **t != Token::Comma) {
+                    let Token::Ident(name) = arg else {
+                        return Err(self.error(".global expects variable names"));
+                    };
+                    let next = self.bytecode.global_vars.len();
+                    self.bytecode
+                        .global_vars
+                        .entry(name.clone())
+                        .or_insert(next);
+                }
+                Ok(())
+            }
+
+            ".func" | ".async" => {
+                if self.function.is_some() {
+                    return Err(self.error("nested .func is not allowed, missing .end?"));
+                }
+                let (name, signature) = match args {
+                    [Token::Ident(name), Token::LParen, params @ .., Token::RParen] => {
+                        (name.clone(), self.signature(params)?)
+                    }
+                    [Token::Ident(name)] => (name.clone(), Signature::default()),
+                    _ => return Err(self.error(format!("{} expects name(param, ...)", directive))),
+                };
+                if self.bytecode.functions.contains_key(&name) {
+                    return Err(self.error(format!("function '{}' defined twice", name)));
+                }
+                let mut local_vars = HashMap::new();
+                for (slot, parameter) in signature.parameters.iter().enumerate() {
+                    if local_vars.insert(parameter.clone(), slot).is_some() {
+                        return Err(self.error(format!("duplicate parameter '{}'", parameter)));
+                    }
+                }
+                self.function = Some(FunctionHeader {
+                    name,
+                    signature,
+                    coroutine: directive == ".async",
+                    local_vars,
+                    cell_vars: Vec::new(),
+                    free_vars: Vec::new(),
+                    section: Section::default(),
+                });
+                Ok(())
+            }
+
+            ".local" | ".cell" | ".free" => {
+                let line = self.line;
+                let Some(function) = &mut self.function else {
+                    return Err(self.error(format!("{} is only valid inside .func", directive)));
+                };
+                for arg in args.iter().filter(|t| **t != Token::Comma) {
+                    let Token::Ident(name) = arg else {
+                        return Err(VMError::Assembly {
+                            line,
+                            message: format!("{} expects variable names", directive),
+                        });
+                    };
+                    let next = function.local_vars.len();
+                    let slot = *function.local_vars.entry(name.clone()).or_insert(next);
+                    let slots = match directive {
+                        ".cell" => &mut function.cell_vars,
+                        ".free" => &mut function.free_vars,
+                        _ => continue,
+                    };
+                    if !slots.contains(&slot) {
+                        slots.push(slot);
+                    }
+                }
+                Ok(())
+            }
+
+            ".end" => {
+                let Some(header) = self.function.take() else {
+                    return Err(self.error(".end without matching .func"));
+                };
+                let instructions = resolve(header.section)?;
+                let generator = !header.coroutine
+                    && instructions.iter().any(|instruction| {
+                        matches!(instruction.opcode, OpCode::YieldValue | OpCode::YieldFrom)
+                    });
+                let signature = header.signature;
+                self.bytecode.functions.insert(
+                    header.name.clone(),
+                    Function {
+                        name: header.name,
+                        parameters: signature.parameters,
+                        kwonly_count: signature.kwonly_count,
+                        varargs: signature.varargs,
+                        varkw: signature.varkw,
+                        defaults: signature.defaults,
+                        kwdefaults: signature.kwdefaults,
+                        generator,
+                        coroutine: header.coroutine,
+                        instructions,
+                        local_vars: header.local_vars,
+                        cell_vars: header.cell_vars,
+                        free_vars: header.free_vars,
+                        handlers: ExceptionTable::default(),
+                        module: 0,
+                        id: 0,
+                    },
+                );
+                Ok(())
+            }
+
+            _ => Err(self.error(format!("unknown directive {}", directive))),
+        }
+    }
+
+    fn instruction(&mut self, mnemonic: &str, args: &[Token]) -> Result<()> {
+        let opcode = OpCode::from_name(mnemonic)
+            .ok_or_else(|| self.error(format!("unknown instruction {}", mnemonic)))?;
+
+        let operand = if !opcode.has_operand() {
+            if !args.is_empty() {
+                return Err(self.error(format!("{} takes no operand", opcode.name())));
+            }
+            Operand::Resolved(0)
+        } else {
+            self.operand(opcode, args)?
+        };
+
+        let line = self.line;
+        self.section().instructions.push(PendingInstruction {
+            line,
+            opcode,
+            operand,
+        });
+        Ok(())
+    }
+
+    fn operand(&mut self, opcode: OpCode, args: &[Token]) -> Result<Operand> {
+        use OpCode::*;
+        let operand = match (opcode, args) {
+            (_, [Token::Hash, Token::Int(index)]) => self.u32(*index)?,
+
+            (_, [Token::Ident(label)]) if opcode.is_jump() => {
+                return Ok(Operand::Label(label.clone()))
+            }
+
+            (LoadConst | GetAttr | HasAttr | CreateClass, [Token::Ident(name)])
+                if self.named_constants.contains_key(name) =>
+            {
+                self.named_constants[name]
+            }
+            (LoadConst, [Token::LParen, ..]) => {
+                let value = self.constant(args)?;
+                self.intern(value)
+            }
+            (LoadConst | GetAttr | HasAttr | CreateClass, [literal]) => {
+                let value = match literal {
+                    // 属性名、类名可以直接写成标识符
+                    Token::Ident(name) if opcode != LoadConst => {
+                        Value::String(name.as_str().into())
+                    }
+                    _ => self.literal(literal)?,
+                };
+                self.intern(value)
+            }
+
+            (LoadFunc, [Token::Ident(name)] | [Token::Str(name)]) => {
+                self.intern(Value::String(name.as_str().into()))
+            }
+
+            (LoadGlobal | StoreGlobal, [Token::Ident(name)]) => self.global(name)?,
+
+            (
+                LoadLocal | StoreLocal | LoadDeref | StoreDeref | LoadClosure,
+                [Token::Ident(name)],
+            ) => self.local(name)?,
+
+            (LoadVar | StoreVar, [Token::Ident(name)]) => {
+                if self.function.is_some() {
+                    self.local(name)?
+                } else {
+                    self.global(name)?
+                }
+            }
+
+            (CreateClass, [Token::Ident(name) | Token::Str(name), Token::Int(bases)]) => {
+                let name_index = self.intern(Value::String(name.as_str().into()));
+                let bases = self.u32(*bases)?;
+                if name_index > 0xFFFF || bases > 0xFFFF {
+                    return Err(
+                        self.error("CREATE_CLASS name index and base count must fit in 16 bits")
+                    );
+                }
+                (bases << 16) | name_index
+            }
+
+            (CallMethod, [Token::Ident(name) | Token::Str(name), Token::Int(argc)]) => {
+                let name_index = self.intern(Value::String(name.as_str().into()));
+                let argc = self.u32(*argc)?;
+                if name_index > 0xFFFF || argc > 0xFFFF {
+                    return Err(self.error("CALL_METHOD name index and argc must fit in 16 bits"));
+                }
+                (argc << 16) | name_index
+            }
+
+            (CatchBegin, []) => CATCH_ALL,
+            (CatchBegin, [Token::Str(name)] | [Token::Ident(name)]) => {
+                self.intern(Value::String(name.as_str().into()))
+            }
+
+            (FormatValue, [] | [Token::Bang, ..] | [Token::Str(_)]) => {
+                let (conversion, rest) = match args {
+                    [Token::Bang, Token::Ident(flag), rest @ ..] => match flag.as_str() {
+                        "s" => (FORMAT_STR, rest),
+                        "r" => (FORMAT_REPR, rest),
+                        _ => return Err(self.error(format!("unknown conversion !{}", flag))),
+                    },
+                    _ => (0, args),
+                };
+                let spec = match rest {
+                    [] => None,
+                    [Token::Str(spec)] => Some(self.intern(Value::String(spec.as_str().into()))),
+                    _ => return Err(self.error("FORMAT_VALUE expects [!s|!r] [\"spec\"]")),
+                };
+                format_operand(conversion, spec)
+            }
+
+            (_, [Token::Int(value)]) => self.u32(*value)?,
+
+            _ => return Err(self.error(format!("invalid operand for {}", opcode.name()))),
+        };
+        Ok(Operand::Resolved(operand))
+    }
+
+    fn u32(&self, value: i64) -> Result<u32> {
+        u32::try_from(value).map_err(|_| self.error(format!("operand {} out of range", value)))
+    }
+
+    fn global(&self, name: &str) -> Result<u32> {
+        self.bytecode
+            .global_vars
+            .get(name)
+            .map(|&slot| slot as u32)
+            .ok_or_else(|| {
+                self.error(format!(
+                    "undeclared global '{}', add .global {}",
+                    name, name
+                ))
+            })
+    }
+
+    fn local(&self, name: &str) -> Result<u32> {
+        let function = self
+            .function
+            .as_ref()
+            .ok_or_else(|| self.error("local variable access outside .func"))?;
+        function
+            .local_vars
+            .get(name)
+            .map(|&slot| slot as u32)
+            .ok_or_else(|| self.error(format!("undeclared local '{}', add .local {}", name, name)))
+    }
+
+    /// 解析 `.func` 的参数表，各参数以逗号分隔
+    fn signature(&self, tokens: &[Token]) -> Result<Signature> {
+        let mut signature = Signature::default();
+        // 遇到 `*` 之后的参数为仅限关键字参数
+        let mut star = false;
+        if tokens.is_empty() {
+            return Ok(signature);
+        }
+        for param in tokens.split(|token| *token == Token::Comma) {
+            if signature.varkw {
+                return Err(self.error("parameter after **kwargs"));
+            }
+            let (name, default) = match param {
+                [Token::Star] if !star => {
+                    star = true;
+                    continue;
+                }
+                [Token::Star, Token::Ident(name)] if !star => {
+                    star = true;
+                    signature.varargs = true;
+                    signature.parameters.push(name.clone());
+                    continue;
+                }
+                [Token::Star, Token::Star, Token::Ident(name)] => {
+                    signature.varkw = true;
+                    signature.parameters.push(name.clone());
+                    continue;
+                }
+                [Token::Ident(name)] => (name, None),
+                [Token::Ident(name), Token::Equals, literal] => {
+                    (name, Some(self.literal(literal)?))
+                }
+                _ => return Err(self.error("expected a parameter like a, b=1, *args, **kwargs")),
+            };
+            match (star, default) {
+                (true, default) => {
+                    signature.kwonly_count += 1;
+                    if let Some(default) = default {
+                        signature.kwdefaults.insert(name.clone(), default);
+                    }
+                }
+                (false, Some(default)) => signature.defaults.push(default),
+                (false, None) if !signature.defaults.is_empty() => {
+                    return Err(self.error("non-default argument follows default argument"));
+                }
+                (false, None) => {}
+            }
+            signature.parameters.push(name.clone());
+        }
+        if star && !signature.varargs && signature.kwonly_count == 0 {
+            return Err(self.error("named arguments must follow bare *"));
+        }
+        Ok(signature)
+    }
+
+    fn literal(&self, token: &Token) -> Result<Value> {
+        let value = match token {
+            Token::Int(i) => Value::Int(*i),
+            Token::Float(f) => Value::Float(*f),
+            Token::Str(s) => Value::String(s.as_str().into()),
+            Token::Bytes(b) => Value::Bytes(b.as_slice().into()),
+            Token::Ident(name) => match name.as_str() {
+                "None" | "null" => Value::Null,
+                "True" | "true" => Value::Bool(true),
+                "False" | "false" => Value::Bool(false),
+                "inf" => Value::Float(f64::INFINITY),
+                "nan" => Value::Float(f64::NAN),
+                _ => return Err(self.error(format!("unknown constant '{}'", name))),
+            },
+            _ => return Err(self.error("expected a literal")),
+        };
+        Ok(value)
+    }
+
+    /// 单个字面量或元组字面量 `(a, b)`
+    fn constant(&self, tokens: &[Token]) -> Result<Value> {
+        match tokens {
+            [literal] => self.literal(literal),
+            [Token::LParen, items @ .., Token::RParen] => {
+                Ok(Value::Tuple(self.literal_list(items)?.into()))
+            }
+            _ => Err(self.error("expected a literal or a tuple of literals")),
+        }
+    }
+
+    /// 以逗号分隔的字面量，允许末尾多一个逗号
+    fn literal_list(&self, tokens: &[Token]) -> Result<Vec<Value>> {
+        let mut items = Vec::new();
+        for (i, token) in tokens.iter().enumerate() {
+            match (i % 2, token) {
+                (0, literal) => items.push(self.literal(literal)?),
+                (1, Token::Comma) => {}
+                _ => return Err(self.error("expected a comma-separated list of literals")),
+            }
+        }
+        Ok(items)
+    }
+
+    /// 把常量加入常量池，相同的常量只保留一份
+    fn intern(&mut self, value: Value) -> u32 {
+        let constants = &mut self.bytecode.constants;
+        let existing = constants.iter().position(|c| same_constant(c, &value));
+        let index = existing.unwrap_or_else(|| {
+            constants.push(value);
+            constants.len() - 1
+        });
+        index as u32
+    }
+
+    fn finish(mut self) -> Result<Bytecode> {
+        if let Some(function) = &self.function {
+            return Err(self.error(format!("missing .end for function '{}'", function.name)));
+        }
+        self.bytecode.instructions = resolve(std::mem::take(&mut self.main))?;
+        Ok(self.bytecode)
+    }
+}
+
+/// 常量去重时的比较：类型和值都必须相同（`1` 与 `1.0`、`True` 不合并）
+pub(super) fn same_constant(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Null, Value::Null) => true,
+        (Value::Bool(x), Value::Bool(y)) => x == y,
+        (Value::Int(x), Value::Int(y)) => x == y,
+        (Value::Float(x), Value::Float(y)) => x.to_bits() == y.to_bits(),
+        (Value::String(x), Value::String(y)) => x == y,
+        (Value::Bytes(x), Value::Bytes(y)) => x == y,
+        (Value::Tuple(x), Value::Tuple(y)) => {
+            x.len() == y.len() && x.iter().zip(y.iter()).all(|(a, b)| same_constant(a, b))
+        }
+        _ => false,
+    }
+}
+
+/// 把代码段中的标签替换为指令编号
+fn resolve(section: Section) -> Result<Vec<Instruction>> {
+    section
+        .instructions
+        .into_iter()
+        .map(|pending| {
+            let operand = match pending.operand {
+                Operand::Resolved(operand) => operand,
+                Operand::Label(label) => {
+                    *section
+                        .labels
+                        .get(&label)
+                        .ok_or_else(|| VMError::Assembly {
+                            line: pending.line,
+                            message: format!("undefined label '{}'", label),
+                        })? as u32
+                }
+            };
+            Ok(Instruction::new(pending.opcode, operand))
+        })
+        .collect()
+}
@@ -0,0 +1,366 @@
+/*!
+反汇编
+
+把 [`Bytecode`] 输出为文本清单，清单本身就是合法的 `.aasm` 源码，
+重新汇编后得到相同的字节码：
+- 开头用不带名字的 `.const` 按序号依次列出常量池，保证重新汇编后序号不变
+- 主程序与每个函数各自成段，函数段写成 `.func` / `.async` ... `.end`，
+  并用 `.local`、`.cell`、`.free` 还原局部变量槽位、被捕获的变量和自由变量
+- 操作数使用汇编语法：常量写成字面量，跳转目标为符号标签 `L0`、`L1` ...，
+  变量槽位解析为变量名；无法写成字面量的常量用 `#索引` 引用
+- 指令编号和 `#索引` 引用的常量值写在行尾注释中，
+  每段末尾以注释列出 `try` 语句的异常处理表
+- 常量池中的代码对象在最后逐一列出，整段都是注释
+
+代码对象、字典等没有字面量形式的常量在 `.const` 中原样显示，
+这样的清单无法重新汇编，汇编器会在对应的行报错。
+*/
+
+use super::asm::same_constant;
+use super::handlers::{self, ExceptionHandler};
+use super::{
+    split_format_operand, Bytecode, Instruction, OpCode, CATCH_ALL, FORMAT_REPR, FORMAT_STR,
+};
+use crate::function::Function;
+use crate::value::{format_bytes, format_float, format_str_repr, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+
+/// 指令行注释开始的列
+const COMMENT_COLUMN: usize = 40;
+
+impl Bytecode {
+    /// 生成可重新汇编的反汇编清单
+    pub fn disassemble(&self) -> String {
+        let mut out = String::new();
+        let global_names = slot_names(&self.global_vars);
+
+        if !self.constants.is_empty() {
+            out.push_str("; constants\n");
+            for (index, constant) in self.constants.iter().enumerate() {
+                let text = literal(constant).unwrap_or_else(|| constant.repr());
+                let line = format!(".const {}", text);
+                let _ = writeln!(out, "{:<w$}; #{}", line, index, w = COMMENT_COLUMN);
+            }
+            out.push('\n');
+        }
+
+        if !global_names.is_empty() {
+            let _ = writeln!(out, ".global {}\n", global_names.join(", "));
+        }
+
+        out.push_str("; == <main> ==\n");
+        Section {
+            bytecode: self,
+            globals: &global_names,
+            locals: None,
+        }
+        .write(&self.instructions, &mut out);
+
+        let mut functions: Vec<&Function> = self.functions.values().collect();
+        functions.sort_by(|a, b| a.name.cmp(&b.name));
+        for function in functions {
+            out.push('\n');
+            self.write_function(function, &global_names, &mut out);
+        }
+
+        // 代码对象只能由二进制格式产生，汇编器无法表示，整段写成注释
+        for (index, constant) in self.constants.iter().enumerate() {
+            if let Value::Code(function) = constant {
+                let mut body = String::new();
+                self.write_function(function, &global_names, &mut body);
+                let _ = writeln!(out, "\n; code object #{}", index);
+                for line in body.lines() {
+                    let _ = writeln!(out, "; {}", line);
+                }
+            }
+        }
+
+        out
+    }
+
+    fn write_function(&self, function: &Function, globals: &[String], out: &mut String) {
+        let local_names = slot_names(&function.local_vars);
+        let kind = if function.generator {
+            "generator"
+        } else if function.coroutine {
+            "coroutine"
+        } else {
+            "function"
+        };
+        let signature = function.signature();
+        let _ = writeln!(out, "; == {} {}({}) ==", kind, function.name, signature);
+        let directive = if function.coroutine {
+            ".async"
+        } else {
+            ".func"
+        };
+        let _ = writeln!(out, "{} {}({})", directive, function.name, signature);
+
+        let locals = &local_names[function.parameters.len().min(local_names.len())..];
+        if !locals.is_empty() {
+            let _ = writeln!(out, ".local {}", locals.join(", "));
+        }
+        for (directive, slots) in [
+            (".cell", &function.cell_vars),
+            (".free", &function.free_vars),
+        ] {
+            if !slots.is_empty() {
+                let names: Vec<&str> = slots
+                    .iter()
+                    .map(|&slot| slot_name(&local_names, slot))
+                    .collect();
+                let _ = writeln!(out, "{} {}", directive, names.join(", "));
+            }
+        }
+
+        Section {
+            bytecode: self,
+            globals,
+            locals: Some(&local_names),
+        }
+        .write(&function.instructions, out);
+        out.push_str(".end\n");
+    }
+}
+
+/// 把名字到槽位的映射反转为按槽位索引的名字表
+fn slot_names(map: &HashMap<String, usize>) -> Vec<String> {
+    let len = map.values().map(|&slot| slot + 1).max().unwrap_or(0);
+    let mut names = vec![String::from("?"); len];
+    for (name, &slot) in map {
+        names[slot] = name.clone();
+    }
+    names
+}
+
+/// 常量的汇编字面量写法，没有字面量形式时返回 `None`
+fn literal(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::Null => "None".to_string(),
+        Value::Bool(b) => if *b { "True" } else { "False" }.to_string(),
+        Value::Int(i) => i.to_string(),
+        // 汇编器不接受 `-inf`
+        Value::Float(f) if *f == f64::NEG_INFINITY => return None,
+        Value::Float(f) => format_float(*f),
+        Value::String(s) => format_str_repr(s),
+        Value::Bytes(b) => format_bytes(b),
+        Value::Tuple(items) => {
+            let items = items
+                .iter()
+                .map(|item| match item {
+                    Value::Tuple(_) => None,
+                    _ => literal(item),
+                })
+                .collect::<Option<Vec<String>>>()?;
+            match items.as_slice() {
+                [item] => format!("({},)", item),
+                _ => format!("({})", items.join(", ")),
+            }
+        }
+        _ => return None,
+    };
+    Some(text)
+}
+
+/// 能否作为汇编器的标识符记号
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+}
+
+/// 名字写成标识符，不是合法标识符时写成字符串字面量
+fn name(text: &str) -> String {
+    if is_identifier(text) {
+        text.to_string()
+    } else {
+        format_str_repr(text)
+    }
+}
+
+/// 一段指令（主程序或单个函数）的反汇编上下文
+struct Section<'a> {
+    bytecode: &'a Bytecode,
+    globals: &'a [String],
+    locals: Option<&'a [String]>,
+}
+
+impl Section<'_> {
+    fn write(&self, instructions: &[Instruction], out: &mut String) {
+        let targets: BTreeSet<usize> = instructions
+            .iter()
+            .filter(|i| i.opcode.is_jump())
+            .map(|i| i.operand as usize)
+            .collect();
+        let labels: HashMap<usize, usize> = targets
+            .iter()
+            .enumerate()
+            .map(|(label, &pc)| (pc, label))
+            .collect();
+
+        for (pc, instruction) in instructions.iter().enumerate() {
+            if let Some(label) = labels.get(&pc) {
+                let _ = writeln!(out, "L{}:", label);
+            }
+            let (operand, note) = self.operand(instruction, &labels);
+            let text = format!("    {:<16} {}", instruction.opcode.name(), operand);
+            let mut line = format!("{:<w$}; {:>4}", text.trim_end(), pc, w = COMMENT_COLUMN);
+            if let Some(note) = note {
+                let _ = write!(line, "  {}", note);
+            }
+            let _ = writeln!(out, "{}", line);
+        }
+
+        // 指向代码末尾的跳转
+        if let Some(label) = labels.get(&instructions.len()) {
+            let _ = writeln!(out, "L{}:", label);
+        }
+
+        match handlers::build(instructions, &self.bytecode.constants) {
+            Ok(table) if table.is_empty() => {}
+            Ok(table) => {
+                out.push_str("    ; handlers\n");
+                for handler in table.handlers() {
+                    let _ = writeln!(out, "    ;   {}", describe_handler(handler));
+                }
+            }
+            Err((pc, reason)) => {
+                let _ = writeln!(out, "    ; handlers: {} at {}", reason, pc);
+            }
+        }
+    }
+
+    /// 汇编语法的操作数，以及需要写在注释中的说明
+    fn operand(
+        &self,
+        instruction: &Instruction,
+        labels: &HashMap<usize, usize>,
+    ) -> (String, Option<String>) {
+        let opcode = instruction.opcode;
+        if !opcode.has_operand() {
+            return (String::new(), None);
+        }
+
+        let index = instruction.operand as usize;
+        let text = match opcode {
+            _ if opcode.is_jump() => format!("L{}", labels[&index]),
+
+            OpCode::LoadConst => match self.canonical(index).and_then(literal) {
+                Some(text) => text,
+                None => return self.reference(index),
+            },
+
+            OpCode::GetAttr | OpCode::HasAttr | OpCode::LoadFunc => match self.string(index) {
+                Some(text) => name(text),
+                None => return self.reference(index),
+            },
+
+            OpCode::CatchBegin if instruction.operand == CATCH_ALL => String::new(),
+            OpCode::CatchBegin => match self.string(index) {
+                Some(text) => name(text),
+                None => return self.reference(index),
+            },
+
+            OpCode::CreateClass | OpCode::CallMethod => {
+                let count = instruction.operand >> 16;
+                match self.string((instruction.operand & 0xFFFF) as usize) {
+                    Some(text) => format!("{} {}", name(text), count),
+                    None => return (format!("#{}", index), None),
+                }
+            }
+
+            OpCode::FormatValue => {
+                let (conversion, spec) = split_format_operand(instruction.operand);
+                let mut parts = Vec::new();
+                match conversion {
+                    FORMAT_STR => parts.push("!s".to_string()),
+                    FORMAT_REPR => parts.push("!r".to_string()),
+                    _ => {}
+                }
+                if let Some(spec) = spec {
+                    match self.string(spec) {
+                        Some(text) => parts.push(format_str_repr(text)),
+                        None => return (format!("#{}", index), None),
+                    }
+                }
+                parts.join(" ")
+            }
+
+            OpCode::LoadGlobal | OpCode::StoreGlobal => variable(self.globals, index),
+
+            OpCode::LoadLocal
+            | OpCode::StoreLocal
+            | OpCode::LoadDeref
+            | OpCode::StoreDeref
+            | OpCode::LoadClosure => match self.locals {
+                Some(locals) => variable(locals, index),
+                None => index.to_string(),
+            },
+
+            OpCode::LoadVar | OpCode::StoreVar => {
+                variable(self.locals.unwrap_or(self.globals), index)
+            }
+
+            _ => index.to_string(),
+        };
+        (text, None)
+    }
+
+    /// 常量池中第一个与 `index` 处相同的常量就在 `index` 时返回它，
+    /// 汇编器按值引用常量时总是得到第一个
+    fn canonical(&self, index: usize) -> Option<&Value> {
+        let constants = &self.bytecode.constants;
+        let value = constants.get(index)?;
+        let first = constants.iter().position(|c| same_constant(c, value));
+        (first == Some(index)).then_some(value)
+    }
+
+    fn string(&self, index: usize) -> Option<&str> {
+        match self.canonical(index)? {
+            Value::String(text) => Some(&**text),
+            _ => None,
+        }
+    }
+
+    /// 以 `#索引` 引用常量，注释中显示常量的值
+    fn reference(&self, index: usize) -> (String, Option<String>) {
+        let value = self
+            .bytecode
+            .constants
+            .get(index)
+            .map(|value| value.repr())
+            .unwrap_or_else(|| "<invalid constant>".to_string());
+        (format!("#{}", index), Some(value))
+    }
+}
+
+/// 槽位有名字时写成变量名，否则写成槽位编号
+fn variable(names: &[String], slot: usize) -> String {
+    match names.get(slot) {
+        Some(name) if is_identifier(name) => name.clone(),
+        _ => slot.to_string(),
+    }
+}
+
+fn slot_name(names: &[String], slot: usize) -> &str {
+    names.get(slot).map(String::as_str).unwrap_or("?")
+}
+
+/// 异常处理表的一项，位置均为指令编号
+fn describe_handler(handler: &ExceptionHandler) -> String {
+    let mut parts = vec![format!("try {}-{}", handler.start, handler.try_end)];
+    for clause in &handler.catches {
+        match &clause.exception_type {
+            Some(name) => parts.push(format!("catch {} {}-{}", name, clause.start, clause.end)),
+            None => parts.push(format!("catch {}-{}", clause.start, clause.end)),
+        }
+    }
+    if let Some(finally) = handler.finally {
+        parts.push(format!("finally {}", finally));
+    }
+    parts.push(format!("end {}", handler.end));
+    parts.join(", ")
+}
@@ -0,0 +1,253 @@
+/*!
+异常处理表
+
+`try` 语句在字节码中的布局（与 `compiler/codegen.py` 的 `compile_try_statement` 一致），
+各段之间没有跳转指令：
+
+```text
+TRY_BEGIN
+    <try 块>
+TRY_END
+CATCH_BEGIN [类型名]        ; 零个或多个 catch 子句
+    STORE_LOCAL e | POP
+    <catch 块>
+CATCH_END
+FINALLY_BEGIN              ; 可选
+    <finally 块>
+FINALLY_END
+```
+
+加载时一次性扫描出每个 `try` 语句各段的位置，执行期按程序计数器查表，
+不再逐条扫描指令寻找处理器。
+*/
+
+use super::{Instruction, OpCode, CATCH_ALL};
+use crate::value::Value;
+use std::rc::Rc;
+
+/// 一个 `catch` 子句
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatchClause {
+    /// `CATCH_BEGIN` 的位置
+    pub start: usize,
+
+    /// `CATCH_END` 的位置
+    pub end: usize,
+
+    /// 捕获的异常类型名，`None` 捕获所有异常
+    pub exception_type: Option<Rc<str>>,
+}
+
+/// 一个 `try` 语句的异常处理器
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExceptionHandler {
+    /// `TRY_BEGIN` 的位置
+    pub start: usize,
+
+    /// `TRY_END` 的位置
+    pub try_end: usize,
+
+    /// 按源码顺序排列的 `catch` 子句
+    pub catches: Vec<CatchClause>,
+
+    /// `FINALLY_BEGIN` 的位置
+    pub finally: Option<usize>,
+
+    /// 整个语句之后的第一条指令
+    pub end: usize,
+
+    /// 进入语句时相对调用帧的栈高度，转入 catch/finally 前操作数栈截断到这里
+    pub depth: usize,
+
+    /// 直接包含本语句的外层语句在表中的索引
+    pub parent: Option<usize>,
+}
+
+/// 程序计数器位于 `try` 语句的哪一部分
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Try,
+    Catch,
+    Finally,
+}
+
+impl ExceptionHandler {
+    /// `pc` 所处的部分，不在语句内时返回 `None`
+    pub fn region(&self, pc: usize) -> Option<Region> {
+        if !(self.start..self.end).contains(&pc) {
+            None
+        } else if pc <= self.try_end {
+            Some(Region::Try)
+        } else if self.finally.is_some_and(|finally| pc >= finally) {
+            Some(Region::Finally)
+        } else {
+            Some(Region::Catch)
+        }
+    }
+
+    /// try 块或 catch 块正常结束后的去向：有 finally 块时进入它，否则跳到语句之后
+    pub fn exit(&self) -> usize {
+        self.finally.unwrap_or(self.end)
+    }
+
+    /// 第一个能捕获 `matches` 所接受类型的 catch 子句
+    pub fn find_catch(&self, mut matches: impl FnMut(&str) -> bool) -> Option<&CatchClause> {
+        self.catches
+            .iter()
+            .find(|catch| catch.exception_type.as_deref().is_none_or(&mut matches))
+    }
+}
+
+/// 一个代码块的异常处理表
+///
+/// 处理器按 `TRY_BEGIN` 的位置排序，内层语句总是排在外层之后；
+/// 每条指令所在的最内层语句在扫描时一次算好，执行期查表不随处理器数量增长。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExceptionTable {
+    pub(super) handlers: Vec<ExceptionHandler>,
+
+    /// 每条指令所在的最内层语句在 `handlers` 中的索引
+    innermost: Vec<Option<u32>>,
+}
+
+impl ExceptionTable {
+    pub fn handlers(&self) -> &[ExceptionHandler] {
+        &self.handlers
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    /// 包含 `pc` 的最内层处理器的索引
+    pub fn innermost(&self, pc: usize) -> Option<usize> {
+        self.innermost
+            .get(pc)
+            .copied()
+            .flatten()
+            .map(|index| index as usize)
+    }
+
+    /// 由内向外列出包含 `pc` 的处理器的索引
+    pub fn enclosing(&self, pc: usize) -> impl Iterator<Item = usize> + '_ {
+        std::iter::successors(self.innermost(pc), |&index| self.handlers[index].parent)
+    }
+}
+
+impl std::ops::Index<usize> for ExceptionTable {
+    type Output = ExceptionHandler;
+
+    fn index(&self, index: usize) -> &ExceptionHandler {
+        &self.handlers[index]
+    }
+}
+
+/// 扫描一个代码块的 `try` 语句结构，失败时返回 (位置, 原因)
+///
+/// 嵌套的语句用显式的栈跟踪，不随嵌套深度递归。栈高度由校验器随后填入。
+pub(super) fn build(
+    instructions: &[Instruction],
+    constants: &[Value],
+) -> std::result::Result<ExceptionTable, (usize, String)> {
+    use OpCode::*;
+    let mut handlers: Vec<ExceptionHandler> = Vec::new();
+    let mut innermost = vec![None; instructions.len()];
+    // 尚未结束的语句：(处理器索引, 当前部分的结束指令)
+    let mut open: Vec<(usize, OpCode)> = Vec::new();
+    let opcode = |pc: usize| instructions.get(pc).map(|instruction| instruction.opcode);
+
+    let mut pc = 0;
+    while let Some(current) = opcode(pc) {
+        match current {
+            TryBegin => {
+                let index = handlers.len();
+                handlers.push(ExceptionHandler {
+                    start: pc,
+                    try_end: pc,
+                    catches: Vec::new(),
+                    finally: None,
+                    end: pc,
+                    depth: 0,
+                    parent: open.last().map(|&(parent, _)| parent),
+                });
+                open.push((index, TryEnd));
+                innermost[pc] = Some(index as u32);
+                pc += 1;
+            }
+            TryEnd | CatchEnd | FinallyEnd => {
+                let Some(&(index, close)) = open.last().filter(|&&(_, close)| close == current)
+                else {
+                    return Err((pc, format!("unexpected {}", current.name())));
+                };
+                innermost[pc] = Some(index as u32);
+                let handler = &mut handlers[index];
+                match close {
+                    TryEnd => handler.try_end = pc,
+                    CatchEnd => handler.catches.last_mut().unwrap().end = pc,
+                    _ => {}
+                }
+                pc += 1;
+
+                // 一个部分结束后依次是 catch 子句、finally 块或语句之后的指令
+                let next = match (close, opcode(pc)) {
+                    (TryEnd | CatchEnd, Some(CatchBegin)) => {
+                        handler.catches.push(CatchClause {
+                            start: pc,
+                            end: pc,
+                            exception_type: exception_type(instructions, constants, pc),
+                        });
+                        Some(CatchEnd)
+                    }
+                    (TryEnd | CatchEnd, Some(FinallyBegin)) => {
+                        handler.finally = Some(pc);
+                        Some(FinallyEnd)
+                    }
+                    _ => None,
+                };
+                match next {
+                    Some(close) => {
+                        innermost[pc] = Some(index as u32);
+                        open.last_mut().unwrap().1 = close;
+                        pc += 1;
+                    }
+                    None if handler.catches.is_empty() && handler.finally.is_none() => {
+                        return Err((
+                            handler.try_end,
+                            "try block without catch or finally".to_string(),
+                        ));
+                    }
+                    None => {
+                        handler.end = pc;
+                        open.pop();
+                    }
+                }
+            }
+            CatchBegin | FinallyBegin => {
+                return Err((pc, format!("unexpected {}", current.name())));
+            }
+            _ => {
+                innermost[pc] = open.last().map(|&(index, _)| index as u32);
+                pc += 1;
+            }
+        }
+    }
+
+    match open.last() {
+        None => Ok(ExceptionTable {
+            handlers,
+            innermost,
+        }),
+        Some((_, close)) => Err((pc, format!("missing {}", close.name()))),
+    }
+}
+
+fn exception_type(instructions: &[Instruction], constants: &[Value], pc: usize) -> Option<Rc<str>> {
+    let operand = instructions[pc].operand;
+    if operand == CATCH_ALL {
+        return None;
+    }
+    match constants.get(operand as usize) {
+        Some(Value::String(name)) => Some(name.clone()),
+        _ => None,
+    }
+}
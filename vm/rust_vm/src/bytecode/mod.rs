@@ -0,0 +1,479 @@
+/*!
+AquaScript 字节码定义
+
+这个模块定义了虚拟机执行的指令格式，以及从 `.acode` 文件加载字节码的入口：
+- 操作码（与 `vm/aquavm.py` 中的编号保持一致）
+- 指令与函数表
+- `.acode` 文件读写（第 1 版 JSON 段格式、第 2 版紧凑二进制格式）
+- 执行前的静态校验与异常处理表
+- 加载时的尾调用改写
+- 反汇编与文本汇编（`.aasm`）
+*/
+
+use crate::function::Function;
+use crate::value::Value;
+use crate::{Result, VMError};
+use rustc_hash::FxHashMap;
+use std::collections::HashMap;
+use std::path::Path;
+
+mod asm;
+mod disasm;
+mod handlers;
+mod tailcall;
+mod v1;
+mod v2;
+mod verify;
+
+pub use asm::assemble;
+pub use handlers::{CatchClause, ExceptionHandler, ExceptionTable, Region};
+pub use tailcall::rewrite_tail_calls;
+pub use verify::{analyze, verify, Analysis};
+
+/// `.acode` 文件魔数
+pub const ACODE_MAGIC: &[u8; 4] = b"AQUA";
+
+/// `FORMAT_VALUE` 操作数的低两位：转换标志
+pub const FORMAT_CONVERSION_MASK: u32 = 0b11;
+/// `!s` 转换
+pub const FORMAT_STR: u32 = 1;
+/// `!r` 转换
+pub const FORMAT_REPR: u32 = 2;
+
+/// `CATCH_BEGIN` 不指定异常类型（捕获所有异常）时的操作数
+pub const CATCH_ALL: u32 = u32::MAX;
+
+/// 组合 `FORMAT_VALUE` 的操作数：高位存放格式说明常量索引加一，0 表示没有格式说明
+pub fn format_operand(conversion: u32, spec: Option<u32>) -> u32 {
+    (spec.map_or(0, |index| index + 1) << 2) | conversion
+}
+
+/// 拆分 `FORMAT_VALUE` 的操作数为 (转换标志, 格式说明常量索引)
+pub fn split_format_operand(operand: u32) -> (u32, Option<usize>) {
+    let spec = (operand >> 2) as usize;
+    (operand & FORMAT_CONVERSION_MASK, spec.checked_sub(1))
+}
+
+/// 字节码操作码
+///
+/// 数值与 Python 实现（`vm/aquavm.py` 的 `OpCode`）一一对应，
+/// 编译器输出的数字可以直接通过 [`OpCode::from_u8`] 解码。
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpCode {
+    // 栈操作
+    LoadConst = 0x01,
+    LoadVar = 0x02,
+    StoreVar = 0x03,
+    LoadGlobal = 0x04,
+    StoreGlobal = 0x05,
+    LoadLocal = 0x06,
+    StoreLocal = 0x07,
+    /// Python 端 `TYPE_CHECK` 与 `POP` 同为 0x60（Enum 别名），
+    /// 文件中的 0x60 总是解码为 `Pop`，这里给类型检查一个独立编码
+    TypeCheck = 0x08,
+    Dup = 0x09,
+
+    // 算术运算
+    Add = 0x10,
+    Sub = 0x11,
+    Mul = 0x12,
+    Div = 0x13,
+    Mod = 0x14,
+    Pow = 0x15,
+
+    // 比较运算
+    Eq = 0x20,
+    Ne = 0x21,
+    Lt = 0x22,
+    Gt = 0x23,
+    Le = 0x24,
+    Ge = 0x25,
+    In = 0x26,
+
+    // 逻辑运算
+    And = 0x30,
+    Or = 0x31,
+    Not = 0x32,
+
+    // 控制流
+    Jump = 0x40,
+    JumpIfFalse = 0x41,
+    JumpIfTrue = 0x42,
+    /// 短路求值：为假时保留栈顶并跳转，否则弹出
+    JumpIfFalseOrPop = 0x43,
+    /// 短路求值：为真时保留栈顶并跳转，否则弹出
+    JumpIfTrueOrPop = 0x44,
+
+    // 函数操作
+    Call = 0x50,
+    Return = 0x51,
+    LoadFunc = 0x52,
+    /// 操作数为单元个数：弹出函数之上的各个单元，创建闭包
+    MakeClosure = 0x53,
+    /// 将局部变量槽位中的单元压栈，供 `MAKE_CLOSURE` 使用
+    LoadClosure = 0x54,
+    /// 读取槽位中单元的值
+    LoadDeref = 0x55,
+    /// 写入槽位中单元的值
+    StoreDeref = 0x56,
+    /// 带关键字参数的调用：操作数为实参总数，栈顶为关键字参数名元组，
+    /// 对应最后几个实参
+    CallKw = 0x57,
+    /// 尾位置上的调用：被调用的函数复用当前调用帧，由加载时的改写生成
+    TailCall = 0x58,
+    /// 生成器产出栈顶的值并挂起，恢复时压入 `send()` 发送的值
+    YieldValue = 0x59,
+    /// `yield from`：栈布局 [子迭代器, 发送的值]，把值转发给子迭代器，
+    /// 子迭代器产出时挂起并在恢复后重新执行本指令，结束时替换为它的返回值
+    YieldFrom = 0x5A,
+    /// `await` 的操作数检查：栈顶必须是协程或 Future，之后由 `YIELD_FROM` 等待它完成
+    GetAwaitable = 0x5B,
+
+    // 类型与栈操作扩展
+    Pop = 0x60,
+    TypeConvert = 0x61,
+    RotTwo = 0x62,
+    RotThree = 0x63,
+
+    // 数据操作
+    Len = 0x64,
+    GetItem = 0x65,
+    SetItem = 0x66,
+    BuildList = 0x67,
+    SetAttr = 0x68,
+    ImportFrom = 0x69,
+    ImportModule = 0x6A,
+    BuildTuple = 0x6B,
+    FormatValue = 0x6C,
+    BuildDict = 0x7A,
+
+    // 迭代器操作
+    GetIter = 0x6D,
+    ForIter = 0x6E,
+    ListAppend = 0x6F,
+
+    // 对象操作
+    GetAttr = 0x70,
+    HasAttr = 0x72,
+
+    // 类和对象操作
+    /// 操作数为 `(基类个数 << 16) | 类名常量`
+    CreateClass = 0x80,
+    CreateObject = 0x81,
+    CallMethod = 0x82,
+
+    // 异常处理
+    TryBegin = 0x90,
+    TryEnd = 0x91,
+    /// 操作数为异常类型名常量，[`CATCH_ALL`] 表示捕获所有异常
+    CatchBegin = 0x92,
+    CatchEnd = 0x93,
+    FinallyBegin = 0x94,
+    FinallyEnd = 0x95,
+    Throw = 0x96,
+    Reraise = 0x97,
+
+    // 其他
+    Print = 0x98,
+    Halt = 0xFF,
+}
+
+impl OpCode {
+    /// 从编译器输出的数值解码操作码
+    pub fn from_u8(byte: u8) -> Option<Self> {
+        use OpCode::*;
+        let opcode = match byte {
+            0x01 => LoadConst,
+            0x02 => LoadVar,
+            0x03 => StoreVar,
+            0x04 => LoadGlobal,
+            0x05 => StoreGlobal,
+            0x06 => LoadLocal,
+            0x07 => StoreLocal,
+            0x08 => TypeCheck,
+            0x09 => Dup,
+            0x10 => Add,
+            0x11 => Sub,
+            0x12 => Mul,
+            0x13 => Div,
+            0x14 => Mod,
+            0x15 => Pow,
+            0x20 => Eq,
+            0x21 => Ne,
+            0x22 => Lt,
+            0x23 => Gt,
+            0x24 => Le,
+            0x25 => Ge,
+            0x26 => In,
+            0x30 => And,
+            0x31 => Or,
+            0x32 => Not,
+            0x40 => Jump,
+            0x41 => JumpIfFalse,
+            0x42 => JumpIfTrue,
+            0x43 => JumpIfFalseOrPop,
+            0x44 => JumpIfTrueOrPop,
+            0x50 => Call,
+            0x51 => Return,
+            0x52 => LoadFunc,
+            0x53 => MakeClosure,
+            0x54 => LoadClosure,
+            0x55 => LoadDeref,
+            0x56 => StoreDeref,
+            0x57 => CallKw,
+            0x58 => TailCall,
+            0x59 => YieldValue,
+            0x5A => YieldFrom,
+            0x5B => GetAwaitable,
+            0x60 => Pop,
+            0x61 => TypeConvert,
+            0x62 => RotTwo,
+            0x63 => RotThree,
+            0x64 => Len,
+            0x65 => GetItem,
+            0x66 => SetItem,
+            0x67 => BuildList,
+            0x68 => SetAttr,
+            0x69 => ImportFrom,
+            0x6A => ImportModule,
+            0x6B => BuildTuple,
+            0x6C => FormatValue,
+            0x6D => GetIter,
+            0x6E => ForIter,
+            0x6F => ListAppend,
+            0x70 => GetAttr,
+            0x72 => HasAttr,
+            0x7A => BuildDict,
+            0x80 => CreateClass,
+            0x81 => CreateObject,
+            0x82 => CallMethod,
+            0x90 => TryBegin,
+            0x91 => TryEnd,
+            0x92 => CatchBegin,
+            0x93 => CatchEnd,
+            0x94 => FinallyBegin,
+            0x95 => FinallyEnd,
+            0x96 => Throw,
+            0x97 => Reraise,
+            0x98 => Print,
+            0xFF => Halt,
+            _ => return None,
+        };
+        Some(opcode)
+    }
+
+    /// 助记符（与 Python 端 `OpCode` 的成员名相同）
+    pub fn name(&self) -> &'static str {
+        use OpCode::*;
+        match self {
+            LoadConst => "LOAD_CONST",
+            LoadVar => "LOAD_VAR",
+            StoreVar => "STORE_VAR",
+            LoadGlobal => "LOAD_GLOBAL",
+            StoreGlobal => "STORE_GLOBAL",
+            LoadLocal => "LOAD_LOCAL",
+            StoreLocal => "STORE_LOCAL",
+            TypeCheck => "TYPE_CHECK",
+            Dup => "DUP",
+            Add => "ADD",
+            Sub => "SUB",
+            Mul => "MUL",
+            Div => "DIV",
+            Mod => "MOD",
+            Pow => "POW",
+            Eq => "EQ",
+            Ne => "NE",
+            Lt => "LT",
+            Gt => "GT",
+            Le => "LE",
+            Ge => "GE",
+            In => "IN",
+            And => "AND",
+            Or => "OR",
+            Not => "NOT",
+            Jump => "JUMP",
+            JumpIfFalse => "JUMP_IF_FALSE",
+            JumpIfTrue => "JUMP_IF_TRUE",
+            JumpIfFalseOrPop => "JUMP_IF_FALSE_OR_POP",
+            JumpIfTrueOrPop => "JUMP_IF_TRUE_OR_POP",
+            Call => "CALL",
+            Return => "RETURN",
+            LoadFunc => "LOAD_FUNC",
+            MakeClosure => "MAKE_CLOSURE",
+            LoadClosure => "LOAD_CLOSURE",
+            LoadDeref => "LOAD_DEREF",
+            StoreDeref => "STORE_DEREF",
+            CallKw => "CALL_KW",
+            TailCall => "TAIL_CALL",
+            YieldValue => "YIELD_VALUE",
+            YieldFrom => "YIELD_FROM",
+            GetAwaitable => "GET_AWAITABLE",
+            Pop => "POP",
+            TypeConvert => "TYPE_CONVERT",
+            RotTwo => "ROT_TWO",
+            RotThree => "ROT_THREE",
+            Len => "LEN",
+            GetItem => "GET_ITEM",
+            SetItem => "SET_ITEM",
+            BuildList => "BUILD_LIST",
+            SetAttr => "SET_ATTR",
+            ImportFrom => "IMPORT_FROM",
+            ImportModule => "IMPORT_MODULE",
+            BuildTuple => "BUILD_TUPLE",
+            FormatValue => "FORMAT_VALUE",
+            GetIter => "GET_ITER",
+            ForIter => "FOR_ITER",
+            ListAppend => "LIST_APPEND",
+            GetAttr => "GET_ATTR",
+            HasAttr => "HAS_ATTR",
+            BuildDict => "BUILD_DICT",
+            CreateClass => "CREATE_CLASS",
+            CreateObject => "CREATE_OBJECT",
+            CallMethod => "CALL_METHOD",
+            TryBegin => "TRY_BEGIN",
+            TryEnd => "TRY_END",
+            CatchBegin => "CATCH_BEGIN",
+            CatchEnd => "CATCH_END",
+            FinallyBegin => "FINALLY_BEGIN",
+            FinallyEnd => "FINALLY_END",
+            Throw => "THROW",
+            Reraise => "RERAISE",
+            Print => "PRINT",
+            Halt => "HALT",
+        }
+    }
+
+    /// 按助记符查找操作码（不区分大小写）
+    pub fn from_name(name: &str) -> Option<Self> {
+        let upper = name.to_ascii_uppercase();
+        (0..=u8::MAX)
+            .filter_map(Self::from_u8)
+            .find(|opcode| opcode.name() == upper)
+    }
+
+    /// 指令是否使用操作数
+    pub fn has_operand(&self) -> bool {
+        use OpCode::*;
+        matches!(
+            self,
+            LoadConst
+                | LoadVar
+                | StoreVar
+                | LoadGlobal
+                | StoreGlobal
+                | LoadLocal
+                | StoreLocal
+                | Jump
+                | JumpIfFalse
+                | JumpIfTrue
+                | JumpIfFalseOrPop
+                | JumpIfTrueOrPop
+                | Call
+                | LoadFunc
+                | MakeClosure
+                | LoadClosure
+                | LoadDeref
+                | StoreDeref
+                | CallKw
+                | TailCall
+                | BuildList
+                | BuildTuple
+                | BuildDict
+                | ForIter
+                | GetAttr
+                | HasAttr
+                | CreateClass
+                | CreateObject
+                | CallMethod
+                | CatchBegin
+                | FormatValue
+        )
+    }
+
+    /// 操作数是否为同一代码块内的跳转目标
+    pub fn is_jump(&self) -> bool {
+        use OpCode::*;
+        matches!(
+            self,
+            Jump | JumpIfFalse | JumpIfTrue | JumpIfFalseOrPop | JumpIfTrueOrPop | ForIter
+        )
+    }
+}
+
+impl TryFrom<u8> for OpCode {
+    type Error = VMError;
+
+    fn try_from(byte: u8) -> Result<Self> {
+        Self::from_u8(byte).ok_or(VMError::InvalidOpcode(byte))
+    }
+}
+
+/// 单条指令
+///
+/// 没有操作数的指令其 `operand` 为 0。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub opcode: OpCode,
+    pub operand: u32,
+}
+
+impl Instruction {
+    pub fn new(opcode: OpCode, operand: u32) -> Self {
+        Self { opcode, operand }
+    }
+}
+
+/// 一个完整的已编译程序
+#[derive(Debug, Clone, Default)]
+pub struct Bytecode {
+    /// 常量池
+    pub constants: Vec<Value>,
+
+    /// 全局变量名到槽位的映射
+    pub global_vars: HashMap<String, usize>,
+
+    /// 函数表
+    pub functions: FxHashMap<String, Function>,
+
+    /// 主程序指令
+    pub instructions: Vec<Instruction>,
+}
+
+impl Bytecode {
+    /// 从 `.acode` 文件内容解析字节码
+    pub fn from_acode_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 6 || &bytes[..4] != ACODE_MAGIC {
+            return Err(VMError::InvalidBytecode(
+                "wrong magic number, not an .acode file".to_string(),
+            ));
+        }
+
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        match version {
+            1 => v1::read(&bytes[6..]),
+            v2::VERSION => v2::read(&bytes[6..]),
+            _ => Err(VMError::InvalidBytecode(format!(
+                "unsupported bytecode version: {}",
+                version
+            ))),
+        }
+    }
+
+    /// 从磁盘读取 `.acode` 文件（自动识别版本）
+    pub fn read_from<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let bytes = std::fs::read(path)?;
+        Self::from_acode_bytes(&bytes)
+    }
+
+    /// 编码为第 2 版二进制 `.acode` 文件内容
+    pub fn to_acode_bytes(&self) -> Result<Vec<u8>> {
+        v2::write(self)
+    }
+
+    /// 以第 2 版格式写入磁盘
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, self.to_acode_bytes()?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,19 @@
+/*!
+尾调用改写
+
+加载时把紧跟 `RETURN` 的 `CALL` 改写为 `TAIL_CALL`，被调用的函数复用调用者的调用帧，
+尾递归不再受调用栈深度限制。`RETURN` 保留在原处：当前帧不能丢弃时
+（如调用位于 try 语句中），`TAIL_CALL` 按普通调用执行，结果仍由它返回。
+*/
+
+use super::{Instruction, OpCode};
+
+/// 改写处于尾位置的调用
+pub fn rewrite_tail_calls(instructions: &mut [Instruction]) {
+    for pc in 1..instructions.len() {
+        if instructions[pc].opcode == OpCode::Return && instructions[pc - 1].opcode == OpCode::Call
+        {
+            instructions[pc - 1].opcode = OpCode::TailCall;
+        }
+    }
+}
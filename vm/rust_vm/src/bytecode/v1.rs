@@ -0,0 +1,218 @@
+/*!
+`.acode` 第 1 版格式读取
+
+文件布局（与 `compiler/codegen.py` 的 `serialize_bytecode` 一致）：
+
+```text
+"AQUA" | u16 版本号 | u32 长度 + 常量池 JSON
+                    | u32 长度 + 全局变量表 JSON
+                    | u32 长度 + 函数表 JSON
+                    | u32 长度 + 主程序指令 JSON
+```
+
+所有整数均为小端序，指令以 `[opcode, operand | null]` 的形式存储。
+*/
+
+use super::{Bytecode, ExceptionTable, Instruction, OpCode, CATCH_ALL};
+use crate::dict::Dict;
+use crate::function::Function;
+use crate::value::Value;
+use crate::{Result, VMError};
+use byteorder::{LittleEndian, ReadBytesExt};
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+
+/// 函数表中单个函数的 JSON 结构
+#[derive(Deserialize)]
+struct RawFunction {
+    parameters: Vec<String>,
+    #[serde(default)]
+    kwonly_count: usize,
+    #[serde(default)]
+    varargs: bool,
+    #[serde(default)]
+    varkw: bool,
+    #[serde(default)]
+    generator: bool,
+    #[serde(default)]
+    coroutine: bool,
+    /// 默认值在常量池中的索引
+    #[serde(default)]
+    defaults: Vec<usize>,
+    #[serde(default)]
+    kwdefaults: HashMap<String, usize>,
+    local_vars: HashMap<String, usize>,
+    #[serde(default)]
+    cell_vars: Vec<usize>,
+    #[serde(default)]
+    free_vars: Vec<usize>,
+    instructions: Vec<(u8, Option<i64>)>,
+}
+
+/// 解析版本号之后的全部内容
+pub(super) fn read(payload: &[u8]) -> Result<Bytecode> {
+    let mut cursor = Cursor::new(payload);
+
+    let constants_json: Vec<serde_json::Value> =
+        parse_section(&read_section(&mut cursor, "constants")?, "constants")?;
+    let global_vars: HashMap<String, usize> =
+        parse_section(&read_section(&mut cursor, "globals")?, "globals")?;
+    let raw_functions: HashMap<String, RawFunction> =
+        parse_section(&read_section(&mut cursor, "functions")?, "functions")?;
+    let raw_main: Vec<(u8, Option<i64>)> =
+        parse_section(&read_section(&mut cursor, "main")?, "main")?;
+
+    if (cursor.position() as usize) < payload.len() {
+        return Err(VMError::InvalidBytecode(format!(
+            "{} trailing bytes after main section",
+            payload.len() - cursor.position() as usize
+        )));
+    }
+
+    let constants = constants_json
+        .iter()
+        .enumerate()
+        .map(|(index, json)| convert_constant(json, index))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut functions = FxHashMap::default();
+    for (name, raw) in raw_functions {
+        let instructions = convert_instructions(&raw.instructions, &name)?;
+        let default = |index: usize| {
+            constants.get(index).cloned().ok_or_else(|| {
+                VMError::InvalidBytecode(format!(
+                    "function '{}' default refers to constant {} out of range",
+                    name, index
+                ))
+            })
+        };
+        let defaults = raw
+            .defaults
+            .iter()
+            .map(|&index| default(index))
+            .collect::<Result<Vec<_>>>()?;
+        let kwdefaults = raw
+            .kwdefaults
+            .iter()
+            .map(|(parameter, &index)| Ok((parameter.clone(), default(index)?)))
+            .collect::<Result<HashMap<_, _>>>()?;
+        let function = Function {
+            name: name.clone(),
+            parameters: raw.parameters,
+            kwonly_count: raw.kwonly_count,
+            varargs: raw.varargs,
+            varkw: raw.varkw,
+            defaults,
+            kwdefaults,
+            generator: raw.generator,
+            coroutine: raw.coroutine,
+            instructions,
+            local_vars: raw.local_vars,
+            cell_vars: raw.cell_vars,
+            free_vars: raw.free_vars,
+            handlers: ExceptionTable::default(),
+            module: 0,
+            id: 0,
+        };
+        functions.insert(name, function);
+    }
+
+    let instructions = convert_instructions(&raw_main, "<main>")?;
+
+    Ok(Bytecode {
+        constants,
+        global_vars,
+        functions,
+        instructions,
+    })
+}
+
+/// 读取一个带 u32 长度前缀的段
+fn read_section(cursor: &mut Cursor<&[u8]>, section: &str) -> Result<Vec<u8>> {
+    let size = cursor
+        .read_u32::<LittleEndian>()
+        .map_err(|_| VMError::InvalidBytecode(format!("truncated {} section header", section)))?
+        as usize;
+
+    let remaining = cursor.get_ref().len() - cursor.position() as usize;
+    if size > remaining {
+        return Err(VMError::InvalidBytecode(format!(
+            "{} section declares {} bytes but only {} remain",
+            section, size, remaining
+        )));
+    }
+
+    let mut data = vec![0u8; size];
+    cursor.read_exact(&mut data)?;
+    Ok(data)
+}
+
+/// 将段内容解析为 JSON 结构
+fn parse_section<T: for<'de> Deserialize<'de>>(data: &[u8], section: &str) -> Result<T> {
+    serde_json::from_slice(data)
+        .map_err(|e| VMError::InvalidBytecode(format!("malformed {} section: {}", section, e)))
+}
+
+/// 将 JSON 常量转换为运行时值
+fn convert_constant(json: &serde_json::Value, index: usize) -> Result<Value> {
+    match json {
+        serde_json::Value::Null => Ok(Value::Null),
+        serde_json::Value::Bool(b) => Ok(Value::Bool(*b)),
+        serde_json::Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Ok(Value::Int(i))
+            } else if let Some(f) = n.as_f64() {
+                Ok(Value::Float(f))
+            } else {
+                Err(VMError::InvalidBytecode(format!(
+                    "constant {} is not a representable number: {}",
+                    index, n
+                )))
+            }
+        }
+        serde_json::Value::String(s) => Ok(Value::String(s.as_str().into())),
+        // 常量池中的数组（如导入名列表）不可变，按元组加载
+        serde_json::Value::Array(items) => Ok(Value::Tuple(
+            items
+                .iter()
+                .map(|item| convert_constant(item, index))
+                .collect::<Result<Vec<_>>>()?
+                .into(),
+        )),
+        // 字典常量是可变的，LOAD_CONST 每次压入它的副本（见 `Value::load_constant`）
+        serde_json::Value::Object(map) => {
+            let mut dict = Dict::new();
+            for (key, value) in map {
+                dict.insert(
+                    Value::String(key.as_str().into()),
+                    convert_constant(value, index)?,
+                )?;
+            }
+            Ok(Value::dict(dict))
+        }
+    }
+}
+
+/// 将 `[opcode, operand]` 对转换为指令
+fn convert_instructions(raw: &[(u8, Option<i64>)], owner: &str) -> Result<Vec<Instruction>> {
+    raw.iter()
+        .enumerate()
+        .map(|(pc, &(byte, operand))| {
+            let opcode = OpCode::try_from(byte)?;
+            let operand = match operand {
+                // 编译器对不带类型的 catch 子句省略操作数
+                None if opcode == OpCode::CatchBegin => CATCH_ALL,
+                None => 0,
+                Some(value) => u32::try_from(value).map_err(|_| {
+                    VMError::InvalidBytecode(format!(
+                        "{}: instruction {} ({:?}) has invalid operand {}",
+                        owner, pc, opcode, value
+                    ))
+                })?,
+            };
+            Ok(Instruction::new(opcode, operand))
+        })
+        .collect()
+}
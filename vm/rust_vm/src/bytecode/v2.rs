@@ -0,0 +1,581 @@
+/*!
+`.acode` 第 2 版二进制格式
+
+相比第 1 版的 JSON 段，第 2 版是紧凑的二进制编码：
+
+```text
+"AQUA" | u16 版本号 (2) | u32 CRC32(载荷) | 载荷
+
+载荷:
+  varint 段数量
+  段表: 每项 u8 段编号 | u32 偏移 | u32 长度（偏移相对于载荷起点）
+  段数据...
+```
+
+段编号：1 常量池、2 全局变量表、3 函数表、4 主程序指令。
+未知编号的段会被忽略，便于以后扩展。
+
+- 整数使用 LEB128 varint，有符号数先做 zigzag 编码
+- 字符串为 varint 长度 + UTF-8 字节
+- 指令为 u8 操作码 + varint 操作数
+- 常量为 u8 标签 + 数据，见 [`tag`]；元组、列表为 varint 个数 + 元素，
+  字典为 varint 个数 + 交替的键和值。常量被每次 `LOAD_CONST` 共享，
+  因此列表与第 1 版一样解码为元组；嵌套超过 [`MAX_DEPTH`] 层的常量视为损坏
+- 函数依次为函数名、参数名、局部变量表、单元和自由变量槽位、仅限关键字参数个数、
+  u8 标志（bit 0 为 `*args`，bit 1 为 `**kwargs`，bit 2 为生成器，bit 3 为协程）、位置参数默认值、仅限关键字参数默认值和指令
+*/
+
+use super::{Bytecode, ExceptionTable, Instruction, OpCode, ACODE_MAGIC};
+use crate::dict::Dict;
+use crate::function::Function;
+use crate::value::Value;
+use crate::{Result, VMError};
+use rustc_hash::FxHashMap;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// 第 2 版格式的版本号
+pub const VERSION: u16 = 2;
+
+/// 段编号
+mod section {
+    pub const CONSTANTS: u8 = 1;
+    pub const GLOBALS: u8 = 2;
+    pub const FUNCTIONS: u8 = 3;
+    pub const MAIN: u8 = 4;
+}
+
+/// 常量标签
+mod tag {
+    pub const NULL: u8 = 0;
+    pub const FALSE: u8 = 1;
+    pub const TRUE: u8 = 2;
+    pub const INT: u8 = 3;
+    pub const FLOAT: u8 = 4;
+    pub const STRING: u8 = 5;
+    pub const BYTES: u8 = 6;
+    pub const CODE: u8 = 7;
+    pub const TUPLE: u8 = 8;
+    pub const LIST: u8 = 9;
+    pub const DICT: u8 = 10;
+}
+
+/// 段表中每一项的字节数
+const SECTION_ENTRY_SIZE: usize = 9;
+
+/// 常量（元组、字典和代码对象）的最大嵌套层数，防止构造的文件耗尽原生栈
+const MAX_DEPTH: usize = 64;
+
+/// 将字节码编码为完整的 v2 文件
+pub(super) fn write(bytecode: &Bytecode) -> Result<Vec<u8>> {
+    let mut constants = Writer::default();
+    constants.varint(bytecode.constants.len() as u64);
+    for constant in &bytecode.constants {
+        constants.constant(constant)?;
+    }
+
+    // 按槽位 / 名称排序，保证同一程序总是产生相同的文件
+    let mut globals_sorted: Vec<_> = bytecode.global_vars.iter().collect();
+    globals_sorted.sort_by_key(|&(name, &slot)| (slot, name));
+    let mut globals = Writer::default();
+    globals.varint(globals_sorted.len() as u64);
+    for (name, &slot) in globals_sorted {
+        globals.string(name);
+        globals.varint(slot as u64);
+    }
+
+    let mut functions_sorted: Vec<_> = bytecode.functions.values().collect();
+    functions_sorted.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut functions = Writer::default();
+    functions.varint(functions_sorted.len() as u64);
+    for function in functions_sorted {
+        functions.code(function)?;
+    }
+
+    let mut main = Writer::default();
+    main.instructions(&bytecode.instructions);
+
+    let sections = [
+        (section::CONSTANTS, constants.buf),
+        (section::GLOBALS, globals.buf),
+        (section::FUNCTIONS, functions.buf),
+        (section::MAIN, main.buf),
+    ];
+
+    let mut payload = Writer::default();
+    payload.varint(sections.len() as u64);
+    let table_end = payload.buf.len() + sections.len() * SECTION_ENTRY_SIZE;
+    let mut offset = table_end;
+    for (id, data) in &sections {
+        payload.buf.push(*id);
+        payload.u32(offset_u32(offset)?);
+        payload.u32(offset_u32(data.len())?);
+        offset += data.len();
+    }
+    for (_, data) in &sections {
+        payload.buf.extend_from_slice(data);
+    }
+
+    let mut bytes = Vec::with_capacity(10 + payload.buf.len());
+    bytes.extend_from_slice(ACODE_MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&crc32fast::hash(&payload.buf).to_le_bytes());
+    bytes.extend_from_slice(&payload.buf);
+    Ok(bytes)
+}
+
+/// 解析版本号之后的全部内容
+pub(super) fn read(data: &[u8]) -> Result<Bytecode> {
+    if data.len() < 4 {
+        return Err(invalid("truncated checksum"));
+    }
+    let expected = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    let payload = &data[4..];
+    let actual = crc32fast::hash(payload);
+    if expected != actual {
+        return Err(invalid(format!(
+            "checksum mismatch (expected {:08x}, got {:08x})",
+            expected, actual
+        )));
+    }
+
+    let mut table = Reader::new(payload, "section table");
+    let count = table.varint()? as usize;
+    let mut sections: HashMap<u8, &[u8]> = HashMap::new();
+    for _ in 0..count {
+        let id = table.u8()?;
+        let offset = table.u32()? as usize;
+        let len = table.u32()? as usize;
+        let data = offset
+            .checked_add(len)
+            .and_then(|end| payload.get(offset..end))
+            .ok_or_else(|| invalid(format!("section {} lies outside the file", id)))?;
+        if sections.insert(id, data).is_some() {
+            return Err(invalid(format!("duplicate section {}", id)));
+        }
+    }
+
+    let required = |id: u8, name: &'static str| {
+        sections
+            .get(&id)
+            .map(|data| Reader::new(data, name))
+            .ok_or_else(|| invalid(format!("missing {} section", name)))
+    };
+
+    let mut reader = required(section::CONSTANTS, "constants")?;
+    let count = reader.varint()? as usize;
+    let mut constants = Vec::with_capacity(count.min(reader.remaining()));
+    for _ in 0..count {
+        constants.push(reader.constant()?);
+    }
+    reader.finish()?;
+
+    let mut reader = required(section::GLOBALS, "globals")?;
+    let count = reader.varint()? as usize;
+    let mut global_vars = HashMap::with_capacity(count.min(reader.remaining()));
+    for _ in 0..count {
+        let name = reader.string()?;
+        let slot = reader.varint()? as usize;
+        global_vars.insert(name, slot);
+    }
+    reader.finish()?;
+
+    let mut reader = required(section::FUNCTIONS, "functions")?;
+    let count = reader.varint()? as usize;
+    let mut functions = FxHashMap::default();
+    for _ in 0..count {
+        let function = reader.code()?;
+        functions.insert(function.name.clone(), function);
+    }
+    reader.finish()?;
+
+    let mut reader = required(section::MAIN, "main")?;
+    let instructions = reader.instructions()?;
+    reader.finish()?;
+
+    Ok(Bytecode {
+        constants,
+        global_vars,
+        functions,
+        instructions,
+    })
+}
+
+fn invalid(message: impl Into<String>) -> VMError {
+    VMError::InvalidBytecode(message.into())
+}
+
+fn offset_u32(value: usize) -> Result<u32> {
+    u32::try_from(value).map_err(|_| invalid("bytecode exceeds 4 GiB"))
+}
+
+/// 二进制编码缓冲区
+#[derive(Default)]
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn u32(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.buf.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.buf.push(value as u8);
+    }
+
+    fn signed_varint(&mut self, value: i64) {
+        self.varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    fn bytes(&mut self, data: &[u8]) {
+        self.varint(data.len() as u64);
+        self.buf.extend_from_slice(data);
+    }
+
+    fn string(&mut self, s: &str) {
+        self.bytes(s.as_bytes());
+    }
+
+    fn sequence(&mut self, items: &[Value]) -> Result<()> {
+        self.varint(items.len() as u64);
+        for item in items {
+            self.constant(item)?;
+        }
+        Ok(())
+    }
+
+    fn instructions(&mut self, instructions: &[Instruction]) {
+        self.varint(instructions.len() as u64);
+        for instruction in instructions {
+            self.buf.push(instruction.opcode as u8);
+            self.varint(instruction.operand as u64);
+        }
+    }
+
+    fn slots(&mut self, slots: &[usize]) {
+        self.varint(slots.len() as u64);
+        for &slot in slots {
+            self.varint(slot as u64);
+        }
+    }
+
+    fn code(&mut self, function: &Function) -> Result<()> {
+        self.string(&function.name);
+        self.varint(function.parameters.len() as u64);
+        for parameter in &function.parameters {
+            self.string(parameter);
+        }
+        let mut locals: Vec<_> = function.local_vars.iter().collect();
+        locals.sort_by_key(|&(name, &slot)| (slot, name));
+        self.varint(locals.len() as u64);
+        for (name, &slot) in locals {
+            self.string(name);
+            self.varint(slot as u64);
+        }
+        self.slots(&function.cell_vars);
+        self.slots(&function.free_vars);
+        self.varint(function.kwonly_count as u64);
+        let flags = function.varargs as u8
+            | (function.varkw as u8) << 1
+            | (function.generator as u8) << 2
+            | (function.coroutine as u8) << 3;
+        self.buf.push(flags);
+        self.varint(function.defaults.len() as u64);
+        for default in &function.defaults {
+            self.constant(default)?;
+        }
+        let mut kwdefaults: Vec<_> = function.kwdefaults.iter().collect();
+        kwdefaults.sort_by_key(|&(name, _)| name);
+        self.varint(kwdefaults.len() as u64);
+        for (name, default) in kwdefaults {
+            self.string(name);
+            self.constant(default)?;
+        }
+        self.instructions(&function.instructions);
+        Ok(())
+    }
+
+    fn constant(&mut self, value: &Value) -> Result<()> {
+        match value {
+            Value::Null => self.buf.push(tag::NULL),
+            Value::Bool(false) => self.buf.push(tag::FALSE),
+            Value::Bool(true) => self.buf.push(tag::TRUE),
+            Value::Int(i) => {
+                self.buf.push(tag::INT);
+                self.signed_varint(*i);
+            }
+            Value::Float(f) => {
+                self.buf.push(tag::FLOAT);
+                self.buf.extend_from_slice(&f.to_le_bytes());
+            }
+            Value::String(s) => {
+                self.buf.push(tag::STRING);
+                self.string(s);
+            }
+            Value::Bytes(b) => {
+                self.buf.push(tag::BYTES);
+                self.bytes(b);
+            }
+            Value::Tuple(items) => {
+                self.buf.push(tag::TUPLE);
+                self.sequence(items)?;
+            }
+            Value::List(items) => {
+                self.buf.push(tag::LIST);
+                self.sequence(&items.borrow())?;
+            }
+            Value::Dict(dict) => {
+                let dict = dict.borrow();
+                self.buf.push(tag::DICT);
+                self.varint(dict.len() as u64);
+                for (key, value) in dict.iter() {
+                    self.constant(key)?;
+                    self.constant(value)?;
+                }
+            }
+            Value::Code(function) => {
+                self.buf.push(tag::CODE);
+                self.code(function)?;
+            }
+            // 运行期才会产生的值
+            Value::Range(_)
+            | Value::Iterator(_)
+            | Value::Class(_)
+            | Value::Instance(_)
+            | Value::BoundMethod(_)
+            | Value::Super(_)
+            | Value::Function(_)
+            | Value::Builtin(_)
+            | Value::Closure(_)
+            | Value::Cell(_)
+            | Value::Module(_)
+            | Value::Generator(_)
+            | Value::Future(_) => {
+                return Err(VMError::InvalidBytecode(format!(
+                    "cannot encode a {} constant",
+                    value.type_name()
+                )))
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 二进制解码游标
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    section: &'static str,
+    /// 当前常量的嵌套层数
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], section: &'static str) -> Self {
+        Self {
+            data,
+            pos: 0,
+            section,
+            depth: 0,
+        }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn truncated(&self) -> VMError {
+        invalid(format!("truncated {} section", self.section))
+    }
+
+    fn finish(&self) -> Result<()> {
+        if self.remaining() != 0 {
+            return Err(invalid(format!(
+                "{} trailing bytes in {} section",
+                self.remaining(),
+                self.section
+            )));
+        }
+        Ok(())
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.remaining() {
+            return Err(self.truncated());
+        }
+        let slice = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    /// 进入一层嵌套的常量
+    fn nested<T>(&mut self, read: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth == MAX_DEPTH {
+            return Err(invalid(format!(
+                "constants nested more than {} levels deep in {} section",
+                MAX_DEPTH, self.section
+            )));
+        }
+        self.depth += 1;
+        let result = read(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid(format!(
+            "varint too long in {} section",
+            self.section
+        )))
+    }
+
+    fn signed_varint(&mut self) -> Result<i64> {
+        let raw = self.varint()?;
+        Ok(((raw >> 1) as i64) ^ -((raw & 1) as i64))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.varint()? as usize;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let raw = self.bytes()?;
+        String::from_utf8(raw.to_vec())
+            .map_err(|_| invalid(format!("invalid UTF-8 string in {} section", self.section)))
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Value>> {
+        let count = self.varint()? as usize;
+        let mut items = Vec::with_capacity(count.min(self.remaining()));
+        for _ in 0..count {
+            items.push(self.constant()?);
+        }
+        Ok(items)
+    }
+
+    fn instructions(&mut self) -> Result<Vec<Instruction>> {
+        let count = self.varint()? as usize;
+        let mut instructions = Vec::with_capacity(count.min(self.remaining()));
+        for _ in 0..count {
+            let opcode = OpCode::try_from(self.u8()?)?;
+            let operand = u32::try_from(self.varint()?).map_err(|_| {
+                invalid(format!("operand out of range in {} section", self.section))
+            })?;
+            instructions.push(Instruction::new(opcode, operand));
+        }
+        Ok(instructions)
+    }
+
+    fn slots(&mut self) -> Result<Vec<usize>> {
+        let count = self.varint()? as usize;
+        let mut slots = Vec::with_capacity(count.min(self.remaining()));
+        for _ in 0..count {
+            slots.push(self.varint()? as usize);
+        }
+        Ok(slots)
+    }
+
+    fn code(&mut self) -> Result<Function> {
+        let name = self.string()?;
+        let count = self.varint()? as usize;
+        let mut parameters = Vec::with_capacity(count.min(self.remaining()));
+        for _ in 0..count {
+            parameters.push(self.string()?);
+        }
+        let count = self.varint()? as usize;
+        let mut local_vars = HashMap::with_capacity(count.min(self.remaining()));
+        for _ in 0..count {
+            let local = self.string()?;
+            let slot = self.varint()? as usize;
+            local_vars.insert(local, slot);
+        }
+        let cell_vars = self.slots()?;
+        let free_vars = self.slots()?;
+        let kwonly_count = self.varint()? as usize;
+        let flags = self.u8()?;
+        let count = self.varint()? as usize;
+        let mut defaults = Vec::with_capacity(count.min(self.remaining()));
+        for _ in 0..count {
+            defaults.push(self.constant()?);
+        }
+        let count = self.varint()? as usize;
+        let mut kwdefaults = HashMap::with_capacity(count.min(self.remaining()));
+        for _ in 0..count {
+            let parameter = self.string()?;
+            kwdefaults.insert(parameter, self.constant()?);
+        }
+        let instructions = self.instructions()?;
+        Ok(Function {
+            name,
+            parameters,
+            kwonly_count,
+            varargs: flags & 1 != 0,
+            varkw: flags & 2 != 0,
+            defaults,
+            kwdefaults,
+            generator: flags & 4 != 0,
+            coroutine: flags & 8 != 0,
+            instructions,
+            local_vars,
+            cell_vars,
+            free_vars,
+            handlers: ExceptionTable::default(),
+            module: 0,
+            id: 0,
+        })
+    }
+
+    fn constant(&mut self) -> Result<Value> {
+        let value = match self.u8()? {
+            tag::NULL => Value::Null,
+            tag::FALSE => Value::Bool(false),
+            tag::TRUE => Value::Bool(true),
+            tag::INT => Value::Int(self.signed_varint()?),
+            tag::FLOAT => {
+                let b = self.take(8)?;
+                let mut raw = [0u8; 8];
+                raw.copy_from_slice(b);
+                Value::Float(f64::from_le_bytes(raw))
+            }
+            tag::STRING => Value::String(self.string()?.into()),
+            tag::BYTES => Value::Bytes(self.bytes()?.into()),
+            tag::CODE => Value::Code(Rc::new(self.nested(Self::code)?)),
+            tag::TUPLE | tag::LIST => Value::Tuple(self.nested(Self::sequence)?.into()),
+            // 与 v1 相同，LOAD_CONST 每次压入字典常量的副本
+            tag::DICT => self.nested(|reader| {
+                let mut dict = Dict::new();
+                for _ in 0..reader.varint()? {
+                    let key = reader.constant()?;
+                    let value = reader.constant()?;
+                    dict.insert(key, value).map_err(|e| invalid(e.to_string()))?;
+                }
+                Ok(Value::dict(dict))
+            })?,
+            other => return Err(invalid(format!("unknown constant tag {}", other))),
+        };
+        Ok(value)
+    }
+}
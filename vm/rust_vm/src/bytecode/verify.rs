@@ -0,0 +1,580 @@
+/*!
+字节码静态校验
+
+在执行前检查程序的结构性错误，避免执行期的越界访问导致进程崩溃：
+- 跳转目标必须落在指令范围内
+- 常量、全局变量、局部变量槽位不能越界
+- `LOAD_FUNC` 必须引用已存在的函数或内置函数
+- `LOAD_DEREF` / `STORE_DEREF` / `LOAD_CLOSURE` 只能访问函数声明的单元槽位
+- `FORMAT_VALUE` 的转换标志合法，格式说明必须是字符串常量
+- `IMPORT_FROM` 之前的 `LOAD_CONST` 给出导入名元组，据此确定压栈的值个数；
+  `CALL_KW` 之前的 `LOAD_CONST` 同样给出关键字参数名元组
+- 参数的种类和默认值与参数表一致
+- 常量池中的代码对象（包括默认值里的代码对象）与具名函数一样逐一校验
+- `YIELD_VALUE` / `YIELD_FROM` 只能出现在生成器函数中，`HALT` 只能出现在主程序中
+- 通过数据流分析计算每条指令处的栈深度，拒绝栈下溢，
+  以及控制流汇合处栈高度不一致的情况
+- `try`/`catch`/`finally` 结构完整，并生成各代码块的异常处理表
+*/
+
+use super::handlers::{self, ExceptionTable};
+use super::{split_format_operand, Bytecode, Instruction, OpCode, CATCH_ALL, FORMAT_REPR};
+use crate::builtins::BuiltinFunction;
+use crate::function::Function;
+use crate::value::Value;
+use crate::{Result, VMError};
+use rustc_hash::FxHashMap;
+
+/// 主程序在错误信息中的名字
+const MAIN_NAME: &str = "<main>";
+
+/// 校验结果：各代码块的异常处理表
+#[derive(Debug, Clone, Default)]
+pub struct Analysis {
+    /// 主程序的异常处理表
+    pub main: ExceptionTable,
+
+    /// 函数名到异常处理表的映射
+    pub functions: FxHashMap<String, ExceptionTable>,
+
+    /// 代码对象常量在常量池中的序号到异常处理表的映射
+    pub codes: FxHashMap<usize, ExceptionTable>,
+}
+
+/// 校验整个程序
+pub fn verify(bytecode: &Bytecode) -> Result<()> {
+    analyze(bytecode).map(drop)
+}
+
+/// 校验整个程序并生成各代码块的异常处理表
+pub fn analyze(bytecode: &Bytecode) -> Result<Analysis> {
+    for (name, &slot) in &bytecode.global_vars {
+        if slot >= bytecode.global_vars.len() {
+            return Err(VMError::Verify {
+                function: MAIN_NAME.to_string(),
+                pc: 0,
+                reason: format!("global '{}' uses slot {} outside the table", name, slot),
+            });
+        }
+    }
+
+    let main = Verifier {
+        bytecode,
+        name: MAIN_NAME,
+        instructions: &bytecode.instructions,
+        locals: None,
+        captured: Vec::new(),
+        generator: false,
+        coroutine: false,
+    }
+    .run()?;
+
+    let mut functions = FxHashMap::default();
+    let mut names: Vec<_> = bytecode.functions.keys().collect();
+    names.sort();
+    for name in names {
+        let function = &bytecode.functions[name];
+        let handlers = Verifier::for_function(bytecode, function)?.run()?;
+        functions.insert(name.clone(), handlers);
+        verify_defaults(bytecode, function)?;
+    }
+    // 常量池顶层的代码对象由 `LOAD_FUNC` 加载执行，需要异常处理表；
+    // 嵌套在其他常量中的代码对象只做校验
+    let mut codes = FxHashMap::default();
+    for (index, constant) in bytecode.constants.iter().enumerate() {
+        match constant {
+            Value::Code(function) => {
+                codes.insert(index, Verifier::for_function(bytecode, function)?.run()?);
+                verify_defaults(bytecode, function)?;
+            }
+            other => verify_nested(bytecode, other)?,
+        }
+    }
+
+    Ok(Analysis {
+        main,
+        functions,
+        codes,
+    })
+}
+
+/// 校验常量中嵌套的代码对象
+fn verify_nested(bytecode: &Bytecode, value: &Value) -> Result<()> {
+    match value {
+        Value::Code(function) => {
+            Verifier::for_function(bytecode, function)?.run()?;
+            verify_defaults(bytecode, function)
+        }
+        Value::Tuple(items) => items
+            .iter()
+            .try_for_each(|item| verify_nested(bytecode, item)),
+        Value::List(items) => items
+            .borrow()
+            .iter()
+            .try_for_each(|item| verify_nested(bytecode, item)),
+        Value::Dict(dict) => dict.borrow().iter().try_for_each(|(key, value)| {
+            verify_nested(bytecode, key)?;
+            verify_nested(bytecode, value)
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// 校验函数默认值中的代码对象
+fn verify_defaults(bytecode: &Bytecode, function: &Function) -> Result<()> {
+    function
+        .defaults
+        .iter()
+        .chain(function.kwdefaults.values())
+        .try_for_each(|default| verify_nested(bytecode, default))
+}
+
+/// 指令执行后控制流的去向
+enum Flow {
+    /// 顺序执行下一条
+    Next,
+    /// 无条件跳转
+    Jump(usize),
+    /// 条件跳转：两条路径上的栈高度相同
+    Branch(usize),
+    /// 短路跳转：跳转时保留栈顶，继续执行时弹出
+    BranchOrPop(usize),
+    /// `FOR_ITER`：继续时多压入一个元素，结束时弹出迭代器并跳转
+    ForIter(usize),
+    /// `TRY_BEGIN`：除顺序执行外，异常可能转入该语句的各个 catch 子句和 finally 块
+    Try(usize),
+    /// 结束当前代码块
+    Stop,
+}
+
+struct Verifier<'a> {
+    bytecode: &'a Bytecode,
+    name: &'a str,
+    instructions: &'a [Instruction],
+    /// 局部变量数量，主程序为 `None`
+    locals: Option<usize>,
+    /// 存放单元的槽位：被捕获的局部变量和自由变量
+    captured: Vec<usize>,
+    /// 是否为生成器函数
+    generator: bool,
+    /// 是否为协程函数
+    coroutine: bool,
+}
+
+impl<'a> Verifier<'a> {
+    fn for_function(bytecode: &'a Bytecode, function: &'a Function) -> Result<Self> {
+        let locals = function.local_vars.len();
+        let verifier = Self {
+            bytecode,
+            name: &function.name,
+            instructions: &function.instructions,
+            locals: Some(locals),
+            captured: [&function.cell_vars[..], &function.free_vars[..]].concat(),
+            generator: function.generator,
+            coroutine: function.coroutine,
+        };
+        if function.parameters.len() > locals {
+            return Err(verifier.error(
+                0,
+                format!(
+                    "{} parameters but only {} local slots",
+                    function.parameters.len(),
+                    locals
+                ),
+            ));
+        }
+        let kinds = function.kwonly_count + function.varargs as usize + function.varkw as usize;
+        if kinds > function.parameters.len() {
+            return Err(verifier.error(
+                0,
+                format!(
+                    "{} keyword-only and variadic parameters but only {} parameters",
+                    kinds,
+                    function.parameters.len()
+                ),
+            ));
+        }
+        if function.defaults.len() > function.positional_count() {
+            return Err(verifier.error(
+                0,
+                format!(
+                    "{} defaults for {} positional parameters",
+                    function.defaults.len(),
+                    function.positional_count()
+                ),
+            ));
+        }
+        let kwonly_start = function.positional_count() + function.varargs as usize;
+        let kwonly = &function.parameters[kwonly_start..kwonly_start + function.kwonly_count];
+        if let Some(name) = function
+            .kwdefaults
+            .keys()
+            .find(|name| !kwonly.contains(name))
+        {
+            return Err(verifier.error(
+                0,
+                format!(
+                    "default for '{}' which is not a keyword-only parameter",
+                    name
+                ),
+            ));
+        }
+        for (local, &slot) in &function.local_vars {
+            if slot >= locals {
+                return Err(verifier.error(
+                    0,
+                    format!("local '{}' uses slot {} outside the table", local, slot),
+                ));
+            }
+        }
+        if let Some(&slot) = verifier.captured.iter().find(|&&slot| slot >= locals) {
+            return Err(verifier.error(
+                0,
+                format!("captured variable slot {} outside the table", slot),
+            ));
+        }
+        Ok(verifier)
+    }
+
+    fn error(&self, pc: usize, reason: impl Into<String>) -> VMError {
+        VMError::Verify {
+            function: self.name.to_string(),
+            pc,
+            reason: reason.into(),
+        }
+    }
+
+    fn run(&self) -> Result<ExceptionTable> {
+        for (pc, instruction) in self.instructions.iter().enumerate() {
+            self.check_operand(pc, instruction)?;
+        }
+        let mut handlers = handlers::build(self.instructions, &self.bytecode.constants)
+            .map_err(|(pc, reason)| self.error(pc, reason))?;
+        let depth = self.check_stack(&handlers)?;
+        for handler in &mut handlers.handlers {
+            handler.depth = depth[handler.start].unwrap_or(0);
+        }
+        Ok(handlers)
+    }
+
+    /// 检查操作数引用的槽位与跳转目标
+    fn check_operand(&self, pc: usize, instruction: &Instruction) -> Result<()> {
+        let operand = instruction.operand as usize;
+        let constants = self.bytecode.constants.len();
+        let globals = self.bytecode.global_vars.len();
+
+        let check_index = |limit: usize, what: &str| {
+            if operand >= limit {
+                Err(self.error(
+                    pc,
+                    format!("{} index {} out of range (size {})", what, operand, limit),
+                ))
+            } else {
+                Ok(())
+            }
+        };
+
+        match instruction.opcode {
+            OpCode::LoadConst | OpCode::GetAttr | OpCode::HasAttr => {
+                check_index(constants, "constant")
+            }
+
+            OpCode::CallMethod | OpCode::CreateClass => {
+                let what = if instruction.opcode == OpCode::CallMethod {
+                    "method"
+                } else {
+                    "class"
+                };
+                let name = (instruction.operand & 0xFFFF) as usize;
+                if name >= constants {
+                    return Err(
+                        self.error(pc, format!("{} name constant {} out of range", what, name))
+                    );
+                }
+                Ok(())
+            }
+
+            OpCode::LoadFunc => {
+                check_index(constants, "constant")?;
+                match &self.bytecode.constants[operand] {
+                    Value::String(name)
+                        if self.bytecode.functions.contains_key(&**name)
+                            || BuiltinFunction::from_name(name).is_some() =>
+                    {
+                        Ok(())
+                    }
+                    Value::String(name) => {
+                        Err(self.error(pc, format!("reference to unknown function '{}'", name)))
+                    }
+                    Value::Code(_) => Ok(()),
+                    other => Err(self.error(
+                        pc,
+                        format!(
+                            "LOAD_FUNC operand is a {}, not a function",
+                            other.type_name()
+                        ),
+                    )),
+                }
+            }
+
+            OpCode::FormatValue => {
+                let (conversion, spec) = split_format_operand(instruction.operand);
+                if conversion > FORMAT_REPR {
+                    return Err(self.error(pc, format!("invalid conversion flag {}", conversion)));
+                }
+                match spec.map(|index| (index, self.bytecode.constants.get(index))) {
+                    None | Some((_, Some(Value::String(_)))) => Ok(()),
+                    Some((index, None)) => {
+                        Err(self.error(pc, format!("format spec constant {} out of range", index)))
+                    }
+                    Some((_, Some(other))) => Err(self.error(
+                        pc,
+                        format!("format spec is a {}, not a str", other.type_name()),
+                    )),
+                }
+            }
+
+            OpCode::CatchBegin if instruction.operand == CATCH_ALL => Ok(()),
+            OpCode::CatchBegin => match self.bytecode.constants.get(operand) {
+                Some(Value::String(_)) => Ok(()),
+                None => Err(self.error(
+                    pc,
+                    format!("exception type constant {} out of range", operand),
+                )),
+                Some(other) => Err(self.error(
+                    pc,
+                    format!("exception type is a {}, not a str", other.type_name()),
+                )),
+            },
+
+            // `await` 编译为 `GET_AWAITABLE; LOAD_CONST None; YIELD_FROM`
+            OpCode::YieldFrom if self.coroutine => Ok(()),
+            OpCode::YieldValue | OpCode::YieldFrom if !self.generator => Err(self.error(
+                pc,
+                format!("{} outside a generator function", instruction.opcode.name()),
+            )),
+            OpCode::GetAwaitable if !self.coroutine => {
+                Err(self.error(pc, "GET_AWAITABLE outside a coroutine function"))
+            }
+
+            // HALT 会清空整个调用栈，函数可能在特殊方法、生成器或导入中被嵌套执行
+            OpCode::Halt if self.locals.is_some() => {
+                Err(self.error(pc, "HALT outside the main program, use RETURN"))
+            }
+
+            OpCode::LoadGlobal | OpCode::StoreGlobal => check_index(globals, "global"),
+
+            OpCode::LoadLocal | OpCode::StoreLocal => match self.locals {
+                Some(locals) => check_index(locals, "local"),
+                None => Err(self.error(pc, "local variable access outside a function")),
+            },
+
+            OpCode::LoadDeref | OpCode::StoreDeref | OpCode::LoadClosure => {
+                if self.locals.is_none() {
+                    return Err(self.error(pc, "captured variable access outside a function"));
+                }
+                if !self.captured.contains(&operand) {
+                    return Err(self.error(
+                        pc,
+                        format!("local slot {} is not a cell or free variable", operand),
+                    ));
+                }
+                Ok(())
+            }
+
+            // 旧指令：函数内访问局部变量，主程序中访问全局变量
+            OpCode::LoadVar | OpCode::StoreVar => match self.locals {
+                Some(locals) => check_index(locals, "local"),
+                None => check_index(globals, "global"),
+            },
+
+            _ if instruction.opcode.is_jump() => {
+                if operand > self.instructions.len() {
+                    Err(self.error(
+                        pc,
+                        format!(
+                            "jump target {} outside code (length {})",
+                            operand,
+                            self.instructions.len()
+                        ),
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
+
+            _ => Ok(()),
+        }
+    }
+
+    /// 栈效应：(弹出数量, 压入数量, 控制流)
+    fn effect(
+        &self,
+        pc: usize,
+        instruction: &Instruction,
+        handlers: &ExceptionTable,
+    ) -> Result<(usize, usize, Flow)> {
+        use OpCode::*;
+        let operand = instruction.operand as usize;
+        let innermost = || {
+            let index = handlers.innermost(pc);
+            &handlers[index.expect("exception table covers every try statement")]
+        };
+        let effect = match instruction.opcode {
+            LoadConst | LoadVar | LoadGlobal | LoadLocal | LoadFunc | LoadDeref | LoadClosure => {
+                (0, 1, Flow::Next)
+            }
+            StoreVar | StoreGlobal | StoreLocal | StoreDeref | Pop | Print => (1, 0, Flow::Next),
+            TypeCheck | FinallyBegin | FinallyEnd => (0, 0, Flow::Next),
+            TryBegin => {
+                let index = handlers
+                    .innermost(pc)
+                    .expect("exception table covers every try statement");
+                (0, 0, Flow::Try(index))
+            }
+            // try 块和 catch 块正常结束后进入 finally 块或跳到语句之后
+            TryEnd | CatchEnd => (0, 0, Flow::Jump(innermost().exit())),
+            Dup => (1, 2, Flow::Next),
+            RotTwo => (2, 2, Flow::Next),
+            RotThree => (3, 3, Flow::Next),
+
+            Add | Sub | Mul | Div | Mod | Pow | Eq | Ne | Lt | Gt | Le | Ge | In | And | Or => {
+                (2, 1, Flow::Next)
+            }
+            Not | TypeConvert | Len | FormatValue | GetIter | GetAttr | HasAttr | ImportModule => {
+                (1, 1, Flow::Next)
+            }
+
+            Jump => (0, 0, Flow::Jump(operand)),
+            JumpIfFalse | JumpIfTrue => (1, 0, Flow::Branch(operand)),
+            JumpIfFalseOrPop | JumpIfTrueOrPop => (1, 0, Flow::BranchOrPop(operand)),
+            ForIter => (1, 1, Flow::ForIter(operand)),
+
+            Call | TailCall | CreateObject => (operand + 1, 1, Flow::Next),
+            // 产出的值出栈，恢复时压入发送的值
+            YieldValue => (1, 1, Flow::Next),
+            // 栈布局 [子迭代器, 发送的值] -> [子迭代器的返回值]
+            YieldFrom => (2, 1, Flow::Next),
+            GetAwaitable => (1, 1, Flow::Next),
+            // 栈布局 [函数, 实参..., 关键字参数名元组] -> [结果]
+            CallKw => {
+                if self.name_tuple(pc).is_none_or(|count| count > operand) {
+                    return Err(self.error(
+                        pc,
+                        "CALL_KW must follow LOAD_CONST of a keyword name tuple within its argument count",
+                    ));
+                }
+                (operand + 2, 1, Flow::Next)
+            }
+            // 栈布局 [函数, 单元...] -> [闭包]
+            MakeClosure => (operand + 1, 1, Flow::Next),
+            // 栈上为 [基类..., 方法表] 或 [接收者, 参数...]
+            CallMethod | CreateClass => ((operand >> 16) + 1, 1, Flow::Next),
+            Return | Throw => (1, 0, Flow::Stop),
+            Reraise | Halt => (0, 0, Flow::Stop),
+
+            GetItem => (2, 1, Flow::Next),
+            SetItem | SetAttr => (3, 0, Flow::Next),
+            BuildList | BuildTuple => (operand, 1, Flow::Next),
+            BuildDict => (operand * 2, 1, Flow::Next),
+            // 栈布局 [list, iterator, element] -> [list, iterator]
+            ListAppend => (3, 2, Flow::Next),
+            // 异常对象由运行时压入
+            CatchBegin => (0, 1, Flow::Next),
+
+            // 栈布局 [模块名, 导入名元组] -> [导入的值...]
+            ImportFrom => {
+                let count = self.name_tuple(pc).ok_or_else(|| {
+                    self.error(pc, "IMPORT_FROM must follow LOAD_CONST of a name tuple")
+                })?;
+                (2, count, Flow::Next)
+            }
+        };
+        Ok(effect)
+    }
+
+    /// 前一条 `LOAD_CONST` 给出的名字元组的长度：`IMPORT_FROM` 的导入名或 `CALL_KW` 的关键字参数名
+    fn name_tuple(&self, pc: usize) -> Option<usize> {
+        let previous = self.instructions.get(pc.checked_sub(1)?)?;
+        if previous.opcode != OpCode::LoadConst {
+            return None;
+        }
+        match self.bytecode.constants.get(previous.operand as usize)? {
+            Value::Tuple(names) if names.iter().all(|name| matches!(name, Value::String(_))) => {
+                Some(names.len())
+            }
+            _ => None,
+        }
+    }
+
+    /// 计算每条指令处的栈深度，不可达的指令为 `None`
+    fn check_stack(&self, handlers: &ExceptionTable) -> Result<Vec<Option<usize>>> {
+        let len = self.instructions.len();
+        let mut depth: Vec<Option<usize>> = vec![None; len + 1];
+        let mut worklist = vec![0usize];
+        depth[0] = Some(0);
+
+        while let Some(pc) = worklist.pop() {
+            let height = depth[pc].unwrap();
+            if pc == len {
+                if self.locals.is_some() {
+                    return Err(self.error(pc, "control flow falls off the end of the function"));
+                }
+                continue;
+            }
+
+            let instruction = &self.instructions[pc];
+            let (pops, pushes, flow) = self.effect(pc, instruction, handlers)?;
+            if height < pops {
+                return Err(self.error(
+                    pc,
+                    format!(
+                        "stack underflow: {:?} needs {} values, stack has {}",
+                        instruction.opcode, pops, height
+                    ),
+                ));
+            }
+            let after = height - pops + pushes;
+
+            let entries: Vec<(usize, usize)>;
+            let successors: &[(usize, usize)] = match flow {
+                Flow::Next => &[(pc + 1, after)],
+                Flow::Jump(target) => &[(target, after)],
+                Flow::Branch(target) => &[(pc + 1, after), (target, after)],
+                Flow::BranchOrPop(target) => &[(pc + 1, after), (target, after + 1)],
+                Flow::ForIter(target) => &[(pc + 1, after + 1), (target, after - 1)],
+                Flow::Try(index) => {
+                    let handler = &handlers[index];
+                    entries = std::iter::once(pc + 1)
+                        .chain(handler.catches.iter().map(|catch| catch.start))
+                        .chain(handler.finally)
+                        .map(|next| (next, after))
+                        .collect();
+                    &entries
+                }
+                Flow::Stop => &[],
+            };
+
+            for &(next, next_height) in successors {
+                match depth[next] {
+                    None => {
+                        depth[next] = Some(next_height);
+                        worklist.push(next);
+                    }
+                    Some(existing) if existing != next_height => {
+                        return Err(self.error(
+                            next,
+                            format!(
+                                "inconsistent stack height at merge point: {} vs {}",
+                                existing, next_height
+                            ),
+                        ));
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+
+        Ok(depth)
+    }
+}
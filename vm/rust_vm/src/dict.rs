@@ -0,0 +1,145 @@
+/*!
+保持插入顺序的字典
+
+键可以是任意可哈希的值。条目按插入顺序存放在数组中，另有一张
+哈希值到条目位置的索引表；哈希冲突时逐个比较候选键。
+
+内置类型的键直接使用 [`Value::hash_key`] 与 [`Value::equals`] 比较；
+[`Dict::find_with`] 允许调用方自行提供哈希值和相等比较。
+*/
+
+use crate::value::Value;
+use crate::Result;
+use rustc_hash::FxHashMap;
+
+/// 字典
+#[derive(Debug, Clone, Default)]
+pub struct Dict {
+    /// 按插入顺序排列的键值对
+    entries: Vec<(Value, Value)>,
+
+    /// 哈希值到条目位置的映射
+    index: FxHashMap<u64, Vec<usize>>,
+}
+
+impl Dict {
+    /// 创建空字典
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 条目数量
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 查找键，键不可哈希时返回错误
+    pub fn get(&self, key: &Value) -> Result<Option<&Value>> {
+        let hash = key.hash_key()?;
+        let position = self.find_with(hash, |candidate| candidate.equals(key))?;
+        Ok(position.map(|i| &self.entries[i].1))
+    }
+
+    /// 是否包含键
+    pub fn contains_key(&self, key: &Value) -> Result<bool> {
+        Ok(self.get(key)?.is_some())
+    }
+
+    /// 插入或覆盖，返回旧值；覆盖时保留原来的位置和键
+    pub fn insert(&mut self, key: Value, value: Value) -> Result<Option<Value>> {
+        let hash = key.hash_key()?;
+        let position = self.find_with(hash, |candidate| candidate.equals(&key))?;
+        Ok(self.insert_at(position, hash, key, value))
+    }
+
+    /// 删除键，返回被删除的值
+    pub fn remove(&mut self, key: &Value) -> Result<Option<Value>> {
+        let hash = key.hash_key()?;
+        let position = self.find_with(hash, |candidate| candidate.equals(key))?;
+        Ok(position.map(|i| self.remove_at(i).1))
+    }
+
+    /// 在给定哈希值的候选条目中查找第一个满足 `eq` 的键，返回条目位置
+    pub fn find_with(
+        &self,
+        hash: u64,
+        mut eq: impl FnMut(&Value) -> Result<bool>,
+    ) -> Result<Option<usize>> {
+        if let Some(positions) = self.index.get(&hash) {
+            for &i in positions {
+                if eq(&self.entries[i].0)? {
+                    return Ok(Some(i));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// 给定哈希值的候选条目位置
+    pub fn candidates(&self, hash: u64) -> &[usize] {
+        self.index.get(&hash).map_or(&[], Vec::as_slice)
+    }
+
+    /// 在已查找过的位置写入：`position` 为 `None` 时追加新条目
+    pub fn insert_at(
+        &mut self,
+        position: Option<usize>,
+        hash: u64,
+        key: Value,
+        value: Value,
+    ) -> Option<Value> {
+        match position {
+            Some(i) => Some(std::mem::replace(&mut self.entries[i].1, value)),
+            None => {
+                self.index.entry(hash).or_default().push(self.entries.len());
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    /// 按位置删除条目，之后的条目前移
+    pub fn remove_at(&mut self, position: usize) -> (Value, Value) {
+        let entry = self.entries.remove(position);
+        self.index.retain(|_, positions| {
+            positions.retain(|&i| i != position);
+            for i in positions.iter_mut() {
+                if *i > position {
+                    *i -= 1;
+                }
+            }
+            !positions.is_empty()
+        });
+        entry
+    }
+
+    /// 按位置取条目
+    pub fn entry_at(&self, position: usize) -> Option<(&Value, &Value)> {
+        self.entries.get(position).map(|(k, v)| (k, v))
+    }
+
+    /// 按插入顺序遍历键值对
+    pub fn iter(&self) -> impl Iterator<Item = (&Value, &Value)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+
+    /// 按插入顺序遍历键
+    pub fn keys(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|(k, _)| k)
+    }
+
+    /// 按插入顺序遍历值
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|(_, v)| v)
+    }
+
+    /// 按插入顺序遍历值的可变引用
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Value> {
+        self.entries.iter_mut().map(|(_, v)| v)
+    }
+}
@@ -0,0 +1,525 @@
+/*!
+事件循环
+
+协程由任务驱动：任务每执行一步就恢复一次协程，协程 `await` 尚未完成的 Future 时
+挂起并把它交给任务，任务登记为该 Future 的等待者，Future 完成时任务回到就绪队列。
+`sleep()` 和 `wait_for()` 的超时由定时器轮在到期时处理。
+
+时间是虚拟的：就绪队列为空时时钟直接拨到下一个定时器的到期时间，`sleep()` 不会真正等待，
+同一个程序总是以同样的顺序执行。到期时间相同的定时器按加入的顺序触发。
+*/
+
+use crate::exceptions::BuiltinExceptions;
+use crate::generator::Generator;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// 虚拟时钟每秒的刻度数（1 毫秒一刻）
+pub const TICKS_PER_SECOND: f64 = 1000.0;
+
+/// 定时器轮的槽数
+const WHEEL_SLOTS: u64 = 64;
+
+/// 共享的 Future
+pub type FutureRef = Rc<RefCell<Future>>;
+
+/// Future 的状态
+#[derive(Debug, Clone)]
+pub enum FutureState {
+    Pending,
+    /// 已得到结果
+    Done(Value),
+    /// 以异常结束
+    Failed(Value),
+    Cancelled,
+}
+
+/// Future 的种类
+#[derive(Debug)]
+pub enum FutureKind {
+    /// 普通 Future，如 `sleep()` 返回的
+    Plain,
+
+    /// 驱动协程的任务
+    Task(Task),
+
+    /// `gather()` 的结果：所有子 Future 完成后得到按顺序排列的结果列表
+    Gather {
+        children: Vec<FutureRef>,
+        results: Vec<Value>,
+        remaining: usize,
+        return_exceptions: bool,
+    },
+
+    /// `wait_for()` 的结果：转交 `inner` 的结果，超时时取消它并以 `TimeoutError` 结束
+    WaitFor { inner: FutureRef, timed_out: bool },
+}
+
+/// 任务的状态
+#[derive(Debug)]
+pub struct Task {
+    pub coroutine: Rc<RefCell<Generator>>,
+
+    /// 正在等待的 Future，任务在就绪队列中时为 `None`
+    pub waiting_on: Option<FutureRef>,
+
+    /// 已请求取消，下次执行时在协程中抛出 `CancelledError`
+    pub cancel_requested: bool,
+}
+
+/// Future 完成时执行的回调
+#[derive(Debug)]
+pub enum Callback {
+    /// 唤醒等待它的任务
+    Wake(FutureRef),
+
+    /// 把结果填入 `gather()` 结果的第 `index` 项
+    Gather { parent: FutureRef, index: usize },
+
+    /// 把结果转交给 `wait_for()` 的结果
+    WaitFor { outer: FutureRef },
+}
+
+/// Future 或任务
+#[derive(Debug)]
+pub struct Future {
+    pub state: FutureState,
+    pub kind: FutureKind,
+    pub callbacks: Vec<Callback>,
+
+    /// 到期时处理本 Future 的定时器，Future 提前结束时被移除
+    timer: Option<TimerId>,
+}
+
+impl Future {
+    pub fn new(kind: FutureKind) -> FutureRef {
+        Rc::new(RefCell::new(Self {
+            state: FutureState::Pending,
+            kind,
+            callbacks: Vec::new(),
+            timer: None,
+        }))
+    }
+
+    pub fn is_pending(&self) -> bool {
+        matches!(self.state, FutureState::Pending)
+    }
+
+    /// 类型名，与 Python 相同
+    pub fn type_name(&self) -> &'static str {
+        match self.kind {
+            FutureKind::Task(_) => "Task",
+            _ => "Future",
+        }
+    }
+}
+
+/// 定时器到期时的动作
+#[derive(Debug)]
+enum TimerAction {
+    /// 以给定的结果完成 `sleep()` 的 Future
+    Resolve(FutureRef, Value),
+    /// `wait_for()` 超时
+    Timeout(FutureRef),
+}
+
+/// 定时器：(到期时间, 序号)
+type TimerId = (u64, u64);
+
+#[derive(Debug)]
+struct Timer {
+    deadline: u64,
+    seq: u64,
+    action: TimerAction,
+}
+
+/// 哈希定时器轮：定时器按到期时间放入 `deadline % WHEEL_SLOTS` 号槽，
+/// 一个槽中可以有相差若干圈的定时器
+#[derive(Debug)]
+struct TimerWheel {
+    slots: Vec<Vec<Timer>>,
+    /// 已推进到的时间
+    now: u64,
+    /// 下一个定时器的序号
+    seq: u64,
+    len: usize,
+}
+
+impl TimerWheel {
+    fn new() -> Self {
+        Self {
+            slots: (0..WHEEL_SLOTS).map(|_| Vec::new()).collect(),
+            now: 0,
+            seq: 0,
+            len: 0,
+        }
+    }
+
+    fn insert(&mut self, deadline: u64, action: TimerAction) -> TimerId {
+        let deadline = deadline.max(self.now);
+        let seq = self.seq;
+        self.seq += 1;
+        self.len += 1;
+        self.slots[(deadline % WHEEL_SLOTS) as usize].push(Timer {
+            deadline,
+            seq,
+            action,
+        });
+        (deadline, seq)
+    }
+
+    fn remove(&mut self, (deadline, seq): TimerId) {
+        let slot = &mut self.slots[(deadline % WHEEL_SLOTS) as usize];
+        if let Some(position) = slot.iter().position(|timer| timer.seq == seq) {
+            slot.swap_remove(position);
+            self.len -= 1;
+        }
+    }
+
+    /// 最早的到期时间：先在一圈之内逐槽查找，找不到时再比较所有定时器
+    fn next_deadline(&self) -> Option<u64> {
+        if self.len == 0 {
+            return None;
+        }
+        (self.now..self.now + WHEEL_SLOTS)
+            .find(|&tick| {
+                self.slots[(tick % WHEEL_SLOTS) as usize]
+                    .iter()
+                    .any(|timer| timer.deadline == tick)
+            })
+            .or_else(|| {
+                self.slots
+                    .iter()
+                    .flatten()
+                    .map(|timer| timer.deadline)
+                    .min()
+            })
+    }
+
+    /// 把时间推进到 `now`，按 (到期时间, 序号) 的顺序取出所有到期的定时器
+    fn advance(&mut self, now: u64) -> Vec<Timer> {
+        let mut expired = Vec::new();
+        let ticks = (now - self.now + 1).min(WHEEL_SLOTS);
+        for tick in self.now..self.now + ticks {
+            let slot = &mut self.slots[(tick % WHEEL_SLOTS) as usize];
+            let mut index = 0;
+            while index < slot.len() {
+                if slot[index].deadline <= now {
+                    expired.push(slot.swap_remove(index));
+                } else {
+                    index += 1;
+                }
+            }
+        }
+        self.now = now;
+        self.len -= expired.len();
+        expired.sort_by_key(|timer| (timer.deadline, timer.seq));
+        expired
+    }
+
+    fn clear(&mut self) {
+        self.slots.iter_mut().for_each(Vec::clear);
+        self.len = 0;
+    }
+}
+
+/// 单线程事件循环
+///
+/// 这里只管理 Future、定时器和就绪队列；恢复协程需要虚拟机，由
+/// [`crate::AquaVM`] 从就绪队列中取出任务执行。
+#[derive(Debug)]
+pub struct EventLoop {
+    exceptions: BuiltinExceptions,
+    timers: TimerWheel,
+
+    /// 可以执行下一步的任务
+    ready: VecDeque<FutureRef>,
+
+    /// 本次运行创建的任务，循环结束时取消其中未完成的
+    tasks: Vec<FutureRef>,
+}
+
+impl EventLoop {
+    pub fn new(exceptions: BuiltinExceptions) -> Self {
+        Self {
+            exceptions,
+            timers: TimerWheel::new(),
+            ready: VecDeque::new(),
+            tasks: Vec::new(),
+        }
+    }
+
+    /// 虚拟时钟的当前时间（秒）
+    pub fn time(&self) -> f64 {
+        self.timers.now as f64 / TICKS_PER_SECOND
+    }
+
+    /// 丢弃所有任务和定时器，时钟停在当前时间
+    pub fn clear(&mut self) {
+        self.timers.clear();
+        self.ready.clear();
+        self.tasks.clear();
+    }
+
+    /// 取出下一个就绪的任务
+    pub fn next_ready(&mut self) -> Option<FutureRef> {
+        self.ready.pop_front()
+    }
+
+    /// 本次运行中尚未完成的任务
+    pub fn pending_tasks(&self) -> Vec<FutureRef> {
+        self.tasks
+            .iter()
+            .filter(|task| task.borrow().is_pending())
+            .cloned()
+            .collect()
+    }
+
+    /// 为协程创建任务，任务进入就绪队列
+    pub fn create_task(&mut self, coroutine: Rc<RefCell<Generator>>) -> FutureRef {
+        let task = Future::new(FutureKind::Task(Task {
+            coroutine,
+            waiting_on: None,
+            cancel_requested: false,
+        }));
+        self.ready.push_back(task.clone());
+        self.tasks.push(task.clone());
+        task
+    }
+
+    /// `sleep(delay, result)`：`delay` 秒后以 `result` 完成的 Future
+    pub fn sleep(&mut self, delay: f64, result: Value) -> FutureRef {
+        let future = Future::new(FutureKind::Plain);
+        let timer = self.timers.insert(
+            self.timers.now + ticks(delay),
+            TimerAction::Resolve(future.clone(), result),
+        );
+        future.borrow_mut().timer = Some(timer);
+        future
+    }
+
+    /// `gather()`：`children` 全部完成后得到结果列表
+    ///
+    /// 子 Future 失败时结果立即以同样的异常结束，`return_exceptions` 为真时异常作为结果放入列表。
+    pub fn gather(&mut self, children: Vec<FutureRef>, return_exceptions: bool) -> FutureRef {
+        let remaining = children.len();
+        let parent = Future::new(FutureKind::Gather {
+            children: children.clone(),
+            results: vec![Value::Null; remaining],
+            remaining,
+            return_exceptions,
+        });
+        if remaining == 0 {
+            parent.borrow_mut().state = FutureState::Done(Value::list(Vec::new()));
+        }
+        for (index, child) in children.iter().enumerate() {
+            self.on_done(
+                child,
+                Callback::Gather {
+                    parent: parent.clone(),
+                    index,
+                },
+            );
+        }
+        parent
+    }
+
+    /// `wait_for()`：转交 `inner` 的结果，`timeout` 秒内没有完成时取消它并以 `TimeoutError` 结束
+    pub fn wait_for(&mut self, inner: FutureRef, timeout: f64) -> FutureRef {
+        let outer = Future::new(FutureKind::WaitFor {
+            inner: inner.clone(),
+            timed_out: false,
+        });
+        self.on_done(
+            &inner,
+            Callback::WaitFor {
+                outer: outer.clone(),
+            },
+        );
+        if outer.borrow().is_pending() {
+            let timer = self.timers.insert(
+                self.timers.now + ticks(timeout),
+                TimerAction::Timeout(outer.clone()),
+            );
+            outer.borrow_mut().timer = Some(timer);
+        }
+        outer
+    }
+
+    /// 任务等待 `future` 完成，已完成时任务直接回到就绪队列
+    pub fn wait(&mut self, task: &FutureRef, future: FutureRef) {
+        if let FutureKind::Task(state) = &mut task.borrow_mut().kind {
+            state.waiting_on = Some(future.clone());
+        }
+        self.on_done(&future, Callback::Wake(task.clone()));
+    }
+
+    /// 以 `state` 结束 Future 并执行它的回调，已结束的 Future 保持不变
+    pub fn complete(&mut self, future: &FutureRef, state: FutureState) {
+        let callbacks = {
+            let mut future = future.borrow_mut();
+            if !future.is_pending() {
+                return;
+            }
+            future.state = state;
+            if let Some(timer) = future.timer.take() {
+                self.timers.remove(timer);
+            }
+            std::mem::take(&mut future.callbacks)
+        };
+        for callback in callbacks {
+            self.run_callback(future, callback);
+        }
+    }
+
+    /// 请求取消 Future，返回它是否尚未结束
+    ///
+    /// 等待中的任务取消它等待的 Future，就绪的任务在下次执行时向协程抛出 `CancelledError`；
+    /// `gather()` 的结果取消所有子 Future，`wait_for()` 的结果取消被等待的 Future。
+    pub fn cancel(&mut self, future: &FutureRef) -> bool {
+        let targets = {
+            let mut future = future.borrow_mut();
+            if !future.is_pending() {
+                return false;
+            }
+            match &mut future.kind {
+                FutureKind::Task(task) => match &task.waiting_on {
+                    Some(waiting) => vec![waiting.clone()],
+                    None => {
+                        task.cancel_requested = true;
+                        return true;
+                    }
+                },
+                FutureKind::Gather { children, .. } => children.clone(),
+                FutureKind::WaitFor { inner, .. } => vec![inner.clone()],
+                FutureKind::Plain => Vec::new(),
+            }
+        };
+        for target in &targets {
+            self.cancel(target);
+        }
+        // 任务在协程处理完 `CancelledError` 后才结束
+        if !matches!(future.borrow().kind, FutureKind::Task(_)) {
+            self.complete(future, FutureState::Cancelled);
+        }
+        true
+    }
+
+    /// 把时钟拨到最早的定时器并触发所有到期的定时器，没有定时器时返回 `false`
+    pub fn fire_timers(&mut self) -> bool {
+        let Some(deadline) = self.timers.next_deadline() else {
+            return false;
+        };
+        for timer in self.timers.advance(deadline) {
+            match timer.action {
+                TimerAction::Resolve(future, result) => {
+                    future.borrow_mut().timer = None;
+                    self.complete(&future, FutureState::Done(result));
+                }
+                TimerAction::Timeout(outer) => {
+                    let inner = {
+                        let mut outer = outer.borrow_mut();
+                        outer.timer = None;
+                        match &mut outer.kind {
+                            FutureKind::WaitFor { inner, timed_out } => {
+                                *timed_out = true;
+                                inner.clone()
+                            }
+                            _ => continue,
+                        }
+                    };
+                    self.cancel(&inner);
+                }
+            }
+        }
+        true
+    }
+
+    /// 新的 `CancelledError` 实例
+    pub fn cancelled_error(&self) -> Value {
+        self.exceptions
+            .new_instance("CancelledError", String::new(), Value::list(Vec::new()))
+    }
+
+    /// 登记 Future 完成时的回调，已完成的 Future 立即执行回调
+    fn on_done(&mut self, future: &FutureRef, callback: Callback) {
+        if future.borrow().is_pending() {
+            future.borrow_mut().callbacks.push(callback);
+        } else {
+            self.run_callback(future, callback);
+        }
+    }
+
+    fn run_callback(&mut self, future: &FutureRef, callback: Callback) {
+        let state = future.borrow().state.clone();
+        match callback {
+            Callback::Wake(task) => {
+                if let FutureKind::Task(state) = &mut task.borrow_mut().kind {
+                    state.waiting_on = None;
+                }
+                self.ready.push_back(task);
+            }
+            Callback::Gather { parent, index } => {
+                if !parent.borrow().is_pending() {
+                    return;
+                }
+                let finished = {
+                    let mut parent = parent.borrow_mut();
+                    let FutureKind::Gather {
+                        results,
+                        remaining,
+                        return_exceptions,
+                        ..
+                    } = &mut parent.kind
+                    else {
+                        return;
+                    };
+                    let (value, failed) = match state {
+                        FutureState::Done(value) => (value, false),
+                        FutureState::Failed(exception) => (exception, !*return_exceptions),
+                        FutureState::Cancelled => (self.cancelled_error(), !*return_exceptions),
+                        FutureState::Pending => return,
+                    };
+                    if failed {
+                        Some(FutureState::Failed(value))
+                    } else {
+                        results[index] = value;
+                        *remaining -= 1;
+                        (*remaining == 0)
+                            .then(|| FutureState::Done(Value::list(std::mem::take(results))))
+                    }
+                };
+                if let Some(state) = finished {
+                    self.complete(&parent, state);
+                }
+            }
+            Callback::WaitFor { outer } => {
+                let timed_out = matches!(
+                    outer.borrow().kind,
+                    FutureKind::WaitFor {
+                        timed_out: true,
+                        ..
+                    }
+                );
+                let state = match state {
+                    FutureState::Cancelled if timed_out => {
+                        FutureState::Failed(self.exceptions.new_instance(
+                            "TimeoutError",
+                            String::new(),
+                            Value::list(Vec::new()),
+                        ))
+                    }
+                    state => state,
+                };
+                self.complete(&outer, state);
+            }
+        }
+    }
+}
+
+/// 秒数对应的刻度数，不足一刻的部分向上取整
+fn ticks(seconds: f64) -> u64 {
+    (seconds.max(0.0) * TICKS_PER_SECOND).ceil() as u64
+}
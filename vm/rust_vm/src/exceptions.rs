@@ -0,0 +1,185 @@
+/*!
+内置异常类
+
+虚拟机内部错误在抛出处转换为下列类的实例，脚本可以按类型捕获：
+
+```text
+Exception
+├── ArithmeticError
+│   ├── OverflowError
+│   └── ZeroDivisionError
+├── LookupError
+│   ├── IndexError
+│   └── KeyError
+├── AttributeError
+├── ImportError
+│   └── ModuleNotFoundError
+├── MemoryError
+├── NameError
+├── TypeError
+├── ValueError
+├── RuntimeError
+│   └── RecursionError
+├── StopIteration
+└── TimeoutError
+GeneratorExit
+CancelledError
+```
+
+异常实例有两个属性：`message` 为错误信息，`traceback` 为抛出时的调用栈（外层在前）。
+`StopIteration` 另有 `value` 属性，为结束的生成器的返回值。与 Python 相同，
+`GeneratorExit` 和 `CancelledError` 不派生自 `Exception`，捕获 `Exception`
+不会拦截生成器的关闭和任务的取消。
+字节码损坏、文件读写等脚本无法处理的错误不做转换。
+*/
+
+use crate::object::{Class, Instance};
+use crate::value::Value;
+use crate::VMError;
+use rustc_hash::FxHashMap;
+use std::rc::Rc;
+
+/// (类名, 基类名)，基类总是排在子类之前
+const HIERARCHY: &[(&str, Option<&str>)] = &[
+    ("Exception", None),
+    ("ArithmeticError", Some("Exception")),
+    ("OverflowError", Some("ArithmeticError")),
+    ("ZeroDivisionError", Some("ArithmeticError")),
+    ("LookupError", Some("Exception")),
+    ("IndexError", Some("LookupError")),
+    ("KeyError", Some("LookupError")),
+    ("AttributeError", Some("Exception")),
+    ("ImportError", Some("Exception")),
+    ("ModuleNotFoundError", Some("ImportError")),
+    ("MemoryError", Some("Exception")),
+    ("NameError", Some("Exception")),
+    ("TypeError", Some("Exception")),
+    ("ValueError", Some("Exception")),
+    ("RuntimeError", Some("Exception")),
+    ("RecursionError", Some("RuntimeError")),
+    ("StopIteration", Some("Exception")),
+    ("TimeoutError", Some("Exception")),
+    ("GeneratorExit", None),
+    ("CancelledError", None),
+];
+
+/// 调用栈超过 `max_call_depth`、执行循环嵌套超过 `max_native_depth`、
+/// 比较或哈希时容器嵌套过深的错误信息，对应 `RecursionError`
+pub const CALL_STACK_OVERFLOW: &str = "Call stack overflow";
+
+/// 内置异常类表
+#[derive(Debug, Clone)]
+pub struct BuiltinExceptions {
+    classes: FxHashMap<&'static str, Rc<Class>>,
+}
+
+impl BuiltinExceptions {
+    pub fn new() -> Self {
+        let mut classes: FxHashMap<&'static str, Rc<Class>> = FxHashMap::default();
+        for &(name, base) in HIERARCHY {
+            let bases = base.map(|base| classes[base].clone()).into_iter().collect();
+            let class = Class::new(name.into(), bases, FxHashMap::default())
+                .expect("single inheritance always has a consistent MRO");
+            classes.insert(name, Rc::new(class));
+        }
+        Self { classes }
+    }
+
+    /// 按名字查找内置异常类
+    pub fn get(&self, name: &str) -> Option<&Rc<Class>> {
+        self.classes.get(name)
+    }
+
+    /// 类是否派生自内置的 `Exception`、`GeneratorExit` 或 `CancelledError`
+    pub fn is_exception(&self, class: &Rc<Class>) -> bool {
+        HIERARCHY
+            .iter()
+            .filter(|(_, base)| base.is_none())
+            .any(|(root, _)| class.is_subclass(&self.classes[root]))
+    }
+
+    /// 创建内置异常类 `name` 的实例
+    pub fn new_instance(&self, name: &str, message: String, traceback: Value) -> Value {
+        let instance = Instance::new(self.classes[name].clone());
+        instance.set_attribute("message".into(), Value::String(message.into()));
+        instance.set_attribute("traceback".into(), traceback);
+        Value::Instance(Rc::new(instance))
+    }
+
+    /// 以 `value` 为返回值的 `StopIteration` 实例
+    pub fn stop_iteration(&self, value: Value, traceback: Value) -> Value {
+        let message = match &value {
+            Value::Null => String::new(),
+            other => other.to_string(),
+        };
+        let exception = self.new_instance("StopIteration", message, traceback);
+        if let Value::Instance(instance) = &exception {
+            instance.set_attribute("value".into(), value);
+        }
+        exception
+    }
+
+    /// 把内部错误转换为异常实例，不能由脚本处理的错误返回 `None`
+    pub fn from_error(&self, error: &VMError, traceback: Value) -> Option<Value> {
+        if let VMError::StopIteration = error {
+            return Some(self.stop_iteration(Value::Null, traceback));
+        }
+        let (name, message) = classify(error)?;
+        Some(self.new_instance(name, message, traceback))
+    }
+
+    /// 异常是否为 `StopIteration`（包括转换前的内部错误）
+    pub fn is_stop_iteration(&self, error: &VMError) -> bool {
+        match error {
+            VMError::StopIteration => true,
+            VMError::Exception(Value::Instance(instance)) => {
+                instance.class.is_subclass(&self.classes["StopIteration"])
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Default for BuiltinExceptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 内部错误对应的异常类名和信息
+fn classify(error: &VMError) -> Option<(&'static str, String)> {
+    let classified = match error {
+        VMError::DivisionByZero => ("ZeroDivisionError", "division by zero".to_string()),
+        VMError::IndexOutOfBounds { .. } => ("IndexError", "index out of range".to_string()),
+        VMError::KeyError(key) => ("KeyError", key.clone()),
+        VMError::AttributeError(message) => ("AttributeError", message.clone()),
+        VMError::TypeError(message) => ("TypeError", message.clone()),
+        VMError::ValueError(message) => ("ValueError", message.clone()),
+        VMError::OverflowError(message) => ("OverflowError", message.clone()),
+        VMError::MemoryError => ("MemoryError", String::new()),
+        VMError::ImportError(message) => ("ImportError", message.clone()),
+        VMError::ModuleNotFound(name) => {
+            ("ModuleNotFoundError", format!("No module named '{}'", name))
+        }
+        VMError::FunctionNotFound(name) => ("NameError", format!("name '{}' is not defined", name)),
+        VMError::RuntimeError(message) if message == CALL_STACK_OVERFLOW => (
+            "RecursionError",
+            "maximum recursion depth exceeded".to_string(),
+        ),
+        VMError::RuntimeError(message) => ("RuntimeError", message.clone()),
+        _ => return None,
+    };
+    Some(classified)
+}
+
+/// 未捕获异常的描述：带 `message` 属性的实例显示为 `类名: 信息`
+pub fn describe(exception: &Value) -> String {
+    match exception {
+        Value::Instance(instance) => match instance.attribute("message") {
+            Some(Value::String(message)) if message.is_empty() => instance.class.name.to_string(),
+            Some(Value::String(message)) => format!("{}: {}", instance.class.name, message),
+            _ => exception.to_string(),
+        },
+        other => other.to_string(),
+    }
+}
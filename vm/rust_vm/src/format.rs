@@ -0,0 +1,555 @@
+/*!
+格式说明迷你语言
+
+实现 Python 内置函数 `format(value, spec)`，输出与 CPython 逐字节一致，
+供 f-string 的 `FORMAT_VALUE` 指令使用：
+
+```text
+[[fill]align][sign]["z"]["#"]["0"][width][grouping]["." precision][type]
+```
+
+- 整数支持 `d` `n` `b` `o` `x` `X` `c`，以及转换为浮点数的 `e` `f` `g` `%` 等
+- 浮点数支持 `e` `E` `f` `F` `g` `G` `n` `%` 和省略类型
+- 字符串支持 `s` 和省略类型
+
+宽度或精度要求的输出超过 [`MAX_STRING_SIZE`] 时抛出 `MemoryError`，不会尝试分配。
+*/
+
+use crate::value::{format_float, Value, MAX_STRING_SIZE};
+use crate::{Result, VMError};
+
+/// 按格式说明格式化值
+pub fn format_value(value: &Value, spec: &str) -> Result<String> {
+    // 空格式说明等价于 str()，包括布尔值输出 True/False
+    if spec.is_empty() {
+        return Ok(value.to_string());
+    }
+    match value {
+        Value::Int(i) => FormatSpec::parse(spec, "int")?.format_int(*i),
+        Value::Bool(b) => FormatSpec::parse(spec, "bool")?.format_int(*b as i64),
+        Value::Float(f) => FormatSpec::parse(spec, "float")?.format_float(*f),
+        Value::String(s) => FormatSpec::parse(spec, "str")?.format_str(s),
+        other => Err(VMError::TypeError(format!(
+            "unsupported format string passed to {}.__format__",
+            other.type_name()
+        ))),
+    }
+}
+
+/// 解析后的格式说明
+#[derive(Debug, Default)]
+struct FormatSpec {
+    fill: Option<char>,
+    align: Option<char>,
+    sign: Option<char>,
+    /// `z`：把负零输出为零
+    coerce_zero: bool,
+    /// `#`：替代形式
+    alternate: bool,
+    /// 宽度前的 `0`
+    zero_pad: bool,
+    width: usize,
+    /// `,` 或 `_`
+    grouping: Option<char>,
+    precision: Option<usize>,
+    kind: Option<char>,
+    /// 被格式化值的类型名，用于错误信息
+    type_name: &'static str,
+}
+
+fn value_error(message: String) -> VMError {
+    VMError::ValueError(message)
+}
+
+impl FormatSpec {
+    fn parse(spec: &str, type_name: &'static str) -> Result<Self> {
+        let chars: Vec<char> = spec.chars().collect();
+        let mut parsed = FormatSpec {
+            type_name,
+            ..FormatSpec::default()
+        };
+        let mut i = 0;
+        let is_align = |c: char| matches!(c, '<' | '>' | '=' | '^');
+
+        if chars.len() >= 2 && is_align(chars[1]) {
+            parsed.fill = Some(chars[0]);
+            parsed.align = Some(chars[1]);
+            i = 2;
+        } else if !chars.is_empty() && is_align(chars[0]) {
+            parsed.align = Some(chars[0]);
+            i = 1;
+        }
+
+        if let Some(&c @ ('+' | '-' | ' ')) = chars.get(i) {
+            parsed.sign = Some(c);
+            i += 1;
+        }
+        if chars.get(i) == Some(&'z') {
+            parsed.coerce_zero = true;
+            i += 1;
+        }
+        if chars.get(i) == Some(&'#') {
+            parsed.alternate = true;
+            i += 1;
+        }
+        if chars.get(i) == Some(&'0') {
+            parsed.zero_pad = true;
+            i += 1;
+        }
+
+        let (width, next) = read_number(&chars, i, spec)?;
+        parsed.width = width.unwrap_or(0);
+        i = next;
+
+        if let Some(&c @ (',' | '_')) = chars.get(i) {
+            parsed.grouping = Some(c);
+            i += 1;
+        }
+
+        if chars.get(i) == Some(&'.') {
+            let (precision, next) = read_number(&chars, i + 1, spec)?;
+            if precision.is_none() {
+                return Err(value_error(
+                    "Format specifier missing precision".to_string(),
+                ));
+            }
+            parsed.precision = precision;
+            i = next;
+        }
+
+        match &chars[i..] {
+            [] => {}
+            [kind] => parsed.kind = Some(*kind),
+            _ => {
+                return Err(value_error(format!(
+                    "Invalid format specifier '{}' for object of type '{}'",
+                    spec, type_name
+                )))
+            }
+        }
+
+        // 字符串省略类型时按 's' 处理
+        let kind = parsed.kind.or((type_name == "str").then_some('s'));
+        if let Some(separator) = parsed.grouping {
+            let allowed = match kind {
+                None => true,
+                Some('d' | 'e' | 'E' | 'f' | 'F' | 'g' | 'G' | '%') => true,
+                Some('b' | 'o' | 'x' | 'X') => separator == '_',
+                Some(_) => false,
+            };
+            if !allowed {
+                return Err(value_error(format!(
+                    "Cannot specify '{}' with '{}'.",
+                    separator,
+                    kind.unwrap()
+                )));
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn unknown_code(&self, kind: char) -> VMError {
+        value_error(format!(
+            "Unknown format code '{}' for object of type '{}'",
+            kind, self.type_name
+        ))
+    }
+
+    fn format_int(&self, value: i64) -> Result<String> {
+        let kind = self.kind.unwrap_or('d');
+        match kind {
+            'e' | 'E' | 'f' | 'F' | 'g' | 'G' | '%' => return self.format_float(value as f64),
+            'd' | 'n' | 'b' | 'o' | 'x' | 'X' | 'c' => {}
+            other => return Err(self.unknown_code(other)),
+        }
+
+        if self.precision.is_some() {
+            return Err(value_error(
+                "Precision not allowed in integer format specifier".to_string(),
+            ));
+        }
+        if self.coerce_zero {
+            return Err(value_error(
+                "Negative zero coercion (z) not allowed in integer format specifier".to_string(),
+            ));
+        }
+
+        let magnitude = value.unsigned_abs();
+        let (digits, prefix, interval) = match kind {
+            'd' | 'n' => (magnitude.to_string(), "", 3),
+            'b' => (format!("{:b}", magnitude), "0b", 4),
+            'o' => (format!("{:o}", magnitude), "0o", 4),
+            'x' => (format!("{:x}", magnitude), "0x", 4),
+            'X' => (format!("{:X}", magnitude), "0X", 4),
+            _ => {
+                if self.sign.is_some() {
+                    return Err(value_error(
+                        "Sign not allowed with integer format specifier 'c'".to_string(),
+                    ));
+                }
+                if self.alternate {
+                    return Err(value_error(
+                        "Alternate form (#) not allowed with integer format specifier 'c'"
+                            .to_string(),
+                    ));
+                }
+                let c = u32::try_from(value)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| {
+                        VMError::RuntimeError("%c arg not in range(0x110000)".to_string())
+                    })?;
+                return self.pad(&c.to_string(), '>');
+            }
+        };
+
+        let prefix = if self.alternate { prefix } else { "" };
+        self.assemble_number(value < 0, prefix, &digits, "", interval)
+    }
+
+    fn format_float(&self, value: f64) -> Result<String> {
+        let kind = self.kind;
+        if let Some(kind) = kind {
+            if !matches!(kind, 'e' | 'E' | 'f' | 'F' | 'g' | 'G' | 'n' | '%') {
+                return Err(self.unknown_code(kind));
+            }
+        }
+        match self.precision {
+            Some(precision) if precision > i32::MAX as usize => {
+                return Err(value_error("precision too big".to_string()))
+            }
+            Some(precision) if precision > MAX_STRING_SIZE => return Err(VMError::MemoryError),
+            _ => {}
+        }
+
+        let mut negative = value.is_sign_negative() && !value.is_nan();
+        let magnitude = value.abs();
+        let upper = matches!(kind, Some('E' | 'F' | 'G'));
+
+        let body = if !magnitude.is_finite() {
+            let text = if magnitude.is_nan() { "nan" } else { "inf" };
+            let text = if upper {
+                text.to_ascii_uppercase()
+            } else {
+                text.to_string()
+            };
+            if kind == Some('%') {
+                text + "%"
+            } else {
+                text
+            }
+        } else {
+            match kind {
+                Some('f' | 'F') => self.fixed(magnitude, self.precision.unwrap_or(6)),
+                Some('e' | 'E') => {
+                    let text = self.exponent(magnitude, self.precision.unwrap_or(6));
+                    if upper {
+                        text.to_ascii_uppercase()
+                    } else {
+                        text
+                    }
+                }
+                Some('%') => self.fixed(magnitude * 100.0, self.precision.unwrap_or(6)) + "%",
+                Some('g' | 'G' | 'n') => {
+                    let text = self.general(magnitude, self.precision.unwrap_or(6), false);
+                    if upper {
+                        text.to_ascii_uppercase()
+                    } else {
+                        text
+                    }
+                }
+                None => match self.precision {
+                    Some(precision) => self.general(magnitude, precision, true),
+                    None => {
+                        // 与 repr() 相同，替代形式保证有小数点
+                        let text = format_float(magnitude);
+                        if self.alternate && !text.contains('.') {
+                            match text.split_once('e') {
+                                Some((mantissa, exponent)) => format!("{}.e{}", mantissa, exponent),
+                                None => text + ".",
+                            }
+                        } else {
+                            text
+                        }
+                    }
+                },
+                Some(_) => unreachable!(),
+            }
+        };
+
+        if self.coerce_zero && negative && magnitude.is_finite() {
+            let mantissa = body.split(['e', 'E']).next().unwrap_or("");
+            if !mantissa.chars().any(|c| matches!(c, '1'..='9')) {
+                negative = false;
+            }
+        }
+
+        let split = body
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(body.len());
+        let (digits, rest) = body.split_at(split);
+        self.assemble_number(negative, "", digits, rest, 3)
+    }
+
+    fn format_str(&self, value: &str) -> Result<String> {
+        if let Some(kind) = self.kind {
+            if kind != 's' {
+                return Err(self.unknown_code(kind));
+            }
+        }
+        match self.sign {
+            Some(' ') => {
+                return Err(value_error(
+                    "Space not allowed in string format specifier".to_string(),
+                ))
+            }
+            Some(_) => {
+                return Err(value_error(
+                    "Sign not allowed in string format specifier".to_string(),
+                ))
+            }
+            None => {}
+        }
+        if self.coerce_zero {
+            return Err(value_error(
+                "Negative zero coercion (z) not allowed in string format specifier".to_string(),
+            ));
+        }
+        if self.alternate {
+            return Err(value_error(
+                "Alternate form (#) not allowed in string format specifier".to_string(),
+            ));
+        }
+        if self.align == Some('=') {
+            return Err(value_error(
+                "'=' alignment not allowed in string format specifier".to_string(),
+            ));
+        }
+        let text: String = match self.precision {
+            Some(precision) => value.chars().take(precision).collect(),
+            None => value.to_string(),
+        };
+        self.pad(&text, '<')
+    }
+
+    /// 定点表示，替代形式在精度为 0 时保留小数点
+    fn fixed(&self, value: f64, precision: usize) -> String {
+        let text = format!("{:.*}", precision, value);
+        if self.alternate && precision == 0 {
+            text + "."
+        } else {
+            text
+        }
+    }
+
+    /// 科学计数法，指数至少两位并带符号
+    fn exponent(&self, value: f64, precision: usize) -> String {
+        let text = format!("{:.*e}", precision, value);
+        let (mantissa, exponent) = text.split_once('e').unwrap();
+        let exponent: i32 = exponent.parse().unwrap();
+        let dot = if self.alternate && precision == 0 {
+            "."
+        } else {
+            ""
+        };
+        format!(
+            "{}{}e{}{:02}",
+            mantissa,
+            dot,
+            if exponent < 0 { '-' } else { '+' },
+            exponent.abs()
+        )
+    }
+
+    /// `g` 格式：按有效数字位数选择定点或科学计数法
+    ///
+    /// `repr_style` 对应省略类型但给出精度的情况：定点表示至少保留一位小数，
+    /// 并且提前一位切换到科学计数法。
+    fn general(&self, value: f64, precision: usize, repr_style: bool) -> String {
+        let precision = precision.max(1);
+        let sci = format!("{:.*e}", precision - 1, value);
+        let (_, exponent) = sci.split_once('e').unwrap();
+        let decimal_point = exponent.parse::<i64>().unwrap() + 1;
+        let limit = if repr_style {
+            precision as i64 - 1
+        } else {
+            precision as i64
+        };
+
+        if decimal_point <= -4 || decimal_point > limit {
+            let text = self.exponent(value, precision - 1);
+            if self.alternate {
+                return text;
+            }
+            let (mantissa, exponent) = text.split_once('e').unwrap();
+            format!("{}e{}", strip_zeros(mantissa), exponent)
+        } else {
+            let decimals = (precision as i64 - decimal_point).max(0) as usize;
+            let text = format!("{:.*}", decimals, value);
+            if self.alternate {
+                if text.contains('.') {
+                    text
+                } else {
+                    text + "."
+                }
+            } else {
+                let text = strip_zeros(&text).to_string();
+                if repr_style && !text.contains('.') {
+                    text + ".0"
+                } else {
+                    text
+                }
+            }
+        }
+    }
+
+    /// 组合符号、前缀、分组后的整数部分与其余部分，再按宽度填充
+    fn assemble_number(
+        &self,
+        negative: bool,
+        prefix: &str,
+        digits: &str,
+        rest: &str,
+        interval: usize,
+    ) -> Result<String> {
+        self.check_width()?;
+        let sign = if negative {
+            "-"
+        } else {
+            match self.sign {
+                Some('+') => "+",
+                Some(' ') => " ",
+                _ => "",
+            }
+        };
+        let fill = self.fill_char();
+        let align = self.align.unwrap_or(if self.zero_pad { '=' } else { '>' });
+
+        // 用 0 填充时，补上的 0 同样参与分组
+        let min_digits = if fill == '0' && align == '=' {
+            self.width
+                .saturating_sub(sign.len() + prefix.len() + rest.chars().count())
+        } else {
+            0
+        };
+        let grouped = group_digits(digits, self.grouping, interval, min_digits);
+
+        let head = format!("{}{}", sign, prefix);
+        let tail = format!("{}{}", grouped, rest);
+        let len = head.chars().count() + tail.chars().count();
+        if len >= self.width {
+            return Ok(head + &tail);
+        }
+        let padding = self.width - len;
+        if align == '=' {
+            let fill: String = std::iter::repeat_n(fill, padding).collect();
+            return Ok(head + &fill + &tail);
+        }
+        Ok(self.pad_with(&(head + &tail), padding, align))
+    }
+
+    fn fill_char(&self) -> char {
+        self.fill.unwrap_or(if self.zero_pad { '0' } else { ' ' })
+    }
+
+    /// 按对齐方式填充到宽度，`default_align` 为未指定对齐时的方式
+    fn pad(&self, text: &str, default_align: char) -> Result<String> {
+        self.check_width()?;
+        let len = text.chars().count();
+        if len >= self.width {
+            return Ok(text.to_string());
+        }
+        Ok(self.pad_with(text, self.width - len, self.align.unwrap_or(default_align)))
+    }
+
+    /// 填充前检查宽度，避免按格式说明中的宽度分配过大的内存
+    fn check_width(&self) -> Result<()> {
+        if self.width > MAX_STRING_SIZE {
+            return Err(VMError::MemoryError);
+        }
+        Ok(())
+    }
+
+    fn pad_with(&self, text: &str, padding: usize, align: char) -> String {
+        let fill = self.fill_char();
+        let (left, right) = match align {
+            '<' => (0, padding),
+            '^' => (padding / 2, padding - padding / 2),
+            _ => (padding, 0),
+        };
+        let mut out = String::with_capacity(text.len() + padding);
+        out.extend(std::iter::repeat_n(fill, left));
+        out.push_str(text);
+        out.extend(std::iter::repeat_n(fill, right));
+        out
+    }
+}
+
+/// 读取一个十进制数，返回 (数值, 结束位置)
+fn read_number(chars: &[char], start: usize, spec: &str) -> Result<(Option<usize>, usize)> {
+    let mut end = start;
+    while end < chars.len() && chars[end].is_ascii_digit() {
+        end += 1;
+    }
+    if end == start {
+        return Ok((None, start));
+    }
+    let text: String = chars[start..end].iter().collect();
+    let number = text.parse().map_err(|_| {
+        value_error(format!(
+            "Too many decimal digits in format string '{}'",
+            spec
+        ))
+    })?;
+    Ok((Some(number), end))
+}
+
+/// 从右向左每 `interval` 位插入分隔符，不足 `min_width` 时用 0 补齐；
+/// 补上的 0 同样分组，与 CPython 的 `InsertThousandsGrouping` 一致
+fn group_digits(
+    digits: &str,
+    separator: Option<char>,
+    interval: usize,
+    min_width: usize,
+) -> String {
+    let separator = match separator {
+        Some(separator) if !digits.is_empty() => separator,
+        _ => {
+            let len = digits.len();
+            if len >= min_width {
+                return digits.to_string();
+            }
+            return "0".repeat(min_width - len) + digits;
+        }
+    };
+
+    let mut source = digits.chars().rev();
+    let mut remaining = digits.len() as isize;
+    let mut min_width = min_width as isize;
+    let interval = interval as isize;
+    let mut out = Vec::new();
+    loop {
+        let len = interval.min(remaining.max(min_width).max(1));
+        let chars = remaining.min(len);
+        out.extend(source.by_ref().take(chars as usize));
+        out.extend(std::iter::repeat_n('0', (len - chars) as usize));
+        remaining -= chars;
+        min_width -= interval;
+        if remaining <= 0 && min_width <= 0 {
+            break;
+        }
+        out.push(separator);
+        min_width -= 1;
+    }
+    out.iter().rev().collect()
+}
+
+/// 去掉小数部分末尾的 0，以及随之多余的小数点
+fn strip_zeros(text: &str) -> &str {
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        text
+    }
+}
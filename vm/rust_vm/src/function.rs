@@ -0,0 +1,325 @@
+/*!
+函数、闭包与调用帧
+
+内层函数通过单元（cell）捕获外层函数的变量：被捕获的局部变量在进入函数时包装为单元，
+创建闭包时把单元的引用交给闭包，调用闭包时再放入内层函数的自由变量槽位。
+捕获同一个变量的多个闭包共享同一个单元，能观察到彼此的修改。
+
+调用时实参按 Python 的规则绑定到参数（见 [`Function::bind`]）：参数依次为位置参数、
+`*args`、仅限关键字参数和 `**kwargs`，位置参数和仅限关键字参数可以有默认值。
+*/
+
+use crate::bytecode::{ExceptionTable, Instruction};
+use crate::dict::Dict;
+use crate::object::Class;
+use crate::value::Value;
+use crate::{Result, VMError};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// 被闭包捕获的变量
+pub type Cell = Rc<RefCell<Value>>;
+
+/// 关键字实参：参数名和值，按调用时的顺序排列
+pub type Keywords = Vec<(Rc<str>, Value)>;
+
+/// 用户定义的函数
+#[derive(Debug, Clone)]
+pub struct Function {
+    /// 函数名（方法为 `类名.方法名`）
+    pub name: String,
+
+    /// 参数名，依次为位置参数、`*args`、仅限关键字参数和 `**kwargs`，占据前 N 个局部变量槽
+    pub parameters: Vec<String>,
+
+    /// 仅限关键字参数的个数
+    pub kwonly_count: usize,
+
+    /// 是否有收集多余位置参数的 `*args`
+    pub varargs: bool,
+
+    /// 是否有收集多余关键字参数的 `**kwargs`
+    pub varkw: bool,
+
+    /// 位置参数的默认值，对应最后 N 个位置参数
+    pub defaults: Vec<Value>,
+
+    /// 仅限关键字参数的默认值
+    pub kwdefaults: HashMap<String, Value>,
+
+    /// 是否为生成器函数：调用时不执行函数体，而是返回持有调用帧的生成器
+    pub generator: bool,
+
+    /// 是否为协程函数（`async func`）：调用时返回持有调用帧的协程，由 `await` 或事件循环驱动
+    pub coroutine: bool,
+
+    /// 函数体指令
+    pub instructions: Vec<Instruction>,
+
+    /// 局部变量名到槽位的映射
+    pub local_vars: HashMap<String, usize>,
+
+    /// 被内层函数捕获的局部变量槽位，进入函数时包装为单元
+    pub cell_vars: Vec<usize>,
+
+    /// 自由变量槽位，依次存放闭包捕获的单元
+    pub free_vars: Vec<usize>,
+
+    /// 异常处理表，由虚拟机加载时根据 [`crate::bytecode::analyze`] 的结果填入
+    pub handlers: ExceptionTable,
+
+    /// 所属模块在虚拟机模块表中的序号，由虚拟机加载时填入
+    pub module: usize,
+
+    /// 在虚拟机函数表中的编号，由虚拟机加载时填入
+    pub id: usize,
+}
+
+impl Function {
+    /// 位置参数（`*args` 之前的参数）的个数
+    pub fn positional_count(&self) -> usize {
+        self.parameters.len() - self.kwonly_count - self.varargs as usize - self.varkw as usize
+    }
+
+    /// 参数表的源码形式，如 `a, b=1, *args, c, **kwargs`
+    pub fn signature(&self) -> String {
+        let positional = self.positional_count();
+        let kwonly_start = positional + self.varargs as usize;
+        let first_default = positional - self.defaults.len();
+        let mut parts = Vec::with_capacity(self.parameters.len() + 1);
+        for (slot, name) in self.parameters.iter().enumerate() {
+            let default = if slot < positional {
+                slot.checked_sub(first_default)
+                    .map(|index| &self.defaults[index])
+            } else {
+                self.kwdefaults.get(name)
+            };
+            if slot == kwonly_start && !self.varargs && self.kwonly_count > 0 {
+                parts.push("*".to_string());
+            }
+            parts.push(match default {
+                Some(value) => format!("{}={}", name, value.repr()),
+                None if self.varargs && slot == positional => format!("*{}", name),
+                None if self.varkw && slot == self.parameters.len() - 1 => format!("**{}", name),
+                None => name.clone(),
+            });
+        }
+        parts.join(", ")
+    }
+
+    /// 把位置实参和关键字实参绑定到参数，返回按参数顺序排列的值
+    ///
+    /// 多余的位置实参放入 `*args` 元组，不对应任何参数的关键字实参放入 `**kwargs` 字典，
+    /// 未传入的参数取默认值；错误信息与 Python 一致。
+    pub fn bind(&self, mut args: Vec<Value>, keywords: Keywords) -> Result<Vec<Value>> {
+        let positional = self.positional_count();
+        let kwonly_start = positional + self.varargs as usize;
+        let named = kwonly_start + self.kwonly_count;
+        let mut bound: Vec<Option<Value>> = vec![None; self.parameters.len()];
+
+        let extra = args.split_off(args.len().min(positional));
+        if !extra.is_empty() && !self.varargs {
+            let kwonly_given = keywords
+                .iter()
+                .filter(|(name, _)| {
+                    self.parameters[kwonly_start..named]
+                        .iter()
+                        .any(|p| **p == **name)
+                })
+                .count();
+            return Err(self.too_many_positional(positional + extra.len(), kwonly_given));
+        }
+        for (slot, value) in args.into_iter().enumerate() {
+            bound[slot] = Some(value);
+        }
+        if self.varargs {
+            bound[positional] = Some(Value::Tuple(extra.into()));
+        }
+
+        let mut varkw = Dict::new();
+        for (name, value) in keywords {
+            let slot = (0..named)
+                .filter(|&slot| !(self.varargs && slot == positional))
+                .find(|&slot| *self.parameters[slot] == *name);
+            match slot {
+                Some(slot) if bound[slot].is_some() => {
+                    return Err(
+                        self.type_error(format!("got multiple values for argument '{}'", name))
+                    );
+                }
+                Some(slot) => bound[slot] = Some(value),
+                None if self.varkw => {
+                    if varkw.insert(Value::String(name.clone()), value)?.is_some() {
+                        return Err(self.type_error(format!(
+                            "got multiple values for keyword argument '{}'",
+                            name
+                        )));
+                    }
+                }
+                None => {
+                    return Err(
+                        self.type_error(format!("got an unexpected keyword argument '{}'", name))
+                    );
+                }
+            }
+        }
+        if self.varkw {
+            bound[named] = Some(Value::Dict(Rc::new(RefCell::new(varkw))));
+        }
+
+        let first_default = positional - self.defaults.len();
+        let missing: Vec<_> = (0..first_default)
+            .filter(|&slot| bound[slot].is_none())
+            .map(|slot| &self.parameters[slot])
+            .collect();
+        if !missing.is_empty() {
+            return Err(self.missing("positional", &missing));
+        }
+        for (slot, default) in (first_default..positional).zip(&self.defaults) {
+            bound[slot].get_or_insert_with(|| default.clone());
+        }
+        let mut missing = Vec::new();
+        for (value, name) in bound[kwonly_start..named]
+            .iter_mut()
+            .zip(&self.parameters[kwonly_start..named])
+        {
+            match self.kwdefaults.get(name) {
+                Some(default) => {
+                    value.get_or_insert_with(|| default.clone());
+                }
+                None if value.is_none() => missing.push(name),
+                None => {}
+            }
+        }
+        if !missing.is_empty() {
+            return Err(self.missing("keyword-only", &missing));
+        }
+
+        Ok(bound.into_iter().map(Option::unwrap_or_default).collect())
+    }
+
+    fn type_error(&self, message: String) -> VMError {
+        VMError::TypeError(format!("{}() {}", self.name, message))
+    }
+
+    /// `f() takes from 1 to 2 positional arguments but 3 were given`
+    fn too_many_positional(&self, given: usize, kwonly_given: usize) -> VMError {
+        let positional = self.positional_count();
+        let (signature, plural) = match self.defaults.len() {
+            0 => (positional.to_string(), positional != 1),
+            defaults => (
+                format!("from {} to {}", positional - defaults, positional),
+                true,
+            ),
+        };
+        let kwonly = match kwonly_given {
+            0 => String::new(),
+            n => format!(
+                " positional argument{} (and {} keyword-only argument{})",
+                if given != 1 { "s" } else { "" },
+                n,
+                if n != 1 { "s" } else { "" }
+            ),
+        };
+        self.type_error(format!(
+            "takes {} positional argument{} but {}{} {} given",
+            signature,
+            if plural { "s" } else { "" },
+            given,
+            kwonly,
+            if given == 1 && kwonly_given == 0 {
+                "was"
+            } else {
+                "were"
+            }
+        ))
+    }
+
+    /// `f() missing 2 required positional arguments: 'a' and 'b'`
+    fn missing(&self, kind: &str, names: &[&String]) -> VMError {
+        let quoted: Vec<_> = names.iter().map(|name| format!("'{}'", name)).collect();
+        let list = match &quoted[..] {
+            [only] => only.clone(),
+            [first, second] => format!("{} and {}", first, second),
+            [init @ .., last] => format!("{}, and {}", init.join(", "), last),
+            [] => String::new(),
+        };
+        self.type_error(format!(
+            "missing {} required {} argument{}: {}",
+            names.len(),
+            kind,
+            if names.len() != 1 { "s" } else { "" },
+            list
+        ))
+    }
+}
+
+/// 闭包：函数及其捕获的单元
+#[derive(Debug, Clone)]
+pub struct Closure {
+    pub function: Rc<Function>,
+
+    /// 与函数的 `free_vars` 一一对应
+    pub cells: Vec<Cell>,
+}
+
+/// 调用帧
+#[derive(Debug, Clone)]
+pub struct CallFrame {
+    /// 正在执行的函数
+    pub function: Rc<Function>,
+
+    /// 程序计数器，帧处于活动状态时由虚拟机缓存，调用其他函数时写回
+    pub pc: usize,
+
+    /// 进入函数时操作数栈的高度，返回时栈被截断到这里
+    pub stack_base: usize,
+
+    /// 局部变量
+    pub locals: Vec<Value>,
+
+    /// 构造调用中由 `__init__` 初始化的实例，帧返回时以它代替返回值
+    pub instance: Option<Value>,
+
+    /// 正在执行的方法所属的类，`super()` 从这里开始查找
+    pub class: Option<Rc<Class>>,
+
+    /// 正在执行的 catch/finally 块，内层在后
+    pub handling: Vec<Handling>,
+
+    /// 被本帧依次替换掉的尾调用帧数
+    pub tail_calls: usize,
+}
+
+/// 调用帧中一个正在执行的 catch 或 finally 块
+#[derive(Debug, Clone)]
+pub enum Handling {
+    /// catch 块及其捕获的异常，`RERAISE` 重新抛出它
+    Catch { handler: usize, exception: Value },
+
+    /// finally 块及其结束后要继续的动作
+    Finally { handler: usize, then: Completion },
+}
+
+impl Handling {
+    /// 所属处理器在函数异常处理表中的索引
+    pub fn handler(&self) -> usize {
+        match self {
+            Handling::Catch { handler, .. } | Handling::Finally { handler, .. } => *handler,
+        }
+    }
+}
+
+/// finally 块结束后继续的动作
+#[derive(Debug, Clone)]
+pub enum Completion {
+    /// 继续执行语句之后的代码
+    Normal,
+
+    /// 继续传播异常
+    Raise(Value),
+
+    /// 继续从函数返回
+    Return(Value),
+}
@@ -0,0 +1,76 @@
+/*!
+生成器
+
+调用生成器函数时不执行函数体，而是创建一个持有调用帧的生成器。每次恢复执行时，
+虚拟机把帧连同它的操作数栈内容放回调用栈，执行到 `YIELD_VALUE` 时再把它们取回，
+帧因此可以跨越多次执行存在。生成器由迭代协议（`for`、`next()`）或 `send()` 驱动，
+函数返回时以 `StopIteration` 结束，`close()` 在挂起处抛出 `GeneratorExit`。
+
+协程（调用 `async func` 得到的对象）使用同样的结构，区别只在于它不能迭代，
+而是由 `await` 或事件循环的任务（见 [`crate::event_loop`]）驱动。
+*/
+
+use crate::function::CallFrame;
+use crate::value::Value;
+
+/// 生成器的执行状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneratorState {
+    /// 已创建，函数体尚未开始执行
+    Created,
+    /// 停在 `YIELD_VALUE` 或 `YIELD_FROM` 处
+    Suspended,
+    /// 正在执行，帧位于调用栈上
+    Running,
+    /// 已返回、抛出异常或被关闭
+    Closed,
+}
+
+/// 生成器对象
+#[derive(Debug, Clone)]
+pub struct Generator {
+    /// 生成器函数名
+    pub name: String,
+
+    /// 是否为协程
+    pub coroutine: bool,
+
+    pub state: GeneratorState,
+
+    /// 挂起的调用帧，`stack_base` 在恢复时重新确定；运行中和结束后为 `None`
+    pub frame: Option<CallFrame>,
+
+    /// 挂起时帧的操作数栈内容
+    pub stack: Vec<Value>,
+}
+
+impl Generator {
+    /// 为尚未开始执行的帧创建生成器
+    pub fn new(frame: CallFrame) -> Self {
+        Self {
+            name: frame.function.name.clone(),
+            coroutine: frame.function.coroutine,
+            state: GeneratorState::Created,
+            frame: Some(frame),
+            stack: Vec::new(),
+        }
+    }
+}
+
+/// 生成器恢复执行的方式
+#[derive(Debug, Clone)]
+pub enum Resume {
+    /// 作为 `yield` 表达式的值继续执行（`next()` 发送 `None`）
+    Send(Value),
+    /// 在挂起处抛出异常
+    Throw(Value),
+}
+
+/// 生成器执行一步的结果
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// 产出一个值后挂起
+    Yielded(Value),
+    /// 函数返回，生成器结束
+    Returned(Value),
+}
@@ -0,0 +1,221 @@
+/*!
+迭代协议
+
+`GET_ITER` 通过 [`Value::iter`] 把可迭代值转换为迭代器，`FOR_ITER` 反复调用
+[`Iter::next_item`]，耗尽时跳出循环。原生迭代器覆盖：
+- 列表、元组、字节串（按元素）
+- 字符串（按字符）
+- 字典（按插入顺序遍历键）
+- [`Range`]：惰性整数序列，`range(10_000_000)` 只保存起点、终点和步长
+*/
+
+use crate::dict::Dict;
+use crate::value::Value;
+use crate::{Result, VMError};
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/// `range(start, stop, step)` 对象
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub start: i64,
+    pub stop: i64,
+    pub step: i64,
+}
+
+impl Range {
+    /// 创建范围，步长不能为 0
+    pub fn new(start: i64, stop: i64, step: i64) -> Result<Self> {
+        if step == 0 {
+            return Err(VMError::ValueError(
+                "range() arg 3 must not be zero".to_string(),
+            ));
+        }
+        Ok(Self { start, stop, step })
+    }
+
+    /// 元素个数
+    pub fn len(&self) -> usize {
+        let (start, stop, step) = (self.start as i128, self.stop as i128, self.step as i128);
+        let len = if step > 0 && start < stop {
+            (stop - start - 1) / step + 1
+        } else if step < 0 && start > stop {
+            (start - stop - 1) / -step + 1
+        } else {
+            0
+        };
+        len as usize
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 第 `index` 个元素（`index` 已确认在范围内）
+    pub fn nth(&self, index: usize) -> i64 {
+        (self.start as i128 + index as i128 * self.step as i128) as i64
+    }
+
+    /// 整数是否落在范围内
+    pub fn contains(&self, value: i64) -> bool {
+        let in_bounds = if self.step > 0 {
+            self.start <= value && value < self.stop
+        } else {
+            self.stop < value && value <= self.start
+        };
+        in_bounds && (value as i128 - self.start as i128) % self.step as i128 == 0
+    }
+}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.step == 1 {
+            write!(f, "range({}, {})", self.start, self.stop)
+        } else {
+            write!(f, "range({}, {}, {})", self.start, self.stop, self.step)
+        }
+    }
+}
+
+/// 迭代器状态
+#[derive(Debug)]
+pub enum Iter {
+    /// 列表迭代器按位置读取，能看到迭代期间追加的元素
+    List {
+        items: Rc<RefCell<Vec<Value>>>,
+        index: usize,
+    },
+    Tuple {
+        items: Rc<[Value]>,
+        index: usize,
+    },
+    /// 字符串迭代器记录字节偏移
+    Str {
+        text: Rc<str>,
+        offset: usize,
+    },
+    Bytes {
+        bytes: Rc<[u8]>,
+        index: usize,
+    },
+    /// 字典键迭代器，迭代期间字典大小改变时报错
+    DictKeys {
+        dict: Rc<RefCell<Dict>>,
+        index: usize,
+        len: usize,
+    },
+    Range {
+        next: i64,
+        step: i64,
+        remaining: usize,
+    },
+}
+
+impl Iter {
+    /// 为内置的可迭代值创建迭代器
+    pub fn new(value: &Value) -> Result<Self> {
+        let iter = match value {
+            Value::List(items) => Iter::List {
+                items: items.clone(),
+                index: 0,
+            },
+            Value::Tuple(items) => Iter::Tuple {
+                items: items.clone(),
+                index: 0,
+            },
+            Value::String(text) => Iter::Str {
+                text: text.clone(),
+                offset: 0,
+            },
+            Value::Bytes(bytes) => Iter::Bytes {
+                bytes: bytes.clone(),
+                index: 0,
+            },
+            Value::Dict(dict) => Iter::DictKeys {
+                len: dict.borrow().len(),
+                dict: dict.clone(),
+                index: 0,
+            },
+            Value::Range(range) => Iter::Range {
+                next: range.start,
+                step: range.step,
+                remaining: range.len(),
+            },
+            other => {
+                return Err(VMError::TypeError(format!(
+                    "'{}' object is not iterable",
+                    other.type_name()
+                )))
+            }
+        };
+        Ok(iter)
+    }
+
+    /// 取下一个元素，耗尽时返回 `None`
+    pub fn next_item(&mut self) -> Result<Option<Value>> {
+        let value = match self {
+            Iter::List { items, index } => {
+                let value = items.borrow().get(*index).cloned();
+                *index += value.is_some() as usize;
+                value
+            }
+            Iter::Tuple { items, index } => {
+                let value = items.get(*index).cloned();
+                *index += value.is_some() as usize;
+                value
+            }
+            Iter::Str { text, offset } => text[*offset..].chars().next().map(|c| {
+                *offset += c.len_utf8();
+                Value::String(c.to_string().into())
+            }),
+            Iter::Bytes { bytes, index } => {
+                let value = bytes.get(*index).map(|&b| Value::Int(b as i64));
+                *index += value.is_some() as usize;
+                value
+            }
+            Iter::DictKeys { dict, index, len } => {
+                let dict = dict.borrow();
+                if dict.len() != *len {
+                    // 与 CPython 相同，出错后迭代器保持出错状态
+                    *len = usize::MAX;
+                    return Err(VMError::RuntimeError(
+                        "dictionary changed size during iteration".to_string(),
+                    ));
+                }
+                let key = dict.entry_at(*index).map(|(key, _)| key.clone());
+                *index += key.is_some() as usize;
+                key
+            }
+            Iter::Range {
+                next,
+                step,
+                remaining,
+            } => {
+                if *remaining == 0 {
+                    return Ok(None);
+                }
+                let value = *next;
+                *remaining -= 1;
+                if *remaining > 0 {
+                    *next += *step;
+                }
+                Some(Value::Int(value))
+            }
+        };
+        Ok(value)
+    }
+
+    /// 与 Python 一致的迭代器类型名
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Iter::List { .. } => "list_iterator",
+            Iter::Tuple { .. } => "tuple_iterator",
+            Iter::Str { .. } => "str_iterator",
+            Iter::Bytes { .. } => "bytes_iterator",
+            Iter::DictKeys { .. } => "dict_keyiterator",
+            Iter::Range { .. } => "range_iterator",
+        }
+    }
+}
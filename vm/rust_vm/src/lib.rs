@@ -10,17 +10,25 @@ AquaScript 高性能虚拟机 - Rust实现
 - 缓存友好的数据结构
 */
 
-use std::collections::HashMap;
 use std::fmt;
-use rustc_hash::FxHashMap;
-use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 pub mod bytecode;
 pub mod vm;
 pub mod value;
+pub mod dict;
 pub mod function;
+pub mod generator;
+pub mod event_loop;
 pub mod builtins;
+pub mod format;
+pub mod iter;
+pub mod object;
+pub mod methods;
+pub mod exceptions;
+pub mod module;
+pub mod package;
+pub mod registry;
 
 #[cfg(feature = "python-bindings")]
 pub mod python;
@@ -50,8 +58,49 @@ pub enum VMError {
     #[error("Division by zero")]
     DivisionByZero,
     
-    #[error("Index out of bounds: {index} >= {len}")]
-    IndexOutOfBounds { index: usize, len: usize },
+    #[error("Invalid bytecode: {0}")]
+    InvalidBytecode(String),
+    
+    #[error("Assembly error at line {line}: {message}")]
+    Assembly { line: usize, message: String },
+    
+    #[error("Verification failed in {function} at pc {pc}: {reason}")]
+    Verify { function: String, pc: usize, reason: String },
+    
+    #[error("Index out of bounds: {index} (length {len})")]
+    IndexOutOfBounds { index: i64, len: usize },
+    
+    #[error("Key error: {0}")]
+    KeyError(String),
+    
+    #[error("Attribute error: {0}")]
+    AttributeError(String),
+    
+    #[error("Value error: {0}")]
+    ValueError(String),
+    
+    #[error("Overflow error: {0}")]
+    OverflowError(String),
+    
+    /// 生成的值超过 [`value::MAX_STRING_SIZE`] 等大小上限
+    #[error("Memory error")]
+    MemoryError,
+    
+    #[error("StopIteration")]
+    StopIteration,
+    
+    #[error("Import error: {0}")]
+    ImportError(String),
+    
+    #[error("No module named '{0}'")]
+    ModuleNotFound(String),
+    
+    #[error("Package error: {0}")]
+    Package(String),
+    
+    /// 脚本抛出且未被捕获的异常，携带被抛出的值
+    #[error("Uncaught exception: {}", exceptions::describe(.0))]
+    Exception(Value),
     
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
@@ -70,6 +119,7 @@ pub struct VMStats {
     pub gc_collections: u64,
     pub peak_stack_size: usize,
     pub peak_call_stack_depth: usize,
+    pub tail_calls: u64,
 }
 
 impl fmt::Display for VMStats {
@@ -80,12 +130,14 @@ impl fmt::Display for VMStats {
              Function calls: {}\n\
              GC collections: {}\n\
              Peak stack size: {}\n\
-             Peak call stack depth: {}",
+             Peak call stack depth: {}\n\
+             Tail calls: {}",
             self.instructions_executed,
             self.function_calls,
             self.gc_collections,
             self.peak_stack_size,
-            self.peak_call_stack_depth
+            self.peak_call_stack_depth,
+            self.tail_calls
         )
     }
 }
\ No newline at end of file
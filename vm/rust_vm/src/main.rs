@@ -0,0 +1,178 @@
+/*!
+AquaScript 虚拟机命令行入口
+
+用法：
+    aqua-vm program.acode [--stats]
+    aqua-vm run program.acode [--stats]
+    aqua-vm run app.apack [--stats]
+    aqua-vm disasm program.acode
+    aqua-vm asm program.aasm -o program.acode
+
+导入的模块先在程序所在目录中查找，其次是环境变量 `AQUA_PATH` 中的目录。
+运行应用包时不搜索包所在目录，包内的模块先于 `AQUA_PATH` 查找。
+应用包的依赖从 `--registry` 指定的目录中解析，默认为环境变量 `AQUA_REGISTRY`，
+其次是应用包所在目录下的 `packages`；解析结果写入应用包旁的 `aqua.lock`。
+*/
+
+use anyhow::Context;
+use aqua_vm::bytecode::{self, Bytecode};
+use aqua_vm::package::Package;
+use aqua_vm::registry::{self, LOCKFILE};
+use aqua_vm::vm::VMConfig;
+use aqua_vm::{AquaVM, VMError};
+use clap::{Args, Parser, Subcommand};
+use std::path::{Path, PathBuf};
+
+/// AquaScript 高性能虚拟机
+#[derive(Parser, Debug)]
+#[command(
+    name = "aqua-vm",
+    version,
+    about,
+    args_conflicts_with_subcommands = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// 不带子命令时等同于 `run`
+    #[command(flatten)]
+    run: Option<RunArgs>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// 执行 .acode 字节码文件或 .apack 应用包
+    Run(RunArgs),
+
+    /// 输出字节码的反汇编清单
+    Disasm {
+        /// 要反汇编的 .acode 文件
+        file: PathBuf,
+    },
+
+    /// 把 .aasm 汇编源码汇编为 .acode 文件
+    Asm {
+        /// 汇编源文件
+        file: PathBuf,
+
+        /// 输出文件，默认与源文件同名、扩展名为 .acode
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Args, Debug)]
+struct RunArgs {
+    /// 要执行的 .acode 字节码文件或 .apack 应用包
+    file: PathBuf,
+
+    /// 执行结束后打印性能统计
+    #[arg(long)]
+    stats: bool,
+
+    /// 启用调试模式
+    #[arg(long)]
+    debug: bool,
+
+    /// 解析应用包依赖的本地包目录
+    #[arg(long)]
+    registry: Option<PathBuf>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match (cli.command, cli.run) {
+        (Some(Command::Run(args)), _) | (None, Some(args)) => run(args),
+        (Some(Command::Disasm { file }), _) => {
+            print!("{}", load(&file)?.disassemble());
+            Ok(())
+        }
+        (Some(Command::Asm { file, output }), _) => asm(&file, output),
+        (None, None) => {
+            use clap::CommandFactory;
+            Cli::command().print_help()?;
+            Ok(())
+        }
+    }
+}
+
+fn load(path: &Path) -> anyhow::Result<Bytecode> {
+    Bytecode::read_from(path)
+        .map_err(vm_error)
+        .with_context(|| format!("failed to load {}", path.display()))
+}
+
+fn asm(file: &Path, output: Option<PathBuf>) -> anyhow::Result<()> {
+    let source = std::fs::read_to_string(file)
+        .with_context(|| format!("failed to read {}", file.display()))?;
+    let bytecode = bytecode::assemble(&source)
+        .map_err(vm_error)
+        .with_context(|| format!("failed to assemble {}", file.display()))?;
+    bytecode::verify(&bytecode).map_err(vm_error)?;
+
+    let output = output.unwrap_or_else(|| file.with_extension("acode"));
+    bytecode
+        .write_to(&output)
+        .map_err(vm_error)
+        .with_context(|| format!("failed to write {}", output.display()))?;
+    Ok(())
+}
+
+fn run(args: RunArgs) -> anyhow::Result<()> {
+    let config = VMConfig {
+        enable_stats: args.stats,
+        debug_mode: args.debug,
+        ..VMConfig::default()
+    };
+
+    let mut vm = if args.file.extension().is_some_and(|ext| ext == "apack") {
+        let package = Package::open(&args.file)
+            .map_err(vm_error)
+            .with_context(|| format!("failed to open {}", args.file.display()))?;
+        let directory = parent_directory(&args.file);
+        let registry = args
+            .registry
+            .or_else(|| std::env::var_os("AQUA_REGISTRY").map(PathBuf::from))
+            .unwrap_or_else(|| directory.join("packages"));
+        let dependencies = registry::install(&package, &registry, &directory.join(LOCKFILE))
+            .map_err(vm_error)
+            .context("failed to resolve dependencies")?;
+
+        let mut vm = AquaVM::with_config(config);
+        for dependency in &dependencies {
+            vm.register_package(dependency);
+        }
+        vm.load_package(&package).map_err(vm_error)?;
+        vm
+    } else {
+        let bytecode = load(&args.file)?;
+        let mut vm = AquaVM::with_config(VMConfig {
+            module_paths: vec![parent_directory(&args.file)],
+            ..config
+        });
+        vm.load_bytecode(&bytecode).map_err(vm_error)?;
+        vm
+    };
+    vm.run().map_err(vm_error)?;
+
+    if args.stats {
+        eprintln!("{}", vm.get_stats());
+    }
+
+    Ok(())
+}
+
+/// 文件所在的目录，相对路径中没有目录部分时为当前目录
+fn parent_directory(file: &Path) -> PathBuf {
+    match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// 虚拟机错误可能携带脚本中的值，不能跨线程传递，转换时只保留错误信息
+fn vm_error(error: VMError) -> anyhow::Error {
+    anyhow::Error::msg(error.to_string())
+}
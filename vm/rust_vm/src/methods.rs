@@ -0,0 +1,433 @@
+/*!
+内置类型的方法
+
+`CALL_METHOD` 的接收者不是用户类的实例时在这里查找，例如 `xs.append(1)`、
+`d.get("k", 0)`、`", ".join(names)`。
+
+与 Python 的差异：字典的 `keys()` / `values()` / `items()` 返回列表快照，
+而不是动态视图。
+*/
+
+use crate::dict::Dict;
+use crate::iter::Iter;
+use crate::value::{position, Value};
+use crate::{Result, VMError};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const LIST_METHODS: &[&str] = &[
+    "append", "extend", "insert", "pop", "remove", "index", "count", "clear", "copy", "reverse",
+    "sort",
+];
+
+const DICT_METHODS: &[&str] = &[
+    "get", "keys", "values", "items", "pop", "update", "clear", "copy",
+];
+
+const STR_METHODS: &[&str] = &[
+    "upper",
+    "lower",
+    "strip",
+    "lstrip",
+    "rstrip",
+    "split",
+    "join",
+    "replace",
+    "startswith",
+    "endswith",
+    "find",
+    "count",
+];
+
+/// 内置类型是否有该方法
+pub fn has_method(receiver: &Value, name: &str) -> bool {
+    let methods = match receiver {
+        Value::List(_) => LIST_METHODS,
+        Value::Dict(_) => DICT_METHODS,
+        Value::String(_) => STR_METHODS,
+        _ => return false,
+    };
+    methods.contains(&name)
+}
+
+/// 调用内置类型的方法，没有该方法时返回 `None`
+pub fn call_method(receiver: &Value, name: &str, args: &[Value]) -> Option<Result<Value>> {
+    if !has_method(receiver, name) {
+        return None;
+    }
+    let call = Call {
+        type_name: receiver.type_name(),
+        name,
+        args,
+    };
+    Some(match receiver {
+        Value::List(items) => list_method(items, &call),
+        Value::Dict(dict) => dict_method(dict, &call),
+        Value::String(s) => str_method(s, &call),
+        _ => unreachable!(),
+    })
+}
+
+/// 一次方法调用的参数，负责参数个数检查
+struct Call<'a> {
+    type_name: &'a str,
+    name: &'a str,
+    args: &'a [Value],
+}
+
+impl Call<'_> {
+    /// 检查参数个数在 `min..=max` 之间
+    fn arity(&self, min: usize, max: usize) -> Result<&[Value]> {
+        let given = self.args.len();
+        if (min..=max).contains(&given) {
+            return Ok(self.args);
+        }
+        let method = format!("{}.{}()", self.type_name, self.name);
+        let message = match (min, max) {
+            (0, 0) => format!("{} takes no arguments ({} given)", method, given),
+            (1, 1) => format!("{} takes exactly one argument ({} given)", method, given),
+            _ if given < min => format!(
+                "{} expected at least {} arguments, got {}",
+                method, min, given
+            ),
+            _ => format!(
+                "{} expected at most {} arguments, got {}",
+                method, max, given
+            ),
+        };
+        Err(VMError::TypeError(message))
+    }
+
+    /// 恰好一个参数
+    fn one(&self) -> Result<&Value> {
+        Ok(&self.arity(1, 1)?[0])
+    }
+
+    /// 恰好两个参数
+    fn two(&self) -> Result<(&Value, &Value)> {
+        let args = self.arity(2, 2)?;
+        Ok((&args[0], &args[1]))
+    }
+
+    fn string_arg<'v>(&self, value: &'v Value) -> Result<&'v str> {
+        match value {
+            Value::String(s) => Ok(s),
+            other => Err(VMError::TypeError(format!(
+                "{}.{}() argument must be str, not {}",
+                self.type_name,
+                self.name,
+                other.type_name()
+            ))),
+        }
+    }
+}
+
+/// 把可迭代值展开为元素列表
+fn collect(value: &Value) -> Result<Vec<Value>> {
+    let mut iter = Iter::new(value)?;
+    let mut items = Vec::new();
+    while let Some(item) = iter.next_item()? {
+        items.push(item);
+    }
+    Ok(items)
+}
+
+/// 稳定的归并排序，只使用 `<` 比较（与 Python 的 `list.sort()` 相同）
+///
+/// 出错时 `items` 仍包含全部元素，但顺序未定义。
+pub fn merge_sort(
+    items: &mut Vec<Value>,
+    less: &mut impl FnMut(&Value, &Value) -> Result<bool>,
+) -> Result<()> {
+    if items.len() <= 1 {
+        return Ok(());
+    }
+    let mut right = items.split_off(items.len() / 2);
+    let mut left = std::mem::take(items);
+    let sorted = merge_sort(&mut left, less).and_then(|_| merge_sort(&mut right, less));
+    if let Err(e) = sorted {
+        left.append(&mut right);
+        *items = left;
+        return Err(e);
+    }
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        let take_right = match less(b, a) {
+            Ok(less) => less,
+            Err(e) => {
+                merged.extend(left.chain(right));
+                *items = merged;
+                return Err(e);
+            }
+        };
+        merged.push(
+            if take_right {
+                right.next()
+            } else {
+                left.next()
+            }
+            .unwrap(),
+        );
+    }
+    merged.extend(left.chain(right));
+    *items = merged;
+    Ok(())
+}
+
+fn list_method(items: &Rc<RefCell<Vec<Value>>>, call: &Call) -> Result<Value> {
+    match call.name {
+        "append" => {
+            let item = call.one()?;
+            items.borrow_mut().push(item.clone());
+        }
+        "extend" => {
+            // 先展开再追加，`xs.extend(xs)` 不会无限增长
+            let iterable = call.one()?;
+            let extra = collect(iterable)?;
+            items.borrow_mut().extend(extra);
+        }
+        "insert" => {
+            let (index, item) = call.two()?;
+            let Value::Int(index) = index else {
+                return Err(VMError::TypeError(format!(
+                    "'{}' object cannot be interpreted as an integer",
+                    index.type_name()
+                )));
+            };
+            let mut items = items.borrow_mut();
+            let len = items.len() as i64;
+            let position = if *index < 0 { index + len } else { *index };
+            items.insert(position.clamp(0, len) as usize, item.clone());
+        }
+        "pop" => {
+            let args = call.arity(0, 1)?;
+            let mut items = items.borrow_mut();
+            let len = items.len();
+            let index = match args {
+                [] => -1,
+                [Value::Int(i)] => *i,
+                [other] => {
+                    return Err(VMError::TypeError(format!(
+                        "'{}' object cannot be interpreted as an integer",
+                        other.type_name()
+                    )))
+                }
+                _ => unreachable!(),
+            };
+            let position = if index < 0 { index + len as i64 } else { index };
+            if position < 0 || position >= len as i64 {
+                return Err(VMError::IndexOutOfBounds { index, len });
+            }
+            return Ok(items.remove(position as usize));
+        }
+        "remove" => {
+            let item = call.one()?;
+            let mut items = items.borrow_mut();
+            let position = position(&items, item)?
+                .ok_or_else(|| VMError::ValueError("list.remove(x): x not in list".to_string()))?;
+            items.remove(position);
+        }
+        "index" => {
+            let item = call.one()?;
+            let position = position(&items.borrow(), item)?;
+            return position
+                .map(|i| Value::Int(i as i64))
+                .ok_or_else(|| VMError::ValueError(format!("{} is not in list", item.repr())));
+        }
+        "count" => {
+            let item = call.one()?;
+            let mut count = 0;
+            for x in items.borrow().iter() {
+                count += x.equals(item)? as i64;
+            }
+            return Ok(Value::Int(count));
+        }
+        "clear" => {
+            call.arity(0, 0)?;
+            items.borrow_mut().clear();
+        }
+        "copy" => {
+            call.arity(0, 0)?;
+            return Ok(Value::list(items.borrow().clone()));
+        }
+        "reverse" => {
+            call.arity(0, 0)?;
+            items.borrow_mut().reverse();
+        }
+        "sort" => {
+            call.arity(0, 0)?;
+            // 排序期间取出元素，比较出错时原样放回
+            let mut sorted = std::mem::take(&mut *items.borrow_mut());
+            let result = merge_sort(&mut sorted, &mut |a, b| Ok(a.lt(b)?.is_truthy()));
+            *items.borrow_mut() = sorted;
+            result?;
+        }
+        _ => unreachable!(),
+    }
+    Ok(Value::Null)
+}
+
+fn dict_method(dict: &Rc<RefCell<Dict>>, call: &Call) -> Result<Value> {
+    match call.name {
+        "get" => {
+            let args = call.arity(1, 2)?;
+            let found = dict.borrow().get(&args[0])?.cloned();
+            Ok(found.unwrap_or_else(|| args.get(1).cloned().unwrap_or(Value::Null)))
+        }
+        "keys" => {
+            call.arity(0, 0)?;
+            Ok(Value::list(dict.borrow().keys().cloned().collect()))
+        }
+        "values" => {
+            call.arity(0, 0)?;
+            Ok(Value::list(dict.borrow().values().cloned().collect()))
+        }
+        "items" => {
+            call.arity(0, 0)?;
+            let items = dict
+                .borrow()
+                .iter()
+                .map(|(k, v)| Value::Tuple(Rc::from([k.clone(), v.clone()])))
+                .collect();
+            Ok(Value::list(items))
+        }
+        "pop" => {
+            let args = call.arity(1, 2)?;
+            let removed = dict.borrow_mut().remove(&args[0])?;
+            match (removed, args.get(1)) {
+                (Some(value), _) => Ok(value),
+                (None, Some(default)) => Ok(default.clone()),
+                (None, None) => Err(VMError::KeyError(args[0].repr())),
+            }
+        }
+        "update" => {
+            let other = call.one()?;
+            let Value::Dict(other) = other else {
+                return Err(VMError::TypeError(format!(
+                    "'{}' object is not a mapping",
+                    other.type_name()
+                )));
+            };
+            let entries: Vec<(Value, Value)> = other
+                .borrow()
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            let mut dict = dict.borrow_mut();
+            for (key, value) in entries {
+                dict.insert(key, value)?;
+            }
+            Ok(Value::Null)
+        }
+        "clear" => {
+            call.arity(0, 0)?;
+            *dict.borrow_mut() = Dict::new();
+            Ok(Value::Null)
+        }
+        "copy" => {
+            call.arity(0, 0)?;
+            Ok(Value::dict(dict.borrow().clone()))
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn str_method(s: &str, call: &Call) -> Result<Value> {
+    let string = |text: &str| Value::String(text.into());
+    match call.name {
+        "upper" => {
+            call.arity(0, 0)?;
+            Ok(string(&s.to_uppercase()))
+        }
+        "lower" => {
+            call.arity(0, 0)?;
+            Ok(string(&s.to_lowercase()))
+        }
+        "strip" | "lstrip" | "rstrip" => {
+            let args = call.arity(0, 1)?;
+            let chars = match args.first() {
+                None | Some(Value::Null) => None,
+                Some(chars) => Some(call.string_arg(chars)?),
+            };
+            let matches = |c: char| match chars {
+                Some(chars) => chars.contains(c),
+                None => c.is_whitespace(),
+            };
+            let result = match call.name {
+                "strip" => s.trim_matches(matches),
+                "lstrip" => s.trim_start_matches(matches),
+                _ => s.trim_end_matches(matches),
+            };
+            Ok(string(result))
+        }
+        "split" => {
+            let args = call.arity(0, 1)?;
+            let parts: Vec<Value> = match args.first() {
+                None | Some(Value::Null) => s.split_whitespace().map(string).collect(),
+                Some(sep) => {
+                    let sep = call.string_arg(sep)?;
+                    if sep.is_empty() {
+                        return Err(VMError::ValueError("empty separator".to_string()));
+                    }
+                    s.split(sep).map(string).collect()
+                }
+            };
+            Ok(Value::list(parts))
+        }
+        "join" => {
+            let iterable = call.one()?;
+            let mut parts = Vec::new();
+            for (i, item) in collect(iterable)?.iter().enumerate() {
+                match item {
+                    Value::String(part) => parts.push(part.clone()),
+                    other => {
+                        return Err(VMError::TypeError(format!(
+                            "sequence item {}: expected str instance, {} found",
+                            i,
+                            other.type_name()
+                        )))
+                    }
+                }
+            }
+            Ok(string(&parts.join(s)))
+        }
+        "replace" => {
+            let (old, new) = call.two()?;
+            Ok(string(
+                &s.replace(call.string_arg(old)?, call.string_arg(new)?),
+            ))
+        }
+        "startswith" | "endswith" => {
+            let affix = call.one()?;
+            let affix = call.string_arg(affix)?;
+            let result = if call.name == "startswith" {
+                s.starts_with(affix)
+            } else {
+                s.ends_with(affix)
+            };
+            Ok(Value::Bool(result))
+        }
+        "find" => {
+            let sub = call.one()?;
+            // 返回字符位置而不是字节偏移
+            let index = s
+                .find(call.string_arg(sub)?)
+                .map_or(-1, |byte| s[..byte].chars().count() as i64);
+            Ok(Value::Int(index))
+        }
+        "count" => {
+            let sub = call.one()?;
+            let sub = call.string_arg(sub)?;
+            let count = if sub.is_empty() {
+                s.chars().count() + 1
+            } else {
+                s.matches(sub).count()
+            };
+            Ok(Value::Int(count as i64))
+        }
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,130 @@
+/*!
+模块
+
+每个字节码文件加载为一个模块，拥有独立的常量池、全局变量和函数表，
+模块中的函数总是在所属模块的全局变量中执行。
+
+`import a.b` 在搜索路径的各目录中依次查找 `a/b.acode`，首次导入时执行模块的顶层代码，
+之后从缓存中返回同一个模块对象。搜索路径依次为 [`VMConfig::module_paths`]、
+环境变量 `AQUA_PATH` 中的目录（按平台的路径列表格式分隔）和标准库目录
+[`VMConfig::stdlib_path`]。标准库目录默认在可执行文件所在目录及其上级目录中查找
+（见 [`default_stdlib`]），其中的 `.aqua` 源码需要先编译为 `.acode`。
+
+[`VMConfig::module_paths`]: crate::vm::VMConfig::module_paths
+[`VMConfig::stdlib_path`]: crate::vm::VMConfig::stdlib_path
+*/
+
+use crate::function::Function;
+use crate::value::Value;
+use rustc_hash::FxHashMap;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// 追加到搜索路径末尾的环境变量
+pub const AQUA_PATH: &str = "AQUA_PATH";
+
+/// 标准库目录的名字
+pub const STDLIB_DIR: &str = "stdlib";
+
+/// 模块文件的扩展名
+pub const EXTENSION: &str = "acode";
+
+/// 主程序的模块名
+pub const MAIN_MODULE: &str = "__main__";
+
+/// 已加载的模块
+#[derive(Debug)]
+pub struct Module {
+    /// 模块名（点分形式）
+    pub name: Rc<str>,
+
+    /// 常量池
+    pub constants: Vec<Value>,
+
+    /// 全局变量
+    pub globals: RefCell<Vec<Value>>,
+
+    /// 全局变量名到槽位的映射
+    pub global_names: HashMap<String, usize>,
+
+    /// 函数表
+    pub functions: FxHashMap<String, Rc<Function>>,
+
+    /// 顶层代码，作为最底层的调用帧执行
+    pub main: Rc<Function>,
+}
+
+impl Module {
+    /// 按名字读取全局变量
+    pub fn global(&self, name: &str) -> Option<Value> {
+        let slot = *self.global_names.get(name)?;
+        self.globals.borrow().get(slot).cloned()
+    }
+
+    /// 给已有的全局变量赋值，模块没有这个全局变量时返回 `false`
+    pub fn set_global(&self, name: &str, value: Value) -> bool {
+        match self.global_names.get(name) {
+            Some(&slot) => {
+                self.globals.borrow_mut()[slot] = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 模块属性：全局变量优先，其次是函数表中的函数
+    pub fn attribute(&self, name: &str) -> Option<Value> {
+        self.global(name).or_else(|| {
+            self.functions
+                .get(name)
+                .map(|function| Value::Function(function.clone()))
+        })
+    }
+}
+
+/// 完整的搜索路径：先是配置的目录，再是 `AQUA_PATH` 中的目录，最后是标准库目录
+pub fn search_path(configured: &[PathBuf], stdlib: Option<&Path>) -> Vec<PathBuf> {
+    let mut paths = configured.to_vec();
+    if let Some(value) = std::env::var_os(AQUA_PATH) {
+        paths.extend(std::env::split_paths(&value).filter(|path| !path.as_os_str().is_empty()));
+    }
+    paths.extend(stdlib.map(Path::to_path_buf));
+    paths
+}
+
+/// 默认的标准库目录：从可执行文件所在目录开始逐级向上，第一个名为 `stdlib` 的子目录
+///
+/// 安装后的 `bin/aqua-vm` 找到同级的 `stdlib/`，仓库中构建的 `target/debug/aqua-vm`
+/// 找到仓库根目录的 `stdlib/`。
+pub fn default_stdlib() -> Option<PathBuf> {
+    let executable = std::env::current_exe().ok()?;
+    executable
+        .ancestors()
+        .skip(1)
+        .map(|directory| directory.join(STDLIB_DIR))
+        .find(|directory| directory.is_dir())
+}
+
+/// 在搜索路径中查找模块文件，找不到时返回 `None`
+pub fn find(search_path: &[PathBuf], name: &str) -> Option<PathBuf> {
+    let relative = relative_path(name)?;
+    search_path
+        .iter()
+        .map(|directory| directory.join(&relative))
+        .find(|path| path.is_file())
+}
+
+/// 模块名对应的相对路径，名字不合法时返回 `None`
+fn relative_path(name: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for part in name.split('.') {
+        if part.is_empty() || part.contains(['/', '\\']) || Path::new(part).is_absolute() {
+            return None;
+        }
+        path.push(part);
+    }
+    path.set_extension(EXTENSION);
+    Some(path)
+}
@@ -0,0 +1,200 @@
+/*!
+类与实例
+
+`CREATE_CLASS` 把编译器生成的方法表（方法名 → 函数表中的 `类名.方法名`）
+解析为 [`Class`]，之后的属性查找和方法调用都直接在类的方法表中进行，
+不再拼接字符串查找函数。
+
+- 调用类对象创建 [`Instance`]，并以新实例为 `self` 调用 `__init__`
+- 从实例上读取方法得到 [`BoundMethod`]，调用时自动把实例作为第一个参数
+- 类可以有基类，方法按 C3 线性化得到的方法解析顺序（MRO）查找，
+  [`Super`] 从 MRO 中当前类之后的位置继续查找
+*/
+
+use crate::function::Function;
+use crate::value::Value;
+use crate::{Result, VMError};
+use rustc_hash::FxHashMap;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// 类对象
+#[derive(Debug)]
+pub struct Class {
+    /// 类名
+    pub name: Rc<str>,
+
+    /// 直接基类
+    pub bases: Vec<Rc<Class>>,
+
+    /// MRO 中除自身以外的部分
+    ancestors: Vec<Rc<Class>>,
+
+    /// 本类定义的方法
+    methods: FxHashMap<Rc<str>, Rc<Function>>,
+}
+
+impl Class {
+    /// 由已解析的方法表创建类，基类无法线性化时返回 `TypeError`
+    pub fn new(
+        name: Rc<str>,
+        bases: Vec<Rc<Class>>,
+        methods: FxHashMap<Rc<str>, Rc<Function>>,
+    ) -> Result<Self> {
+        let ancestors = linearize(&bases)?;
+        Ok(Self {
+            name,
+            bases,
+            ancestors,
+            methods,
+        })
+    }
+
+    /// 方法解析顺序，从自身开始
+    pub fn mro(self: &Rc<Self>) -> impl Iterator<Item = &Rc<Class>> {
+        std::iter::once(self).chain(self.ancestors.iter())
+    }
+
+    /// 按 MRO 查找方法
+    pub fn method(&self, name: &str) -> Option<&Rc<Function>> {
+        self.methods.get(name).or_else(|| {
+            self.ancestors
+                .iter()
+                .find_map(|class| class.methods.get(name))
+        })
+    }
+
+    /// 按 MRO 查找方法，同时返回定义它的类
+    pub fn lookup(self: &Rc<Self>, name: &str) -> Option<(&Rc<Class>, &Rc<Function>)> {
+        self.mro()
+            .find_map(|class| class.methods.get(name).map(|method| (class, method)))
+    }
+
+    /// 跳过 MRO 中 `after` 及其之前的类查找方法，用于 `super()`
+    pub fn lookup_after(
+        self: &Rc<Self>,
+        after: &Rc<Class>,
+        name: &str,
+    ) -> Option<(&Rc<Class>, &Rc<Function>)> {
+        self.mro()
+            .skip_while(|class| !Rc::ptr_eq(class, after))
+            .skip(1)
+            .find_map(|class| class.methods.get(name).map(|method| (class, method)))
+    }
+
+    /// 是否为 `other` 本身或其子类
+    pub fn is_subclass(self: &Rc<Self>, other: &Rc<Class>) -> bool {
+        self.mro().any(|class| Rc::ptr_eq(class, other))
+    }
+}
+
+/// C3 线性化：合并各基类的 MRO 与基类列表本身
+fn linearize(bases: &[Rc<Class>]) -> Result<Vec<Rc<Class>>> {
+    for (i, base) in bases.iter().enumerate() {
+        if bases[..i].iter().any(|other| Rc::ptr_eq(other, base)) {
+            return Err(VMError::TypeError(format!(
+                "duplicate base class {}",
+                base.name
+            )));
+        }
+    }
+
+    let mut sequences: Vec<Vec<Rc<Class>>> = bases
+        .iter()
+        .map(|base| base.mro().cloned().collect())
+        .chain(std::iter::once(bases.to_vec()))
+        .collect();
+    let mut result = Vec::new();
+    loop {
+        sequences.retain(|sequence| !sequence.is_empty());
+        if sequences.is_empty() {
+            return Ok(result);
+        }
+        // 取第一个不出现在任何序列尾部的头元素
+        let head = sequences
+            .iter()
+            .map(|sequence| &sequence[0])
+            .find(|candidate| {
+                sequences
+                    .iter()
+                    .all(|sequence| !sequence[1..].iter().any(|c| Rc::ptr_eq(c, candidate)))
+            })
+            .cloned()
+            .ok_or_else(|| {
+                let names: Vec<&str> = bases.iter().map(|base| &*base.name).collect();
+                VMError::TypeError(format!(
+                    "Cannot create a consistent method resolution order (MRO) for bases {}",
+                    names.join(", ")
+                ))
+            })?;
+        for sequence in &mut sequences {
+            if Rc::ptr_eq(&sequence[0], &head) {
+                sequence.remove(0);
+            }
+        }
+        result.push(head);
+    }
+}
+
+/// 类的实例
+#[derive(Debug)]
+pub struct Instance {
+    /// 所属的类
+    pub class: Rc<Class>,
+
+    /// 实例属性
+    attributes: RefCell<FxHashMap<Rc<str>, Value>>,
+}
+
+impl Instance {
+    /// 创建没有任何属性的实例
+    pub fn new(class: Rc<Class>) -> Self {
+        Self {
+            class,
+            attributes: RefCell::default(),
+        }
+    }
+
+    /// 读取实例属性（不查找类）
+    pub fn attribute(&self, name: &str) -> Option<Value> {
+        self.attributes.borrow().get(name).cloned()
+    }
+
+    /// 设置实例属性
+    pub fn set_attribute(&self, name: Rc<str>, value: Value) {
+        self.attributes.borrow_mut().insert(name, value);
+    }
+}
+
+/// 绑定了接收者的方法
+#[derive(Debug)]
+pub struct BoundMethod {
+    /// 调用时作为第一个参数传入的对象
+    pub receiver: Value,
+
+    /// 定义该方法的类，方法体中的 `super()` 从这里开始查找
+    pub class: Rc<Class>,
+
+    /// 方法体
+    pub function: Rc<Function>,
+}
+
+/// `super()` 返回的代理对象
+#[derive(Debug)]
+pub struct Super {
+    /// 从该类在 MRO 中之后的位置开始查找
+    pub class: Rc<Class>,
+
+    /// 被代理的实例
+    pub receiver: Rc<Instance>,
+}
+
+impl Super {
+    /// 在被代理实例的 MRO 中查找 `class` 之后的方法
+    pub fn lookup(&self, name: &str) -> Option<(Rc<Class>, Rc<Function>)> {
+        self.receiver
+            .class
+            .lookup_after(&self.class, name)
+            .map(|(class, function)| (class.clone(), function.clone()))
+    }
+}
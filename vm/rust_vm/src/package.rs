@@ -0,0 +1,218 @@
+/*!
+`.apack` 应用包
+
+应用包是由 `tools/apack.py` 生成的 zip 文件：
+
+```text
+manifest.json        包的描述，见 [`Manifest`]
+main.acode           入口程序（由 manifest 的 `main` 指定）
+lib.acode            其余字节码文件注册为可导入的模块，
+pkg/util.acode       子目录对应点分模块名 `pkg.util`
+```
+
+包在内存中直接读取，不解压到磁盘。manifest 中声明的依赖由 [`crate::registry`] 解析。
+*/
+
+use crate::bytecode::Bytecode;
+use crate::module::EXTENSION;
+use crate::{Result, VMError};
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// 虚拟机实现的 AquaScript 语言版本，与 manifest 的 `aqua_version` 要求比较
+pub const AQUA_VERSION: &str = "1.0.0";
+
+/// 包描述文件在包内的路径
+pub const MANIFEST: &str = "manifest.json";
+
+/// `manifest.json` 的内容
+#[derive(Debug, Clone, Deserialize)]
+pub struct Manifest {
+    /// 包名
+    pub name: String,
+
+    /// 包的版本，语义化版本号
+    pub version: String,
+
+    /// 入口程序在包内的路径
+    pub main: String,
+
+    /// 需要的语言版本范围，如 `1.0`（即 `^1.0`）
+    pub aqua_version: String,
+
+    #[serde(default)]
+    pub description: String,
+
+    #[serde(default)]
+    pub author: String,
+
+    /// 依赖的其他包
+    #[serde(default)]
+    pub dependencies: Vec<Dependency>,
+}
+
+/// manifest 中的一项依赖，如 `{"name": "mathx", "version": "^1.2"}`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Dependency {
+    /// 包名
+    pub name: String,
+
+    /// 版本范围
+    pub version: String,
+}
+
+impl Dependency {
+    /// 解析版本范围
+    pub fn requirement(&self) -> Result<VersionReq> {
+        VersionReq::parse(&self.version).map_err(|error| {
+            invalid(format!(
+                "dependency '{}' has an invalid version requirement '{}' ({})",
+                self.name, self.version, error
+            ))
+        })
+    }
+}
+
+impl Manifest {
+    /// 从 zip 数据中读取并校验 manifest，不读取其余条目
+    pub fn read<R: Read + Seek>(reader: R) -> Result<Self> {
+        let mut archive = ZipArchive::new(reader).map_err(zip_error)?;
+        Self::from_archive(&mut archive)
+    }
+
+    fn from_archive<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<Self> {
+        let manifest = read_entry(archive, MANIFEST)?;
+        let manifest: Manifest = serde_json::from_slice(&manifest)
+            .map_err(|error| invalid(format!("malformed {}: {}", MANIFEST, error)))?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    /// 解析包的版本号
+    pub fn version(&self) -> Result<Version> {
+        Version::parse(&self.version).map_err(|error| {
+            invalid(format!(
+                "manifest field 'version' is not a semantic version: '{}' ({})",
+                self.version, error
+            ))
+        })
+    }
+
+    /// 检查必填字段和版本要求
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(invalid("manifest field 'name' is empty"));
+        }
+        self.version()?;
+        if !self.main.ends_with(&format!(".{}", EXTENSION)) {
+            return Err(invalid(format!(
+                "manifest field 'main' must name an .{} file, got '{}'",
+                EXTENSION, self.main
+            )));
+        }
+
+        let required = VersionReq::parse(&self.aqua_version).map_err(|error| {
+            invalid(format!(
+                "manifest field 'aqua_version' is not a version requirement: '{}' ({})",
+                self.aqua_version, error
+            ))
+        })?;
+        let provided = Version::parse(AQUA_VERSION).expect("AQUA_VERSION is a valid version");
+        if !required.matches(&provided) {
+            return Err(invalid(format!(
+                "package '{}' requires AquaScript {}, but this VM implements {}",
+                self.name, self.aqua_version, AQUA_VERSION
+            )));
+        }
+
+        for dependency in &self.dependencies {
+            dependency.requirement()?;
+        }
+        Ok(())
+    }
+}
+
+/// 读入内存的应用包
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub manifest: Manifest,
+
+    /// 入口程序
+    pub main: Bytecode,
+
+    /// 包内其余的模块，按模块名排序
+    pub modules: Vec<(String, Bytecode)>,
+}
+
+impl Package {
+    /// 打开磁盘上的 `.apack` 文件
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::read(File::open(path)?)
+    }
+
+    /// 从 zip 数据中读取包并校验 manifest
+    pub fn read<R: Read + Seek>(reader: R) -> Result<Self> {
+        let mut archive = ZipArchive::new(reader).map_err(zip_error)?;
+        let manifest = Manifest::from_archive(&mut archive)?;
+
+        let main = Bytecode::from_acode_bytes(&read_entry(&mut archive, &manifest.main)?)?;
+
+        let mut names: Vec<String> = archive
+            .file_names()
+            .filter(|name| *name != manifest.main)
+            .map(str::to_string)
+            .collect();
+        names.sort();
+
+        let mut modules = Vec::new();
+        for entry in names {
+            let Some(name) = module_name(&entry) else {
+                continue;
+            };
+            let bytecode = Bytecode::from_acode_bytes(&read_entry(&mut archive, &entry)?)
+                .map_err(|error| invalid(format!("{}: {}", entry, error)))?;
+            modules.push((name, bytecode));
+        }
+
+        Ok(Self {
+            manifest,
+            main,
+            modules,
+        })
+    }
+}
+
+/// 包内路径对应的模块名，不是字节码文件时返回 `None`
+fn module_name(entry: &str) -> Option<String> {
+    let stem = entry.strip_suffix(&format!(".{}", EXTENSION))?;
+    let parts: Vec<&str> = stem.split('/').collect();
+    if parts
+        .iter()
+        .any(|part| part.is_empty() || part.contains('.'))
+    {
+        return None;
+    }
+    Some(parts.join("."))
+}
+
+fn read_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Result<Vec<u8>> {
+    let mut file = archive.by_name(name).map_err(|error| match error {
+        zip::result::ZipError::FileNotFound => invalid(format!("missing {} in package", name)),
+        other => zip_error(other),
+    })?;
+    let mut data = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn invalid(message: impl Into<String>) -> VMError {
+    VMError::Package(message.into())
+}
+
+fn zip_error(error: zip::result::ZipError) -> VMError {
+    invalid(format!("not a valid .apack archive: {}", error))
+}
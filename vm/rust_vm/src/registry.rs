@@ -0,0 +1,366 @@
+/*!
+本地依赖解析
+
+依赖从本地的包目录（registry）中查找，目录下每个 `.apack` 文件是某个包的一个版本。
+解析时每个包只选一个版本：取满足所有依赖方版本范围的最高版本，
+锁文件 `aqua.lock` 中记录的版本仍然满足要求时优先使用它。
+选中的版本引入的要求无法满足时回溯，改用较低的版本；
+所有组合都不可行时才报告冲突，并列出各个要求的来源。
+
+解析结果写回锁文件。运行时依赖包的入口程序按包名导入，
+包内其余模块按 `包名.模块名` 导入。
+*/
+
+use crate::package::{Manifest, Package};
+use crate::{Result, VMError};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// 锁文件名，位于应用包所在目录
+pub const LOCKFILE: &str = "aqua.lock";
+
+/// 包目录中的一个包版本
+#[derive(Debug, Clone)]
+struct Entry {
+    version: Version,
+    manifest: Manifest,
+    path: PathBuf,
+}
+
+/// 本地包目录的索引
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    /// 包名到各版本的映射，版本从低到高排列
+    packages: BTreeMap<String, Vec<Entry>>,
+}
+
+/// 解析选中的一个依赖包
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolved {
+    pub name: String,
+    pub version: Version,
+
+    /// `.apack` 文件的路径
+    pub path: PathBuf,
+}
+
+/// `aqua.lock` 的内容
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockfile {
+    /// 锁定的包，按包名排序
+    pub packages: Vec<LockedPackage>,
+}
+
+/// 锁文件中的一个包
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+}
+
+impl Registry {
+    /// 读取目录下所有 `.apack` 文件的 manifest
+    pub fn scan<P: AsRef<Path>>(directory: P) -> Result<Self> {
+        let directory = directory.as_ref();
+        let entries = std::fs::read_dir(directory).map_err(|error| {
+            VMError::Package(format!(
+                "cannot read package registry {}: {}",
+                directory.display(),
+                error
+            ))
+        })?;
+
+        let mut registry = Self::default();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "apack") {
+                continue;
+            }
+            let manifest =
+                Manifest::read(File::open(&path)?).map_err(|error| in_file(&path, error))?;
+            registry.insert(manifest, path)?;
+        }
+        Ok(registry)
+    }
+
+    /// 登记一个包版本
+    pub fn insert(&mut self, manifest: Manifest, path: PathBuf) -> Result<()> {
+        let version = manifest.version()?;
+        let versions = self.packages.entry(manifest.name.clone()).or_default();
+        if let Some(existing) = versions.iter().find(|entry| entry.version == version) {
+            return Err(VMError::Package(format!(
+                "{} {} is provided by both {} and {}",
+                manifest.name,
+                version,
+                existing.path.display(),
+                path.display()
+            )));
+        }
+        versions.push(Entry {
+            version,
+            manifest,
+            path,
+        });
+        versions.sort_by(|a, b| a.version.cmp(&b.version));
+        Ok(())
+    }
+
+    /// 解析 `root` 的全部依赖（含间接依赖），结果按包名排序
+    ///
+    /// 按包名顺序逐个选择版本，每选中一个版本就登记它的依赖要求；
+    /// 后续的包无法满足时回溯到前面的选择，改用下一个候选版本。
+    /// 所有组合都失败时报告搜索中遇到的第一个冲突。
+    pub fn resolve(&self, root: &Manifest, lockfile: &Lockfile) -> Result<Vec<Resolved>> {
+        let mut search = Search {
+            registry: self,
+            lockfile,
+            root: &root.name,
+            selected: BTreeMap::new(),
+            requirements: BTreeMap::new(),
+            failure: None,
+        };
+        search.require(root, root.name.clone())?;
+        if !search.solve()? {
+            return Err(search
+                .failure
+                .unwrap_or_else(|| VMError::Package("dependency resolution failed".to_string())));
+        }
+        Ok(search
+            .selected
+            .into_iter()
+            .map(|(name, entry)| Resolved {
+                name: name.to_string(),
+                version: entry.version.clone(),
+                path: entry.path.clone(),
+            })
+            .collect())
+    }
+
+    /// 满足所有要求的候选版本：锁定的版本在前，其余从高到低
+    fn candidates(
+        &self,
+        name: &str,
+        requirements: &[(VersionReq, String)],
+        lockfile: &Lockfile,
+    ) -> Result<Vec<&Entry>> {
+        let Some(versions) = self.packages.get(name) else {
+            return Err(VMError::Package(format!(
+                "package '{}' not found in registry: {}",
+                name,
+                describe(requirements)
+            )));
+        };
+
+        let mut candidates: Vec<&Entry> = versions
+            .iter()
+            .rev()
+            .filter(|entry| satisfies(entry, requirements))
+            .collect();
+        if candidates.is_empty() {
+            return Err(self.conflict(name, requirements));
+        }
+        if let Some(locked) = lockfile.version(name) {
+            if let Some(index) = candidates.iter().position(|entry| entry.version == locked) {
+                let entry = candidates.remove(index);
+                candidates.insert(0, entry);
+            }
+        }
+        Ok(candidates)
+    }
+
+    /// 没有版本能同时满足 `requirements` 时的错误
+    fn conflict(&self, name: &str, requirements: &[(VersionReq, String)]) -> VMError {
+        let available: Vec<String> = self
+            .packages
+            .get(name)
+            .into_iter()
+            .flatten()
+            .map(|entry| entry.version.to_string())
+            .collect();
+        VMError::Package(format!(
+            "conflicting requirements for '{}': {}; available versions: {}",
+            name,
+            describe(requirements),
+            available.join(", ")
+        ))
+    }
+}
+
+/// 回溯搜索的状态
+struct Search<'a> {
+    registry: &'a Registry,
+    lockfile: &'a Lockfile,
+
+    /// 应用包的名字，依赖它自身的要求被忽略
+    root: &'a str,
+
+    /// 已选中的版本
+    selected: BTreeMap<&'a str, &'a Entry>,
+
+    /// 各包收到的版本要求及其来源，来自应用包和已选中的版本
+    requirements: BTreeMap<&'a str, Vec<(VersionReq, String)>>,
+
+    /// 搜索中遇到的第一个失败
+    failure: Option<VMError>,
+}
+
+impl<'a> Search<'a> {
+    /// 选择下一个尚未选中的包，返回是否找到了完整的解
+    fn solve(&mut self) -> Result<bool> {
+        let Some(name) = self
+            .requirements
+            .keys()
+            .copied()
+            .find(|name| !self.selected.contains_key(name))
+        else {
+            return Ok(true);
+        };
+        let candidates =
+            match self
+                .registry
+                .candidates(name, &self.requirements[name], self.lockfile)
+            {
+                Ok(candidates) => candidates,
+                Err(error) => {
+                    self.failure.get_or_insert(error);
+                    return Ok(false);
+                }
+            };
+
+        for entry in candidates {
+            let from = format!("{} {}", entry.manifest.name, entry.version);
+            let added = self.require(&entry.manifest, from)?;
+            // 新的要求不能与已经选中的版本冲突
+            let conflict = added.iter().copied().find(|dependency| {
+                self.selected
+                    .get(dependency)
+                    .is_some_and(|selected| !satisfies(selected, &self.requirements[dependency]))
+            });
+            if let Some(dependency) = conflict {
+                let error = self
+                    .registry
+                    .conflict(dependency, &self.requirements[dependency]);
+                self.failure.get_or_insert(error);
+            } else {
+                self.selected.insert(name, entry);
+                if self.solve()? {
+                    return Ok(true);
+                }
+                self.selected.remove(name);
+            }
+            self.unrequire(&added);
+        }
+        Ok(false)
+    }
+
+    /// 登记 `manifest` 的依赖要求，返回收到新要求的包名
+    fn require(&mut self, manifest: &'a Manifest, from: String) -> Result<Vec<&'a str>> {
+        let mut added = Vec::with_capacity(manifest.dependencies.len());
+        for dependency in &manifest.dependencies {
+            if dependency.name == self.root {
+                continue;
+            }
+            self.requirements
+                .entry(&dependency.name)
+                .or_default()
+                .push((dependency.requirement()?, from.clone()));
+            added.push(dependency.name.as_str());
+        }
+        Ok(added)
+    }
+
+    /// 撤销 [`Search::require`] 登记的要求
+    fn unrequire(&mut self, added: &[&'a str]) {
+        for name in added.iter().rev() {
+            let requirements = self.requirements.get_mut(name).unwrap();
+            requirements.pop();
+            if requirements.is_empty() {
+                self.requirements.remove(name);
+            }
+        }
+    }
+}
+
+/// 版本是否满足所有要求
+fn satisfies(entry: &Entry, requirements: &[(VersionReq, String)]) -> bool {
+    requirements
+        .iter()
+        .all(|(requirement, _)| requirement.matches(&entry.version))
+}
+
+/// 要求及其来源的列表，如 `^1.2 (required by app)`
+fn describe(requirements: &[(VersionReq, String)]) -> String {
+    requirements
+        .iter()
+        .map(|(requirement, from)| format!("{} (required by {})", requirement, from))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Lockfile {
+    /// 读取锁文件，文件不存在时返回空的锁文件
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        match std::fs::read(path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(|error| {
+                VMError::Package(format!("malformed {}: {}", path.display(), error))
+            }),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// 由解析结果生成锁文件
+    pub fn from_resolved(resolved: &[Resolved]) -> Self {
+        Self {
+            packages: resolved
+                .iter()
+                .map(|package| LockedPackage {
+                    name: package.name.clone(),
+                    version: package.version.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    /// 锁定的版本
+    pub fn version(&self, name: &str) -> Option<Version> {
+        let package = self.packages.iter().find(|package| package.name == name)?;
+        Version::parse(&package.version).ok()
+    }
+
+    /// 写入磁盘，内容没有变化时不改写文件
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut text = serde_json::to_string_pretty(self)?;
+        text.push('\n');
+        if std::fs::read_to_string(&path).is_ok_and(|existing| existing == text) {
+            return Ok(());
+        }
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+/// 解析应用包的依赖并更新锁文件，返回按包名排序的依赖包
+pub fn install(app: &Package, registry: &Path, lockfile: &Path) -> Result<Vec<Package>> {
+    if app.manifest.dependencies.is_empty() {
+        return Ok(Vec::new());
+    }
+    let resolved = Registry::scan(registry)?.resolve(&app.manifest, &Lockfile::load(lockfile)?)?;
+    Lockfile::from_resolved(&resolved).save(lockfile)?;
+    resolved
+        .iter()
+        .map(|package| Package::open(&package.path).map_err(|error| in_file(&package.path, error)))
+        .collect()
+}
+
+/// 在包错误信息前加上出错的文件
+fn in_file(path: &Path, error: VMError) -> VMError {
+    match error {
+        VMError::Package(message) => VMError::Package(format!("{}: {}", path.display(), message)),
+        other => VMError::Package(format!("{}: {}", path.display(), other)),
+    }
+}
@@ -0,0 +1,950 @@
+/*!
+AquaScript 运行时值
+
+所有值都可以廉价克隆：标量按值存储，字符串和容器使用引用计数共享。
+列表和字典是引用值，多个变量可以指向同一个对象并观察到彼此的修改。
+*/
+
+use crate::builtins::BuiltinFunction;
+use crate::dict::Dict;
+use crate::event_loop::{FutureKind, FutureRef, FutureState};
+use crate::function::{Cell, Closure, Function};
+use crate::generator::Generator;
+use crate::iter::{Iter, Range};
+use crate::module::Module;
+use crate::object::{BoundMethod, Class, Instance, Super};
+use crate::{exceptions, Result, VMError};
+use rustc_hash::FxHasher;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+/// 运行时生成的字符串的最大字节数，超过时抛出 `MemoryError`
+pub const MAX_STRING_SIZE: usize = 1 << 30;
+
+/// 虚拟机中的动态类型值
+#[derive(Debug, Clone, Default)]
+pub enum Value {
+    #[default]
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(Rc<str>),
+    Bytes(Rc<[u8]>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Tuple(Rc<[Value]>),
+    Dict(Rc<RefCell<Dict>>),
+    /// 惰性整数序列
+    Range(Rc<Range>),
+    /// 迭代器，由 `GET_ITER` 创建
+    Iterator(Rc<RefCell<Iter>>),
+    /// 类对象
+    Class(Rc<Class>),
+    /// 类的实例
+    Instance(Rc<Instance>),
+    /// 绑定了实例的方法
+    BoundMethod(Rc<BoundMethod>),
+    /// `super()` 代理对象
+    Super(Rc<Super>),
+    /// 常量池中的代码对象，加载后以 [`Value::Function`] 出现
+    Code(Rc<Function>),
+    /// 已加载的函数，由 `LOAD_FUNC` 按编号从虚拟机函数表取得
+    Function(Rc<Function>),
+    /// 内置函数
+    Builtin(BuiltinFunction),
+    /// 捕获了外层变量的函数
+    Closure(Rc<Closure>),
+    /// 被捕获变量的单元，只出现在局部变量槽位和 `MAKE_CLOSURE` 的操作数中
+    Cell(Cell),
+    /// 已导入的模块
+    Module(Rc<Module>),
+    /// 生成器或协程，由调用生成器函数或协程函数创建
+    Generator(Rc<RefCell<Generator>>),
+    /// 事件循环中的 Future 或任务
+    Future(FutureRef),
+}
+
+impl Value {
+    /// 与 Python 一致的类型名，用于错误信息；实例的类型名即类名
+    pub fn type_name(&self) -> &str {
+        match self {
+            Value::Null => "NoneType",
+            Value::Bool(_) => "bool",
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::String(_) => "str",
+            Value::Bytes(_) => "bytes",
+            Value::List(_) => "list",
+            Value::Tuple(_) => "tuple",
+            Value::Dict(_) => "dict",
+            Value::Range(_) => "range",
+            Value::Iterator(iter) => iter.borrow().type_name(),
+            Value::Class(_) => "type",
+            Value::Instance(instance) => &instance.class.name,
+            Value::BoundMethod(_) => "method",
+            Value::Super(_) => "super",
+            Value::Code(_) => "code",
+            Value::Function(_) => "function",
+            Value::Builtin(_) => "builtin_function_or_method",
+            Value::Closure(_) => "function",
+            Value::Cell(_) => "cell",
+            Value::Module(_) => "module",
+            Value::Generator(generator) if generator.borrow().coroutine => "coroutine",
+            Value::Generator(_) => "generator",
+            Value::Future(future) => future.borrow().type_name(),
+        }
+    }
+
+    /// 与 Python `repr()` 一致的表示（字符串带引号）
+    pub fn repr(&self) -> String {
+        match self {
+            Value::String(s) => format_str_repr(s),
+            other => other.to_string(),
+        }
+    }
+
+    /// 真值判断（与 Python 规则相同）
+    #[inline]
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Bool(b) => *b,
+            Value::Int(i) => *i != 0,
+            Value::Float(f) => *f != 0.0,
+            Value::String(s) => !s.is_empty(),
+            Value::Bytes(b) => !b.is_empty(),
+            Value::List(items) => !items.borrow().is_empty(),
+            Value::Tuple(items) => !items.is_empty(),
+            Value::Dict(dict) => !dict.borrow().is_empty(),
+            Value::Range(range) => !range.is_empty(),
+            Value::Iterator(_)
+            | Value::Class(_)
+            | Value::Instance(_)
+            | Value::BoundMethod(_)
+            | Value::Super(_)
+            | Value::Code(_)
+            | Value::Function(_)
+            | Value::Builtin(_)
+            | Value::Closure(_)
+            | Value::Cell(_)
+            | Value::Module(_)
+            | Value::Generator(_)
+            | Value::Future(_) => true,
+        }
+    }
+
+    /// 由元素创建新列表
+    pub fn list(items: Vec<Value>) -> Value {
+        Value::List(Rc::new(RefCell::new(items)))
+    }
+
+    /// 由字典创建新的字典值
+    pub fn dict(dict: Dict) -> Value {
+        Value::Dict(Rc::new(RefCell::new(dict)))
+    }
+
+    /// `LOAD_CONST` 压栈的值
+    ///
+    /// 常量池中的字典（包括嵌套在元组和字典中的）每次加载都复制为新的对象，
+    /// 程序修改它不会影响常量本身；其余常量不可变，直接共享。
+    pub fn load_constant(&self) -> Value {
+        match self {
+            Value::Dict(dict) => {
+                let mut copy = dict.borrow().clone();
+                for value in copy.values_mut() {
+                    *value = value.load_constant();
+                }
+                Value::dict(copy)
+            }
+            Value::Tuple(items) if items.iter().any(Value::holds_dict) => {
+                Value::Tuple(items.iter().map(Value::load_constant).collect())
+            }
+            _ => self.clone(),
+        }
+    }
+
+    /// 常量中是否含有字典
+    fn holds_dict(&self) -> bool {
+        match self {
+            Value::Dict(_) => true,
+            Value::Tuple(items) => items.iter().any(Value::holds_dict),
+            _ => false,
+        }
+    }
+
+    /// 创建迭代器（`iter()`）；迭代器本身原样返回
+    pub fn iter(&self) -> Result<Value> {
+        match self {
+            Value::Generator(generator) if generator.borrow().coroutine => Err(VMError::TypeError(
+                "'coroutine' object is not iterable".to_string(),
+            )),
+            Value::Iterator(_) | Value::Generator(_) => Ok(self.clone()),
+            other => Ok(Value::Iterator(Rc::new(RefCell::new(Iter::new(other)?)))),
+        }
+    }
+
+    /// 哈希值，与 `==` 一致：相等的数值（`1`、`1.0`、`True`）哈希相同
+    pub fn hash_key(&self) -> Result<u64> {
+        if let Some(i) = self.as_i64() {
+            return Ok(i as u64);
+        }
+        let mut hasher = FxHasher::default();
+        match self {
+            Value::Null => return Ok(0x6e6f_6e65),
+            Value::Float(f) if f.fract() == 0.0 && f.abs() < 9.2e18 => return Ok(*f as i64 as u64),
+            Value::Float(f) => f.to_bits().hash(&mut hasher),
+            Value::String(s) => s.hash(&mut hasher),
+            Value::Bytes(b) => b.hash(&mut hasher),
+            Value::Tuple(items) => {
+                let _guard = NestingGuard::enter()?;
+                items.len().hash(&mut hasher);
+                for item in items.iter() {
+                    item.hash_key()?.hash(&mut hasher);
+                }
+            }
+            Value::Range(range) => {
+                // 相等的范围（元素序列相同）哈希相同
+                let len = range.len();
+                len.hash(&mut hasher);
+                if len > 0 {
+                    range.start.hash(&mut hasher);
+                }
+                if len > 1 {
+                    range.step.hash(&mut hasher);
+                }
+            }
+            Value::Iterator(iter) => (Rc::as_ptr(iter) as usize).hash(&mut hasher),
+            Value::Class(class) => (Rc::as_ptr(class) as usize).hash(&mut hasher),
+            Value::Instance(instance) => (Rc::as_ptr(instance) as usize).hash(&mut hasher),
+            Value::BoundMethod(method) => {
+                (Rc::as_ptr(&method.function) as usize).hash(&mut hasher);
+                method.receiver.hash_key()?.hash(&mut hasher);
+            }
+            Value::Super(proxy) => (Rc::as_ptr(proxy) as usize).hash(&mut hasher),
+            Value::Code(code) => (Rc::as_ptr(code) as usize).hash(&mut hasher),
+            Value::Function(function) => function.id.hash(&mut hasher),
+            Value::Builtin(builtin) => builtin.id().hash(&mut hasher),
+            Value::Closure(closure) => (Rc::as_ptr(closure) as usize).hash(&mut hasher),
+            Value::Cell(cell) => (Rc::as_ptr(cell) as usize).hash(&mut hasher),
+            Value::Module(module) => (Rc::as_ptr(module) as usize).hash(&mut hasher),
+            Value::Generator(generator) => (Rc::as_ptr(generator) as usize).hash(&mut hasher),
+            Value::Future(future) => (Rc::as_ptr(future) as usize).hash(&mut hasher),
+            _ => {
+                return Err(VMError::TypeError(format!(
+                    "unhashable type: '{}'",
+                    self.type_name()
+                )))
+            }
+        }
+        Ok(hasher.finish())
+    }
+
+    /// `len()`
+    pub fn length(&self) -> Result<usize> {
+        match self {
+            Value::String(s) => Ok(s.chars().count()),
+            Value::Bytes(b) => Ok(b.len()),
+            Value::List(items) => Ok(items.borrow().len()),
+            Value::Tuple(items) => Ok(items.len()),
+            Value::Dict(dict) => Ok(dict.borrow().len()),
+            Value::Range(range) => Ok(range.len()),
+            other => Err(VMError::TypeError(format!(
+                "object of type '{}' has no len()",
+                other.type_name()
+            ))),
+        }
+    }
+
+    /// 下标读取 `self[index]`
+    pub fn get_item(&self, index: &Value) -> Result<Value> {
+        match self {
+            Value::List(items) => {
+                let items = items.borrow();
+                let i = sequence_index(self, index, items.len())?;
+                Ok(items[i].clone())
+            }
+            Value::Tuple(items) => {
+                let i = sequence_index(self, index, items.len())?;
+                Ok(items[i].clone())
+            }
+            Value::String(s) => {
+                let i = sequence_index(self, index, s.chars().count())?;
+                let c = s.chars().nth(i).unwrap();
+                Ok(Value::String(c.to_string().into()))
+            }
+            Value::Bytes(b) => {
+                let i = sequence_index(self, index, b.len())?;
+                Ok(Value::Int(b[i] as i64))
+            }
+            Value::Dict(dict) => match dict.borrow().get(index)? {
+                Some(value) => Ok(value.clone()),
+                None => Err(VMError::KeyError(index.repr())),
+            },
+            Value::Range(range) => {
+                let i = sequence_index(self, index, range.len())?;
+                Ok(Value::Int(range.nth(i)))
+            }
+            other => Err(VMError::TypeError(format!(
+                "'{}' object is not subscriptable",
+                other.type_name()
+            ))),
+        }
+    }
+
+    /// 下标赋值 `self[index] = value`
+    pub fn set_item(&self, index: &Value, value: Value) -> Result<()> {
+        match self {
+            Value::List(items) => {
+                let mut items = items.borrow_mut();
+                let i = sequence_index(self, index, items.len())?;
+                items[i] = value;
+                Ok(())
+            }
+            Value::Dict(dict) => {
+                dict.borrow_mut().insert(index.clone(), value)?;
+                Ok(())
+            }
+            other => Err(VMError::TypeError(format!(
+                "'{}' object does not support item assignment",
+                other.type_name()
+            ))),
+        }
+    }
+
+    /// 数值视图：bool 按 0/1 参与运算
+    #[inline]
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Bool(b) => Some(*b as i64 as f64),
+            Value::Int(i) => Some(*i as f64),
+            Value::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Bool(b) => Some(*b as i64),
+            Value::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    fn unsupported(&self, op: &str, other: &Value) -> VMError {
+        VMError::TypeError(format!(
+            "unsupported operand type(s) for {}: '{}' and '{}'",
+            op,
+            self.type_name(),
+            other.type_name()
+        ))
+    }
+
+    /// 加法
+    #[inline]
+    pub fn add(&self, other: &Value) -> Result<Value> {
+        if let (Some(a), Some(b)) = (self.as_i64(), other.as_i64()) {
+            return a.checked_add(b).map(Value::Int).ok_or_else(overflow);
+        }
+        match (self, other) {
+            (Value::String(a), Value::String(b)) => {
+                let mut s = String::with_capacity(a.len() + b.len());
+                s.push_str(a);
+                s.push_str(b);
+                Ok(Value::String(s.into()))
+            }
+            _ => match (self.as_f64(), other.as_f64()) {
+                (Some(a), Some(b)) => Ok(Value::Float(a + b)),
+                _ => Err(self.unsupported("+", other)),
+            },
+        }
+    }
+
+    /// 减法
+    #[inline]
+    pub fn sub(&self, other: &Value) -> Result<Value> {
+        if let (Some(a), Some(b)) = (self.as_i64(), other.as_i64()) {
+            return a.checked_sub(b).map(Value::Int).ok_or_else(overflow);
+        }
+        match (self.as_f64(), other.as_f64()) {
+            (Some(a), Some(b)) => Ok(Value::Float(a - b)),
+            _ => Err(self.unsupported("-", other)),
+        }
+    }
+
+    /// 乘法（支持字符串重复）
+    #[inline]
+    pub fn mul(&self, other: &Value) -> Result<Value> {
+        if let (Some(a), Some(b)) = (self.as_i64(), other.as_i64()) {
+            return a.checked_mul(b).map(Value::Int).ok_or_else(overflow);
+        }
+        match (self, other) {
+            (Value::String(s), n) | (n, Value::String(s)) if n.as_i64().is_some() => {
+                let count = n.as_i64().unwrap().max(0) as usize;
+                // 与 Python 相同，长度超出 isize 时为 OverflowError
+                let size = s
+                    .len()
+                    .checked_mul(count)
+                    .filter(|&size| size <= isize::MAX as usize)
+                    .ok_or_else(|| {
+                        VMError::OverflowError("repeated string is too long".to_string())
+                    })?;
+                if size > MAX_STRING_SIZE {
+                    return Err(VMError::MemoryError);
+                }
+                Ok(Value::String(s.repeat(count).into()))
+            }
+            _ => match (self.as_f64(), other.as_f64()) {
+                (Some(a), Some(b)) => Ok(Value::Float(a * b)),
+                _ => Err(self.unsupported("*", other)),
+            },
+        }
+    }
+
+    /// 真除法，结果总是浮点数
+    #[inline]
+    pub fn div(&self, other: &Value) -> Result<Value> {
+        match (self.as_f64(), other.as_f64()) {
+            (Some(_), Some(0.0)) => Err(VMError::DivisionByZero),
+            (Some(a), Some(b)) => Ok(Value::Float(a / b)),
+            _ => Err(self.unsupported("/", other)),
+        }
+    }
+
+    /// 取模，结果的符号与除数相同（Python 的向下取整取模）
+    #[inline]
+    pub fn rem(&self, other: &Value) -> Result<Value> {
+        if let (Some(a), Some(b)) = (self.as_i64(), other.as_i64()) {
+            if b == 0 {
+                return Err(VMError::DivisionByZero);
+            }
+            let mut r = a.wrapping_rem(b);
+            if r != 0 && (r < 0) != (b < 0) {
+                r += b;
+            }
+            return Ok(Value::Int(r));
+        }
+        match (self.as_f64(), other.as_f64()) {
+            (Some(_), Some(0.0)) => Err(VMError::DivisionByZero),
+            (Some(a), Some(b)) => {
+                let r = a % b;
+                let r = if r == 0.0 {
+                    0.0f64.copysign(b)
+                } else if (r < 0.0) != (b < 0.0) {
+                    r + b
+                } else {
+                    r
+                };
+                Ok(Value::Float(r))
+            }
+            _ => Err(self.unsupported("%", other)),
+        }
+    }
+
+    /// 幂运算，负整数指数得到浮点数
+    pub fn pow(&self, other: &Value) -> Result<Value> {
+        if let (Some(a), Some(b)) = (self.as_i64(), other.as_i64()) {
+            if b >= 0 {
+                return u32::try_from(b)
+                    .ok()
+                    .and_then(|b| a.checked_pow(b))
+                    .map(Value::Int)
+                    .ok_or_else(overflow);
+            }
+        }
+        match (self.as_f64(), other.as_f64()) {
+            (Some(a), Some(b)) if a == 0.0 && b < 0.0 => Err(VMError::DivisionByZero),
+            (Some(a), Some(b)) if a < 0.0 && b.is_finite() && b.fract() != 0.0 => {
+                Err(VMError::RuntimeError(
+                    "negative number cannot be raised to a fractional power".to_string(),
+                ))
+            }
+            (Some(a), Some(b)) => Ok(Value::Float(a.powf(b))),
+            _ => Err(self.unsupported("** or pow()", other)),
+        }
+    }
+
+    /// 大小比较，`op` 只用于错误信息
+    fn order(&self, other: &Value, op: &str) -> Result<Option<Ordering>> {
+        if let (Some(a), Some(b)) = (self.as_i64(), other.as_i64()) {
+            return Ok(Some(a.cmp(&b)));
+        }
+        match (self, other) {
+            (Value::String(a), Value::String(b)) => Ok(Some(a.cmp(b))),
+            (Value::Bytes(a), Value::Bytes(b)) => Ok(Some(a.cmp(b))),
+            (Value::List(a), Value::List(b)) => {
+                // 先复制元素，避免比较同一个列表时重复借用
+                let (a, b) = (a.borrow().clone(), b.borrow().clone());
+                order_sequences(&a, &b, op)
+            }
+            (Value::Tuple(a), Value::Tuple(b)) => order_sequences(a, b, op),
+            _ => match (self.as_f64(), other.as_f64()) {
+                (Some(a), Some(b)) => Ok(a.partial_cmp(&b)),
+                _ => Err(VMError::TypeError(format!(
+                    "'{}' not supported between instances of '{}' and '{}'",
+                    op,
+                    self.type_name(),
+                    other.type_name()
+                ))),
+            },
+        }
+    }
+
+    /// 小于
+    pub fn lt(&self, other: &Value) -> Result<Value> {
+        let ord = self.order(other, "<")?;
+        Ok(Value::Bool(ord == Some(Ordering::Less)))
+    }
+
+    /// 大于
+    pub fn gt(&self, other: &Value) -> Result<Value> {
+        let ord = self.order(other, ">")?;
+        Ok(Value::Bool(ord == Some(Ordering::Greater)))
+    }
+
+    /// 小于等于
+    pub fn le(&self, other: &Value) -> Result<Value> {
+        let ord = self.order(other, "<=")?;
+        Ok(Value::Bool(matches!(
+            ord,
+            Some(Ordering::Less | Ordering::Equal)
+        )))
+    }
+
+    /// 大于等于
+    pub fn ge(&self, other: &Value) -> Result<Value> {
+        let ord = self.order(other, ">=")?;
+        Ok(Value::Bool(matches!(
+            ord,
+            Some(Ordering::Greater | Ordering::Equal)
+        )))
+    }
+
+    /// 成员测试 `item in self`
+    pub fn contains(&self, item: &Value) -> Result<bool> {
+        match (self, item) {
+            (Value::String(s), Value::String(sub)) => Ok(s.contains(&**sub)),
+            (Value::String(_), other) => Err(VMError::TypeError(format!(
+                "'in <string>' requires string as left operand, not {}",
+                other.type_name()
+            ))),
+            (Value::Bytes(b), Value::Bytes(sub)) => {
+                Ok(sub.is_empty() || b.windows(sub.len()).any(|w| w == &**sub))
+            }
+            (Value::Bytes(b), Value::Int(byte)) => match u8::try_from(*byte) {
+                Ok(byte) => Ok(b.contains(&byte)),
+                Err(_) => Err(VMError::RuntimeError(
+                    "byte must be in range(0, 256)".to_string(),
+                )),
+            },
+            (Value::Bytes(_), other) => Err(VMError::TypeError(format!(
+                "a bytes-like object is required, not '{}'",
+                other.type_name()
+            ))),
+            (Value::List(items), _) => Ok(position(&items.borrow(), item)?.is_some()),
+            (Value::Tuple(items), _) => Ok(position(items, item)?.is_some()),
+            (Value::Dict(dict), _) => dict.borrow().contains_key(item),
+            (Value::Range(range), Value::Float(f)) => {
+                Ok(f.fract() == 0.0 && f.abs() < 9.2e18 && range.contains(*f as i64))
+            }
+            (Value::Range(range), _) => Ok(item.as_i64().is_some_and(|i| range.contains(i))),
+            _ => Err(VMError::TypeError(format!(
+                "argument of type '{}' is not iterable",
+                self.type_name()
+            ))),
+        }
+    }
+
+    /// 相等比较遵循 Python 规则：数值跨类型比较（`1 == 1.0 == True`），
+    /// 其余类型不同即不相等
+    ///
+    /// 容器逐层比较元素，嵌套过深（包括互相引用的不同容器）时报告 `RecursionError`，
+    /// 而不是耗尽栈空间。
+    pub fn equals(&self, other: &Value) -> Result<bool> {
+        match (self, other) {
+            (Value::List(a), Value::List(b)) => {
+                if Rc::ptr_eq(a, b) {
+                    return Ok(true);
+                }
+                let _guard = NestingGuard::enter()?;
+                all_equal(&a.borrow(), &b.borrow())
+            }
+            (Value::Tuple(a), Value::Tuple(b)) => {
+                let _guard = NestingGuard::enter()?;
+                all_equal(a, b)
+            }
+            (Value::Dict(a), Value::Dict(b)) => {
+                if Rc::ptr_eq(a, b) {
+                    return Ok(true);
+                }
+                let _guard = NestingGuard::enter()?;
+                dicts_equal(&a.borrow(), &b.borrow())
+            }
+            (Value::BoundMethod(a), Value::BoundMethod(b)) => {
+                Ok(Rc::ptr_eq(&a.function, &b.function) && a.receiver.equals(&b.receiver)?)
+            }
+            _ => Ok(self.equals_flat(other)),
+        }
+    }
+
+    /// 不含元素的值之间的相等比较
+    fn equals_flat(&self, other: &Value) -> bool {
+        if let (Some(a), Some(b)) = (self.as_i64(), other.as_i64()) {
+            return a == b;
+        }
+        match (self, other) {
+            (Value::Null, Value::Null) => true,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Bytes(a), Value::Bytes(b)) => a == b,
+            (Value::Range(a), Value::Range(b)) => {
+                let len = a.len();
+                len == b.len() && (len == 0 || a.start == b.start) && (len <= 1 || a.step == b.step)
+            }
+            (Value::Iterator(a), Value::Iterator(b)) => Rc::ptr_eq(a, b),
+            (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            (Value::Super(a), Value::Super(b)) => Rc::ptr_eq(a, b),
+            (Value::Code(a), Value::Code(b)) => Rc::ptr_eq(a, b),
+            (Value::Function(a), Value::Function(b)) => a.id == b.id,
+            (Value::Builtin(a), Value::Builtin(b)) => a == b,
+            (Value::Closure(a), Value::Closure(b)) => Rc::ptr_eq(a, b),
+            (Value::Cell(a), Value::Cell(b)) => Rc::ptr_eq(a, b),
+            (Value::Module(a), Value::Module(b)) => Rc::ptr_eq(a, b),
+            (Value::Generator(a), Value::Generator(b)) => Rc::ptr_eq(a, b),
+            (Value::Future(a), Value::Future(b)) => Rc::ptr_eq(a, b),
+            _ => match (self.as_f64(), other.as_f64()) {
+                (Some(a), Some(b)) => a == b,
+                _ => false,
+            },
+        }
+    }
+}
+
+/// `==`，见 [`Value::equals`]；无法报告错误，嵌套过深时视为不相等
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        self.equals(other).unwrap_or(false)
+    }
+}
+
+/// 逐个比较两个序列的元素
+fn all_equal(a: &[Value], b: &[Value]) -> Result<bool> {
+    if a.len() != b.len() {
+        return Ok(false);
+    }
+    for (x, y) in a.iter().zip(b) {
+        if !x.equals(y)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// 两个字典的键值对相同（与顺序无关）
+fn dicts_equal(a: &Dict, b: &Dict) -> Result<bool> {
+    if a.len() != b.len() {
+        return Ok(false);
+    }
+    for (key, value) in a.iter() {
+        match b.get(key)? {
+            Some(other) if other.equals(value)? => {}
+            _ => return Ok(false),
+        }
+    }
+    Ok(true)
+}
+
+/// 序列中第一个与 `item` 相等的元素的位置
+pub(crate) fn position(items: &[Value], item: &Value) -> Result<Option<usize>> {
+    for (i, x) in items.iter().enumerate() {
+        if x.equals(item)? {
+            return Ok(Some(i));
+        }
+    }
+    Ok(None)
+}
+
+/// 把整数下标（可为负）换算为序列中的位置
+fn sequence_index(sequence: &Value, index: &Value, len: usize) -> Result<usize> {
+    let Some(i) = index.as_i64() else {
+        return Err(VMError::TypeError(format!(
+            "{} indices must be integers, not {}",
+            sequence.type_name(),
+            index.type_name()
+        )));
+    };
+    let position = if i < 0 { i + len as i64 } else { i };
+    if position < 0 || position >= len as i64 {
+        return Err(VMError::IndexOutOfBounds { index: i, len });
+    }
+    Ok(position as usize)
+}
+
+/// 序列按字典序比较：第一个不相等的元素决定结果，否则比较长度
+fn order_sequences(a: &[Value], b: &[Value], op: &str) -> Result<Option<Ordering>> {
+    for (x, y) in a.iter().zip(b) {
+        if x != y {
+            return x.order(y, op);
+        }
+    }
+    Ok(Some(a.len().cmp(&b.len())))
+}
+
+fn overflow() -> VMError {
+    VMError::OverflowError("integer overflow".to_string())
+}
+
+/// 按 Python `repr(float)` 的规则格式化浮点数
+pub fn format_float(f: f64) -> String {
+    if f.is_nan() {
+        return "nan".to_string();
+    }
+    if f.is_infinite() {
+        return if f > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    if f == 0.0 {
+        return if f.is_sign_negative() { "-0.0" } else { "0.0" }.to_string();
+    }
+
+    // `{:e}` 给出最短的可往返十进制表示，例如 "1.2345e3"
+    let sci = format!("{:e}", f);
+    let (mantissa, exponent) = sci.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+
+    if (-4..16).contains(&exponent) {
+        let plain = f.to_string();
+        if plain.contains('.') {
+            plain
+        } else {
+            format!("{}.0", plain)
+        }
+    } else {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", mantissa, sign, exponent.abs())
+    }
+}
+
+/// 按 Python `repr(str)` 的规则格式化字符串
+pub fn format_str_repr(s: &str) -> String {
+    let quote = if s.contains('\'') && !s.contains('"') {
+        '"'
+    } else {
+        '\''
+    };
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push(quote);
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            _ if c == quote => {
+                out.push('\\');
+                out.push(quote);
+            }
+            _ if (c as u32) < 0x20 || c as u32 == 0x7f => {
+                out.push_str(&format!("\\x{:02x}", c as u32))
+            }
+            _ => out.push(c),
+        }
+    }
+    out.push(quote);
+    out
+}
+
+/// 按 Python `repr(bytes)` 的规则格式化字节串
+pub fn format_bytes(bytes: &[u8]) -> String {
+    let quote = if bytes.contains(&b'\'') && !bytes.contains(&b'"') {
+        '"'
+    } else {
+        '\''
+    };
+    let mut out = String::with_capacity(bytes.len() + 3);
+    out.push('b');
+    out.push(quote);
+    for &byte in bytes {
+        match byte {
+            b'\\' => out.push_str("\\\\"),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            _ if byte as char == quote => {
+                out.push('\\');
+                out.push(quote);
+            }
+            0x20..=0x7e => out.push(byte as char),
+            _ => out.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    out.push(quote);
+    out
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "None"),
+            Value::Bool(true) => write!(f, "True"),
+            Value::Bool(false) => write!(f, "False"),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{}", format_float(*x)),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Bytes(b) => write!(f, "{}", format_bytes(b)),
+            Value::List(items) => {
+                let Some(_guard) = ReprGuard::enter(Rc::as_ptr(items) as *const ()) else {
+                    return write!(f, "[...]");
+                };
+                write!(f, "[")?;
+                write_items(f, items.borrow().iter())?;
+                write!(f, "]")
+            }
+            Value::Tuple(items) => {
+                write!(f, "(")?;
+                write_items(f, items.iter())?;
+                if items.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            Value::Dict(dict) => {
+                let Some(_guard) = ReprGuard::enter(Rc::as_ptr(dict) as *const ()) else {
+                    return write!(f, "{{...}}");
+                };
+                write!(f, "{{")?;
+                for (i, (key, value)) in dict.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key.repr(), value.repr())?;
+                }
+                write!(f, "}}")
+            }
+            Value::Range(range) => write!(f, "{}", range),
+            Value::Iterator(iter) => write!(f, "<{} object>", iter.borrow().type_name()),
+            Value::Class(class) => write!(f, "<class '{}'>", class.name),
+            Value::Instance(instance) => write!(f, "<{} object>", instance.class.name),
+            Value::BoundMethod(method) => {
+                write!(
+                    f,
+                    "<bound method {} of {}>",
+                    method.function.name, method.receiver
+                )
+            }
+            Value::Super(proxy) => write!(
+                f,
+                "<super: <class '{}'>, <{} object>>",
+                proxy.class.name, proxy.receiver.class.name
+            ),
+            Value::Code(code) => write!(f, "<code object {}>", code.name),
+            Value::Function(function) => write!(f, "<function {}>", function.name),
+            Value::Builtin(builtin) => write!(f, "<built-in function {}>", builtin.name()),
+            Value::Closure(closure) => write!(f, "<function {}>", closure.function.name),
+            Value::Cell(cell) => write!(f, "<cell: {} object>", cell.borrow().type_name()),
+            Value::Module(module) => write!(f, "<module '{}'>", module.name),
+            Value::Generator(generator) => {
+                let generator = generator.borrow();
+                let kind = if generator.coroutine {
+                    "coroutine"
+                } else {
+                    "generator"
+                };
+                write!(f, "<{} object {}>", kind, generator.name)
+            }
+            Value::Future(future) => {
+                let future = future.borrow();
+                let state = match future.state {
+                    FutureState::Pending => "pending",
+                    FutureState::Done(_) | FutureState::Failed(_) => "finished",
+                    FutureState::Cancelled => "cancelled",
+                };
+                match &future.kind {
+                    FutureKind::Task(task) => write!(
+                        f,
+                        "<Task {} coro=<coroutine object {}>>",
+                        state,
+                        task.coroutine.borrow().name
+                    ),
+                    _ => write!(f, "<Future {}>", state),
+                }
+            }
+        }
+    }
+}
+
+/// 以逗号分隔输出元素的 repr
+fn write_items<'a>(
+    f: &mut fmt::Formatter<'_>,
+    items: impl Iterator<Item = &'a Value>,
+) -> fmt::Result {
+    for (i, item) in items.enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", item.repr())?;
+    }
+    Ok(())
+}
+
+thread_local! {
+    /// 正在输出的容器，用于识别自引用（与 Python 一样输出 `[...]`）
+    static REPR_STACK: RefCell<Vec<*const ()>> = const { RefCell::new(Vec::new()) };
+}
+
+/// 输出容器期间把它记录在 [`REPR_STACK`] 中
+struct ReprGuard;
+
+impl ReprGuard {
+    fn enter(ptr: *const ()) -> Option<ReprGuard> {
+        REPR_STACK.with(|stack| {
+            let mut stack = stack.borrow_mut();
+            if stack.contains(&ptr) {
+                return None;
+            }
+            stack.push(ptr);
+            Some(ReprGuard)
+        })
+    }
+}
+
+impl Drop for ReprGuard {
+    fn drop(&mut self) {
+        REPR_STACK.with(|stack| {
+            stack.borrow_mut().pop();
+        });
+    }
+}
+
+/// 比较和哈希时容器嵌套的最大层数，与 Python 默认的递归上限相同
+const MAX_NESTING_DEPTH: usize = 1000;
+
+thread_local! {
+    /// 正在比较或哈希的容器的嵌套层数
+    static NESTING_DEPTH: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// 比较或哈希容器期间把 [`NESTING_DEPTH`] 加一，超过 [`MAX_NESTING_DEPTH`] 时报告 `RecursionError`
+struct NestingGuard;
+
+impl NestingGuard {
+    fn enter() -> Result<NestingGuard> {
+        NESTING_DEPTH.with(|depth| {
+            if depth.get() >= MAX_NESTING_DEPTH {
+                return Err(VMError::RuntimeError(
+                    exceptions::CALL_STACK_OVERFLOW.to_string(),
+                ));
+            }
+            depth.set(depth.get() + 1);
+            Ok(NestingGuard)
+        })
+    }
+}
+
+impl Drop for NestingGuard {
+    fn drop(&mut self) {
+        NESTING_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
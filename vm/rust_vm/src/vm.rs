@@ -6,37 +6,72 @@ AquaScript 虚拟机核心执行引擎
 - 函数调用管理
 - 内存管理
 - 性能优化
+- 用户对象的特殊方法协议（运算符重载、`len()`、`str()`、下标、`in`、哈希和迭代）
+- 基于异常处理表的 `try`/`catch`/`finally` 与跨调用帧的栈展开，
+  运行时错误转换为内置异常类的实例
+- 按搜索路径导入其他字节码模块，每个模块有独立的全局变量
+- 生成器：挂起的调用帧保存在生成器中，由迭代协议、`send()` 和 `yield from` 恢复执行
+- 协程：`await` 与 `yield from` 共用挂起机制，由内置 `asyncio` 模块的单线程事件循环驱动，
+  时间由虚拟时钟推进
 */
 
 use crate::{Result, VMError, VMStats};
-use crate::bytecode::{OpCode, Instruction, Bytecode};
+use crate::bytecode::{self, OpCode, Instruction, Bytecode, ExceptionTable, Region};
 use crate::value::Value;
-use crate::function::{Function, CallFrame};
+use crate::dict::Dict;
+use crate::function::{Function, CallFrame, Cell, Closure, Completion, Handling, Keywords};
 use crate::builtins::BuiltinFunction;
-use rustc_hash::FxHashMap;
+use crate::event_loop::{EventLoop, FutureKind, FutureRef, FutureState};
+use crate::exceptions::{self, BuiltinExceptions};
+use crate::generator::{Generator, GeneratorState, Resume, Step};
+use crate::format;
+use crate::methods;
+use crate::module::{self, Module, MAIN_MODULE};
+use crate::object::{BoundMethod, Class, Instance, Super};
+use crate::package::Package;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::rc::Rc;
 
 /// 高性能AquaScript虚拟机
 pub struct AquaVM {
-    /// 常量池
-    constants: Vec<Value>,
+    /// 已加载的模块，第 0 个是主程序；函数按 [`Function::module`] 找到所属模块
+    modules: Vec<Rc<Module>>,
     
-    /// 全局变量
-    globals: Vec<Value>,
+    /// 已导入的模块，按模块名缓存
+    module_cache: FxHashMap<String, Rc<Module>>,
     
-    /// 函数表
-    functions: FxHashMap<String, Function>,
+    /// 正在执行顶层代码的模块，外层在前，用于发现循环导入
+    importing: Vec<String>,
     
-    /// 内置函数
-    builtins: FxHashMap<String, BuiltinFunction>,
+    /// 注册到内存中的模块字节码（如应用包中的模块），导入时先于搜索路径查找
+    registered: FxHashMap<String, Rc<Bytecode>>,
     
-    /// 主程序指令
-    instructions: Vec<Instruction>,
+    /// 已注册的依赖包名，包内模块之间的导入先在包内查找
+    packages: FxHashSet<String>,
+    
+    /// 函数表：内置函数在前，之后是各模块加载时登记的函数，`LOAD_FUNC` 的操作数为表中的编号
+    functions: Vec<Value>,
+    
+    /// 内置异常类
+    exceptions: BuiltinExceptions,
     
     /// 运行时状态
     stack: Vec<Value>,
     call_stack: Vec<CallFrame>,
+    /// 当前帧的程序计数器，调用时保存到调用者的帧中
     pc: usize,
+    /// 刚执行 `YIELD_VALUE` 挂起的生成器帧及其操作数栈内容，由 [`Self::resume`] 取回
+    suspended: Option<(CallFrame, Vec<Value>)>,
+    /// 嵌套的执行循环层数：特殊方法、生成器恢复和导入都在 Rust 中递归进入 [`Self::execute`]
+    native_depth: usize,
+    
+    /// 事件循环，由 `asyncio.run()` 或 [`Self::run_async_main`] 启动
+    event_loop: EventLoop,
+    /// 事件循环是否正在运行
+    loop_running: bool,
     
     /// 性能统计
     stats: VMStats,
@@ -54,11 +89,20 @@ pub struct VMConfig {
     /// 最大调用栈深度
     pub max_call_depth: usize,
     
+    /// 执行循环的最大嵌套层数，每层都占用宿主线程的栈，默认值按 2MB 的线程栈留出余量
+    pub max_native_depth: usize,
+    
     /// 是否启用性能统计
     pub enable_stats: bool,
     
     /// 是否启用调试模式
     pub debug_mode: bool,
+    
+    /// 模块搜索路径，排在环境变量 `AQUA_PATH` 中的目录之前
+    pub module_paths: Vec<PathBuf>,
+    
+    /// 标准库目录，排在搜索路径的最后，默认为 [`module::default_stdlib`]
+    pub stdlib_path: Option<PathBuf>,
 }
 
 impl Default for VMConfig {
@@ -66,8 +110,12 @@ impl Default for VMConfig {
         Self {
             max_stack_size: 1024 * 1024,  // 1M 栈大小
             max_call_depth: 1000,
+            // 调试构建中每层执行循环的栈帧约大二十倍
+            max_native_depth: if cfg!(debug_assertions) { 24 } else { 400 },
             enable_stats: true,
             debug_mode: false,
+            module_paths: Vec::new(),
+            stdlib_path: module::default_stdlib(),
         }
     }
 }
@@ -81,49 +129,127 @@ impl AquaVM {
     /// 使用指定配置创建虚拟机
     pub fn with_config(config: VMConfig) -> Self {
         let mut vm = Self {
-            constants: Vec::new(),
-            globals: Vec::new(),
-            functions: FxHashMap::default(),
-            builtins: FxHashMap::default(),
-            instructions: Vec::new(),
+            modules: Vec::new(),
+            module_cache: FxHashMap::default(),
+            importing: Vec::new(),
+            registered: FxHashMap::default(),
+            packages: FxHashSet::default(),
+            functions: BuiltinFunction::ALL.into_iter().map(Value::Builtin).collect(),
+            exceptions: BuiltinExceptions::new(),
             stack: Vec::with_capacity(1024),
             call_stack: Vec::with_capacity(64),
             pc: 0,
+            suspended: None,
+            native_depth: 0,
+            event_loop: EventLoop::new(BuiltinExceptions::new()),
+            loop_running: false,
             stats: VMStats::default(),
             config,
         };
         
-        // 注册内置函数
-        vm.register_builtins();
+        vm.add_module(MAIN_MODULE, &Bytecode::default(), bytecode::Analysis::default())
+            .expect("empty program always loads");
         vm
     }
     
     /// 加载字节码
+    ///
+    /// 程序先经过 [`bytecode::analyze`] 校验，校验失败时虚拟机状态保持不变；
+    /// 校验生成的异常处理表随函数一起保存。程序作为主模块加载，之前导入的模块被丢弃。
+    /// `LOAD_FUNC` 引用的函数在此一次性解析为函数表中的编号。
     pub fn load_bytecode(&mut self, bytecode: &Bytecode) -> Result<()> {
-        self.constants = bytecode.constants.clone();
-        self.globals = vec![Value::Null; bytecode.global_vars.len()];
-        self.functions = bytecode.functions.clone();
-        self.instructions = bytecode.instructions.clone();
+        let analysis = bytecode::analyze(bytecode)?;
         
-        // 初始化全局变量
-        self.initialize_globals(&bytecode.global_vars)?;
+        self.modules.clear();
+        self.module_cache.clear();
+        self.functions.truncate(BuiltinFunction::ALL.len());
+        self.add_module(MAIN_MODULE, bytecode, analysis)?;
         
         Ok(())
     }
     
+    /// 注册可以按 `name` 导入的模块字节码，同名时覆盖之前的注册
+    pub fn register_module(&mut self, name: &str, bytecode: Bytecode) {
+        self.registered.insert(name.to_string(), Rc::new(bytecode));
+    }
+    
+    /// 注册依赖包：入口程序按包名导入，其余模块按 `包名.模块名` 导入
+    pub fn register_package(&mut self, package: &Package) {
+        let name = &package.manifest.name;
+        self.register_module(name, package.main.clone());
+        for (module, bytecode) in &package.modules {
+            self.register_module(&format!("{}.{}", name, module), bytecode.clone());
+        }
+        self.packages.insert(name.clone());
+    }
+    
+    /// 加载应用包：注册包内的模块，并把入口程序作为主程序加载
+    pub fn load_package(&mut self, package: &Package) -> Result<()> {
+        for (name, bytecode) in &package.modules {
+            self.register_module(name, bytecode.clone());
+        }
+        self.load_bytecode(&package.main)
+    }
+    
     /// 运行虚拟机
     pub fn run(&mut self) -> Result<()> {
-        self.pc = 0;
+        self.stack.clear();
+        self.call_stack.clear();
+        self.push_frame(self.modules[0].main.clone(), Vec::new())?;
+        self.execute(0)
+    }
+    
+    /// 运行主程序，再以事件循环运行其中定义的 `async func main()`，返回它的结果
+    ///
+    /// 相当于在主程序末尾执行 `asyncio.run(main())`。
+    pub fn run_async_main(&mut self) -> Result<Value> {
+        self.run()?;
+        let main = self.get_global("main")
+            .ok_or_else(|| VMError::FunctionNotFound("main".to_string()))?;
         
-        while self.pc < self.instructions.len() {
-            let instruction = self.instructions[self.pc];
+        // 协程在主程序帧之上恢复，挂起时回到这一帧
+        let mut frame = self.new_frame(self.modules[0].main.clone(), Vec::new());
+        frame.pc = frame.function.instructions.len();
+        self.enter_frame(frame)?;
+        let result = self.run_call(|vm| vm.call_value(main, Vec::new(), Vec::new()))
+            .and_then(|coroutine| self.asyncio_run(&coroutine));
+        self.call_stack.clear();
+        self.stack.clear();
+        result
+    }
+    
+    /// 执行指令，直到调用栈回落到 `depth` 层
+    ///
+    /// 嵌套层数超过 `max_native_depth` 时报告 `RecursionError`，以免耗尽宿主线程的栈。
+    fn execute(&mut self, depth: usize) -> Result<()> {
+        if self.native_depth >= self.config.max_native_depth {
+            return Err(VMError::RuntimeError(exceptions::CALL_STACK_OVERFLOW.to_string()));
+        }
+        self.native_depth += 1;
+        let result = self.execute_frames(depth);
+        self.native_depth -= 1;
+        result
+    }
+    
+    /// 执行循环本体
+    fn execute_frames(&mut self, depth: usize) -> Result<()> {
+        while self.call_stack.len() > depth {
+            let frame = self.call_stack.last().unwrap();
+            let Some(&instruction) = frame.function.instructions.get(self.pc) else {
+                // 只有主程序可以执行到末尾，函数体末尾已由校验器排除
+                self.handle_return_value(Value::Null)?;
+                continue;
+            };
             self.pc += 1;
             
             if self.config.enable_stats {
                 self.stats.instructions_executed += 1;
             }
             
-            self.execute_instruction(instruction)?;
+            // 错误在当前 execute 负责的帧中寻找处理器
+            if let Err(error) = self.execute_instruction(instruction) {
+                self.unwind(error, depth)?;
+            }
             
             // 检查栈大小限制
             if self.stack.len() > self.config.max_stack_size {
@@ -145,13 +271,14 @@ impl AquaVM {
     fn execute_instruction(&mut self, instruction: Instruction) -> Result<()> {
         match instruction.opcode {
             OpCode::LoadConst => {
-                let value = self.constants[instruction.operand as usize].clone();
+                let value = self.module().constants[instruction.operand as usize].load_constant();
                 self.stack.push(value);
             }
             
+            // 旧指令：函数内访问局部变量，主程序中访问全局变量
             OpCode::LoadVar => {
-                let value = if self.call_stack.is_empty() {
-                    self.globals[instruction.operand as usize].clone()
+                let value = if self.in_main() {
+                    self.module().globals.borrow()[instruction.operand as usize].clone()
                 } else {
                     let frame = self.call_stack.last().unwrap();
                     frame.locals[instruction.operand as usize].clone()
@@ -161,52 +288,238 @@ impl AquaVM {
             
             OpCode::StoreVar => {
                 let value = self.stack.pop().ok_or(VMError::StackUnderflow)?;
-                if self.call_stack.is_empty() {
-                    self.globals[instruction.operand as usize] = value;
+                if self.in_main() {
+                    self.module().globals.borrow_mut()[instruction.operand as usize] = value;
                 } else {
                     let frame = self.call_stack.last_mut().unwrap();
                     frame.locals[instruction.operand as usize] = value;
                 }
             }
             
+            OpCode::LoadGlobal => {
+                let value = self.module().globals.borrow()[instruction.operand as usize].clone();
+                self.stack.push(value);
+            }
+            
+            OpCode::StoreGlobal => {
+                let value = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                self.module().globals.borrow_mut()[instruction.operand as usize] = value;
+            }
+            
+            OpCode::LoadLocal => {
+                let frame = self.call_stack.last().unwrap();
+                let value = frame.locals[instruction.operand as usize].clone();
+                self.stack.push(value);
+            }
+            
+            OpCode::StoreLocal => {
+                let value = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                let frame = self.call_stack.last_mut().unwrap();
+                frame.locals[instruction.operand as usize] = value;
+            }
+            
+            OpCode::Pop => {
+                self.stack.pop().ok_or(VMError::StackUnderflow)?;
+            }
+            
+            OpCode::Dup => {
+                let value = self.stack.last().ok_or(VMError::StackUnderflow)?.clone();
+                self.stack.push(value);
+            }
+            
+            OpCode::RotTwo => {
+                let len = self.stack.len();
+                if len < 2 {
+                    return Err(VMError::StackUnderflow);
+                }
+                self.stack.swap(len - 1, len - 2);
+            }
+            
+            OpCode::RotThree => {
+                // (a, b, c) -> (c, a, b)
+                let len = self.stack.len();
+                if len < 3 {
+                    return Err(VMError::StackUnderflow);
+                }
+                self.stack[len - 3..].rotate_right(1);
+            }
+            
             OpCode::Add => {
                 let b = self.stack.pop().ok_or(VMError::StackUnderflow)?;
                 let a = self.stack.pop().ok_or(VMError::StackUnderflow)?;
-                self.stack.push(a.add(&b)?);
+                let result = self.binary(a, b, &ADD)?;
+                self.stack.push(result);
             }
             
             OpCode::Sub => {
                 let b = self.stack.pop().ok_or(VMError::StackUnderflow)?;
                 let a = self.stack.pop().ok_or(VMError::StackUnderflow)?;
-                self.stack.push(a.sub(&b)?);
+                let result = self.binary(a, b, &SUB)?;
+                self.stack.push(result);
             }
             
             OpCode::Mul => {
                 let b = self.stack.pop().ok_or(VMError::StackUnderflow)?;
                 let a = self.stack.pop().ok_or(VMError::StackUnderflow)?;
-                self.stack.push(a.mul(&b)?);
+                let result = self.binary(a, b, &MUL)?;
+                self.stack.push(result);
             }
             
             OpCode::Div => {
                 let b = self.stack.pop().ok_or(VMError::StackUnderflow)?;
                 let a = self.stack.pop().ok_or(VMError::StackUnderflow)?;
-                self.stack.push(a.div(&b)?);
+                let result = self.binary(a, b, &DIV)?;
+                self.stack.push(result);
+            }
+            
+            OpCode::Mod => {
+                let (a, b) = self.pop_pair()?;
+                let result = self.binary(a, b, &MOD)?;
+                self.stack.push(result);
+            }
+            
+            OpCode::Pow => {
+                let (a, b) = self.pop_pair()?;
+                let result = self.binary(a, b, &POW)?;
+                self.stack.push(result);
+            }
+            
+            OpCode::Eq => {
+                let (a, b) = self.pop_pair()?;
+                let result = self.binary(a, b, &EQ)?;
+                self.stack.push(result);
+            }
+            
+            OpCode::Ne => {
+                let (a, b) = self.pop_pair()?;
+                let result = self.binary(a, b, &NE)?;
+                self.stack.push(result);
+            }
+            
+            OpCode::Lt => {
+                let (a, b) = self.pop_pair()?;
+                let result = self.binary(a, b, &LT)?;
+                self.stack.push(result);
+            }
+            
+            OpCode::Gt => {
+                let (a, b) = self.pop_pair()?;
+                let result = self.binary(a, b, &GT)?;
+                self.stack.push(result);
+            }
+            
+            OpCode::Le => {
+                let (a, b) = self.pop_pair()?;
+                let result = self.binary(a, b, &LE)?;
+                self.stack.push(result);
+            }
+            
+            OpCode::Ge => {
+                let (a, b) = self.pop_pair()?;
+                let result = self.binary(a, b, &GE)?;
+                self.stack.push(result);
+            }
+            
+            OpCode::In => {
+                // 栈布局 [元素, 容器]
+                let (item, container) = self.pop_pair()?;
+                let found = self.contains(&container, &item)?;
+                self.stack.push(Value::Bool(found));
+            }
+            
+            // 非短路形式：两个操作数都已求值，结果是其中之一
+            OpCode::And => {
+                let (a, b) = self.pop_pair()?;
+                self.stack.push(if a.is_truthy() { b } else { a });
+            }
+            
+            OpCode::Or => {
+                let (a, b) = self.pop_pair()?;
+                self.stack.push(if a.is_truthy() { a } else { b });
+            }
+            
+            OpCode::Not => {
+                let a = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                self.stack.push(Value::Bool(!a.is_truthy()));
             }
             
             OpCode::Call => {
                 self.handle_call(instruction.operand as usize)?;
             }
             
+            OpCode::CallKw => {
+                self.handle_call_kw(instruction.operand as usize)?;
+            }
+            
+            OpCode::TailCall => {
+                self.handle_tail_call(instruction.operand as usize)?;
+            }
+            
+            OpCode::YieldValue => {
+                let value = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                self.suspend(value);
+            }
+            
+            OpCode::YieldFrom => {
+                self.handle_yield_from()?;
+            }
+            
+            OpCode::GetAwaitable => {
+                let awaitable = self.stack.last().ok_or(VMError::StackUnderflow)?;
+                check_awaitable(awaitable)?;
+            }
+            
             OpCode::Return => {
                 self.handle_return()?;
             }
             
+            // 操作数在加载时已改写为函数表中的编号；函数记录所属模块，
+            // 导入到其他模块后仍在定义它的模块中执行
             OpCode::LoadFunc => {
-                let func_name = match &self.constants[instruction.operand as usize] {
-                    Value::String(name) => name.clone(),
-                    _ => return Err(VMError::TypeError("Expected string for function name".to_string())),
+                let function = self.functions[instruction.operand as usize].clone();
+                self.stack.push(function);
+            }
+            
+            OpCode::MakeClosure => {
+                let cells = self.pop_n(instruction.operand as usize)?
+                    .into_iter()
+                    .map(|value| match value {
+                        Value::Cell(cell) => Ok(cell),
+                        other => Err(VMError::TypeError(format!(
+                            "MAKE_CLOSURE expects cells, got '{}'",
+                            other.type_name()
+                        ))),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let function = match self.stack.pop().ok_or(VMError::StackUnderflow)? {
+                    Value::Function(function) => function,
+                    other => return Err(VMError::TypeError(format!(
+                        "MAKE_CLOSURE expects a function, got '{}'",
+                        other.type_name()
+                    ))),
                 };
-                self.stack.push(Value::String(func_name));
+                if cells.len() != function.free_vars.len() {
+                    return Err(VMError::RuntimeError(format!(
+                        "function '{}' has {} free variables, got {} cells",
+                        function.name, function.free_vars.len(), cells.len()
+                    )));
+                }
+                self.stack.push(Value::Closure(Rc::new(Closure { function, cells })));
+            }
+            
+            OpCode::LoadClosure => {
+                let cell = self.cell(instruction.operand)?;
+                self.stack.push(Value::Cell(cell));
+            }
+            
+            OpCode::LoadDeref => {
+                let value = self.cell(instruction.operand)?.borrow().clone();
+                self.stack.push(value);
+            }
+            
+            OpCode::StoreDeref => {
+                let value = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                *self.cell(instruction.operand)?.borrow_mut() = value;
             }
             
             OpCode::Jump => {
@@ -227,12 +540,268 @@ impl AquaVM {
                 }
             }
             
+            OpCode::BuildList => {
+                let items = self.pop_n(instruction.operand as usize)?;
+                self.stack.push(Value::list(items));
+            }
+            
+            OpCode::BuildTuple => {
+                let items = self.pop_n(instruction.operand as usize)?;
+                self.stack.push(Value::Tuple(items.into()));
+            }
+            
+            OpCode::BuildDict => {
+                // 栈布局 [k1, v1, k2, v2, ...]，重复的键保留最后一个值
+                let items = self.pop_n(instruction.operand as usize * 2)?;
+                let dict = Rc::new(RefCell::new(Dict::new()));
+                let mut items = items.into_iter();
+                while let (Some(key), Some(value)) = (items.next(), items.next()) {
+                    self.dict_insert(&dict, key, value)?;
+                }
+                self.stack.push(Value::Dict(dict));
+            }
+            
+            OpCode::GetItem => {
+                let (container, index) = self.pop_pair()?;
+                let value = self.get_item(&container, &index)?;
+                self.stack.push(value);
+            }
+            
+            OpCode::SetItem => {
+                // 栈布局 [容器, 下标, 值]
+                let value = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                let (container, index) = self.pop_pair()?;
+                self.set_item(&container, index, value)?;
+            }
+            
+            OpCode::Len => {
+                let value = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                let length = self.length(&value)?;
+                self.stack.push(Value::Int(length as i64));
+            }
+            
+            OpCode::FormatValue => {
+                let value = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                let (conversion, spec) = bytecode::split_format_operand(instruction.operand);
+                let value = match conversion {
+                    bytecode::FORMAT_STR => Value::String(self.render(&value, false)?.into()),
+                    bytecode::FORMAT_REPR => Value::String(self.render(&value, true)?.into()),
+                    _ => value,
+                };
+                let text = match spec.map(|index| &self.module().constants[index]) {
+                    Some(Value::String(spec)) => format::format_value(&value, spec)?,
+                    _ => self.render(&value, false)?,
+                };
+                self.stack.push(Value::String(text.into()));
+            }
+            
+            OpCode::GetIter => {
+                let iterable = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                let iterator = self.get_iter(&iterable)?;
+                self.stack.push(iterator);
+            }
+            
+            OpCode::ForIter => {
+                // 迭代器留在栈上；耗尽时弹出迭代器并跳到循环之后
+                let iterator = self.stack.last().cloned().ok_or(VMError::StackUnderflow)?;
+                match self.next_item(&iterator)? {
+                    Some(value) => self.stack.push(value),
+                    None => {
+                        self.stack.pop();
+                        self.pc = instruction.operand as usize;
+                    }
+                }
+            }
+            
+            OpCode::ListAppend => {
+                // 列表推导式中的栈布局 [list, iterator, element] -> [list, iterator]
+                let element = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                let len = self.stack.len();
+                if len < 2 {
+                    return Err(VMError::StackUnderflow);
+                }
+                match &self.stack[len - 2] {
+                    Value::List(items) => items.borrow_mut().push(element),
+                    other => {
+                        return Err(VMError::TypeError(format!(
+                            "LIST_APPEND target must be a list, not '{}'",
+                            other.type_name()
+                        )))
+                    }
+                }
+            }
+            
+            OpCode::JumpIfFalseOrPop => {
+                let condition = self.stack.last().ok_or(VMError::StackUnderflow)?;
+                if condition.is_truthy() {
+                    self.stack.pop();
+                } else {
+                    self.pc = instruction.operand as usize;
+                }
+            }
+            
+            OpCode::JumpIfTrueOrPop => {
+                let condition = self.stack.last().ok_or(VMError::StackUnderflow)?;
+                if condition.is_truthy() {
+                    self.pc = instruction.operand as usize;
+                } else {
+                    self.stack.pop();
+                }
+            }
+            
+            OpCode::CreateClass => {
+                // 栈布局 [bases..., methods]
+                let methods = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                let bases = self.pop_n((instruction.operand >> 16) as usize)?;
+                let name = self.constant_name(instruction.operand & 0xFFFF)?;
+                let class = self.create_class(name, bases, &methods)?;
+                self.stack.push(Value::Class(Rc::new(class)));
+            }
+            
+            OpCode::CreateObject => {
+                self.handle_call(instruction.operand as usize)?;
+            }
+            
+            OpCode::GetAttr => {
+                let object = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                let name = self.constant_name(instruction.operand)?;
+                let value = self.get_attr(&object, &name)?;
+                self.stack.push(value);
+            }
+            
+            OpCode::SetAttr => {
+                // 栈布局 [object, value, name]
+                let name = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                let (object, value) = self.pop_pair()?;
+                let Value::String(name) = name else {
+                    return Err(VMError::TypeError(format!(
+                        "attribute name must be string, not '{}'",
+                        name.type_name()
+                    )));
+                };
+                match &object {
+                    Value::Instance(instance) => instance.set_attribute(name, value),
+                    Value::Class(class) => {
+                        return Err(VMError::TypeError(format!(
+                            "cannot set '{}' attribute of immutable type '{}'",
+                            name, class.name
+                        )))
+                    }
+                    Value::Module(module) => {
+                        if !module.set_global(&name, value) {
+                            return Err(no_attribute(&object, &name));
+                        }
+                    }
+                    other => return Err(no_attribute(other, &name)),
+                }
+            }
+            
+            OpCode::HasAttr => {
+                let object = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                let name = self.constant_name(instruction.operand)?;
+                let found = match &object {
+                    Value::Instance(instance) => {
+                        instance.attribute(&name).is_some() || instance.class.method(&name).is_some()
+                    }
+                    Value::Class(class) => class.method(&name).is_some(),
+                    Value::Super(proxy) => proxy.lookup(&name).is_some(),
+                    Value::Module(module) => module.attribute(&name).is_some(),
+                    other => methods::has_method(other, &name),
+                };
+                self.stack.push(Value::Bool(found));
+            }
+            
+            OpCode::CallMethod => {
+                let argc = (instruction.operand >> 16) as usize;
+                let name = self.constant_name(instruction.operand & 0xFFFF)?;
+                self.handle_call_method(&name, argc)?;
+            }
+            
             OpCode::TypeCheck => {
                 // 暂时跳过类型检查
             }
             
+            OpCode::TryBegin | OpCode::FinallyBegin => {
+                // 处理器已在加载时登记到异常处理表
+            }
+            
+            OpCode::TryEnd | OpCode::CatchEnd => {
+                self.finish_block();
+            }
+            
+            OpCode::CatchBegin => {
+                // 只由栈展开转入，压入捕获的异常供 catch 块绑定
+                match self.call_stack.last().unwrap().handling.last() {
+                    Some(Handling::Catch { exception, .. }) => self.stack.push(exception.clone()),
+                    _ => return Err(VMError::RuntimeError("CATCH_BEGIN outside exception handling".to_string())),
+                }
+            }
+            
+            OpCode::FinallyEnd => {
+                self.finish_finally()?;
+            }
+            
+            OpCode::Throw => {
+                let exception = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                if let Value::Instance(instance) = &exception {
+                    if self.exceptions.is_exception(&instance.class) {
+                        instance.set_attribute("traceback".into(), self.traceback());
+                    }
+                }
+                return Err(VMError::Exception(exception));
+            }
+            
+            OpCode::Reraise => {
+                let handling = &self.call_stack.last().unwrap().handling;
+                let exception = handling.iter().rev().find_map(|handling| match handling {
+                    Handling::Catch { exception, .. } => Some(exception.clone()),
+                    Handling::Finally { .. } => None,
+                });
+                return Err(match exception {
+                    Some(exception) => VMError::Exception(exception),
+                    None => VMError::RuntimeError("No active exception to reraise".to_string()),
+                });
+            }
+            
             OpCode::Halt => {
-                return Ok(());
+                // 导入的模块执行到 HALT 时只结束该模块的顶层代码
+                if self.in_main() && self.call_stack.last().unwrap().function.module != 0 {
+                    self.handle_return_value(Value::Null)?;
+                } else {
+                    self.call_stack.clear();
+                }
+            }
+            
+            OpCode::ImportModule => {
+                let name = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                let module = self.import(&module_name(&name)?)?;
+                self.stack.push(Value::Module(module));
+            }
+            
+            OpCode::ImportFrom => {
+                // 栈布局 [模块名, 导入名元组]；导入的值逆序压栈，随后的 STORE 按源码顺序取出
+                let (name, names) = self.pop_pair()?;
+                let module = self.import(&module_name(&name)?)?;
+                let Value::Tuple(names) = names else {
+                    return Err(VMError::TypeError(format!(
+                        "IMPORT_FROM expects a tuple of names, not '{}'",
+                        names.type_name()
+                    )));
+                };
+                for name in names.iter().rev() {
+                    let value = match name {
+                        Value::String(name) => module.attribute(name).ok_or_else(|| {
+                            VMError::ImportError(format!("cannot import name '{}' from '{}'", name, module.name))
+                        })?,
+                        other => {
+                            return Err(VMError::TypeError(format!(
+                                "imported name must be str, not '{}'",
+                                other.type_name()
+                            )))
+                        }
+                    };
+                    self.stack.push(value);
+                }
             }
             
             _ => {
@@ -243,115 +812,1835 @@ impl AquaVM {
         Ok(())
     }
     
+    /// 弹出二元运算的两个操作数，返回 (左, 右)
+    #[inline]
+    fn pop_pair(&mut self) -> Result<(Value, Value)> {
+        let b = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+        let a = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+        Ok((a, b))
+    }
+    
+    /// 弹出栈顶的 `n` 个值，按压栈顺序返回
+    fn pop_n(&mut self, n: usize) -> Result<Vec<Value>> {
+        if self.stack.len() < n {
+            return Err(VMError::StackUnderflow);
+        }
+        Ok(self.stack.split_off(self.stack.len() - n))
+    }
+    
     /// 处理函数调用
     fn handle_call(&mut self, argc: usize) -> Result<()> {
-        // 检查调用栈深度
-        if self.call_stack.len() >= self.config.max_call_depth {
-            return Err(VMError::RuntimeError("Call stack overflow".to_string()));
-        }
-        
         // 获取参数
-        let mut args = Vec::with_capacity(argc);
-        for _ in 0..argc {
-            args.push(self.stack.pop().ok_or(VMError::StackUnderflow)?);
+        let args = self.pop_n(argc)?;
+        
+        // 获取被调用的值
+        let callee = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+        self.call_value(callee, args, Vec::new())
+    }
+    
+    /// 处理尾调用：被调用的用户函数压入调用帧后，移除它下面的当前帧，
+    /// 被调用的函数直接返回到当前帧的调用者
+    ///
+    /// 顶层代码、`__init__`、生成器、协程以及位于 try 语句或 catch/finally 块中的调用不移除当前帧，
+    /// 按普通调用执行，随后的 `RETURN` 照常返回。
+    fn handle_tail_call(&mut self, argc: usize) -> Result<()> {
+        let depth = self.call_stack.len();
+        let frame = self.call_stack.last().unwrap();
+        let replaceable = depth > 1
+            && !self.in_main()
+            && frame.instance.is_none()
+            && !frame.function.generator
+            && !frame.function.coroutine
+            && frame.handling.is_empty()
+            && frame.function.handlers.enclosing(self.pc - 1).next().is_none();
+        
+        self.handle_call(argc)?;
+        if !replaceable || self.call_stack.len() == depth {
+            return Ok(());
         }
-        args.reverse();
         
-        // 获取函数
-        let func_name = match self.stack.pop().ok_or(VMError::StackUnderflow)? {
-            Value::String(name) => name,
-            _ => return Err(VMError::TypeError("Expected function name".to_string())),
+        let frame = self.call_stack.remove(depth - 1);
+        let callee = self.call_stack.last_mut().unwrap();
+        self.stack.drain(frame.stack_base..callee.stack_base);
+        callee.stack_base = frame.stack_base;
+        callee.tail_calls = frame.tail_calls + 1;
+        if self.config.enable_stats {
+            self.stats.tail_calls += 1;
+        }
+        Ok(())
+    }
+    
+    /// 处理 `CALL_KW`：栈布局 [函数, 实参..., 关键字参数名元组]，最后几个实参是关键字参数的值
+    fn handle_call_kw(&mut self, argc: usize) -> Result<()> {
+        let names = match self.stack.pop().ok_or(VMError::StackUnderflow)? {
+            Value::Tuple(names) if names.len() <= argc => names,
+            other => return Err(VMError::TypeError(format!(
+                "CALL_KW expects a tuple of at most {} keyword names, got {}",
+                argc,
+                other.repr()
+            ))),
         };
+        let mut args = self.pop_n(argc)?;
+        let values = args.split_off(argc - names.len());
+        let keywords = names.iter()
+            .zip(values)
+            .map(|(name, value)| match name {
+                Value::String(name) => Ok((name.clone(), value)),
+                other => Err(VMError::TypeError(format!(
+                    "keywords must be strings, not '{}'",
+                    other.type_name()
+                ))),
+            })
+            .collect::<Result<Keywords>>()?;
         
+        let callee = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+        self.call_value(callee, args, keywords)
+    }
+    
+    /// 调用任意可调用的值：函数、内置函数、闭包、类或绑定方法
+    ///
+    /// 内置函数的结果直接压栈；用户函数压入新的调用帧，结果在返回时压栈。
+    /// 除 `asyncio` 模块的函数外，内置函数不接受关键字参数。
+    fn call_value(&mut self, callee: Value, mut args: Vec<Value>, keywords: Keywords) -> Result<()> {
         if self.config.enable_stats {
             self.stats.function_calls += 1;
         }
         
-        // 检查是否为内置函数
-        if let Some(builtin) = self.builtins.get(&func_name) {
-            let result = builtin.call(&args)?;
-            self.stack.push(result);
-            return Ok(());
+        match callee {
+            Value::Function(function) => self.call_function(function, args, keywords),
+            // asyncio 的函数需要读写事件循环
+            Value::Builtin(builtin) if BuiltinFunction::ASYNCIO.contains(&builtin) => {
+                let result = self.call_asyncio(builtin, args, keywords)?;
+                self.stack.push(result);
+                Ok(())
+            }
+            Value::Builtin(builtin) if !keywords.is_empty() => Err(VMError::TypeError(format!(
+                "{}() takes no keyword arguments",
+                builtin.name()
+            ))),
+            // super() 需要读取当前调用帧，不能作为普通内置函数
+            Value::Builtin(BuiltinFunction::Super) => {
+                let proxy = self.super_proxy(&args)?;
+                self.stack.push(proxy);
+                Ok(())
+            }
+            Value::Builtin(builtin) => {
+                let result = self.call_builtin(builtin, args)?;
+                self.stack.push(result);
+                Ok(())
+            }
+            Value::Closure(closure) => self.call_closure(&closure, args, keywords),
+            Value::BoundMethod(method) => {
+                args.insert(0, method.receiver.clone());
+                self.call_method_function(method.class.clone(), method.function.clone(), args, keywords)
+            }
+            Value::Class(class) => self.instantiate(class, args, keywords),
+            other => Err(VMError::TypeError(format!(
+                "'{}' object is not callable",
+                other.type_name()
+            ))),
+        }
+    }
+    
+    /// 调用用户函数
+    fn call_function(&mut self, function: Rc<Function>, args: Vec<Value>, keywords: Keywords) -> Result<()> {
+        self.enter_function(function, args, keywords, &[], None)
+    }
+    
+    /// 调用闭包：捕获的单元放入函数的自由变量槽位
+    fn call_closure(&mut self, closure: &Closure, args: Vec<Value>, keywords: Keywords) -> Result<()> {
+        self.enter_function(closure.function.clone(), args, keywords, &closure.cells, None)
+    }
+    
+    /// 调用在 `class` 中找到的方法，记录所属类供 `super()` 使用
+    fn call_method_function(&mut self, class: Rc<Class>, function: Rc<Function>, args: Vec<Value>, keywords: Keywords) -> Result<()> {
+        self.enter_function(function, args, keywords, &[], Some(class))
+    }
+    
+    /// 把实参绑定到参数（见 [`Function::bind`]）后为用户函数创建调用帧，
+    /// `cells` 放入自由变量槽位，`class` 为方法所属的类
+    ///
+    /// 普通函数的帧压入调用栈；生成器函数和协程函数的帧交给新建的生成器（协程），生成器压栈。
+    fn enter_function(&mut self, function: Rc<Function>, args: Vec<Value>, keywords: Keywords, cells: &[Cell], class: Option<Rc<Class>>) -> Result<()> {
+        let args = function.bind(args, keywords)?;
+        let mut frame = self.new_frame(function, args);
+        for (&slot, cell) in frame.function.free_vars.iter().zip(cells) {
+            frame.locals[slot] = Value::Cell(cell.clone());
         }
+        frame.class = class;
         
-        // 检查用户定义函数
-        if let Some(function) = self.functions.get(&func_name).cloned() {
-            if args.len() != function.parameters.len() {
-                return Err(VMError::RuntimeError(
-                    format!("Function '{}' expects {} arguments, got {}", 
-                           func_name, function.parameters.len(), args.len())
-                ));
+        if frame.function.generator || frame.function.coroutine {
+            let generator = Generator::new(frame);
+            self.stack.push(Value::Generator(Rc::new(RefCell::new(generator))));
+            return Ok(());
+        }
+        self.enter_frame(frame)
+    }
+    
+    /// 调用类对象：创建实例并以它为 `self` 调用 `__init__`
+    ///
+    /// 没有定义 `__init__` 的异常类使用内置的初始化，见 [`Self::init_exception`]。
+    fn instantiate(&mut self, class: Rc<Class>, mut args: Vec<Value>, keywords: Keywords) -> Result<()> {
+        let instance = Rc::new(Instance::new(class.clone()));
+        match class.lookup("__init__") {
+            Some((_, init)) if init.generator || init.coroutine => Err(VMError::TypeError(format!(
+                "__init__() should return None, not '{}'",
+                if init.generator { "generator" } else { "coroutine" }
+            ))),
+            Some((owner, init)) => {
+                args.insert(0, Value::Instance(instance.clone()));
+                self.call_method_function(owner.clone(), init.clone(), args, keywords)?;
+                self.call_stack.last_mut().unwrap().instance = Some(Value::Instance(instance));
+                Ok(())
             }
-            
-            // 创建新的调用帧
-            let mut locals = vec![Value::Null; function.local_vars.len()];
-            for (i, arg) in args.into_iter().enumerate() {
-                locals[i] = arg;
+            None if !keywords.is_empty() => Err(VMError::TypeError(format!(
+                "{}() takes no keyword arguments",
+                class.name
+            ))),
+            None if self.exceptions.is_exception(&class) => {
+                self.init_exception(&instance, &args)?;
+                self.stack.push(Value::Instance(instance));
+                Ok(())
             }
-            
-            let frame = CallFrame {
-                function,
-                return_address: self.pc,
-                pc: 0,
-                locals,
-            };
-            
-            self.call_stack.push(frame);
-            return Ok(());
+            None if args.is_empty() => {
+                self.stack.push(Value::Instance(instance));
+                Ok(())
+            }
+            None => Err(VMError::TypeError(format!("{}() takes no arguments", class.name))),
         }
-        
-        Err(VMError::FunctionNotFound(func_name))
     }
     
-    /// 处理函数返回
-    fn handle_return(&mut self) -> Result<()> {
-        let return_value = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+    /// 执行 `CALL_METHOD`：栈布局 [receiver, args...]
+    ///
+    /// 实例先查实例属性（按普通值调用），再查类的方法表并绑定 `self`；
+    /// 类对象上取到的方法不绑定；`super()` 代理绑定被代理的实例；
+    /// 其余内置类型使用原生方法。
+    fn handle_call_method(&mut self, name: &str, argc: usize) -> Result<()> {
+        let mut args = self.pop_n(argc)?;
+        let receiver = self.stack.pop().ok_or(VMError::StackUnderflow)?;
         
-        if let Some(frame) = self.call_stack.pop() {
-            self.pc = frame.return_address;
+        match &receiver {
+            Value::Instance(instance) => {
+                if let Some(attribute) = instance.attribute(name) {
+                    return self.call_value(attribute, args, Vec::new());
+                }
+                let (class, method) = instance.class.lookup(name)
+                    .ok_or_else(|| no_attribute(&receiver, name))?;
+                let (class, method) = (class.clone(), method.clone());
+                args.insert(0, receiver.clone());
+                self.call_method_function(class, method, args, Vec::new())
+            }
+            Value::Class(class) => {
+                let (class, method) = class.lookup(name)
+                    .ok_or_else(|| no_attribute(&receiver, name))?;
+                let (class, method) = (class.clone(), method.clone());
+                self.call_method_function(class, method, args, Vec::new())
+            }
+            Value::Module(module) => {
+                let function = module.attribute(name)
+                    .ok_or_else(|| no_attribute(&receiver, name))?;
+                self.call_value(function, args, Vec::new())
+            }
+            Value::Generator(generator) => {
+                let result = self.generator_method(generator, name, &args)?;
+                self.stack.push(result);
+                Ok(())
+            }
+            Value::Future(future) => {
+                let result = self.future_method(future, name, &args)?;
+                self.stack.push(result);
+                Ok(())
+            }
+            Value::Super(proxy) => match proxy.lookup(name) {
+                Some((class, method)) => {
+                    args.insert(0, Value::Instance(proxy.receiver.clone()));
+                    self.call_method_function(class, method, args, Vec::new())
+                }
+                // 内置异常类的 `__init__` 没有字节码实现
+                None if name == "__init__" && self.exceptions.is_exception(&proxy.receiver.class) => {
+                    self.init_exception(&proxy.receiver, &args)?;
+                    self.stack.push(Value::Null);
+                    Ok(())
+                }
+                None => Err(no_attribute(&receiver, name)),
+            },
+            // 元素中有实例时按 `__lt__` 排序
+            Value::List(items) if name == "sort" && args.is_empty()
+                && items.borrow().iter().any(|item| matches!(item, Value::Instance(_))) => {
+                let mut sorted = std::mem::take(&mut *items.borrow_mut());
+                let result = methods::merge_sort(&mut sorted, &mut |a, b| self.less_than(a, b));
+                *items.borrow_mut() = sorted;
+                result?;
+                self.stack.push(Value::Null);
+                Ok(())
+            }
+            _ => {
+                let result = methods::call_method(&receiver, name, &args)
+                    .ok_or_else(|| no_attribute(&receiver, name))??;
+                self.stack.push(result);
+                Ok(())
+            }
         }
-        
-        self.stack.push(return_value);
-        Ok(())
     }
     
-    /// 初始化全局变量
-    fn initialize_globals(&mut self, global_vars: &HashMap<String, usize>) -> Result<()> {
-        // 这里需要执行全局变量的初始化代码
-        // 暂时设置为默认值
-        for (_, &index) in global_vars {
-            if index < self.globals.len() {
-                self.globals[index] = Value::Null;
+    /// 读取属性：实例属性优先，其次是类中的方法（绑定到实例）
+    fn get_attr(&self, object: &Value, name: &str) -> Result<Value> {
+        match object {
+            Value::Instance(instance) => {
+                if let Some(value) = instance.attribute(name) {
+                    return Ok(value);
+                }
+                match instance.class.lookup(name) {
+                    Some((class, function)) => Ok(Value::BoundMethod(Rc::new(BoundMethod {
+                        receiver: object.clone(),
+                        class: class.clone(),
+                        function: function.clone(),
+                    }))),
+                    None => Err(no_attribute(object, name)),
+                }
             }
+            Value::Class(class) => match (name, class.method(name)) {
+                ("__name__", _) => Ok(Value::String(class.name.clone())),
+                ("__bases__", _) => Ok(Value::Tuple(class.bases.iter().cloned().map(Value::Class).collect())),
+                ("__mro__", _) => Ok(Value::Tuple(class.mro().cloned().map(Value::Class).collect())),
+                (_, Some(function)) => Ok(Value::Function(function.clone())),
+                (_, None) => Err(no_attribute(object, name)),
+            },
+            Value::Super(proxy) => match proxy.lookup(name) {
+                Some((class, function)) => Ok(Value::BoundMethod(Rc::new(BoundMethod {
+                    receiver: Value::Instance(proxy.receiver.clone()),
+                    class,
+                    function,
+                }))),
+                None => Err(no_attribute(object, name)),
+            },
+            Value::Function(function) if name == "__name__" => Ok(Value::String(function.name.as_str().into())),
+            Value::Closure(closure) if name == "__name__" => Ok(Value::String(closure.function.name.as_str().into())),
+            Value::Builtin(builtin) if name == "__name__" => Ok(Value::String(builtin.name().into())),
+            Value::Module(module) => match (name, module.attribute(name)) {
+                ("__name__", _) => Ok(Value::String(module.name.clone())),
+                (_, Some(value)) => Ok(value),
+                (_, None) => Err(no_attribute(object, name)),
+            },
+            other => Err(no_attribute(other, name)),
         }
-        Ok(())
     }
     
-    /// 注册内置函数
-    fn register_builtins(&mut self) {
-        self.builtins.insert("print".to_string(), BuiltinFunction::Print);
-        self.builtins.insert("str".to_string(), BuiltinFunction::Str);
-        self.builtins.insert("int".to_string(), BuiltinFunction::Int);
-        self.builtins.insert("float".to_string(), BuiltinFunction::Float);
-        self.builtins.insert("len".to_string(), BuiltinFunction::Len);
+    /// 由编译器生成的方法表（方法名 → 函数名）创建类，方法在此一次性解析
+    fn create_class(&self, name: Rc<str>, bases: Vec<Value>, methods: &Value) -> Result<Class> {
+        let bases = bases.into_iter()
+            .map(|base| match base {
+                Value::Class(class) => Ok(class),
+                other => Err(VMError::TypeError(format!(
+                    "bases must be types, not '{}'",
+                    other.type_name()
+                ))),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        
+        let Value::Dict(methods) = methods else {
+            return Err(VMError::TypeError(format!(
+                "CREATE_CLASS expects a method table, not '{}'",
+                methods.type_name()
+            )));
+        };
+        let mut table = FxHashMap::default();
+        for (method, function) in methods.borrow().iter() {
+            let (Value::String(method), Value::String(function)) = (method, function) else {
+                return Err(VMError::TypeError(format!(
+                    "invalid method table entry {}: {}",
+                    method.repr(),
+                    function.repr()
+                )));
+            };
+            let function = self.module().functions.get(&**function)
+                .ok_or_else(|| VMError::FunctionNotFound(function.to_string()))?;
+            table.insert(method.clone(), function.clone());
+        }
+        Class::new(name, bases, table)
     }
     
-    /// 获取性能统计
-    pub fn get_stats(&self) -> &VMStats {
-        &self.stats
+    /// 内置异常类的初始化：`message` 为唯一参数的字符串形式，多个参数时为参数元组的字符串形式
+    fn init_exception(&mut self, instance: &Instance, args: &[Value]) -> Result<()> {
+        let message = match args {
+            [] => String::new(),
+            [message] => self.render(message, false)?,
+            _ => self.render(&Value::Tuple(args.to_vec().into()), false)?,
+        };
+        instance.set_attribute("message".into(), Value::String(message.into()));
+        // 与 Python 相同，`StopIteration` 的第一个参数是生成器的返回值
+        if instance.class.is_subclass(self.exceptions.get("StopIteration").unwrap()) {
+            instance.set_attribute("value".into(), args.first().cloned().unwrap_or_default());
+        }
+        Ok(())
     }
     
-    /// 重置虚拟机状态
+    /// 创建 `super()` 代理
+    ///
+    /// 无参数时使用当前方法所属的类和第一个参数（`self`），
+    /// 也可以显式写成 `super(类, 实例)`。
+    fn super_proxy(&self, args: &[Value]) -> Result<Value> {
+        let (class, receiver) = match args {
+            [] => {
+                let frame = self.call_stack.last().expect("super() without an active frame");
+                let class = frame.class.clone().ok_or_else(|| {
+                    VMError::RuntimeError("super(): __class__ cell not found".to_string())
+                })?;
+                let receiver = frame.locals.first()
+                    .filter(|_| !frame.function.parameters.is_empty())
+                    .cloned()
+                    .ok_or_else(|| VMError::RuntimeError("super(): no arguments".to_string()))?;
+                (class, receiver)
+            }
+            [Value::Class(class), receiver] => (class.clone(), receiver.clone()),
+            [other, _] => {
+                return Err(VMError::TypeError(format!(
+                    "super() argument 1 must be a type, not {}",
+                    other.type_name()
+                )))
+            }
+            _ => {
+                return Err(VMError::TypeError(format!(
+                    "super() expects 0 or 2 arguments, got {}",
+                    args.len()
+                )))
+            }
+        };
+        
+        match receiver {
+            Value::Instance(instance) if instance.class.is_subclass(&class) => {
+                Ok(Value::Super(Rc::new(Super { class, receiver: instance })))
+            }
+            _ => Err(VMError::TypeError(
+                "super(type, obj): obj must be an instance or subtype of type".to_string(),
+            )),
+        }
+    }
+    
+    /// 操作数引用的字符串常量（属性名、方法名、类名）
+    fn constant_name(&self, index: u32) -> Result<Rc<str>> {
+        match &self.module().constants[index as usize] {
+            Value::String(name) => Ok(name.clone()),
+            other => Err(VMError::TypeError(format!(
+                "attribute name must be string, not '{}'",
+                other.type_name()
+            ))),
+        }
+    }
+    
+    /// 压入新的调用帧，参数依次放入前几个局部变量槽
+    fn push_frame(&mut self, function: Rc<Function>, args: Vec<Value>) -> Result<()> {
+        let frame = self.new_frame(function, args);
+        self.enter_frame(frame)
+    }
+    
+    /// 创建从函数开头执行的调用帧，参数依次放入前几个局部变量槽
+    fn new_frame(&self, function: Rc<Function>, mut args: Vec<Value>) -> CallFrame {
+        args.resize(function.local_vars.len().max(args.len()), Value::Null);
+        
+        // 被内层函数捕获的变量（包括参数）放入单元，闭包与本帧共享它们
+        for &slot in &function.cell_vars {
+            let value = std::mem::take(&mut args[slot]);
+            args[slot] = Value::Cell(Rc::new(RefCell::new(value)));
+        }
+        
+        CallFrame {
+            function,
+            pc: 0,
+            stack_base: self.stack.len(),
+            locals: args,
+            instance: None,
+            class: None,
+            handling: Vec::new(),
+            tail_calls: 0,
+        }
+    }
+    
+    /// 把帧压入调用栈，从帧保存的程序计数器处继续执行
+    fn enter_frame(&mut self, frame: CallFrame) -> Result<()> {
+        // 检查调用栈深度
+        if self.call_stack.len() >= self.config.max_call_depth {
+            return Err(VMError::RuntimeError(exceptions::CALL_STACK_OVERFLOW.to_string()));
+        }
+        
+        // 保存调用者的程序计数器
+        if let Some(caller) = self.call_stack.last_mut() {
+            caller.pc = self.pc;
+        }
+        
+        self.pc = frame.pc;
+        self.call_stack.push(frame);
+        Ok(())
+    }
+    
+    /// 处理函数返回
+    fn handle_return(&mut self) -> Result<()> {
+        let return_value = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+        self.return_through_finally(return_value)
+    }
+    
+    /// 从当前函数返回；`RETURN` 位于带 finally 块的 try/catch 块中时先执行 finally 块
+    fn return_through_finally(&mut self, value: Value) -> Result<()> {
+        let pc = self.pc - 1;
+        let function = self.call_stack.last().unwrap().function.clone();
+        for index in function.handlers.enclosing(pc) {
+            let handler = &function.handlers[index];
+            match (handler.region(pc), handler.finally) {
+                (Some(Region::Finally), _) | (_, None) => continue,
+                (_, Some(finally)) => {
+                    let then = Completion::Return(value);
+                    self.enter_handler(index, Handling::Finally { handler: index, then }, finally);
+                    return Ok(());
+                }
+            }
+        }
+        self.handle_return_value(value)
+    }
+    
+    /// 弹出当前帧，恢复调用者的程序计数器并压入返回值
+    ///
+    /// 主程序帧返回时整个程序结束，返回值被丢弃。
+    /// `__init__` 帧返回时压入被初始化的实例。
+    fn handle_return_value(&mut self, value: Value) -> Result<()> {
+        let frame = self.call_stack.pop().expect("return without an active frame");
+        self.stack.truncate(frame.stack_base);
+        
+        let value = match frame.instance {
+            Some(_) if !matches!(value, Value::Null) => {
+                return Err(VMError::TypeError(format!(
+                    "__init__() should return None, not '{}'",
+                    value.type_name()
+                )));
+            }
+            Some(instance) => instance,
+            None => value,
+        };
+        
+        if let Some(caller) = self.call_stack.last() {
+            self.pc = caller.pc;
+            self.stack.push(value);
+        }
+        Ok(())
+    }
+    
+    /// 当前帧所属的模块
+    #[inline]
+    fn module(&self) -> &Module {
+        let frame = self.call_stack.last().expect("no active frame");
+        &self.modules[frame.function.module]
+    }
+    
+    /// 当前帧槽位中的单元
+    fn cell(&self, slot: u32) -> Result<Cell> {
+        let frame = self.call_stack.last().unwrap();
+        match &frame.locals[slot as usize] {
+            Value::Cell(cell) => Ok(cell.clone()),
+            _ => Err(VMError::RuntimeError(format!(
+                "local slot {} of '{}' does not hold a cell",
+                slot, frame.function.name
+            ))),
+        }
+    }
+    
+    /// 当前是否在模块的顶层代码中执行
+    fn in_main(&self) -> bool {
+        let function = &self.call_stack.last().unwrap().function;
+        Rc::ptr_eq(function, &self.modules[function.module].main)
+    }
+    
+    /// 把主程序指令包装为函数，便于与普通函数统一按帧执行
+    fn main_function(name: String, instructions: Vec<Instruction>, handlers: ExceptionTable, module: usize, id: usize) -> Function {
+        Function {
+            name,
+            parameters: Vec::new(),
+            kwonly_count: 0,
+            varargs: false,
+            varkw: false,
+            defaults: Vec::new(),
+            kwdefaults: HashMap::new(),
+            generator: false,
+            coroutine: false,
+            instructions,
+            local_vars: HashMap::new(),
+            cell_vars: Vec::new(),
+            free_vars: Vec::new(),
+            handlers,
+            module,
+            id,
+        }
+    }
+    
+    /// 由校验过的字节码创建模块并加入模块表
+    ///
+    /// 模块的函数（按名字排序）、常量池中的代码对象和顶层代码依次登记到函数表，
+    /// 所有 `LOAD_FUNC` 的操作数改写为函数表中的编号：函数名先在本模块的函数中查找，
+    /// 其次是内置函数。
+    fn add_module(&mut self, name: &str, bytecode: &Bytecode, mut analysis: bytecode::Analysis) -> Result<Rc<Module>> {
+        let index = self.modules.len();
+        let base = self.functions.len();
+        let mut names: Vec<&String> = bytecode.functions.keys().collect();
+        names.sort();
+        let codes: Vec<usize> = bytecode.constants.iter()
+            .enumerate()
+            .filter(|(_, constant)| matches!(constant, Value::Code(_)))
+            .map(|(constant, _)| constant)
+            .collect();
+        let ids: FxHashMap<&str, usize> = names.iter()
+            .enumerate()
+            .map(|(offset, name)| (name.as_str(), base + offset))
+            .collect();
+        
+        // LOAD_FUNC 的常量操作数 → 函数表编号
+        let resolve = |constant: usize| -> Result<usize> {
+            match &bytecode.constants[constant] {
+                Value::String(name) => ids.get(&**name)
+                    .copied()
+                    .or_else(|| BuiltinFunction::from_name(name).map(BuiltinFunction::id))
+                    .ok_or_else(|| VMError::FunctionNotFound(name.to_string())),
+                _ => Ok(base + names.len() + codes.iter().position(|&code| code == constant).unwrap()),
+            }
+        };
+        let link = |instructions: &[Instruction]| -> Result<Vec<Instruction>> {
+            instructions.iter()
+                .map(|instruction| match instruction.opcode {
+                    OpCode::LoadFunc => Ok(Instruction::new(OpCode::LoadFunc, resolve(instruction.operand as usize)? as u32)),
+                    _ => Ok(*instruction),
+                })
+                .collect()
+        };
+        
+        let mut functions = FxHashMap::default();
+        let mut table = Vec::with_capacity(names.len() + codes.len());
+        for name in &names {
+            let function = &bytecode.functions[*name];
+            let handlers = analysis.functions.remove(*name).unwrap_or_default();
+            let mut instructions = link(&function.instructions)?;
+            bytecode::rewrite_tail_calls(&mut instructions);
+            let id = base + table.len();
+            let function = Rc::new(Function { instructions, handlers, module: index, id, ..function.clone() });
+            functions.insert((*name).clone(), function.clone());
+            table.push(Value::Function(function));
+        }
+        for &constant in &codes {
+            let Value::Code(function) = &bytecode.constants[constant] else { unreachable!() };
+            let handlers = analysis.codes.remove(&constant).unwrap_or_default();
+            let mut instructions = link(&function.instructions)?;
+            bytecode::rewrite_tail_calls(&mut instructions);
+            let id = base + table.len();
+            table.push(Value::Function(Rc::new(Function { instructions, handlers, module: index, id, ..(**function).clone() })));
+        }
+        let main_name = if index == 0 { "<main>".to_string() } else { format!("<module {}>", name) };
+        let main = Rc::new(Self::main_function(main_name, link(&bytecode.instructions)?, analysis.main, index, base + table.len()));
+        table.push(Value::Function(main.clone()));
+        self.functions.extend(table);
+        
+        let module = Rc::new(Module {
+            name: name.into(),
+            constants: bytecode.constants.clone(),
+            globals: RefCell::new(self.initial_globals(&bytecode.global_vars)),
+            global_names: bytecode.global_vars.clone(),
+            functions,
+            main,
+        });
+        self.modules.push(module.clone());
+        Ok(module)
+    }
+    
+    /// 依赖包中的模块导入同一包内的模块时，`lib` 解析为 `包名.lib`
+    fn qualify(&self, name: &str) -> String {
+        let current = &self.module().name;
+        let package = current.split('.').next().unwrap_or_default();
+        if self.packages.contains(package) {
+            let qualified = format!("{}.{}", package, name);
+            if self.registered.contains_key(&qualified) {
+                return qualified;
+            }
+        }
+        name.to_string()
+    }
+    
+    /// 全局变量的初始值
+    ///
+    /// 与内置异常类同名的全局变量预先绑定到该类，便于 `class E(Exception)`
+    /// 和 `isinstance(e, ValueError)` 直接引用；与内置函数同名的全局变量绑定到内置函数。
+    fn initial_globals(&self, global_vars: &HashMap<String, usize>) -> Vec<Value> {
+        let mut globals = vec![Value::Null; global_vars.len()];
+        for (name, &index) in global_vars {
+            let Some(slot) = globals.get_mut(index) else { continue };
+            if let Some(class) = self.exceptions.get(name) {
+                *slot = Value::Class(class.clone());
+            } else if let Some(builtin) = BuiltinFunction::from_name(name) {
+                *slot = Value::Builtin(builtin);
+            }
+        }
+        globals
+    }
+    
+    /// 导入模块：已导入的模块直接从缓存返回，否则依次在注册的模块、内置模块（`asyncio`）
+    /// 和搜索路径中查找，并执行它的顶层代码
+    ///
+    /// 正在执行顶层代码的模块再次被导入时报告循环导入，错误信息包含导入链。
+    /// 顶层代码出错时模块不进入缓存。
+    fn import(&mut self, name: &str) -> Result<Rc<Module>> {
+        let name = &*self.qualify(name);
+        if let Some(module) = self.module_cache.get(name) {
+            return Ok(module.clone());
+        }
+        if let Some(start) = self.importing.iter().position(|importing| importing == name) {
+            let mut chain = self.importing[start..].to_vec();
+            chain.push(name.to_string());
+            return Err(VMError::ImportError(format!("circular import: {}", chain.join(" -> "))));
+        }
+        
+        let bytecode = match self.registered.get(name) {
+            Some(bytecode) => bytecode.clone(),
+            None if name == "asyncio" => return self.asyncio_module(),
+            None => {
+                let search_path = module::search_path(&self.config.module_paths, self.config.stdlib_path.as_deref());
+                let path = module::find(&search_path, name)
+                    .ok_or_else(|| VMError::ModuleNotFound(name.to_string()))?;
+                Rc::new(Bytecode::read_from(&path)?)
+            }
+        };
+        let analysis = bytecode::analyze(&bytecode)?;
+        let module = self.add_module(name, &bytecode, analysis)?;
+        
+        self.importing.push(name.to_string());
+        let result = self.run_call(|vm| vm.push_frame(module.main.clone(), Vec::new()));
+        self.importing.pop();
+        result?;
+        
+        self.module_cache.insert(name.to_string(), module.clone());
+        Ok(module)
+    }
+    
+    /// 按名字读取主程序的全局变量
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.modules[0].global(name)
+    }
+    
+    /// 获取性能统计
+    pub fn get_stats(&self) -> &VMStats {
+        &self.stats
+    }
+    
+    /// 事件循环虚拟时钟的当前时间（秒），循环结束后保留最后的时间
+    pub fn loop_time(&self) -> f64 {
+        self.event_loop.time()
+    }
+    
+    /// 重置虚拟机状态
     pub fn reset(&mut self) {
         self.stack.clear();
         self.call_stack.clear();
         self.pc = 0;
+        self.suspended = None;
+        self.event_loop.clear();
+        self.loop_running = false;
         self.stats = VMStats::default();
     }
 }
 
+/// 二元运算对应的特殊方法及内置实现
+struct Operator {
+    /// 左操作数的特殊方法
+    method: &'static str,
+    
+    /// 左操作数未定义时尝试的右操作数反射方法
+    reflected: &'static str,
+    
+    /// 两边都未定义时对该运算取反（`!=` 默认为 `==` 取反）
+    inverse: Option<&'static Operator>,
+    
+    /// 内置类型的实现
+    native: fn(&Value, &Value) -> Result<Value>,
+}
+
+impl Operator {
+    const fn new(method: &'static str, reflected: &'static str, native: fn(&Value, &Value) -> Result<Value>) -> Self {
+        Self { method, reflected, inverse: None, native }
+    }
+}
+
+const ADD: Operator = Operator::new("__add__", "__radd__", Value::add);
+const SUB: Operator = Operator::new("__sub__", "__rsub__", Value::sub);
+const MUL: Operator = Operator::new("__mul__", "__rmul__", Value::mul);
+const DIV: Operator = Operator::new("__truediv__", "__rtruediv__", Value::div);
+const MOD: Operator = Operator::new("__mod__", "__rmod__", Value::rem);
+const POW: Operator = Operator::new("__pow__", "__rpow__", Value::pow);
+const EQ: Operator = Operator::new("__eq__", "__eq__", |a, b| Ok(Value::Bool(a.equals(b)?)));
+const NE: Operator = Operator {
+    inverse: Some(&EQ),
+    ..Operator::new("__ne__", "__ne__", |a, b| Ok(Value::Bool(!a.equals(b)?)))
+};
+const LT: Operator = Operator::new("__lt__", "__gt__", Value::lt);
+const GT: Operator = Operator::new("__gt__", "__lt__", Value::gt);
+const LE: Operator = Operator::new("__le__", "__ge__", Value::le);
+const GE: Operator = Operator::new("__ge__", "__le__", Value::ge);
+
+/// 特殊方法协议
+///
+/// 只有实例参与时才查找特殊方法，内置类型之间的运算直接使用 [`Value`] 的实现。
+/// 特殊方法在嵌套的执行循环中同步运行。原生的字典方法（`get`、`pop` 等）
+/// 仍按对象标识比较实例键。
+impl AquaVM {
+    /// 同步执行 `call` 发起的调用，返回调用结果
+    ///
+    /// 出错时丢弃这次调用压入的帧和栈内容，调用者可以继续执行。
+    fn run_call(&mut self, call: impl FnOnce(&mut Self) -> Result<()>) -> Result<Value> {
+        let depth = self.call_stack.len();
+        let base = self.stack.len();
+        let pc = self.pc;
+        
+        let result = call(self)
+            .and_then(|()| self.execute(depth))
+            .and_then(|()| self.stack.pop().ok_or(VMError::StackUnderflow));
+        if result.is_err() {
+            self.call_stack.truncate(depth);
+            self.stack.truncate(base);
+            self.pc = pc;
+        }
+        result
+    }
+    
+    /// 调用实例的特殊方法，实例未定义该方法时返回 `None`
+    fn call_special(&mut self, receiver: &Value, name: &str, mut args: Vec<Value>) -> Result<Option<Value>> {
+        let Value::Instance(instance) = receiver else {
+            return Ok(None);
+        };
+        let Some((class, function)) = instance.class.lookup(name) else {
+            return Ok(None);
+        };
+        let (class, function) = (class.clone(), function.clone());
+        args.insert(0, receiver.clone());
+        self.run_call(|vm| vm.call_method_function(class, function, args, Vec::new())).map(Some)
+    }
+    
+    /// 执行二元运算：先试左操作数的特殊方法，再试右操作数的反射方法
+    #[inline]
+    fn binary(&mut self, a: Value, b: Value, op: &Operator) -> Result<Value> {
+        if !matches!(a, Value::Instance(_)) && !matches!(b, Value::Instance(_)) {
+            return (op.native)(&a, &b);
+        }
+        
+        if let Some(result) = self.call_special(&a, op.method, vec![b.clone()])? {
+            return Ok(result);
+        }
+        if let Some(result) = self.call_special(&b, op.reflected, vec![a.clone()])? {
+            return Ok(result);
+        }
+        if let Some(inverse) = op.inverse {
+            let result = self.binary(a, b, inverse)?;
+            return Ok(Value::Bool(!result.is_truthy()));
+        }
+        (op.native)(&a, &b)
+    }
+    
+    /// 相等比较，同一对象总是相等（与 Python 容器的比较规则相同）
+    fn equals(&mut self, a: &Value, b: &Value) -> Result<bool> {
+        if a.equals(b)? {
+            return Ok(true);
+        }
+        if !matches!(a, Value::Instance(_)) && !matches!(b, Value::Instance(_)) {
+            return Ok(false);
+        }
+        Ok(self.binary(a.clone(), b.clone(), &EQ)?.is_truthy())
+    }
+    
+    /// `a < b`，用于排序
+    fn less_than(&mut self, a: &Value, b: &Value) -> Result<bool> {
+        Ok(self.binary(a.clone(), b.clone(), &LT)?.is_truthy())
+    }
+    
+    /// `len()`：实例调用 `__len__`
+    fn length(&mut self, value: &Value) -> Result<usize> {
+        match self.call_special(value, "__len__", Vec::new())? {
+            Some(Value::Int(n)) if n >= 0 => Ok(n as usize),
+            Some(Value::Bool(b)) => Ok(b as usize),
+            Some(Value::Int(_)) => Err(VMError::ValueError("__len__() should return >= 0".to_string())),
+            Some(other) => Err(VMError::TypeError(format!(
+                "'{}' object cannot be interpreted as an integer",
+                other.type_name()
+            ))),
+            None => value.length(),
+        }
+    }
+    
+    /// `str()`（`repr` 为假）或 `repr()`
+    ///
+    /// 实例依次尝试 `__str__`、`__repr__`，容器中的元素按 `repr()` 递归处理。
+    fn render(&mut self, value: &Value, repr: bool) -> Result<String> {
+        let mut out = String::new();
+        self.render_into(value, repr, &mut out, &mut Vec::new())?;
+        Ok(out)
+    }
+    
+    fn render_into(&mut self, value: &Value, repr: bool, out: &mut String, seen: &mut Vec<*const ()>) -> Result<()> {
+        // 自引用的容器显示为 `[...]` / `{...}`
+        let container = match value {
+            Value::List(items) => Some(Rc::as_ptr(items) as *const ()),
+            Value::Dict(dict) => Some(Rc::as_ptr(dict) as *const ()),
+            _ => None,
+        };
+        if let Some(ptr) = container {
+            if seen.contains(&ptr) {
+                out.push_str(if matches!(value, Value::List(_)) { "[...]" } else { "{...}" });
+                return Ok(());
+            }
+            seen.push(ptr);
+        }
+        
+        match value {
+            Value::Instance(_) => {
+                let names: &[&str] = if repr { &["__repr__"] } else { &["__str__", "__repr__"] };
+                let mut text = None;
+                for name in names {
+                    match self.call_special(value, name, Vec::new())? {
+                        Some(Value::String(s)) => text = Some(s.to_string()),
+                        Some(other) => {
+                            return Err(VMError::TypeError(format!(
+                                "{} returned non-string (type {})",
+                                name,
+                                other.type_name()
+                            )))
+                        }
+                        None => continue,
+                    }
+                    break;
+                }
+                let text = text
+                    .or_else(|| self.exception_text(value, repr))
+                    .unwrap_or_else(|| value.to_string());
+                out.push_str(&text);
+            }
+            Value::List(items) => {
+                let items = items.borrow().clone();
+                out.push('[');
+                self.render_items(&items, out, seen)?;
+                out.push(']');
+            }
+            Value::Tuple(items) => {
+                out.push('(');
+                self.render_items(items, out, seen)?;
+                if items.len() == 1 {
+                    out.push(',');
+                }
+                out.push(')');
+            }
+            Value::Dict(dict) => {
+                let entries: Vec<(Value, Value)> = dict.borrow().iter()
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect();
+                out.push('{');
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    self.render_into(key, true, out, seen)?;
+                    out.push_str(": ");
+                    self.render_into(value, true, out, seen)?;
+                }
+                out.push('}');
+            }
+            other if repr => out.push_str(&other.repr()),
+            other => out.push_str(&other.to_string()),
+        }
+        
+        if container.is_some() {
+            seen.pop();
+        }
+        Ok(())
+    }
+    
+    fn render_items(&mut self, items: &[Value], out: &mut String, seen: &mut Vec<*const ()>) -> Result<()> {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            self.render_into(item, true, out, seen)?;
+        }
+        Ok(())
+    }
+    
+    /// 没有定义 `__str__`/`__repr__` 的异常实例按 Python 的方式显示：
+    /// `str()` 为 `message`，`repr()` 为 `类名('message')`
+    fn exception_text(&self, value: &Value, repr: bool) -> Option<String> {
+        let Value::Instance(instance) = value else {
+            return None;
+        };
+        if !self.exceptions.is_exception(&instance.class) {
+            return None;
+        }
+        let message = instance.attribute("message")?;
+        Some(match (repr, &message) {
+            (false, Value::String(message)) => message.to_string(),
+            (true, Value::String(text)) if text.is_empty() => format!("{}()", instance.class.name),
+            _ => format!("{}({})", instance.class.name, message.repr()),
+        })
+    }
+    
+    /// 字典键的哈希值：实例调用 `__hash__`，定义了 `__eq__` 而没有 `__hash__` 的实例不可哈希
+    fn hash_value(&mut self, value: &Value) -> Result<u64> {
+        self.special_hash(value)?.unwrap_or_else(|| value.clone()).hash_key()
+    }
+    
+    /// 实例定义的哈希值（整数），未定义 `__hash__` 时返回 `None`
+    fn special_hash(&mut self, value: &Value) -> Result<Option<Value>> {
+        let Value::Instance(instance) = value else {
+            return Ok(None);
+        };
+        match self.call_special(value, "__hash__", Vec::new())? {
+            Some(Value::Int(hash)) => Ok(Some(Value::Int(hash))),
+            Some(Value::Bool(hash)) => Ok(Some(Value::Int(hash as i64))),
+            Some(_) => Err(VMError::TypeError("__hash__ method should return an integer".to_string())),
+            None if instance.class.method("__eq__").is_some() => Err(VMError::TypeError(format!(
+                "unhashable type: '{}'",
+                instance.class.name
+            ))),
+            None => Ok(None),
+        }
+    }
+    
+    /// 查找字典键，返回哈希值和条目位置；比较键时可能调用 `__eq__`
+    fn dict_find(&mut self, dict: &Rc<RefCell<Dict>>, key: &Value) -> Result<(u64, Option<usize>)> {
+        let hash = self.hash_value(key)?;
+        let candidates = dict.borrow().candidates(hash).to_vec();
+        for position in candidates {
+            let candidate = dict.borrow().entry_at(position).map(|(candidate, _)| candidate.clone());
+            if let Some(candidate) = candidate {
+                if self.equals(&candidate, key)? {
+                    return Ok((hash, Some(position)));
+                }
+            }
+        }
+        Ok((hash, None))
+    }
+    
+    /// 写入字典，键不是实例时直接使用 [`Dict::insert`]
+    fn dict_insert(&mut self, dict: &Rc<RefCell<Dict>>, key: Value, value: Value) -> Result<()> {
+        if !matches!(key, Value::Instance(_)) {
+            dict.borrow_mut().insert(key, value)?;
+            return Ok(());
+        }
+        let (hash, position) = self.dict_find(dict, &key)?;
+        dict.borrow_mut().insert_at(position, hash, key, value);
+        Ok(())
+    }
+    
+    /// 下标读取：实例调用 `__getitem__`
+    fn get_item(&mut self, container: &Value, index: &Value) -> Result<Value> {
+        match (container, index) {
+            (Value::Instance(_), _) => {
+                match self.call_special(container, "__getitem__", vec![index.clone()])? {
+                    Some(value) => Ok(value),
+                    None => container.get_item(index),
+                }
+            }
+            (Value::Dict(dict), Value::Instance(_)) => {
+                let (_, position) = self.dict_find(dict, index)?;
+                match position.and_then(|i| dict.borrow().entry_at(i).map(|(_, value)| value.clone())) {
+                    Some(value) => Ok(value),
+                    None => Err(VMError::KeyError(self.render(index, true)?)),
+                }
+            }
+            _ => container.get_item(index),
+        }
+    }
+    
+    /// 下标赋值：实例调用 `__setitem__`
+    fn set_item(&mut self, container: &Value, index: Value, value: Value) -> Result<()> {
+        match (container, &index) {
+            (Value::Instance(_), _) => {
+                match self.call_special(container, "__setitem__", vec![index.clone(), value.clone()])? {
+                    Some(_) => Ok(()),
+                    None => container.set_item(&index, value),
+                }
+            }
+            (Value::Dict(dict), Value::Instance(_)) => self.dict_insert(dict, index, value),
+            _ => container.set_item(&index, value),
+        }
+    }
+    
+    /// `item in container`：实例先用 `__contains__`，再退回到遍历 `__iter__`
+    fn contains(&mut self, container: &Value, item: &Value) -> Result<bool> {
+        match (container, item) {
+            (Value::Instance(instance), _) => {
+                if let Some(result) = self.call_special(container, "__contains__", vec![item.clone()])? {
+                    return Ok(result.is_truthy());
+                }
+                if instance.class.method("__iter__").is_none() {
+                    return container.contains(item);
+                }
+                let iterator = self.get_iter(container)?;
+                while let Some(element) = self.next_item(&iterator)? {
+                    if self.equals(&element, item)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            (Value::List(_) | Value::Tuple(_), Value::Instance(_)) => {
+                let iterator = container.iter()?;
+                while let Some(element) = self.next_item(&iterator)? {
+                    if self.equals(&element, item)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            (Value::Dict(dict), Value::Instance(_)) => Ok(self.dict_find(dict, item)?.1.is_some()),
+            _ => container.contains(item),
+        }
+    }
+    
+    /// `iter()`：实例调用 `__iter__`，返回值必须是迭代器
+    fn get_iter(&mut self, iterable: &Value) -> Result<Value> {
+        let Some(iterator) = self.call_special(iterable, "__iter__", Vec::new())? else {
+            return iterable.iter();
+        };
+        match &iterator {
+            Value::Iterator(_) | Value::Generator(_) => Ok(iterator),
+            Value::Instance(instance) if instance.class.method("__next__").is_some() => Ok(iterator),
+            other => Err(VMError::TypeError(format!(
+                "iter() returned non-iterator of type '{}'",
+                other.type_name()
+            ))),
+        }
+    }
+    
+    /// 取迭代器的下一个元素：实例调用 `__next__`，生成器恢复执行，`StopIteration` 表示耗尽
+    fn next_item(&mut self, iterator: &Value) -> Result<Option<Value>> {
+        match iterator {
+            Value::Iterator(iter) => iter.borrow_mut().next_item(),
+            Value::Instance(instance) if instance.class.method("__next__").is_some() => {
+                match self.call_special(iterator, "__next__", Vec::new()) {
+                    Ok(value) => Ok(value),
+                    Err(e) if self.exceptions.is_stop_iteration(&e) => Ok(None),
+                    Err(e) => Err(e),
+                }
+            }
+            Value::Generator(generator) if !generator.borrow().coroutine => match self.resume(generator, Resume::Send(Value::Null)) {
+                Ok(Step::Yielded(value)) => Ok(Some(value)),
+                Ok(Step::Returned(_)) | Err(VMError::StopIteration) => Ok(None),
+                Err(e) => Err(e),
+            },
+            other => Err(VMError::TypeError(format!(
+                "'{}' object is not an iterator",
+                other.type_name()
+            ))),
+        }
+    }
+    
+    /// 调用内置函数，涉及实例时先经过特殊方法协议
+    fn call_builtin(&mut self, builtin: BuiltinFunction, args: Vec<Value>) -> Result<Value> {
+        match (builtin, args.as_slice()) {
+            (BuiltinFunction::Print, _) => {
+                let texts = args.iter()
+                    .map(|arg| Ok(Value::String(self.render(arg, false)?.into())))
+                    .collect::<Result<Vec<_>>>()?;
+                builtin.call(&texts)
+            }
+            (BuiltinFunction::Str, [value]) => Ok(Value::String(self.render(value, false)?.into())),
+            (BuiltinFunction::Repr, [value]) => Ok(Value::String(self.render(value, true)?.into())),
+            (BuiltinFunction::Len, [value]) => Ok(Value::Int(self.length(value)? as i64)),
+            (BuiltinFunction::Iter, [value]) => self.get_iter(value),
+            (BuiltinFunction::Hash, [value @ Value::Instance(_)]) => match self.special_hash(value)? {
+                Some(hash) => Ok(hash),
+                None => builtin.call(&args),
+            },
+            (BuiltinFunction::Next, [iterator @ (Value::Instance(_) | Value::Generator(_)), default @ ..]) if default.len() <= 1 => {
+                match (self.next_item(iterator)?, default.first()) {
+                    (Some(value), _) => Ok(value),
+                    (None, Some(default)) => Ok(default.clone()),
+                    (None, None) => Err(VMError::StopIteration),
+                }
+            }
+            _ => builtin.call(&args),
+        }
+    }
+}
+
+/// 生成器
+///
+/// 生成器的帧只在恢复执行期间位于调用栈上：[`AquaVM::resume`] 把帧和它保存的操作数栈内容
+/// 放回虚拟机并在嵌套的执行循环中运行，`YIELD_VALUE` 把它们取下交还给生成器。
+/// 生成器中逃逸的 `StopIteration` 与 Python 一样转换为 `RuntimeError`。
+impl AquaVM {
+    /// 恢复生成器执行，直到它产出值、返回或抛出异常
+    ///
+    /// 停在 `yield from` 上的生成器先把抛入的异常交给委托的子生成器：子生成器产出值时
+    /// 整体仍然挂起，子生成器返回时 `yield from` 以返回值结束，子生成器的异常继续抛入本生成器。
+    fn resume(&mut self, generator: &Rc<RefCell<Generator>>, resume: Resume) -> Result<Step> {
+        let resume = match (resume, Self::delegate(generator)) {
+            (Resume::Throw(exception), Some(delegate)) => match self.resume(&delegate, Resume::Throw(exception)) {
+                Ok(Step::Yielded(value)) => return Ok(Step::Yielded(value)),
+                Ok(Step::Returned(value)) => {
+                    let mut generator = generator.borrow_mut();
+                    generator.stack.pop();
+                    generator.frame.as_mut().unwrap().pc += 1;
+                    Resume::Send(value)
+                }
+                Err(VMError::Exception(exception)) => Resume::Throw(exception),
+                Err(error) => match self.exceptions.from_error(&error, self.traceback()) {
+                    Some(exception) => Resume::Throw(exception),
+                    None => return Err(error),
+                },
+            },
+            (resume, _) => resume,
+        };
+        
+        let (mut frame, saved, started) = {
+            let mut generator = generator.borrow_mut();
+            match (generator.state, &resume) {
+                (GeneratorState::Running, _) => {
+                    return Err(VMError::ValueError("generator already executing".to_string()));
+                }
+                (GeneratorState::Closed, Resume::Send(_)) => return Err(VMError::StopIteration),
+                (GeneratorState::Closed, Resume::Throw(exception)) => {
+                    return Err(VMError::Exception(exception.clone()));
+                }
+                (GeneratorState::Created, Resume::Send(value)) if !matches!(value, Value::Null) => {
+                    return Err(VMError::TypeError(
+                        "can't send non-None value to a just-started generator".to_string()
+                    ));
+                }
+                // 尚未开始的生成器不执行函数体，直接结束并抛出异常
+                (GeneratorState::Created, Resume::Throw(exception)) => {
+                    generator.state = GeneratorState::Closed;
+                    generator.frame = None;
+                    return Err(VMError::Exception(exception.clone()));
+                }
+                _ => {}
+            }
+            let started = generator.state == GeneratorState::Suspended;
+            generator.state = GeneratorState::Running;
+            let frame = generator.frame.take().expect("suspended generator keeps its frame");
+            (frame, std::mem::take(&mut generator.stack), started)
+        };
+        
+        let depth = self.call_stack.len();
+        let base = self.stack.len();
+        let pc = self.pc;
+        frame.stack_base = base;
+        self.stack.extend(saved);
+        let result = self.enter_frame(frame)
+            .and_then(|()| match resume {
+                Resume::Send(value) if started => {
+                    self.stack.push(value);
+                    Ok(())
+                }
+                Resume::Send(_) => Ok(()),
+                Resume::Throw(exception) => self.unwind(VMError::Exception(exception), depth),
+            })
+            .and_then(|()| self.execute(depth))
+            .and_then(|()| self.stack.pop().ok_or(VMError::StackUnderflow));
+        
+        let mut generator = generator.borrow_mut();
+        match (result, self.suspended.take()) {
+            (Ok(value), Some((frame, stack))) => {
+                generator.state = GeneratorState::Suspended;
+                generator.frame = Some(frame);
+                generator.stack = stack;
+                Ok(Step::Yielded(value))
+            }
+            (Ok(value), None) => {
+                generator.state = GeneratorState::Closed;
+                Ok(Step::Returned(value))
+            }
+            (Err(error), _) => {
+                generator.state = GeneratorState::Closed;
+                self.call_stack.truncate(depth);
+                self.stack.truncate(base);
+                self.pc = pc;
+                if self.exceptions.is_stop_iteration(&error) {
+                    return Err(VMError::RuntimeError("generator raised StopIteration".to_string()));
+                }
+                Err(error)
+            }
+        }
+    }
+    
+    /// 停在 `YIELD_FROM` 处的生成器所委托的子生成器
+    fn delegate(generator: &Rc<RefCell<Generator>>) -> Option<Rc<RefCell<Generator>>> {
+        let generator = generator.borrow();
+        let frame = generator.frame.as_ref().filter(|_| generator.state == GeneratorState::Suspended)?;
+        match (frame.function.instructions[frame.pc].opcode, generator.stack.last()) {
+            (OpCode::YieldFrom, Some(Value::Generator(delegate))) => Some(delegate.clone()),
+            _ => None,
+        }
+    }
+    
+    /// 挂起当前的生成器帧：帧和它的操作数栈内容留给 [`Self::resume`] 取回，
+    /// 产出的值压入恢复者的栈
+    fn suspend(&mut self, value: Value) {
+        let mut frame = self.call_stack.pop().expect("yield without an active frame");
+        let stack = self.stack.split_off(frame.stack_base);
+        frame.pc = self.pc;
+        self.suspended = Some((frame, stack));
+        self.pc = self.call_stack.last().expect("generators are resumed from a frame").pc;
+        self.stack.push(value);
+    }
+    
+    /// `YIELD_FROM`：把发送的值转发给栈上的子迭代器
+    ///
+    /// 子迭代器产出值时，生成器停在本指令处挂起，恢复后带着新发送的值重新执行本指令；
+    /// 子迭代器结束时弹出它并压入它的返回值。子生成器之外的迭代器只能接收 `None`。
+    /// `await` 的 Future 尚未完成时挂起并产出它本身。
+    fn handle_yield_from(&mut self) -> Result<()> {
+        let sent = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+        let iterator = self.stack.last().cloned().ok_or(VMError::StackUnderflow)?;
+        let step = match &iterator {
+            Value::Generator(generator) => match self.resume(generator, Resume::Send(sent)) {
+                Err(VMError::StopIteration) => Step::Returned(Value::Null),
+                step => step?,
+            },
+            // 未完成的 Future 一直交给驱动协程的任务，完成后以它的结果结束
+            Value::Future(future) => match &future.borrow().state {
+                FutureState::Pending => Step::Yielded(iterator.clone()),
+                FutureState::Done(value) => Step::Returned(value.clone()),
+                FutureState::Failed(exception) => return Err(VMError::Exception(exception.clone())),
+                FutureState::Cancelled => return Err(VMError::Exception(self.event_loop.cancelled_error())),
+            },
+            _ if matches!(sent, Value::Null) => match self.next_item(&iterator)? {
+                Some(value) => Step::Yielded(value),
+                None => Step::Returned(Value::Null),
+            },
+            other => return Err(no_attribute(other, "send")),
+        };
+        match step {
+            Step::Yielded(value) => {
+                self.pc -= 1;
+                self.suspend(value);
+            }
+            Step::Returned(value) => {
+                self.stack.pop();
+                self.stack.push(value);
+            }
+        }
+        Ok(())
+    }
+    
+    /// 生成器的方法：`send(value)`、`close()` 和 `__next__()`
+    fn generator_method(&mut self, generator: &Rc<RefCell<Generator>>, name: &str, args: &[Value]) -> Result<Value> {
+        match (name, args) {
+            ("send", [value]) => self.send(generator, Resume::Send(value.clone())),
+            ("__next__", _) if generator.borrow().coroutine => {
+                Err(no_attribute(&Value::Generator(generator.clone()), name))
+            }
+            ("__next__", []) => self.send(generator, Resume::Send(Value::Null)),
+            ("close", []) => {
+                self.close(generator)?;
+                Ok(Value::Null)
+            }
+            ("send", _) => Err(VMError::TypeError(format!(
+                "generator.send() takes exactly one argument ({} given)",
+                args.len()
+            ))),
+            ("__next__" | "close", _) => Err(VMError::TypeError(format!(
+                "generator.{}() takes no arguments ({} given)",
+                name,
+                args.len()
+            ))),
+            _ => Err(no_attribute(&Value::Generator(generator.clone()), name)),
+        }
+    }
+    
+    /// 恢复生成器并返回产出的值；生成器返回时抛出带返回值的 `StopIteration`
+    fn send(&mut self, generator: &Rc<RefCell<Generator>>, resume: Resume) -> Result<Value> {
+        match self.resume(generator, resume)? {
+            Step::Yielded(value) => Ok(value),
+            Step::Returned(value) => {
+                Err(VMError::Exception(self.exceptions.stop_iteration(value, self.traceback())))
+            }
+        }
+    }
+    
+    /// `close()`：在挂起处抛出 `GeneratorExit`，先关闭 `yield from` 委托的子生成器
+    ///
+    /// 生成器因此结束或再次抛出 `GeneratorExit` 都视为正常关闭；继续产出值是错误。
+    fn close(&mut self, generator: &Rc<RefCell<Generator>>) -> Result<()> {
+        {
+            let mut generator = generator.borrow_mut();
+            match generator.state {
+                GeneratorState::Suspended => {}
+                GeneratorState::Running => {
+                    return Err(VMError::ValueError("generator already executing".to_string()));
+                }
+                GeneratorState::Created | GeneratorState::Closed => {
+                    generator.state = GeneratorState::Closed;
+                    generator.frame = None;
+                    return Ok(());
+                }
+            }
+        }
+        if let Some(delegate) = Self::delegate(generator) {
+            self.close(&delegate)?;
+        }
+        
+        let exit = self.exceptions.new_instance("GeneratorExit", String::new(), self.traceback());
+        match self.resume(generator, Resume::Throw(exit)) {
+            Ok(Step::Yielded(_)) => Err(VMError::RuntimeError("generator ignored GeneratorExit".to_string())),
+            Ok(Step::Returned(_)) => Ok(()),
+            Err(VMError::Exception(exception)) if exception_matches(&exception, "GeneratorExit") => Ok(()),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+/// 协程与事件循环
+///
+/// 任务每执行一步就在调用栈之上恢复一次它的协程（见 [`AquaVM::resume`]）。协程 `await`
+/// 未完成的 Future 时一路挂起到任务，任务把自己登记为 Future 的等待者；
+/// Future、定时器和就绪队列由 [`EventLoop`] 管理。
+impl AquaVM {
+    /// 调用 `asyncio` 模块的函数
+    fn call_asyncio(&mut self, builtin: BuiltinFunction, args: Vec<Value>, keywords: Keywords) -> Result<Value> {
+        if builtin == BuiltinFunction::Run {
+            let [coroutine] = &bind_asyncio(builtin, args, keywords, &["main"], 1)?[..] else { unreachable!() };
+            return self.asyncio_run(coroutine);
+        }
+        if !self.loop_running {
+            return Err(VMError::RuntimeError("no running event loop".to_string()));
+        }
+        
+        let future = match builtin {
+            BuiltinFunction::Sleep => {
+                let [delay, result] = &bind_asyncio(builtin, args, keywords, &["delay", "result"], 1)?[..] else { unreachable!() };
+                let delay = seconds(builtin, delay)?;
+                self.event_loop.sleep(delay, result.clone())
+            }
+            BuiltinFunction::CreateTask => {
+                let [coroutine] = &bind_asyncio(builtin, args, keywords, &["coro"], 1)?[..] else { unreachable!() };
+                match coroutine {
+                    Value::Generator(generator) if generator.borrow().coroutine => {
+                        self.event_loop.create_task(generator.clone())
+                    }
+                    other => return Err(VMError::TypeError(format!(
+                        "a coroutine was expected, got {}",
+                        other.repr()
+                    ))),
+                }
+            }
+            BuiltinFunction::Gather => {
+                let mut return_exceptions = false;
+                for (name, value) in keywords {
+                    match &*name {
+                        "return_exceptions" => return_exceptions = value.is_truthy(),
+                        _ => return Err(VMError::TypeError(format!(
+                            "gather() got an unexpected keyword argument '{}'",
+                            name
+                        ))),
+                    }
+                }
+                let children = args.iter()
+                    .map(|awaitable| self.ensure_future(awaitable))
+                    .collect::<Result<Vec<_>>>()?;
+                self.event_loop.gather(children, return_exceptions)
+            }
+            BuiltinFunction::WaitFor => {
+                let [awaitable, timeout] = &bind_asyncio(builtin, args, keywords, &["fut", "timeout"], 2)?[..] else { unreachable!() };
+                let inner = self.ensure_future(awaitable)?;
+                match timeout {
+                    Value::Null => inner,
+                    timeout => {
+                        let timeout = seconds(builtin, timeout)?;
+                        self.event_loop.wait_for(inner, timeout)
+                    }
+                }
+            }
+            _ => unreachable!("{} is not an asyncio function", builtin.name()),
+        };
+        Ok(Value::Future(future))
+    }
+    
+    /// 可等待对象对应的 Future：协程包装为新任务
+    fn ensure_future(&mut self, awaitable: &Value) -> Result<FutureRef> {
+        match awaitable {
+            Value::Future(future) => Ok(future.clone()),
+            Value::Generator(generator) if generator.borrow().coroutine => {
+                Ok(self.event_loop.create_task(generator.clone()))
+            }
+            _ => Err(VMError::TypeError(
+                "An asyncio.Future, a coroutine or an awaitable is required".to_string()
+            )),
+        }
+    }
+    
+    /// `asyncio.run(coro)`：在新的事件循环中运行协程直到它结束，返回它的结果
+    ///
+    /// 协程结束后取消其余未完成的任务，并等待它们处理完 `CancelledError`。
+    /// 虚拟时钟从 0 开始，循环结束后保留最后的时间（见 [`Self::loop_time`]）。
+    fn asyncio_run(&mut self, coroutine: &Value) -> Result<Value> {
+        if self.loop_running {
+            return Err(VMError::RuntimeError(
+                "asyncio.run() cannot be called from a running event loop".to_string()
+            ));
+        }
+        let Value::Generator(generator) = coroutine else {
+            return Err(VMError::ValueError(format!("a coroutine was expected, got {}", coroutine.repr())));
+        };
+        if !generator.borrow().coroutine {
+            return Err(VMError::ValueError(format!("a coroutine was expected, got {}", coroutine.repr())));
+        }
+        
+        self.event_loop = EventLoop::new(self.exceptions.clone());
+        self.loop_running = true;
+        let main = self.event_loop.create_task(generator.clone());
+        let result = self.run_until(&main).and_then(|()| {
+            let pending = self.event_loop.pending_tasks();
+            for task in &pending {
+                self.event_loop.cancel(task);
+            }
+            let all = self.event_loop.gather(pending, true);
+            self.run_until(&all)
+        });
+        self.loop_running = false;
+        self.event_loop.clear();
+        result?;
+        
+        let state = main.borrow().state.clone();
+        match state {
+            FutureState::Done(value) => Ok(value),
+            FutureState::Failed(exception) => Err(VMError::Exception(exception)),
+            FutureState::Cancelled | FutureState::Pending => {
+                Err(VMError::Exception(self.event_loop.cancelled_error()))
+            }
+        }
+    }
+    
+    /// 运行事件循环直到 `future` 完成：依次执行就绪的任务，就绪队列为空时触发下一批定时器
+    fn run_until(&mut self, future: &FutureRef) -> Result<()> {
+        while future.borrow().is_pending() {
+            match self.event_loop.next_ready() {
+                Some(task) => self.step_task(&task)?,
+                None if self.event_loop.fire_timers() => {}
+                None => return Err(VMError::RuntimeError(
+                    "Event loop stopped before Future completed.".to_string()
+                )),
+            }
+        }
+        Ok(())
+    }
+    
+    /// 执行任务的一步：恢复它的协程，直到协程等待未完成的 Future、返回或抛出异常
+    ///
+    /// 请求了取消的任务向协程抛出 `CancelledError`；逃逸出协程的 `CancelledError`
+    /// 使任务以取消结束。脚本无法处理的内部错误中止整个事件循环。
+    fn step_task(&mut self, task: &FutureRef) -> Result<()> {
+        let (coroutine, resume) = {
+            let mut task = task.borrow_mut();
+            let FutureKind::Task(state) = &mut task.kind else { unreachable!("only tasks are scheduled") };
+            let resume = match std::mem::take(&mut state.cancel_requested) {
+                true => Resume::Throw(self.event_loop.cancelled_error()),
+                false => Resume::Send(Value::Null),
+            };
+            (state.coroutine.clone(), resume)
+        };
+        
+        let state = match self.resume(&coroutine, resume) {
+            Ok(Step::Yielded(Value::Future(future))) => {
+                self.event_loop.wait(task, future);
+                return Ok(());
+            }
+            Ok(Step::Yielded(other)) => {
+                self.close(&coroutine)?;
+                let message = format!("Task got bad yield: {}", other.repr());
+                FutureState::Failed(self.exceptions.new_instance("RuntimeError", message, self.traceback()))
+            }
+            Ok(Step::Returned(value)) => FutureState::Done(value),
+            Err(VMError::Exception(exception)) if exception_matches(&exception, "CancelledError") => {
+                FutureState::Cancelled
+            }
+            Err(VMError::Exception(exception)) => FutureState::Failed(exception),
+            Err(error) => match self.exceptions.from_error(&error, self.traceback()) {
+                Some(exception) => FutureState::Failed(exception),
+                None => return Err(error),
+            },
+        };
+        self.event_loop.complete(task, state);
+        Ok(())
+    }
+    
+    /// Future 和任务的方法：`done()`、`cancelled()`、`result()`、`exception()` 和 `cancel()`
+    fn future_method(&mut self, future: &FutureRef, name: &str, args: &[Value]) -> Result<Value> {
+        if !args.is_empty() && matches!(name, "done" | "cancelled" | "result" | "exception" | "cancel") {
+            return Err(VMError::TypeError(format!(
+                "{}.{}() takes no arguments ({} given)",
+                future.borrow().type_name(),
+                name,
+                args.len()
+            )));
+        }
+        let state = future.borrow().state.clone();
+        match (name, state) {
+            ("done", state) => Ok(Value::Bool(!matches!(state, FutureState::Pending))),
+            ("cancelled", state) => Ok(Value::Bool(matches!(state, FutureState::Cancelled))),
+            ("cancel", _) => Ok(Value::Bool(self.event_loop.cancel(future))),
+            ("result" | "exception", FutureState::Cancelled) => {
+                Err(VMError::Exception(self.event_loop.cancelled_error()))
+            }
+            ("result" | "exception", FutureState::Pending) => Err(VMError::RuntimeError(format!(
+                "{} is not set.",
+                if name == "result" { "Result" } else { "Exception" }
+            ))),
+            ("result", FutureState::Done(value)) => Ok(value),
+            ("result", FutureState::Failed(exception)) => Err(VMError::Exception(exception)),
+            ("exception", FutureState::Done(_)) => Ok(Value::Null),
+            ("exception", FutureState::Failed(exception)) => Ok(exception),
+            _ => Err(no_attribute(&Value::Future(future.clone()), name)),
+        }
+    }
+    
+    /// 内置的 `asyncio` 模块：事件循环的函数以及 `CancelledError`、`TimeoutError`
+    fn asyncio_module(&mut self) -> Result<Rc<Module>> {
+        let names = BuiltinFunction::ASYNCIO.iter()
+            .map(BuiltinFunction::name)
+            .chain(["CancelledError", "TimeoutError"]);
+        let bytecode = Bytecode {
+            global_vars: names.enumerate().map(|(slot, name)| (name.to_string(), slot)).collect(),
+            ..Bytecode::default()
+        };
+        let module = self.add_module("asyncio", &bytecode, bytecode::Analysis::default())?;
+        for builtin in BuiltinFunction::ASYNCIO {
+            module.set_global(builtin.name(), Value::Builtin(builtin));
+        }
+        self.module_cache.insert("asyncio".to_string(), module.clone());
+        Ok(module)
+    }
+}
+
+/// 异常处理
+///
+/// 每个函数在加载时生成异常处理表，执行期按程序计数器查表。运行时错误在展开前
+/// 转换为内置异常类的实例（见 [`crate::exceptions`]），无人处理时仍以原来的错误返回。调用帧的 `handling`
+/// 记录正在执行的 catch/finally 块；通过 `break` 等跳转离开的块在下次转入同一语句
+/// 或外层语句的处理块时被清理。
+impl AquaVM {
+    /// 转入第 `index` 个处理器的 catch 或 finally 块：丢弃该语句及内层语句的处理状态，
+    /// 把操作数栈截断到进入语句时的高度
+    fn enter_handler(&mut self, index: usize, handling: Handling, target: usize) {
+        let frame = self.call_stack.last_mut().unwrap();
+        let depth = frame.stack_base + frame.function.handlers[index].depth;
+        frame.handling.retain(|active| active.handler() < index);
+        frame.handling.push(handling);
+        self.stack.truncate(depth);
+        self.pc = target;
+    }
+    
+    /// `TRY_END`/`CATCH_END`：try 块或 catch 块正常结束，进入 finally 块或跳到语句之后
+    fn finish_block(&mut self) {
+        let pc = self.pc - 1;
+        let function = self.call_stack.last().unwrap().function.clone();
+        let index = function.handlers.enclosing(pc)
+            .next()
+            .expect("exception table covers every try statement");
+        let handler = &function.handlers[index];
+        match handler.finally {
+            Some(finally) => {
+                let then = Completion::Normal;
+                self.enter_handler(index, Handling::Finally { handler: index, then }, finally);
+            }
+            None => {
+                self.call_stack.last_mut().unwrap().handling.retain(|active| active.handler() < index);
+                self.pc = handler.end;
+            }
+        }
+    }
+    
+    /// `FINALLY_END`：继续 finally 块之前被打断的动作
+    fn finish_finally(&mut self) -> Result<()> {
+        let pc = self.pc - 1;
+        let frame = self.call_stack.last_mut().unwrap();
+        let index = frame.function.handlers.enclosing(pc)
+            .next()
+            .expect("exception table covers every try statement");
+        frame.handling.retain(|active| active.handler() <= index);
+        match frame.handling.pop() {
+            Some(Handling::Finally { handler, then }) if handler == index => match then {
+                Completion::Normal => Ok(()),
+                Completion::Raise(exception) => Err(VMError::Exception(exception)),
+                Completion::Return(value) => self.return_through_finally(value),
+            },
+            _ => Err(VMError::RuntimeError("FINALLY_END outside a finally block".to_string())),
+        }
+    }
+    
+    /// 从发生错误的指令开始逐帧寻找处理器，最多展开到第 `depth` 层调用帧
+    ///
+    /// 内部错误先转换为内置异常类的实例，不能转换的错误直接返回。
+    /// 在 try 块中先找类型匹配的 catch 子句，其次在 try/catch 块中执行 finally 块，
+    /// 异常在 finally 块结束时继续传播。没有处理器时返回原来的错误。
+    fn unwind(&mut self, error: VMError, depth: usize) -> Result<()> {
+        let exception = match &error {
+            VMError::Exception(exception) => exception.clone(),
+            other => match self.exceptions.from_error(other, self.traceback()) {
+                Some(exception) => exception,
+                None => return Err(error),
+            },
+        };
+        
+        loop {
+            // 调用者帧保存的程序计数器指向调用指令之后
+            let pc = self.pc.saturating_sub(1);
+            let function = self.call_stack.last().unwrap().function.clone();
+            for index in function.handlers.enclosing(pc) {
+                let handler = &function.handlers[index];
+                let region = handler.region(pc);
+                if region == Some(Region::Try) {
+                    if let Some(catch) = handler.find_catch(|name| exception_matches(&exception, name)) {
+                        let (target, exception) = (catch.start, exception.clone());
+                        self.enter_handler(index, Handling::Catch { handler: index, exception }, target);
+                        return Ok(());
+                    }
+                }
+                if let (Some(finally), false) = (handler.finally, region == Some(Region::Finally)) {
+                    let then = Completion::Raise(exception.clone());
+                    self.enter_handler(index, Handling::Finally { handler: index, then }, finally);
+                    return Ok(());
+                }
+            }
+            
+            if self.call_stack.len() <= depth + 1 {
+                return Err(error);
+            }
+            let frame = self.call_stack.pop().unwrap();
+            self.stack.truncate(frame.stack_base);
+            self.pc = self.call_stack.last().unwrap().pc;
+        }
+    }
+    
+    /// 当前调用栈的描述，外层在前，每帧为 `函数名 at pc N`
+    ///
+    /// 被尾调用替换掉的帧在替换它们的帧之前显示为 `(N tail calls elided)`。
+    fn traceback(&self) -> Value {
+        let depth = self.call_stack.len();
+        let mut frames = Vec::with_capacity(depth);
+        for (level, frame) in self.call_stack.iter().enumerate() {
+            match frame.tail_calls {
+                0 => {}
+                1 => frames.push(Value::String("(1 tail call elided)".into())),
+                n => frames.push(Value::String(format!("({} tail calls elided)", n).into())),
+            }
+            // 活动帧的程序计数器缓存在虚拟机中，调用者帧保存的位置指向调用指令之后
+            let pc = if level + 1 == depth { self.pc } else { frame.pc };
+            frames.push(Value::String(format!("{} at pc {}", frame.function.name, pc.saturating_sub(1)).into()));
+        }
+        Value::list(frames)
+    }
+}
+
 impl Default for AquaVM {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+/// 属性不存在时的错误，信息与 Python 相同
+fn no_attribute(object: &Value, name: &str) -> VMError {
+    match object {
+        Value::Module(module) => VMError::AttributeError(format!(
+            "module '{}' has no attribute '{}'",
+            module.name, name
+        )),
+        Value::Class(class) => VMError::AttributeError(format!(
+            "type object '{}' has no attribute '{}'",
+            class.name, name
+        )),
+        other => VMError::AttributeError(format!(
+            "'{}' object has no attribute '{}'",
+            other.type_name(), name
+        )),
+    }
+}
+
+/// `IMPORT_MODULE`/`IMPORT_FROM` 的模块名操作数
+fn module_name(name: &Value) -> Result<String> {
+    match name {
+        Value::String(name) => Ok(name.to_string()),
+        other => Err(VMError::TypeError(format!(
+            "module name must be str, not '{}'",
+            other.type_name()
+        ))),
+    }
+}
+
+/// 异常是否属于 catch 子句指定的类型
+///
+/// 实例按所属类及其基类的名字匹配，其他被抛出的值都视为 `Exception`。
+fn exception_matches(exception: &Value, name: &str) -> bool {
+    match exception {
+        Value::Instance(instance) => instance.class.mro().any(|class| &*class.name == name),
+        _ => name == "Exception",
+    }
+}
+
+/// `GET_AWAITABLE`：`await` 的对象必须是协程或 Future，已经结束的协程不能再次等待
+fn check_awaitable(awaitable: &Value) -> Result<()> {
+    match awaitable {
+        Value::Generator(generator) if generator.borrow().coroutine => {
+            if generator.borrow().state == GeneratorState::Closed {
+                return Err(VMError::RuntimeError("cannot reuse already awaited coroutine".to_string()));
+            }
+            Ok(())
+        }
+        Value::Future(_) => Ok(()),
+        other => Err(VMError::TypeError(format!(
+            "object {} can't be used in 'await' expression",
+            other.type_name()
+        ))),
+    }
+}
+
+/// 按参数名绑定 `asyncio` 函数的实参，前 `required` 个参数必须提供，其余缺省为 `None`
+fn bind_asyncio(builtin: BuiltinFunction, mut args: Vec<Value>, keywords: Keywords, parameters: &[&str], required: usize) -> Result<Vec<Value>> {
+    let name = builtin.name();
+    if args.len() > parameters.len() {
+        return Err(VMError::TypeError(format!(
+            "{}() takes at most {} arguments ({} given)",
+            name,
+            parameters.len(),
+            args.len()
+        )));
+    }
+    let given = args.len();
+    args.resize(parameters.len(), Value::Null);
+    let mut bound = vec![false; parameters.len()];
+    bound[..given].fill(true);
+    for (keyword, value) in keywords {
+        let Some(slot) = parameters.iter().position(|parameter| **parameter == *keyword) else {
+            return Err(VMError::TypeError(format!(
+                "{}() got an unexpected keyword argument '{}'",
+                name, keyword
+            )));
+        };
+        if bound[slot] {
+            return Err(VMError::TypeError(format!(
+                "{}() got multiple values for argument '{}'",
+                name, keyword
+            )));
+        }
+        args[slot] = value;
+        bound[slot] = true;
+    }
+    if let Some(slot) = (0..required).find(|&slot| !bound[slot]) {
+        return Err(VMError::TypeError(format!(
+            "{}() missing required argument '{}'",
+            name, parameters[slot]
+        )));
+    }
+    Ok(args)
+}
+
+/// `sleep()` 的延迟和 `wait_for()` 的超时（秒）
+fn seconds(builtin: BuiltinFunction, value: &Value) -> Result<f64> {
+    match value {
+        Value::Int(i) => Ok(*i as f64),
+        Value::Float(f) => Ok(*f),
+        Value::Bool(b) => Ok(*b as i64 as f64),
+        other => Err(VMError::TypeError(format!(
+            "{}() argument must be a number, not '{}'",
+            builtin.name(),
+            other.type_name()
+        ))),
+    }
+}
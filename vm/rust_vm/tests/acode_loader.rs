@@ -0,0 +1,195 @@
+use aqua_vm::bytecode::{Bytecode, OpCode};
+use aqua_vm::{VMError, Value};
+
+mod common;
+use common::{global, load_bytecode};
+
+/// 按 `codegen.py` 的布局拼出一个 v1 文件
+fn acode_v1(constants: &str, globals: &str, functions: &str, main: &str) -> Vec<u8> {
+    let mut bytes = b"AQUA".to_vec();
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    for section in [constants, globals, functions, main] {
+        bytes.extend_from_slice(&(section.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(section.as_bytes());
+    }
+    bytes
+}
+
+/// 只有常量段的 v2 文件，唯一的常量是 `depth` 层嵌套的单元素元组
+fn nested_tuples_v2(depth: usize) -> Vec<u8> {
+    let mut constants = vec![1];
+    for _ in 0..depth {
+        // 元组标签 8，一个元素
+        constants.extend([8, 1]);
+    }
+    // 整数标签 3，值 0
+    constants.extend([3, 0]);
+
+    // 段数量 1，常量段（编号 1）紧跟在 9 字节的段表之后
+    let mut payload = vec![1, 1];
+    payload.extend(10u32.to_le_bytes());
+    payload.extend((constants.len() as u32).to_le_bytes());
+    payload.extend(constants);
+
+    let mut bytes = b"AQUA".to_vec();
+    bytes.extend(2u16.to_le_bytes());
+    bytes.extend(crc32fast::hash(&payload).to_le_bytes());
+    bytes.extend(payload);
+    bytes
+}
+
+#[test]
+fn reads_codegen_layout() {
+    let bytes = acode_v1(
+        r#"[1, "add", 2.5, null, true]"#,
+        r#"{"x": 0}"#,
+        r#"{"add": {"parameters": ["a", "b"], "local_vars": {"a": 0, "b": 1},
+            "instructions": [[6, 0], [6, 1], [16, null], [81, null]]}}"#,
+        r#"[[1, 0], [5, 0], [96, null], [255, null]]"#,
+    );
+
+    let bytecode = Bytecode::from_acode_bytes(&bytes).unwrap();
+    assert!(matches!(bytecode.constants[0], Value::Int(1)));
+    assert!(matches!(bytecode.constants[2], Value::Float(f) if f == 2.5));
+    assert!(matches!(bytecode.constants[3], Value::Null));
+    assert_eq!(bytecode.global_vars["x"], 0);
+
+    let add = &bytecode.functions["add"];
+    assert_eq!(add.parameters, vec!["a", "b"]);
+    assert_eq!(add.instructions[2].opcode, OpCode::Add);
+    assert_eq!(add.instructions[3].opcode, OpCode::Return);
+
+    let opcodes: Vec<_> = bytecode.instructions.iter().map(|i| i.opcode).collect();
+    assert_eq!(
+        opcodes,
+        vec![
+            OpCode::LoadConst,
+            OpCode::StoreGlobal,
+            OpCode::Pop,
+            OpCode::Halt
+        ]
+    );
+}
+
+#[test]
+fn rejects_malformed_sections() {
+    let bad_magic = b"AQUB\x01\x00".to_vec();
+    assert!(matches!(
+        Bytecode::from_acode_bytes(&bad_magic),
+        Err(VMError::InvalidBytecode(_))
+    ));
+
+    let bad_json = acode_v1("[1,", "{}", "{}", "[]");
+    let err = Bytecode::from_acode_bytes(&bad_json).unwrap_err();
+    assert!(err.to_string().contains("constants"), "{}", err);
+
+    let mut truncated = acode_v1("[]", "{}", "{}", "[[255, null]]");
+    truncated.truncate(truncated.len() - 3);
+    assert!(matches!(
+        Bytecode::from_acode_bytes(&truncated),
+        Err(VMError::InvalidBytecode(_))
+    ));
+
+    let unknown_opcode = acode_v1("[]", "{}", "{}", "[[250, null]]");
+    assert!(matches!(
+        Bytecode::from_acode_bytes(&unknown_opcode),
+        Err(VMError::InvalidOpcode(250))
+    ));
+}
+
+#[test]
+fn v2_round_trip_preserves_constants_and_code() {
+    let v1 = acode_v1(
+        r#"[1, 1.0, -7, "s", null, false, ["a", ["b"]], {"m": "C.m"}]"#,
+        r#"{"x": 0, "y": 1}"#,
+        r#"{"f": {"parameters": ["a"], "local_vars": {"a": 0, "t": 1},
+            "instructions": [[6, 0], [7, 1], [6, 1], [81, null]]}}"#,
+        r#"[[1, 1], [5, 0], [64, 300000], [255, null]]"#,
+    );
+    let original = Bytecode::from_acode_bytes(&v1).unwrap();
+
+    let encoded = original.to_acode_bytes().unwrap();
+    assert_eq!(&encoded[..4], b"AQUA");
+    assert_eq!(u16::from_le_bytes([encoded[4], encoded[5]]), 2);
+    assert!(encoded.len() < v1.len());
+
+    let decoded = Bytecode::from_acode_bytes(&encoded).unwrap();
+    assert!(matches!(decoded.constants[0], Value::Int(1)));
+    assert!(matches!(decoded.constants[1], Value::Float(f) if f == 1.0));
+    assert!(matches!(decoded.constants[2], Value::Int(-7)));
+    assert_eq!(decoded.constants[6].repr(), "('a', ('b',))");
+    assert_eq!(decoded.constants[7].repr(), "{'m': 'C.m'}");
+    assert_eq!(decoded.global_vars, original.global_vars);
+    assert_eq!(decoded.instructions, original.instructions);
+    assert_eq!(
+        decoded.functions["f"].instructions,
+        original.functions["f"].instructions
+    );
+    assert_eq!(
+        decoded.functions["f"].local_vars,
+        original.functions["f"].local_vars
+    );
+
+    // 同一程序总是编码为同样的字节
+    assert_eq!(decoded.to_acode_bytes().unwrap(), encoded);
+}
+
+#[test]
+fn dict_constants_are_copied_on_load() {
+    // a = {"k": {"n": 1}}; b = 同一个常量; a["k"]["n"] = 99; a["x"] = 0
+    let v1 = acode_v1(
+        r#"[{"k": {"n": 1}}, "k", "n", 99, "x", 0]"#,
+        r#"{"a": 0, "b": 1}"#,
+        "{}",
+        r#"[[1, 0], [5, 0], [1, 0], [5, 1],
+            [4, 0], [1, 1], [101, null], [1, 2], [1, 3], [102, null],
+            [4, 0], [1, 4], [1, 5], [102, null]]"#,
+    );
+    let original = Bytecode::from_acode_bytes(&v1).unwrap();
+    let decoded = Bytecode::from_acode_bytes(&original.to_acode_bytes().unwrap()).unwrap();
+    for bytecode in [original, decoded] {
+        let mut vm = load_bytecode(&bytecode).unwrap();
+        vm.run().unwrap();
+        assert_eq!(global(&vm, "a"), "{'k': {'n': 99}, 'x': 0}");
+        assert_eq!(global(&vm, "b"), "{'k': {'n': 1}}");
+        assert_eq!(bytecode.constants[0].repr(), "{'k': {'n': 1}}");
+    }
+}
+
+#[test]
+fn v2_rejects_corrupted_payload() {
+    let bytecode = Bytecode::from_acode_bytes(&acode_v1("[42]", "{}", "{}", "[[1, 0]]")).unwrap();
+    let mut encoded = bytecode.to_acode_bytes().unwrap();
+    let last = encoded.len() - 1;
+    encoded[last] ^= 0xff;
+
+    let err = Bytecode::from_acode_bytes(&encoded).unwrap_err();
+    assert!(err.to_string().contains("checksum"), "{}", err);
+}
+
+#[test]
+fn v2_constants_are_immutable_and_bounded_in_depth() {
+    // 列表常量解码为元组，LOAD_CONST 不会交出可以修改的共享对象
+    let bytecode = Bytecode {
+        constants: vec![Value::list(vec![Value::Int(1), Value::Int(2)])],
+        ..Bytecode::default()
+    };
+    let decoded = Bytecode::from_acode_bytes(&bytecode.to_acode_bytes().unwrap()).unwrap();
+    assert!(matches!(&decoded.constants[0], Value::Tuple(items) if items.len() == 2));
+
+    // 构造的文件：常量为 depth 层嵌套的单元素元组，解码在超过上限时停止，不会耗尽栈
+    let error = |depth: usize| {
+        Bytecode::from_acode_bytes(&nested_tuples_v2(depth))
+            .unwrap_err()
+            .to_string()
+    };
+    assert_eq!(
+        error(64),
+        "Invalid bytecode: missing globals section",
+        "64 levels are accepted"
+    );
+    assert_eq!(
+        error(100_000),
+        "Invalid bytecode: constants nested more than 64 levels deep in constants section"
+    );
+}
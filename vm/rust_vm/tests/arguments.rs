@@ -0,0 +1,144 @@
+use aqua_vm::bytecode::{self, Bytecode};
+use aqua_vm::VMError;
+
+mod common;
+use common::{global, run};
+
+/// `describe` 把收到的各个参数打包为元组返回
+const DESCRIBE: &str = r#"
+.func describe(a, b=10, *rest, c, d="d", **extra)
+    LOAD_LOCAL a
+    LOAD_LOCAL b
+    LOAD_LOCAL rest
+    LOAD_LOCAL c
+    LOAD_LOCAL d
+    LOAD_LOCAL extra
+    BUILD_TUPLE 6
+    RETURN
+.end
+"#;
+
+#[test]
+fn binds_defaults_keywords_and_variadic_arguments() {
+    let vm = run(&format!(
+        "{}{}",
+        DESCRIBE,
+        r#"
+.global minimal, full, named
+    LOAD_FUNC describe
+    LOAD_CONST 1
+    LOAD_CONST 3
+    LOAD_CONST ("c",)
+    CALL_KW 2
+    STORE_GLOBAL minimal
+    LOAD_FUNC describe
+    LOAD_CONST 1
+    LOAD_CONST 2
+    LOAD_CONST 3
+    LOAD_CONST 4
+    LOAD_CONST 5
+    LOAD_CONST 6
+    LOAD_CONST ("e", "c")
+    CALL_KW 6
+    STORE_GLOBAL full
+    LOAD_FUNC describe
+    LOAD_CONST "x"
+    LOAD_CONST "y"
+    LOAD_CONST "z"
+    LOAD_CONST ("d", "c", "a")
+    CALL_KW 3
+    STORE_GLOBAL named
+"#
+    ))
+    .unwrap();
+
+    assert_eq!(global(&vm, "minimal"), "(1, 10, (), 3, 'd', {})");
+    assert_eq!(global(&vm, "full"), "(1, 2, (3, 4), 6, 'd', {'e': 5})");
+    assert_eq!(global(&vm, "named"), "('z', 10, (), 'y', 'x', {})");
+}
+
+#[test]
+fn reports_argument_errors_like_python() {
+    let call = |args: &str| {
+        let source = format!(
+            "{}\n.func pair(x, y=2)\n    LOAD_LOCAL x\n    RETURN\n.end\n\
+             .func only(a, *, b)\n    LOAD_LOCAL a\n    RETURN\n.end\n{}",
+            DESCRIBE, args
+        );
+        match run(&source) {
+            Err(VMError::TypeError(message)) => message,
+            other => panic!("expected a TypeError, got {:?}", other.err()),
+        }
+    };
+
+    assert_eq!(
+        call("    LOAD_FUNC pair\n    CALL 0\n"),
+        "pair() missing 1 required positional argument: 'x'"
+    );
+    assert_eq!(
+        call("    LOAD_FUNC pair\n    LOAD_CONST 1\n    LOAD_CONST 2\n    LOAD_CONST 3\n    CALL 3\n"),
+        "pair() takes from 1 to 2 positional arguments but 3 were given"
+    );
+    assert_eq!(
+        call("    LOAD_FUNC only\n    LOAD_CONST 1\n    LOAD_CONST 2\n    CALL 2\n"),
+        "only() takes 1 positional argument but 2 were given"
+    );
+    assert_eq!(
+        call("    LOAD_FUNC only\n    LOAD_CONST 1\n    CALL 1\n"),
+        "only() missing 1 required keyword-only argument: 'b'"
+    );
+    assert_eq!(
+        call("    LOAD_FUNC pair\n    LOAD_CONST 1\n    LOAD_CONST 2\n    LOAD_CONST (\"x\",)\n    CALL_KW 2\n"),
+        "pair() got multiple values for argument 'x'"
+    );
+    assert_eq!(
+        call("    LOAD_FUNC pair\n    LOAD_CONST 1\n    LOAD_CONST 2\n    LOAD_CONST (\"z\",)\n    CALL_KW 2\n"),
+        "pair() got an unexpected keyword argument 'z'"
+    );
+    assert_eq!(
+        call("    LOAD_FUNC describe\n    CALL 0\n"),
+        "describe() missing 1 required positional argument: 'a'"
+    );
+    assert_eq!(
+        call(
+            "    LOAD_FUNC len\n    LOAD_CONST \"ab\"\n    LOAD_CONST (\"obj\",)\n    CALL_KW 1\n"
+        ),
+        "len() takes no keyword arguments"
+    );
+}
+
+#[test]
+fn signatures_survive_disassembly_and_the_binary_format() {
+    let bytecode = bytecode::assemble(DESCRIBE).unwrap();
+    assert!(
+        bytecode
+            .disassemble()
+            .contains("== function describe(a, b=10, *rest, c, d='d', **extra) =="),
+        "{}",
+        bytecode.disassemble()
+    );
+
+    let decoded = Bytecode::from_acode_bytes(&bytecode.to_acode_bytes().unwrap()).unwrap();
+    let describe = &decoded.functions["describe"];
+    assert_eq!(describe.positional_count(), 2);
+    assert_eq!(
+        (describe.kwonly_count, describe.varargs, describe.varkw),
+        (2, true, true)
+    );
+    assert_eq!(describe.defaults.len(), 1);
+    assert_eq!(describe.kwdefaults["d"].repr(), "'d'");
+
+    match bytecode::assemble(".func f(a=1, b)\n.end\n") {
+        Err(VMError::Assembly { message, .. }) => {
+            assert_eq!(message, "non-default argument follows default argument")
+        }
+        other => panic!("expected an assembly error, got {:?}", other.err()),
+    }
+    match run("    LOAD_FUNC len\n    LOAD_CONST 1\n    CALL_KW 1\n") {
+        Err(VMError::Verify { reason, .. }) => assert_eq!(
+            reason,
+            "CALL_KW must follow LOAD_CONST of a keyword name tuple within its argument count"
+        ),
+        other => panic!("expected a verify error, got {:?}", other.err()),
+    }
+}
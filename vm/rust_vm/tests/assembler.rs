@@ -0,0 +1,91 @@
+use aqua_vm::bytecode::{self, Instruction, OpCode};
+use aqua_vm::{VMError, Value};
+
+const SOURCE: &str = r#"
+; 汇编器回归样例
+.const greeting "hello"
+.global total, name
+
+.func add(a, b)
+.local tmp
+    LOAD_LOCAL a
+    LOAD_LOCAL b
+    ADD
+    STORE_LOCAL tmp
+    LOAD_LOCAL tmp
+    RETURN
+.end
+
+    LOAD_FUNC add
+    LOAD_CONST 1
+    LOAD_CONST 2
+    CALL 2
+    STORE_GLOBAL total
+    LOAD_CONST greeting
+    CALL_METHOD upper 0
+    STORE_GLOBAL name
+top:
+    LOAD_GLOBAL total
+    JUMP_IF_FALSE done
+    JUMP top
+done:
+    HALT
+"#;
+
+#[test]
+fn assembles_functions_labels_and_constants() {
+    let bytecode = bytecode::assemble(SOURCE).unwrap();
+    bytecode::verify(&bytecode).unwrap();
+
+    assert_eq!(bytecode.global_vars["total"], 0);
+    assert_eq!(bytecode.global_vars["name"], 1);
+
+    let add = &bytecode.functions["add"];
+    assert_eq!(add.parameters, vec!["a", "b"]);
+    assert_eq!(add.local_vars["tmp"], 2);
+    assert_eq!(add.instructions[3], Instruction::new(OpCode::StoreLocal, 2));
+
+    let constant = |value: Value| {
+        bytecode
+            .constants
+            .iter()
+            .position(|c| c.repr() == value.repr())
+            .unwrap() as u32
+    };
+    let main = &bytecode.instructions;
+    assert_eq!(
+        main[0],
+        Instruction::new(OpCode::LoadFunc, constant(Value::String("add".into())))
+    );
+    assert_eq!(
+        main[1],
+        Instruction::new(OpCode::LoadConst, constant(Value::Int(1)))
+    );
+    assert_eq!(main[5].operand, constant(Value::String("hello".into())));
+    let upper = constant(Value::String("upper".into()));
+    assert_eq!(main[6], Instruction::new(OpCode::CallMethod, upper));
+    assert_eq!(main[9], Instruction::new(OpCode::JumpIfFalse, 11));
+    assert_eq!(main[10], Instruction::new(OpCode::Jump, 8));
+}
+
+#[test]
+fn reports_errors_with_line_numbers() {
+    let line = |source: &str| match bytecode::assemble(source) {
+        Err(VMError::Assembly { line, message }) => (line, message),
+        other => panic!("expected assembly error, got {:?}", other),
+    };
+
+    let (at, message) = line("    LOAD_CONST 1\n    JUMP nowhere\n");
+    assert_eq!(at, 2);
+    assert!(message.contains("nowhere"), "{}", message);
+
+    let (at, message) = line("\n\n    FROB 1\n");
+    assert_eq!(at, 3);
+    assert!(message.contains("FROB"), "{}", message);
+
+    let (at, _) = line(".func f(a)\n    LOAD_LOCAL b\n.end\n");
+    assert_eq!(at, 2);
+
+    let (at, _) = line(".func f()\n    RETURN\n");
+    assert_eq!(at, 2);
+}
@@ -0,0 +1,111 @@
+use aqua_vm::bytecode;
+use aqua_vm::vm::VMConfig;
+use aqua_vm::{AquaVM, VMError, Value};
+
+mod common;
+use common::run;
+
+#[test]
+fn recursive_calls_run_callee_bodies() {
+    // fib(n)：n 为 0 或 1 时直接返回 n
+    let vm = run(r#"
+.global result
+.func fib(n)
+    LOAD_VAR n
+    JUMP_IF_FALSE base
+    LOAD_VAR n
+    LOAD_CONST 1
+    SUB
+    JUMP_IF_FALSE base
+    LOAD_FUNC fib
+    LOAD_VAR n
+    LOAD_CONST 1
+    SUB
+    CALL 1
+    LOAD_FUNC fib
+    LOAD_VAR n
+    LOAD_CONST 2
+    SUB
+    CALL 1
+    ADD
+    RETURN
+base:
+    LOAD_VAR n
+    RETURN
+.end
+    LOAD_FUNC fib
+    LOAD_CONST 15
+    CALL 1
+    STORE_VAR result
+    HALT
+"#)
+    .unwrap();
+    assert!(matches!(vm.get_global("result"), Some(Value::Int(610))));
+}
+
+#[test]
+fn caller_state_survives_nested_calls() {
+    // 调用返回后，调用者的局部变量和后续指令都要继续生效
+    let vm = run(r#"
+.global a, b
+.func double(x)
+    LOAD_VAR x
+    LOAD_VAR x
+    ADD
+    RETURN
+.end
+.func quad(x)
+.local y
+    LOAD_FUNC double
+    LOAD_VAR x
+    CALL 1
+    STORE_VAR y
+    LOAD_FUNC double
+    LOAD_VAR y
+    CALL 1
+    LOAD_VAR x
+    SUB
+    RETURN
+.end
+    LOAD_CONST "ok"
+    STORE_VAR a
+    LOAD_FUNC quad
+    LOAD_CONST 5
+    CALL 1
+    STORE_VAR b
+"#)
+    .unwrap();
+    assert!(matches!(vm.get_global("b"), Some(Value::Int(15))));
+    assert_eq!(vm.get_global("a").unwrap().to_string(), "ok");
+}
+
+#[test]
+fn unbounded_recursion_hits_call_depth_limit() {
+    let source = r#"
+.func forever(n)
+    LOAD_FUNC forever
+    LOAD_VAR n
+    CALL 1
+    LOAD_CONST 1
+    ADD
+    RETURN
+.end
+    LOAD_FUNC forever
+    LOAD_CONST 0
+    CALL 1
+    HALT
+"#;
+    let mut vm = AquaVM::with_config(VMConfig {
+        max_call_depth: 50,
+        ..VMConfig::default()
+    });
+    vm.load_bytecode(&bytecode::assemble(source).unwrap())
+        .unwrap();
+    let error = vm.run().unwrap_err();
+    assert!(
+        matches!(&error, VMError::RuntimeError(message) if message.contains("Call stack overflow")),
+        "{}",
+        error
+    );
+    assert_eq!(vm.get_stats().peak_call_stack_depth, 50);
+}
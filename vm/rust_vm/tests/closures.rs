@@ -0,0 +1,185 @@
+use aqua_vm::VMError;
+
+mod common;
+use common::{global, run};
+
+/// `make_counter()` 返回共享同一个 `count` 的 `(increment, get)`
+const COUNTER: &str = r#"
+.func make_counter()
+.local count
+.cell count
+    LOAD_CONST 0
+    STORE_DEREF count
+    LOAD_FUNC increment
+    LOAD_CLOSURE count
+    MAKE_CLOSURE 1
+    LOAD_FUNC get
+    LOAD_CLOSURE count
+    MAKE_CLOSURE 1
+    BUILD_TUPLE 2
+    RETURN
+.end
+
+.func increment()
+.free count
+    LOAD_DEREF count
+    LOAD_CONST 1
+    ADD
+    DUP
+    STORE_DEREF count
+    RETURN
+.end
+
+.func get()
+.free count
+    LOAD_DEREF count
+    RETURN
+.end
+"#;
+
+#[test]
+fn closures_over_the_same_variable_share_its_cell() {
+    let vm = run(&format!(
+        "{}{}",
+        COUNTER,
+        r#"
+.global inc, get, after_two, seen, fresh, still
+    LOAD_FUNC make_counter
+    CALL 0
+    DUP
+    LOAD_CONST 0
+    GET_ITEM
+    STORE_GLOBAL inc
+    LOAD_CONST 1
+    GET_ITEM
+    STORE_GLOBAL get
+    LOAD_GLOBAL inc
+    CALL 0
+    POP
+    LOAD_GLOBAL inc
+    CALL 0
+    STORE_GLOBAL after_two
+    LOAD_GLOBAL get
+    CALL 0
+    STORE_GLOBAL seen
+    LOAD_FUNC make_counter
+    CALL 0
+    LOAD_CONST 0
+    GET_ITEM
+    CALL 0
+    STORE_GLOBAL fresh
+    LOAD_GLOBAL get
+    CALL 0
+    STORE_GLOBAL still
+"#
+    ))
+    .unwrap();
+
+    assert_eq!(global(&vm, "after_two"), "2");
+    // get 读到 increment 写入的值
+    assert_eq!(global(&vm, "seen"), "2");
+    // 每次调用 make_counter 创建新的单元
+    assert_eq!(global(&vm, "fresh"), "1");
+    assert_eq!(global(&vm, "still"), "2");
+    assert_eq!(global(&vm, "inc"), "<function increment>");
+}
+
+#[test]
+fn captures_parameters_and_passes_cells_through_nested_functions() {
+    let vm = run(r#"
+.global result, nested
+
+.func adder(n)
+.cell n
+    LOAD_FUNC add
+    LOAD_CLOSURE n
+    MAKE_CLOSURE 1
+    RETURN
+.end
+
+.func add(x)
+.free n
+    LOAD_LOCAL x
+    LOAD_DEREF n
+    ADD
+    RETURN
+.end
+
+; middle 本身不使用 x，只把单元传给 inner
+.func outer()
+.cell x
+    LOAD_CONST 1
+    STORE_DEREF x
+    LOAD_FUNC middle
+    LOAD_CLOSURE x
+    MAKE_CLOSURE 1
+    LOAD_CONST 7
+    STORE_DEREF x
+    CALL 0
+    RETURN
+.end
+
+.func middle()
+.free x
+    LOAD_FUNC inner
+    LOAD_CLOSURE x
+    MAKE_CLOSURE 1
+    RETURN
+.end
+
+.func inner()
+.free x
+    LOAD_DEREF x
+    LOAD_CONST 10
+    MUL
+    RETURN
+.end
+
+    LOAD_FUNC adder
+    LOAD_CONST 5
+    CALL 1
+    LOAD_CONST 10
+    CALL 1
+    STORE_GLOBAL result
+    LOAD_FUNC outer
+    CALL 0
+    CALL 0
+    STORE_GLOBAL nested
+"#)
+    .unwrap();
+
+    assert_eq!(global(&vm, "result"), "15");
+    // 创建闭包之后对 x 的赋值同样可见
+    assert_eq!(global(&vm, "nested"), "70");
+}
+
+#[test]
+fn rejects_invalid_captured_variable_access() {
+    let source = r#"
+.func f(a)
+    LOAD_DEREF a
+    RETURN
+.end
+"#;
+    match run(source) {
+        Err(VMError::Verify {
+            function, reason, ..
+        }) => {
+            assert_eq!(function, "f");
+            assert_eq!(reason, "local slot 0 is not a cell or free variable");
+        }
+        other => panic!("expected a verify error, got {:?}", other.err()),
+    }
+
+    let source = format!("{}\n    LOAD_FUNC get\n    MAKE_CLOSURE 0\n", COUNTER);
+    let error = run(&source)
+        .err()
+        .expect("MAKE_CLOSURE without cells must fail");
+    assert!(
+        error
+            .to_string()
+            .contains("function 'get' has 1 free variables, got 0 cells"),
+        "{}",
+        error
+    );
+}
@@ -0,0 +1,197 @@
+use aqua_vm::dict::Dict;
+use aqua_vm::{exceptions, VMError, Value};
+use std::rc::Rc;
+
+mod common;
+use common::{global, run};
+
+#[test]
+fn builds_and_indexes_collections() {
+    let vm = run(r#"
+.global xs, t, d, last, n, v
+    LOAD_CONST 1
+    LOAD_CONST "two"
+    LOAD_CONST 3.5
+    BUILD_LIST 3
+    STORE_GLOBAL xs
+    LOAD_CONST 7
+    BUILD_TUPLE 1
+    STORE_GLOBAL t
+    LOAD_CONST "b"
+    LOAD_CONST 1
+    LOAD_CONST "a"
+    LOAD_CONST 2
+    LOAD_CONST "b"
+    LOAD_CONST 3
+    BUILD_DICT 3
+    STORE_GLOBAL d
+    LOAD_GLOBAL xs
+    LOAD_CONST -1
+    GET_ITEM
+    STORE_GLOBAL last
+    LOAD_GLOBAL d
+    LEN
+    STORE_GLOBAL n
+    LOAD_GLOBAL d
+    LOAD_CONST "b"
+    GET_ITEM
+    STORE_GLOBAL v
+"#)
+    .unwrap();
+    assert_eq!(global(&vm, "xs"), "[1, 'two', 3.5]");
+    assert_eq!(global(&vm, "t"), "(7,)");
+    // 重复的键保留首次出现的位置和最后一个值
+    assert_eq!(global(&vm, "d"), "{'b': 3, 'a': 2}");
+    assert_eq!(global(&vm, "last"), "3.5");
+    assert_eq!(global(&vm, "n"), "2");
+    assert_eq!(global(&vm, "v"), "3");
+}
+
+#[test]
+fn lists_are_shared_references() {
+    // b = a; b[0] = 99; a.append(b) 之后 a 同时反映两次修改
+    let vm = run(r#"
+.global a, b
+    LOAD_CONST 1
+    LOAD_CONST 2
+    BUILD_LIST 2
+    STORE_GLOBAL a
+    LOAD_GLOBAL a
+    STORE_GLOBAL b
+    LOAD_GLOBAL b
+    LOAD_CONST 0
+    LOAD_CONST 99
+    SET_ITEM
+    LOAD_GLOBAL a
+    LOAD_CONST 0
+    LOAD_GLOBAL b
+    LIST_APPEND
+    POP
+    POP
+"#)
+    .unwrap();
+    assert_eq!(global(&vm, "a"), "[99, 2, [...]]");
+    assert_eq!(global(&vm, "b"), global(&vm, "a"));
+}
+
+#[test]
+fn nested_comparisons_raise_recursion_error() {
+    // a = [a]、b = [b]：同一个列表相等，两个不同的自引用列表逐层比较直到超过嵌套上限
+    let vm = run(r#"
+.global a, b, same, caught
+    BUILD_LIST 0
+    STORE_GLOBAL a
+    LOAD_GLOBAL a
+    LOAD_GLOBAL a
+    CALL_METHOD append 1
+    POP
+    BUILD_LIST 0
+    STORE_GLOBAL b
+    LOAD_GLOBAL b
+    LOAD_GLOBAL b
+    CALL_METHOD append 1
+    POP
+    LOAD_GLOBAL a
+    LOAD_GLOBAL a
+    EQ
+    STORE_GLOBAL same
+    TRY_BEGIN
+    LOAD_GLOBAL a
+    LOAD_GLOBAL b
+    EQ
+    POP
+    TRY_END
+    CATCH_BEGIN RecursionError
+    STORE_GLOBAL caught
+    CATCH_END
+"#)
+    .unwrap();
+    assert_eq!(global(&vm, "same"), "True");
+    assert_eq!(
+        exceptions::describe(&vm.get_global("caught").unwrap()),
+        "RecursionError: maximum recursion depth exceeded"
+    );
+
+    // 嵌套很深的元组作为字典键时，哈希和比较同样报告错误
+    let nested = |depth| {
+        (0..depth).fold(Value::Int(0), |inner, _| {
+            Value::Tuple(Rc::from(vec![inner]))
+        })
+    };
+    let (x, y) = (nested(5000), nested(5000));
+    assert!(nested(10).equals(&nested(10)).unwrap());
+    assert!(matches!(x.equals(&y), Err(VMError::RuntimeError(_))));
+    assert!(matches!(
+        Dict::new().insert(x, Value::Null),
+        Err(VMError::RuntimeError(_))
+    ));
+}
+
+#[test]
+fn indexing_errors() {
+    let index = |container: &str, key: &str| {
+        run(&format!(
+            "{}\n LOAD_CONST {}\n GET_ITEM\n POP\n",
+            container, key
+        ))
+        .err()
+        .unwrap()
+    };
+    let list = " LOAD_CONST 1\n LOAD_CONST 2\n BUILD_LIST 2";
+    assert!(matches!(
+        index(list, "-3"),
+        VMError::IndexOutOfBounds { index: -3, len: 2 }
+    ));
+    assert!(matches!(
+        index(list, "2"),
+        VMError::IndexOutOfBounds { index: 2, len: 2 }
+    ));
+    assert!(index(list, "\"x\"")
+        .to_string()
+        .contains("list indices must be integers, not str"));
+
+    let dict = " LOAD_CONST 1\n LOAD_CONST 2\n BUILD_DICT 1";
+    match index(dict, "\"k\"") {
+        VMError::KeyError(key) => assert_eq!(key, "'k'"),
+        other => panic!("expected key error, got {:?}", other),
+    }
+    assert_eq!(index(dict, "2").to_string(), "Key error: 2");
+    assert!(index(" LOAD_CONST 5", "0")
+        .to_string()
+        .contains("'int' object is not subscriptable"));
+}
+
+#[test]
+fn dict_keys_follow_python_hashing() {
+    let mut dict = Dict::new();
+    dict.insert(Value::Int(1), Value::String("int".into()))
+        .unwrap();
+    // 1、1.0、True 是同一个键
+    dict.insert(Value::Float(1.0), Value::String("float".into()))
+        .unwrap();
+    dict.insert(Value::Bool(true), Value::String("bool".into()))
+        .unwrap();
+    let pair = Value::Tuple(vec![Value::Int(1), Value::String("x".into())].into());
+    dict.insert(pair.clone(), Value::Null).unwrap();
+    dict.insert(Value::String("z".into()), Value::Int(0))
+        .unwrap();
+
+    assert_eq!(dict.len(), 3);
+    assert_eq!(dict.get(&Value::Int(1)).unwrap().unwrap().repr(), "'bool'");
+    assert!(dict.contains_key(&pair).unwrap());
+
+    let unhashable = Value::list(vec![]);
+    let error = dict.insert(unhashable, Value::Null).unwrap_err();
+    assert_eq!(error.to_string(), "Type error: unhashable type: 'list'");
+
+    assert_eq!(
+        dict.remove(&Value::Float(1.0)).unwrap().unwrap().repr(),
+        "'bool'"
+    );
+    let keys: Vec<String> = dict.keys().map(Value::repr).collect();
+    assert_eq!(keys, ["(1, 'x')", "'z'"]);
+    assert_eq!(
+        dict.get(&Value::String("z".into())).unwrap(),
+        Some(&Value::Int(0))
+    );
+}
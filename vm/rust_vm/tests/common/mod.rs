@@ -0,0 +1,47 @@
+//! 集成测试共用的辅助函数
+
+#![allow(dead_code)]
+
+use aqua_vm::bytecode::{self, Bytecode};
+use aqua_vm::{exceptions, AquaVM, VMError, Value};
+
+/// 加载字节码，不执行
+pub fn load_bytecode(bytecode: &Bytecode) -> Result<AquaVM, VMError> {
+    let mut vm = AquaVM::new();
+    vm.load_bytecode(bytecode)?;
+    Ok(vm)
+}
+
+/// 汇编并加载程序，不执行
+pub fn load(source: &str) -> Result<AquaVM, VMError> {
+    load_bytecode(&bytecode::assemble(source)?)
+}
+
+/// 汇编、加载并执行程序
+pub fn run(source: &str) -> Result<AquaVM, VMError> {
+    let mut vm = load(source)?;
+    vm.run()?;
+    Ok(vm)
+}
+
+/// 汇编并执行 `lhs OP rhs`，返回结果
+pub fn binary(lhs: &str, op: &str, rhs: &str) -> Result<Value, VMError> {
+    let source = format!(
+        ".global r\n LOAD_CONST {}\n LOAD_CONST {}\n {}\n STORE_VAR r\n",
+        lhs, rhs, op
+    );
+    Ok(run(&source)?.get_global("r").unwrap().clone())
+}
+
+/// 主程序全局变量的 repr
+pub fn global(vm: &AquaVM, name: &str) -> String {
+    vm.get_global(name).unwrap().repr()
+}
+
+/// 未捕获异常的描述，其他错误直接失败
+pub fn uncaught(error: VMError) -> String {
+    match error {
+        VMError::Exception(exception) => exceptions::describe(&exception),
+        other => panic!("expected an uncaught exception, got {}", other),
+    }
+}
@@ -0,0 +1,198 @@
+use aqua_vm::VMError;
+use std::time::{Duration, Instant};
+
+mod common;
+use common::{global, load, uncaught};
+
+/// `worker(name, delay)`：等待 delay 秒后把 name 追加到 order，返回 name
+const WORKER: &str = r#"
+.global asyncio, order, task, main
+.async worker(name, delay)
+    LOAD_GLOBAL asyncio
+    LOAD_LOCAL delay
+    CALL_METHOD sleep 1
+    GET_AWAITABLE
+    LOAD_CONST None
+    YIELD_FROM
+    POP
+    LOAD_GLOBAL order
+    LOAD_LOCAL name
+    CALL_METHOD append 1
+    POP
+    LOAD_LOCAL name
+    RETURN
+.end
+"#;
+
+/// 主程序：导入 asyncio，order 置为空列表，导出 main
+const PRELUDE: &str = r#"
+    LOAD_CONST "asyncio"
+    IMPORT_MODULE
+    STORE_GLOBAL asyncio
+    BUILD_LIST 0
+    STORE_GLOBAL order
+    LOAD_FUNC main
+    STORE_GLOBAL main
+"#;
+
+#[test]
+fn gather_runs_on_a_virtual_clock() {
+    // 一小时的 sleep 立即完成；结果按参数顺序排列，完成顺序按到期时间，同时到期的按创建顺序
+    let mut vm = load(&format!(
+        r#"{}
+.async main()
+    LOAD_GLOBAL asyncio
+    LOAD_FUNC worker
+    LOAD_CONST "slow"
+    LOAD_CONST 3600
+    CALL 2
+    LOAD_FUNC worker
+    LOAD_CONST "fast"
+    LOAD_CONST 0.25
+    CALL 2
+    LOAD_FUNC worker
+    LOAD_CONST "tie"
+    LOAD_CONST 0.25
+    CALL 2
+    CALL_METHOD gather 3
+    GET_AWAITABLE
+    LOAD_CONST None
+    YIELD_FROM
+    RETURN
+.end
+{}"#,
+        WORKER, PRELUDE
+    ))
+    .unwrap();
+    let started = Instant::now();
+    let result = vm.run_async_main().unwrap();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(result.repr(), "['slow', 'fast', 'tie']");
+    assert_eq!(global(&vm, "order"), "['fast', 'tie', 'slow']");
+    assert_eq!(vm.loop_time(), 3600.0);
+}
+
+#[test]
+fn wait_for_timeouts_and_cancellation() {
+    // 超时取消被等待的任务，TimeoutError 传到 main 之外
+    let mut vm = load(&format!(
+        r#"{}
+.async main()
+    LOAD_GLOBAL asyncio
+    LOAD_FUNC worker
+    LOAD_CONST "late"
+    LOAD_CONST 10
+    CALL 2
+    LOAD_CONST 2.5
+    CALL_METHOD wait_for 2
+    GET_AWAITABLE
+    LOAD_CONST None
+    YIELD_FROM
+    RETURN
+.end
+{}"#,
+        WORKER, PRELUDE
+    ))
+    .unwrap();
+    assert_eq!(uncaught(vm.run_async_main().unwrap_err()), "TimeoutError");
+    assert_eq!(global(&vm, "order"), "[]");
+    assert_eq!(vm.loop_time(), 2.5);
+
+    // 取消正在 sleep 的任务；main 结束时仍未完成的任务被取消，时钟不再前进
+    let mut vm = load(&format!(
+        r#"{}
+.async main()
+    LOAD_GLOBAL asyncio
+    LOAD_FUNC worker
+    LOAD_CONST "cancelled"
+    LOAD_CONST 10
+    CALL 2
+    CALL_METHOD create_task 1
+    STORE_GLOBAL task
+    LOAD_GLOBAL asyncio
+    LOAD_CONST 1
+    CALL_METHOD sleep 1
+    GET_AWAITABLE
+    LOAD_CONST None
+    YIELD_FROM
+    POP
+    LOAD_GLOBAL task
+    CALL_METHOD cancel 0
+    POP
+    LOAD_GLOBAL asyncio
+    LOAD_FUNC worker
+    LOAD_CONST "abandoned"
+    LOAD_CONST 100
+    CALL 2
+    CALL_METHOD create_task 1
+    POP
+    LOAD_GLOBAL asyncio
+    LOAD_CONST 1
+    CALL_METHOD sleep 1
+    GET_AWAITABLE
+    LOAD_CONST None
+    YIELD_FROM
+    POP
+    LOAD_GLOBAL task
+    CALL_METHOD cancelled 0
+    RETURN
+.end
+{}"#,
+        WORKER, PRELUDE
+    ))
+    .unwrap();
+    assert_eq!(vm.run_async_main().unwrap().repr(), "True");
+    assert_eq!(global(&vm, "order"), "[]");
+    assert_eq!(
+        global(&vm, "task"),
+        "<Task cancelled coro=<coroutine object worker>>"
+    );
+    assert_eq!(vm.loop_time(), 2.0);
+}
+
+#[test]
+fn coroutine_misuse_is_reported_like_python() {
+    let error = |main: &str| {
+        let mut vm = load(&format!(
+            "{}\n.async main()\n{}\n.end\n{}",
+            WORKER, main, PRELUDE
+        ))
+        .unwrap();
+        uncaught(vm.run_async_main().unwrap_err())
+    };
+    assert_eq!(
+        error(
+            "    LOAD_CONST 5\n    GET_AWAITABLE\n    LOAD_CONST None\n    YIELD_FROM\n    RETURN"
+        ),
+        "TypeError: object int can't be used in 'await' expression"
+    );
+    assert_eq!(
+        error("    LOAD_FUNC worker\n    LOAD_CONST \"x\"\n    LOAD_CONST 0\n    CALL 2\n    GET_ITER\n    RETURN"),
+        "TypeError: 'coroutine' object is not iterable"
+    );
+    assert_eq!(
+        error("    LOAD_GLOBAL asyncio\n    LOAD_FUNC main\n    CALL 0\n    CALL_METHOD run 1\n    RETURN"),
+        "RuntimeError: asyncio.run() cannot be called from a running event loop"
+    );
+
+    // 事件循环之外调用 sleep()
+    let mut vm = load(&format!(
+        "{}    LOAD_CONST \"asyncio\"\n    IMPORT_MODULE\n    LOAD_CONST 1\n    CALL_METHOD sleep 1\n    POP\n",
+        WORKER
+    ))
+    .unwrap();
+    assert!(vm
+        .run()
+        .unwrap_err()
+        .to_string()
+        .contains("no running event loop"));
+
+    // 普通函数中的 await
+    let source = ".func f(x)\n    LOAD_LOCAL x\n    GET_AWAITABLE\n    LOAD_CONST None\n    YIELD_FROM\n    RETURN\n.end\n";
+    match load(source) {
+        Err(VMError::Verify { reason, .. }) => {
+            assert_eq!(reason, "GET_AWAITABLE outside a coroutine function")
+        }
+        other => panic!("expected a verify error, got {:?}", other.err()),
+    }
+}
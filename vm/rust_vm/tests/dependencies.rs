@@ -0,0 +1,225 @@
+use aqua_vm::package::Package;
+use aqua_vm::registry::{self, Lockfile, LOCKFILE};
+use aqua_vm::{bytecode, AquaVM, VMError};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+/// 每个测试使用独立的目录，其中 `packages` 为包目录
+fn workspace(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("aqua-deps-{}-{}", std::process::id(), test));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("packages")).unwrap();
+    dir
+}
+
+/// 写出一个应用包，`modules` 为 (包内路径, 汇编源码)
+fn write_package(path: &Path, name: &str, version: &str, deps: &str, modules: &[(&str, &str)]) {
+    let manifest = format!(
+        r#"{{"name": "{}", "version": "{}", "main": "main.acode", "aqua_version": "1.0", "dependencies": [{}]}}"#,
+        name, version, deps
+    );
+    let mut writer = ZipWriter::new(File::create(path).unwrap());
+    writer
+        .start_file("manifest.json", SimpleFileOptions::default())
+        .unwrap();
+    writer.write_all(manifest.as_bytes()).unwrap();
+    for &(entry, source) in modules {
+        writer
+            .start_file(entry, SimpleFileOptions::default())
+            .unwrap();
+        let data = bytecode::assemble(source)
+            .unwrap()
+            .to_acode_bytes()
+            .unwrap();
+        writer.write_all(&data).unwrap();
+    }
+    writer.finish().unwrap();
+}
+
+/// mathx 的入口程序从包内的 `lib` 模块导入版本号
+fn write_mathx(dir: &Path, version: &str) {
+    let lib = format!(
+        ".global VERSION\n    LOAD_CONST \"{}\"\n    STORE_GLOBAL VERSION\n",
+        version
+    );
+    write_package(
+        &dir.join(format!("packages/mathx-{}.apack", version)),
+        "mathx",
+        version,
+        "",
+        &[
+            (
+                "main.acode",
+                ".global VERSION\n    LOAD_CONST \"lib\"\n    LOAD_CONST (\"VERSION\",)\n    IMPORT_FROM\n    STORE_GLOBAL VERSION\n",
+            ),
+            ("lib.acode", &lib),
+        ],
+    );
+}
+
+const APP_MAIN: &str = r#"
+.global mathx, geom
+    LOAD_CONST "mathx"
+    IMPORT_MODULE
+    GET_ATTR VERSION
+    STORE_GLOBAL mathx
+    LOAD_CONST "geom"
+    IMPORT_MODULE
+    GET_ATTR mathx
+    STORE_GLOBAL geom
+"#;
+
+/// geom 要求 mathx 低于 1.4，并转述它看到的 mathx 版本
+fn write_geom(dir: &Path) {
+    write_package(
+        &dir.join("packages/geom-1.0.0.apack"),
+        "geom",
+        "1.0.0",
+        r#"{"name": "mathx", "version": ">=1.2, <1.4"}"#,
+        &[(
+            "main.acode",
+            ".global mathx\n    LOAD_CONST \"mathx\"\n    IMPORT_MODULE\n    GET_ATTR VERSION\n    STORE_GLOBAL mathx\n",
+        )],
+    );
+}
+
+fn write_app(dir: &Path, deps: &str) -> Package {
+    let path = dir.join("app.apack");
+    write_package(&path, "app", "0.1.0", deps, &[("main.acode", APP_MAIN)]);
+    Package::open(path).unwrap()
+}
+
+fn install(dir: &Path, app: &Package) -> Result<Vec<Package>, VMError> {
+    registry::install(app, &dir.join("packages"), &dir.join(LOCKFILE))
+}
+
+#[test]
+fn resolves_compatible_versions_and_locks_them() {
+    let dir = workspace("resolve");
+    for version in ["1.2.0", "1.4.1", "2.0.0"] {
+        write_mathx(&dir, version);
+    }
+    write_geom(&dir);
+    let app = write_app(
+        &dir,
+        r#"{"name": "mathx", "version": "^1.2"}, {"name": "geom", "version": "^1.0"}"#,
+    );
+
+    let dependencies = install(&dir, &app).unwrap();
+    let mut vm = AquaVM::new();
+    for dependency in &dependencies {
+        vm.register_package(dependency);
+    }
+    vm.load_package(&app).unwrap();
+    vm.run().unwrap();
+    // 1.4.1 满足 ^1.2 但不满足 geom 的要求，两边看到同一个 mathx 1.2.0
+    assert_eq!(vm.get_global("mathx").unwrap().repr(), "'1.2.0'");
+    assert_eq!(vm.get_global("geom").unwrap().repr(), "'1.2.0'");
+
+    let lock = Lockfile::load(dir.join(LOCKFILE)).unwrap();
+    let locked: Vec<_> = lock
+        .packages
+        .iter()
+        .map(|package| format!("{} {}", package.name, package.version))
+        .collect();
+    assert_eq!(locked, ["geom 1.0.0", "mathx 1.2.0"]);
+
+    // 新版本出现后仍使用锁定的版本
+    write_mathx(&dir, "1.3.0");
+    let dependencies = install(&dir, &app).unwrap();
+    let mathx = dependencies
+        .iter()
+        .find(|package| package.manifest.name == "mathx")
+        .unwrap();
+    assert_eq!(mathx.manifest.version, "1.2.0");
+}
+
+#[test]
+fn reports_conflicts_and_missing_packages() {
+    let dir = workspace("conflict");
+    for version in ["1.2.0", "2.0.0"] {
+        write_mathx(&dir, version);
+    }
+    write_geom(&dir);
+
+    let app = write_app(
+        &dir,
+        r#"{"name": "mathx", "version": "^2.0"}, {"name": "geom", "version": "^1.0"}"#,
+    );
+    match install(&dir, &app) {
+        Err(VMError::Package(message)) => assert_eq!(
+            message,
+            "conflicting requirements for 'mathx': ^2.0 (required by app), \
+             >=1.2, <1.4 (required by geom 1.0.0); available versions: 1.2.0, 2.0.0"
+        ),
+        other => panic!(
+            "expected a conflict, got {:?}",
+            other.map(|deps| deps.len())
+        ),
+    }
+    assert!(!dir.join(LOCKFILE).exists());
+
+    let app = write_app(&dir, r#"{"name": "stats", "version": "*"}"#);
+    match install(&dir, &app) {
+        Err(VMError::Package(message)) => assert_eq!(
+            message,
+            "package 'stats' not found in registry: * (required by app)"
+        ),
+        other => panic!(
+            "expected a missing package, got {:?}",
+            other.map(|deps| deps.len())
+        ),
+    }
+}
+
+#[test]
+fn backtracks_to_older_versions_before_reporting_conflicts() {
+    let dir = workspace("backtrack");
+    let lib = |name: &str, version: &str, deps: &str| {
+        write_package(
+            &dir.join(format!("packages/{}-{}.apack", name, version)),
+            name,
+            version,
+            deps,
+            &[("main.acode", "    HALT\n")],
+        );
+    };
+    lib("a", "1.0.0", r#"{"name": "c", "version": "^1"}"#);
+    lib("a", "2.0.0", r#"{"name": "c", "version": "^2"}"#);
+    lib("c", "1.0.0", "");
+    lib("c", "2.0.0", "");
+    // b 的最新版本依赖一个不存在的包
+    lib("b", "1.0.0", "");
+    lib("b", "1.1.0", r#"{"name": "missing", "version": "*"}"#);
+
+    let app = write_app(
+        &dir,
+        r#"{"name": "a", "version": "*"}, {"name": "b", "version": "^1"}, {"name": "c", "version": "^1"}"#,
+    );
+    let versions: Vec<_> = install(&dir, &app)
+        .unwrap()
+        .iter()
+        .map(|package| format!("{} {}", package.manifest.name, package.manifest.version))
+        .collect();
+    assert_eq!(versions, ["a 1.0.0", "b 1.0.0", "c 1.0.0"]);
+
+    // 没有任何组合可行时才报告冲突，报告的是搜索中遇到的第一个冲突（先尝试锁定的 a 1.0.0）
+    let app = write_app(
+        &dir,
+        r#"{"name": "a", "version": "*"}, {"name": "c", "version": "^3"}"#,
+    );
+    match install(&dir, &app) {
+        Err(VMError::Package(message)) => assert_eq!(
+            message,
+            "conflicting requirements for 'c': ^3 (required by app), \
+             ^1 (required by a 1.0.0); available versions: 1.0.0, 2.0.0"
+        ),
+        other => panic!(
+            "expected a conflict, got {:?}",
+            other.map(|deps| deps.len())
+        ),
+    }
+}
@@ -0,0 +1,281 @@
+use aqua_vm::bytecode::{self, Bytecode, ExceptionTable, Instruction, OpCode};
+use aqua_vm::function::Function;
+use aqua_vm::Value;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// 覆盖各类操作数、函数头部和 `try` 语句的汇编源码
+const SOURCE: &str = r#"
+.global Point, total, label, names, worker
+.func Point.__init__(self, x, y=0)
+    LOAD_LOCAL x
+    LOAD_LOCAL self
+    LOAD_CONST "x"
+    SET_ATTR
+    LOAD_CONST None
+    RETURN
+.end
+.func counter(start, *, step=1, **options)
+.cell start
+    LOAD_FUNC bump
+    LOAD_CLOSURE start
+    MAKE_CLOSURE 1
+    RETURN
+.end
+.func bump()
+.free start
+    LOAD_DEREF start
+    LOAD_CONST 1.5
+    ADD
+    RETURN
+.end
+.func squares(n, *rest)
+.local i
+    LOAD_CONST 0
+    STORE_LOCAL i
+top:
+    LOAD_LOCAL i
+    LOAD_LOCAL n
+    LT
+    JUMP_IF_FALSE done
+    LOAD_LOCAL i
+    LOAD_LOCAL i
+    MUL
+    YIELD_VALUE
+    POP
+    LOAD_LOCAL i
+    LOAD_CONST 1
+    ADD
+    STORE_LOCAL i
+    JUMP top
+done:
+    LOAD_CONST None
+    RETURN
+.end
+.async worker(delay=-0.5)
+    TRY_BEGIN
+    LOAD_CONST 1
+    LOAD_CONST 0
+    DIV
+    RETURN
+    TRY_END
+    CATCH_BEGIN ZeroDivisionError
+    POP
+    CATCH_END
+    CATCH_BEGIN
+    POP
+    CATCH_END
+    FINALLY_BEGIN
+    LOAD_CONST b"done\n"
+    POP
+    FINALLY_END
+    LOAD_CONST 'it\'s'
+    RETURN
+.end
+    LOAD_CONST "Point"
+    LOAD_FUNC Point.__init__
+    LOAD_CONST "__init__"
+    BUILD_DICT 1
+    CREATE_CLASS Point 0
+    STORE_GLOBAL Point
+    LOAD_GLOBAL Point
+    LOAD_CONST 2
+    LOAD_CONST ("y",)
+    CALL_KW 1
+    GET_ATTR y
+    LOAD_CONST 40
+    ADD
+    STORE_GLOBAL total
+    LOAD_GLOBAL total
+    FORMAT_VALUE !r ">8"
+    STORE_GLOBAL label
+    LOAD_CONST ("a", "b")
+    LOAD_CONST ()
+    BUILD_LIST 2
+    CALL_METHOD copy 0
+    STORE_GLOBAL names
+    HALT
+"#;
+
+#[test]
+fn listing_resolves_constants_labels_and_slots() {
+    let mut bytecode = Bytecode {
+        constants: vec![Value::String("count".into()), Value::Int(3)],
+        global_vars: HashMap::from([("total".to_string(), 0)]),
+        instructions: vec![
+            Instruction::new(OpCode::LoadConst, 1),
+            Instruction::new(OpCode::StoreGlobal, 0),
+            Instruction::new(OpCode::Halt, 0),
+        ],
+        ..Bytecode::default()
+    };
+    bytecode.functions.insert(
+        "count".to_string(),
+        Function {
+            name: "count".to_string(),
+            parameters: vec!["n".to_string()],
+            kwonly_count: 0,
+            varargs: false,
+            varkw: false,
+            defaults: Vec::new(),
+            kwdefaults: HashMap::new(),
+            generator: false,
+            coroutine: false,
+            instructions: vec![
+                Instruction::new(OpCode::LoadLocal, 0),
+                Instruction::new(OpCode::JumpIfFalse, 3),
+                Instruction::new(OpCode::Jump, 0),
+                Instruction::new(OpCode::LoadLocal, 0),
+                Instruction::new(OpCode::Return, 0),
+            ],
+            local_vars: HashMap::from([("n".to_string(), 0)]),
+            cell_vars: Vec::new(),
+            free_vars: Vec::new(),
+            handlers: ExceptionTable::default(),
+            module: 0,
+            id: 0,
+        },
+    );
+
+    let listing = bytecode.disassemble();
+    assert!(listing.contains(".const 'count'"), "{}", listing);
+    assert!(listing.contains(".global total"), "{}", listing);
+    assert!(listing.contains("; == <main> =="), "{}", listing);
+    assert!(listing.contains("    LOAD_CONST       3"), "{}", listing);
+    assert!(
+        listing.contains("    STORE_GLOBAL     total"),
+        "{}",
+        listing
+    );
+    assert!(listing.contains("; == function count(n) =="), "{}", listing);
+    assert!(listing.contains(".func count(n)\n"), "{}", listing);
+    assert!(listing.contains("    LOAD_LOCAL       n "), "{}", listing);
+    assert!(listing.contains("    JUMP_IF_FALSE    L1 "), "{}", listing);
+    assert!(listing.contains("    JUMP             L0 "), "{}", listing);
+    assert!(
+        listing.contains("L1:\n    LOAD_LOCAL       n                  ;    3"),
+        "{}",
+        listing
+    );
+}
+
+#[test]
+fn listing_reassembles_to_the_same_bytecode() {
+    let original = bytecode::assemble(SOURCE).unwrap();
+    bytecode::verify(&original).unwrap();
+
+    let listing = original.disassemble();
+    let reassembled = bytecode::assemble(&listing).unwrap_or_else(|e| panic!("{}\n{}", e, listing));
+    assert_eq!(
+        reassembled.to_acode_bytes().unwrap(),
+        original.to_acode_bytes().unwrap(),
+        "{}",
+        listing
+    );
+    assert_eq!(reassembled.disassemble(), listing);
+
+    for expected in [
+        "; == function Point.__init__(self, x, y=0) ==\n.func Point.__init__(self, x, y=0)\n",
+        "; == function counter(start, *, step=1, **options) ==",
+        ".cell start\n",
+        ".free start\n",
+        "; == generator squares(n, *rest) ==\n.func squares(n, *rest)\n.local i\n",
+        "; == coroutine worker(delay=-0.5) ==\n.async worker(delay=-0.5)\n",
+        "    CREATE_CLASS     Point 0",
+        "    CALL_METHOD      copy 0",
+        "    FORMAT_VALUE     !r '>8'",
+        "    CATCH_BEGIN      ZeroDivisionError",
+        "    LOAD_CONST       ('y',)",
+        "    LOAD_CONST       ()",
+        "    LOAD_CONST       b'done\\n'",
+        "    LOAD_CONST       \"it's\"",
+        "    GET_ATTR         y ",
+    ] {
+        assert!(
+            listing.contains(expected),
+            "{:?} not in\n{}",
+            expected,
+            listing
+        );
+    }
+}
+
+#[test]
+fn listing_shows_exception_tables() {
+    let listing = bytecode::assemble(SOURCE).unwrap().disassemble();
+    let worker = &listing[listing.find(".async worker").unwrap()..];
+    assert!(
+        worker.contains(
+            "    ; handlers\n    ;   try 0-5, catch ZeroDivisionError 6-8, catch 9-11, finally 12, end 16\n.end"
+        ),
+        "{}",
+        listing
+    );
+    assert_eq!(listing.matches("; handlers").count(), 1, "{}", listing);
+
+    // 结构不完整的 try 语句显示扫描时的错误，不影响其余部分
+    let broken = bytecode::assemble("    TRY_BEGIN\n    HALT\n").unwrap();
+    let listing = broken.disassemble();
+    assert!(listing.contains("    ; handlers: "), "{}", listing);
+    assert!(listing.contains("    HALT"), "{}", listing);
+}
+
+#[test]
+fn listing_includes_nested_code_objects() {
+    // 与 v2 文件一样，把具名函数改为常量池中的代码对象
+    let mut bytecode = bytecode::assemble(
+        r#"
+.func inner(a)
+    TRY_BEGIN
+    LOAD_LOCAL a
+    RETURN
+    TRY_END
+    CATCH_BEGIN
+    POP
+    CATCH_END
+    LOAD_CONST "fallback"
+    RETURN
+.end
+    LOAD_FUNC inner
+    LOAD_CONST 1
+    CALL 1
+    HALT
+"#,
+    )
+    .unwrap();
+    let inner = bytecode.functions.remove("inner").unwrap();
+    bytecode.constants.push(Value::Code(Rc::new(inner)));
+    let code = bytecode.constants.len() - 1;
+    bytecode.instructions[0].operand = code as u32;
+
+    let listing = bytecode.disassemble();
+    assert!(
+        listing.contains(&format!("    LOAD_FUNC        #{}", code)),
+        "{}",
+        listing
+    );
+    assert!(listing.contains("<code object inner>"), "{}", listing);
+    for expected in [
+        format!(
+            "; code object #{}\n; ; == function inner(a) ==\n; .func inner(a)\n",
+            code
+        ),
+        ";     LOAD_CONST       'fallback'".to_string(),
+        ";     ; handlers\n;     ;   try 0-3, catch 4-6, end 7\n; .end\n".to_string(),
+    ] {
+        assert!(
+            listing.contains(&expected),
+            "{:?} not in\n{}",
+            expected,
+            listing
+        );
+    }
+
+    // 代码对象没有汇编写法，清单在常量池中报错而不是静默地改变常量序号
+    match bytecode::assemble(&listing) {
+        Err(aqua_vm::VMError::Assembly { message, .. }) => {
+            assert_eq!(message, "unexpected character '<'")
+        }
+        other => panic!("expected an assembly error, got {:?}", other.map(|_| ())),
+    }
+}
@@ -0,0 +1,367 @@
+use aqua_vm::{AquaVM, VMError};
+
+mod common;
+use common::{global, run};
+
+/// `log(item)` 把 `item` 追加到全局列表 `trace`，用于观察各块的执行顺序
+const TRACE: &str = r#"
+.global trace
+.func log(item)
+    LOAD_GLOBAL trace
+    LOAD_VAR item
+    CALL_METHOD append 1
+    RETURN
+.end
+    BUILD_LIST 0
+    STORE_GLOBAL trace
+"#;
+
+fn run_traced(body: &str) -> Result<AquaVM, VMError> {
+    run(&format!("{}{}", TRACE, body))
+}
+
+#[test]
+fn catch_binds_exception_and_finally_runs() {
+    let vm = run_traced(
+        r#"
+.global caught
+    TRY_BEGIN
+    LOAD_CONST "try"
+    LOAD_FUNC log
+    ROT_TWO
+    CALL 1
+    POP
+    LOAD_CONST "boom"
+    THROW
+    LOAD_CONST "unreachable"
+    STORE_GLOBAL caught
+    TRY_END
+    CATCH_BEGIN ValueError
+    POP
+    CATCH_END
+    CATCH_BEGIN Exception
+    STORE_GLOBAL caught
+    LOAD_FUNC log
+    LOAD_CONST "catch"
+    CALL 1
+    POP
+    CATCH_END
+    FINALLY_BEGIN
+    LOAD_FUNC log
+    LOAD_CONST "finally"
+    CALL 1
+    POP
+    FINALLY_END
+    LOAD_FUNC log
+    LOAD_CONST "after"
+    CALL 1
+    POP
+"#,
+    )
+    .unwrap();
+    assert_eq!(global(&vm, "caught"), "'boom'");
+    assert_eq!(global(&vm, "trace"), "['try', 'catch', 'finally', 'after']");
+}
+
+/// `check(n)` 在 `n > 2` 时抛出 `Big(n)`；`guarded(n)` 在 try 块中返回 `check(n)`，
+/// finally 块记录 `n`
+const GUARDED: &str = r#"
+.global Big
+.func Big.__init__(self, n)
+    LOAD_VAR self
+    LOAD_VAR n
+    LOAD_CONST "n"
+    SET_ATTR
+    LOAD_CONST None
+    RETURN
+.end
+.func check(n)
+    LOAD_VAR n
+    LOAD_CONST 2
+    GT
+    JUMP_IF_FALSE ok
+    LOAD_GLOBAL Big
+    LOAD_VAR n
+    CALL 1
+    THROW
+ok:
+    LOAD_VAR n
+    RETURN
+.end
+.func guarded(n)
+    TRY_BEGIN
+    LOAD_FUNC check
+    LOAD_VAR n
+    CALL 1
+    RETURN
+    TRY_END
+    FINALLY_BEGIN
+    LOAD_FUNC log
+    LOAD_VAR n
+    CALL 1
+    POP
+    FINALLY_END
+    LOAD_CONST None
+    RETURN
+.end
+    LOAD_CONST "__init__"
+    LOAD_CONST "Big.__init__"
+    BUILD_DICT 1
+    CREATE_CLASS Big
+    STORE_GLOBAL Big
+"#;
+
+#[test]
+fn unwinds_across_frames_through_finally() {
+    let vm = run_traced(&format!(
+        "{}{}",
+        GUARDED,
+        r#"
+.global small, big
+    LOAD_FUNC guarded
+    LOAD_CONST 1
+    CALL 1
+    STORE_GLOBAL small
+    LOAD_CONST 100
+    TRY_BEGIN
+    LOAD_CONST 7
+    LOAD_FUNC guarded
+    LOAD_CONST 5
+    CALL 1
+    ADD
+    STORE_GLOBAL big
+    TRY_END
+    CATCH_BEGIN Big
+    GET_ATTR n
+    STORE_GLOBAL big
+    CATCH_END
+    POP
+"#
+    ))
+    .unwrap();
+    // return 先执行 finally 块；异常经过 guarded 的 finally 块后在主程序被捕获
+    assert_eq!(global(&vm, "small"), "1");
+    assert_eq!(global(&vm, "big"), "5");
+    assert_eq!(global(&vm, "trace"), "[1, 5]");
+}
+
+#[test]
+fn rethrow_from_catch_reaches_outer_handler() {
+    let vm = run_traced(
+        r#"
+.global outer
+    TRY_BEGIN
+    TRY_BEGIN
+    LOAD_CONST "inner"
+    THROW
+    TRY_END
+    CATCH_BEGIN
+    POP
+    LOAD_CONST "again"
+    THROW
+    CATCH_END
+    FINALLY_BEGIN
+    LOAD_FUNC log
+    LOAD_CONST "inner finally"
+    CALL 1
+    POP
+    FINALLY_END
+    TRY_END
+    CATCH_BEGIN
+    STORE_GLOBAL outer
+    CATCH_END
+"#,
+    )
+    .unwrap();
+    assert_eq!(global(&vm, "outer"), "'again'");
+    assert_eq!(global(&vm, "trace"), "['inner finally']");
+
+    let vm = run_traced(
+        r#"
+.global outer
+    TRY_BEGIN
+    TRY_BEGIN
+    LOAD_CONST "first"
+    THROW
+    TRY_END
+    CATCH_BEGIN
+    POP
+    RERAISE
+    CATCH_END
+    TRY_END
+    CATCH_BEGIN
+    STORE_GLOBAL outer
+    CATCH_END
+"#,
+    )
+    .unwrap();
+    assert_eq!(global(&vm, "outer"), "'first'");
+}
+
+#[test]
+fn uncaught_exceptions_carry_the_thrown_value() {
+    let err = run_traced(&format!(
+        "{}{}",
+        GUARDED,
+        r#"
+    TRY_BEGIN
+    LOAD_FUNC guarded
+    LOAD_CONST 3
+    CALL 1
+    POP
+    TRY_END
+    CATCH_BEGIN KeyError
+    POP
+    CATCH_END
+"#
+    ))
+    .err()
+    .unwrap();
+    match &err {
+        VMError::Exception(value) => assert_eq!(value.type_name(), "Big"),
+        other => panic!("expected script exception, got {:?}", other),
+    }
+    assert_eq!(err.to_string(), "Uncaught exception: <Big object>");
+
+    let err = run("    RERAISE\n").err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "Runtime error: No active exception to reraise"
+    );
+}
+
+#[test]
+fn runtime_errors_become_builtin_exceptions() {
+    let vm = run(r#"
+.global zero, index, called, kind, ZeroDivisionError
+.func divide(a, b)
+    LOAD_VAR a
+    LOAD_VAR b
+    DIV
+    RETURN
+.end
+    TRY_BEGIN
+    LOAD_FUNC divide
+    LOAD_CONST 1
+    LOAD_CONST 0
+    CALL 2
+    POP
+    TRY_END
+    CATCH_BEGIN ArithmeticError
+    STORE_GLOBAL zero
+    CATCH_END
+    TRY_BEGIN
+    BUILD_LIST 0
+    LOAD_CONST 3
+    GET_ITEM
+    POP
+    TRY_END
+    CATCH_BEGIN KeyError
+    POP
+    CATCH_END
+    CATCH_BEGIN LookupError
+    STORE_GLOBAL index
+    CATCH_END
+    TRY_BEGIN
+    LOAD_CONST "missing"
+    CALL 0
+    POP
+    TRY_END
+    CATCH_BEGIN TypeError
+    GET_ATTR message
+    STORE_GLOBAL called
+    CATCH_END
+    LOAD_FUNC isinstance
+    LOAD_GLOBAL zero
+    LOAD_GLOBAL ZeroDivisionError
+    CALL 2
+    STORE_GLOBAL kind
+"#)
+    .unwrap();
+    let zero = vm.get_global("zero").unwrap();
+    assert_eq!(zero.type_name(), "ZeroDivisionError");
+    assert_eq!(global(&vm, "kind"), "True");
+    match zero {
+        aqua_vm::Value::Instance(instance) => {
+            assert_eq!(
+                instance.attribute("message").unwrap().repr(),
+                "'division by zero'"
+            );
+            assert_eq!(
+                instance.attribute("traceback").unwrap().repr(),
+                "['<main> at pc 4', 'divide at pc 2']"
+            );
+        }
+        other => panic!("expected an instance, got {:?}", other),
+    }
+    assert_eq!(vm.get_global("index").unwrap().type_name(), "IndexError");
+    // 字符串不能作为函数名调用
+    assert_eq!(global(&vm, "called"), "\"'str' object is not callable\"");
+
+    // 没有处理器时仍返回原来的错误
+    let err = run(r#"
+    LOAD_CONST 1
+    LOAD_CONST 0
+    DIV
+"#)
+    .err()
+    .unwrap();
+    assert!(matches!(err, VMError::DivisionByZero));
+}
+
+#[test]
+fn user_exceptions_extend_builtin_classes() {
+    let vm = run(r#"
+.global Exception, ValueError, AppError, caught, text, code
+.func AppError.__init__(self, message, code)
+    LOAD_FUNC super
+    CALL 0
+    LOAD_VAR message
+    CALL_METHOD __init__ 1
+    POP
+    LOAD_VAR self
+    LOAD_VAR code
+    LOAD_CONST "code"
+    SET_ATTR
+    LOAD_CONST None
+    RETURN
+.end
+    LOAD_GLOBAL ValueError
+    LOAD_CONST "__init__"
+    LOAD_CONST "AppError.__init__"
+    BUILD_DICT 1
+    CREATE_CLASS AppError 1
+    STORE_GLOBAL AppError
+    TRY_BEGIN
+    LOAD_GLOBAL AppError
+    LOAD_CONST "bad config"
+    LOAD_CONST 7
+    CALL 2
+    THROW
+    TRY_END
+    CATCH_BEGIN Exception
+    STORE_GLOBAL caught
+    CATCH_END
+    LOAD_FUNC repr
+    LOAD_GLOBAL caught
+    CALL 1
+    STORE_GLOBAL text
+    LOAD_GLOBAL caught
+    GET_ATTR code
+    STORE_GLOBAL code
+"#)
+    .unwrap();
+    assert_eq!(global(&vm, "text"), "\"AppError('bad config')\"");
+    assert_eq!(global(&vm, "code"), "7");
+
+    let err = run(r#"
+.global ValueError
+    LOAD_GLOBAL ValueError
+    LOAD_CONST "bad"
+    CALL 1
+    THROW
+"#)
+    .err()
+    .unwrap();
+    assert_eq!(err.to_string(), "Uncaught exception: ValueError: bad");
+}
@@ -0,0 +1,197 @@
+use aqua_vm::format::format_value;
+use aqua_vm::{VMError, Value};
+
+mod common;
+use common::run;
+
+fn s(text: &str) -> Value {
+    Value::String(text.into())
+}
+
+/// 期望值均取自 CPython 的 `format(value, spec)`
+#[test]
+fn matches_python_format() {
+    let cases: &[(Value, &str, &str)] = &[
+        // 整数
+        (Value::Int(42), "", "42"),
+        (Value::Int(42), "+d", "+42"),
+        (Value::Int(42), " d", " 42"),
+        (Value::Int(-42), "=+8", "-     42"),
+        (Value::Int(5), "<05", "50000"),
+        (Value::Int(12), "x<6", "12xxxx"),
+        (Value::Int(255), "#010b", "0b11111111"),
+        (Value::Int(42), "#o", "0o52"),
+        (Value::Int(42), "#X", "0X2A"),
+        (Value::Int(12), "+#x", "+0xc"),
+        (Value::Int(-5), "#x", "-0x5"),
+        (Value::Int(65), "c", "A"),
+        (Value::Int(255), "n", "255"),
+        (Value::Bool(true), "", "True"),
+        (Value::Bool(true), ">5", "    1"),
+        (Value::Bool(false), "d", "0"),
+        // 分组与补零
+        (Value::Int(1234567), "_", "1_234_567"),
+        (Value::Int(1234), ",", "1,234"),
+        (Value::Int(1234), "08,d", "0,001,234"),
+        (Value::Int(1234), "010,d", "00,001,234"),
+        (Value::Int(-1234), "010,d", "-0,001,234"),
+        (Value::Int(1234), "0=10,d", "00,001,234"),
+        (Value::Int(1234), "0>10,d", "000001,234"),
+        (Value::Int(1234), "010_x", "0_0000_04d2"),
+        (Value::Int(1234), "_b", "100_1101_0010"),
+        (Value::Float(1234.5678), "012,.2f", "0,001,234.57"),
+        (Value::Float(-1234.5), "=+12,.1f", "-    1,234.5"),
+        // 定点与科学计数法
+        (Value::Float(1.23456), ".2f", "1.23"),
+        (Value::Float(9.999), ".2f", "10.00"),
+        (Value::Float(2.5), ".0f", "2"),
+        (Value::Float(0.1), ".20f", "0.10000000000000000555"),
+        (Value::Float(-0.0), ".2f", "-0.00"),
+        (Value::Float(-0.0001), "z.2f", "0.00"),
+        (Value::Float(1.5), "#.0f", "2."),
+        (Value::Int(7), "08.3f", "0007.000"),
+        (Value::Float(12345.678), "e", "1.234568e+04"),
+        (Value::Float(12345.678), ".3E", "1.235E+04"),
+        (Value::Float(1.0), ".0e", "1e+00"),
+        (Value::Float(0.5), "%", "50.000000%"),
+        (Value::Float(0.125), ".1%", "12.5%"),
+        (Value::Float(1e-7), "%", "0.000010%"),
+        // 通用格式与省略类型
+        (Value::Float(0.0), "g", "0"),
+        (Value::Float(1e-5), "g", "1e-05"),
+        (Value::Float(123456789.0), "g", "1.23457e+08"),
+        (Value::Float(100000.0), "g", "100000"),
+        (Value::Float(1e6), "g", "1e+06"),
+        (Value::Float(2.0), "#g", "2.00000"),
+        (Value::Float(1.5), "#.3g", "1.50"),
+        (Value::Float(1234.5), "n", "1234.5"),
+        (Value::Float(100.0), ".3", "1e+02"),
+        (Value::Float(10.0), ".3", "10.0"),
+        (Value::Float(999.9), ".3", "1e+03"),
+        (Value::Float(123456.0), ".5", "1.2346e+05"),
+        (Value::Float(1e16), ".17", "1e+16"),
+        (Value::Float(1234.5), ",", "1,234.5"),
+        (Value::Float(1e16), "#", "1.e+16"),
+        (Value::Float(1.5), "010", "00000001.5"),
+        (Value::Float(-0.0), "z", "0.0"),
+        (Value::Float(f64::INFINITY), "08", "00000inf"),
+        (Value::Float(f64::NAN), "+.2f", "+nan"),
+        (Value::Float(f64::NEG_INFINITY), "F", "-INF"),
+        // 字符串
+        (s("abc"), "^9", "   abc   "),
+        (s("abc"), "*>6.2", "****ab"),
+        (s("abc"), ".0", ""),
+        (s("x"), "05", "x0000"),
+        (s("é"), "_^5", "__é__"),
+    ];
+
+    for (value, spec, expected) in cases {
+        let actual = format_value(value, spec)
+            .unwrap_or_else(|e| panic!("format({}, {:?}) failed: {}", value.repr(), spec, e));
+        assert_eq!(actual, *expected, "format({}, {:?})", value.repr(), spec);
+    }
+}
+
+#[test]
+fn rejects_invalid_specs_like_python() {
+    let cases: &[(Value, &str, &str)] = &[
+        (
+            Value::Null,
+            ">5",
+            "Type error: unsupported format string passed to NoneType.__format__",
+        ),
+        (
+            Value::Int(3),
+            ".1",
+            "Value error: Precision not allowed in integer format specifier",
+        ),
+        (
+            Value::Float(1.5),
+            "d",
+            "Value error: Unknown format code 'd' for object of type 'float'",
+        ),
+        (
+            Value::Int(5),
+            ",x",
+            "Value error: Cannot specify ',' with 'x'.",
+        ),
+        (
+            s("x"),
+            "=5",
+            "Value error: '=' alignment not allowed in string format specifier",
+        ),
+        (
+            s("x"),
+            "+",
+            "Value error: Sign not allowed in string format specifier",
+        ),
+        (
+            Value::Int(1),
+            ".",
+            "Value error: Format specifier missing precision",
+        ),
+        (
+            Value::Int(1),
+            "5dd",
+            "Value error: Invalid format specifier '5dd' for object of type 'int'",
+        ),
+        (
+            Value::Float(1.5),
+            ".2147483648f",
+            "Value error: precision too big",
+        ),
+        (
+            s("x"),
+            ".2147483648d",
+            "Value error: Unknown format code 'd' for object of type 'str'",
+        ),
+        // 超大的宽度和精度不会真的分配内存
+        (Value::Int(1), "9999999999999", "Memory error"),
+        (s("x"), "^9999999999999", "Memory error"),
+        (Value::Int(-1), "09999999999999,d", "Memory error"),
+        (Value::Float(1.5), ".2000000000%", "Memory error"),
+    ];
+
+    for (value, spec, message) in cases {
+        let err = format_value(value, spec).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            *message,
+            "format({}, {:?})",
+            value.repr(),
+            spec
+        );
+    }
+}
+
+#[test]
+fn format_value_applies_conversion_and_spec() {
+    let vm = run(r#"
+.global price, label, plain
+    LOAD_CONST 3.14159
+    FORMAT_VALUE ">8.2f"
+    STORE_GLOBAL price
+    LOAD_CONST "ab"
+    FORMAT_VALUE !r "*<6"
+    STORE_GLOBAL label
+    LOAD_CONST 1.0
+    FORMAT_VALUE
+    LOAD_CONST 2
+    FORMAT_VALUE !s
+    ADD
+    STORE_GLOBAL plain
+"#)
+    .unwrap();
+    assert_eq!(vm.get_global("price").unwrap().to_string(), "    3.14");
+    assert_eq!(vm.get_global("label").unwrap().to_string(), "'ab'**");
+    assert_eq!(vm.get_global("plain").unwrap().to_string(), "1.02");
+
+    let err = run(r#"
+    LOAD_CONST 1.5
+    FORMAT_VALUE "d"
+    POP
+"#)
+    .err()
+    .unwrap();
+    assert!(matches!(err, VMError::ValueError(_)), "{}", err);
+}
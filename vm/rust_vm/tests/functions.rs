@@ -0,0 +1,157 @@
+use aqua_vm::bytecode::{self, Bytecode, OpCode};
+use aqua_vm::{VMError, Value};
+use std::rc::Rc;
+
+mod common;
+use common::{global, load_bytecode, run};
+
+#[test]
+fn function_values_are_stored_and_called_indirectly() {
+    let vm = run(r#"
+.global ops, table, sum, size, same, f, b
+.func double(x)
+    LOAD_LOCAL x
+    LOAD_CONST 2
+    MUL
+    RETURN
+.end
+
+.func square(x)
+    LOAD_LOCAL x
+    LOAD_LOCAL x
+    MUL
+    RETURN
+.end
+
+    LOAD_FUNC double
+    LOAD_FUNC square
+    BUILD_LIST 2
+    STORE_GLOBAL ops
+    LOAD_CONST "size"
+    LOAD_FUNC len
+    BUILD_DICT 1
+    STORE_GLOBAL table
+    LOAD_GLOBAL ops
+    LOAD_CONST 0
+    GET_ITEM
+    LOAD_CONST 5
+    CALL 1
+    LOAD_GLOBAL ops
+    LOAD_CONST 1
+    GET_ITEM
+    LOAD_CONST 5
+    CALL 1
+    ADD
+    STORE_GLOBAL sum
+    LOAD_GLOBAL table
+    LOAD_CONST "size"
+    GET_ITEM
+    LOAD_GLOBAL ops
+    CALL 1
+    STORE_GLOBAL size
+    LOAD_FUNC square
+    LOAD_GLOBAL ops
+    LOAD_CONST 1
+    GET_ITEM
+    EQ
+    STORE_GLOBAL same
+    LOAD_FUNC double
+    STORE_GLOBAL f
+    LOAD_FUNC len
+    STORE_GLOBAL b
+"#)
+    .unwrap();
+
+    assert_eq!(global(&vm, "sum"), "35");
+    assert_eq!(global(&vm, "size"), "2");
+    assert_eq!(global(&vm, "same"), "True");
+    assert_eq!(global(&vm, "f"), "<function double>");
+    assert_eq!(global(&vm, "b"), "<built-in function len>");
+}
+
+#[test]
+fn builtin_names_are_bound_as_global_values() {
+    let vm = run(r#"
+.global len, alias, n
+    LOAD_GLOBAL len
+    STORE_GLOBAL alias
+    LOAD_GLOBAL alias
+    LOAD_CONST "abcd"
+    CALL 1
+    STORE_GLOBAL n
+"#)
+    .unwrap();
+    assert_eq!(global(&vm, "n"), "4");
+    assert_eq!(global(&vm, "alias"), "<built-in function len>");
+}
+
+#[test]
+fn strings_are_not_callable_and_unknown_functions_are_rejected() {
+    let error = run(r#"
+.func greet()
+    LOAD_CONST "hi"
+    RETURN
+.end
+    LOAD_CONST "greet"
+    CALL 0
+"#)
+    .err()
+    .expect("calling a string must fail");
+    assert!(
+        error.to_string().contains("'str' object is not callable"),
+        "{}",
+        error
+    );
+
+    match run("    LOAD_FUNC missing\n") {
+        Err(VMError::Verify { reason, .. }) => {
+            assert_eq!(reason, "reference to unknown function 'missing'")
+        }
+        other => panic!("expected a verify error, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn code_constants_are_called_with_their_exception_tables() {
+    // 把汇编出的具名函数改为 v2 文件中的代码对象常量
+    let mut bytecode = bytecode::assemble(
+        r#"
+.global results
+.func safe(n)
+    TRY_BEGIN
+    LOAD_CONST 10
+    LOAD_LOCAL n
+    DIV
+    RETURN
+    TRY_END
+    CATCH_BEGIN ZeroDivisionError
+    POP
+    LOAD_CONST "caught"
+    RETURN
+    CATCH_END
+.end
+    LOAD_FUNC safe
+    LOAD_CONST 0
+    CALL 1
+    LOAD_FUNC safe
+    LOAD_CONST 4
+    CALL 1
+    BUILD_LIST 2
+    STORE_GLOBAL results
+"#,
+    )
+    .unwrap();
+    let safe = bytecode.functions.remove("safe").unwrap();
+    bytecode.constants.push(Value::Code(Rc::new(safe)));
+    let code = bytecode.constants.len() as u32 - 1;
+    for instruction in &mut bytecode.instructions {
+        if instruction.opcode == OpCode::LoadFunc {
+            instruction.operand = code;
+        }
+    }
+    let decoded = Bytecode::from_acode_bytes(&bytecode.to_acode_bytes().unwrap()).unwrap();
+
+    let mut vm = load_bytecode(&decoded).unwrap();
+    vm.run().unwrap();
+    assert_eq!(global(&vm, "results"), "['caught', 2.5]");
+}
@@ -0,0 +1,303 @@
+use aqua_vm::{exceptions, VMError};
+
+mod common;
+use common::{global, run};
+
+/// `running_sum(n)`：产出 0..n，每次把 `send()` 发送的值累加到 total，返回 total
+const RUNNING_SUM: &str = r#"
+.func running_sum(n)
+.local i, total, sent
+    LOAD_CONST 0
+    STORE_LOCAL i
+    LOAD_CONST 0
+    STORE_LOCAL total
+loop:
+    LOAD_LOCAL i
+    LOAD_LOCAL n
+    LT
+    JUMP_IF_FALSE done
+    LOAD_LOCAL i
+    YIELD_VALUE
+    STORE_LOCAL sent
+    LOAD_LOCAL sent
+    JUMP_IF_FALSE next
+    LOAD_LOCAL total
+    LOAD_LOCAL sent
+    ADD
+    STORE_LOCAL total
+next:
+    LOAD_LOCAL i
+    LOAD_CONST 1
+    ADD
+    STORE_LOCAL i
+    JUMP loop
+done:
+    LOAD_LOCAL total
+    RETURN
+.end
+"#;
+
+#[test]
+fn generators_drive_iteration_and_send() {
+    let vm = run(&format!(
+        "{}{}",
+        RUNNING_SUM,
+        r#"
+.global items, gen, first, second, result, after, kind
+    BUILD_LIST 0
+    STORE_GLOBAL items
+    LOAD_FUNC running_sum
+    LOAD_CONST 3
+    CALL 1
+    GET_ITER
+each:
+    FOR_ITER end
+    STORE_GLOBAL gen
+    LOAD_GLOBAL items
+    LOAD_GLOBAL gen
+    CALL_METHOD append 1
+    POP
+    JUMP each
+end:
+    LOAD_FUNC running_sum
+    LOAD_CONST 5
+    CALL 1
+    STORE_GLOBAL gen
+    LOAD_FUNC next
+    LOAD_GLOBAL gen
+    CALL 1
+    STORE_GLOBAL first
+    LOAD_GLOBAL gen
+    LOAD_CONST 10
+    CALL_METHOD send 1
+    STORE_GLOBAL second
+    TRY_BEGIN
+    LOAD_GLOBAL gen
+    LOAD_CONST 20
+    CALL_METHOD send 1
+    POP
+    LOAD_GLOBAL gen
+    LOAD_CONST 12
+    CALL_METHOD send 1
+    POP
+    LOAD_GLOBAL gen
+    LOAD_CONST 0
+    CALL_METHOD send 1
+    POP
+    LOAD_GLOBAL gen
+    LOAD_CONST 0
+    CALL_METHOD send 1
+    POP
+    TRY_END
+    CATCH_BEGIN StopIteration
+    GET_ATTR value
+    STORE_GLOBAL result
+    CATCH_END
+    LOAD_FUNC next
+    LOAD_GLOBAL gen
+    LOAD_CONST "exhausted"
+    CALL 2
+    STORE_GLOBAL after
+    LOAD_FUNC running_sum
+    LOAD_CONST 1
+    CALL 1
+    STORE_GLOBAL kind
+"#
+    ))
+    .unwrap();
+
+    assert_eq!(global(&vm, "items"), "[0, 1, 2]");
+    assert_eq!(global(&vm, "first"), "0");
+    assert_eq!(global(&vm, "second"), "1");
+    assert_eq!(global(&vm, "result"), "42");
+    assert_eq!(global(&vm, "after"), "'exhausted'");
+    assert_eq!(global(&vm, "kind"), "<generator object running_sum>");
+}
+
+#[test]
+fn yield_from_delegates_and_close_runs_finally_blocks() {
+    let vm = run(&format!(
+        "{}{}",
+        RUNNING_SUM,
+        r#"
+.global log, outer_result, gen, closed
+.func outer()
+.local result
+    LOAD_FUNC running_sum
+    LOAD_CONST 2
+    CALL 1
+    GET_ITER
+    LOAD_CONST None
+    YIELD_FROM
+    STORE_LOCAL result
+    TRY_BEGIN
+    LOAD_CONST ("a", "b")
+    GET_ITER
+    LOAD_CONST None
+    YIELD_FROM
+    POP
+    TRY_END
+    FINALLY_BEGIN
+    LOAD_GLOBAL log
+    LOAD_CONST "cleanup"
+    CALL_METHOD append 1
+    POP
+    FINALLY_END
+    LOAD_LOCAL result
+    RETURN
+.end
+    BUILD_LIST 0
+    STORE_GLOBAL log
+    LOAD_FUNC outer
+    CALL 0
+    STORE_GLOBAL gen
+    LOAD_GLOBAL gen
+    LOAD_CONST None
+    CALL_METHOD send 1
+    POP
+    LOAD_GLOBAL gen
+    LOAD_CONST 7
+    CALL_METHOD send 1
+    POP
+    TRY_BEGIN
+    LOAD_GLOBAL gen
+    CALL_METHOD __next__ 0
+    POP
+    LOAD_GLOBAL gen
+    CALL_METHOD __next__ 0
+    POP
+    LOAD_GLOBAL gen
+    CALL_METHOD __next__ 0
+    POP
+    TRY_END
+    CATCH_BEGIN StopIteration
+    GET_ATTR value
+    STORE_GLOBAL outer_result
+    CATCH_END
+    LOAD_FUNC outer
+    CALL 0
+    STORE_GLOBAL gen
+    LOAD_FUNC next
+    LOAD_GLOBAL gen
+    CALL 1
+    POP
+    LOAD_FUNC next
+    LOAD_GLOBAL gen
+    CALL 1
+    POP
+    LOAD_FUNC next
+    LOAD_GLOBAL gen
+    CALL 1
+    STORE_GLOBAL closed
+    LOAD_GLOBAL gen
+    CALL_METHOD close 0
+    POP
+    LOAD_FUNC next
+    LOAD_GLOBAL gen
+    LOAD_CONST "closed"
+    CALL 2
+    STORE_GLOBAL closed
+"#
+    ))
+    .unwrap();
+
+    // 子生成器的返回值成为 `yield from` 表达式的值，finally 块在正常结束和关闭时都会执行
+    assert_eq!(global(&vm, "outer_result"), "7");
+    assert_eq!(global(&vm, "log"), "['cleanup', 'cleanup']");
+    assert_eq!(global(&vm, "closed"), "'closed'");
+}
+
+#[test]
+fn generator_misuse_is_reported_like_python() {
+    let error = |source: &str| match run(&format!("{}{}", RUNNING_SUM, source)) {
+        Err(error) => error.to_string(),
+        Ok(_) => panic!("expected an error"),
+    };
+
+    assert!(error(
+        "    LOAD_FUNC running_sum\n    LOAD_CONST 1\n    CALL 1\n    LOAD_CONST 5\n    CALL_METHOD send 1\n"
+    )
+    .contains("can't send non-None value to a just-started generator"));
+
+    // 生成器中逃逸的 StopIteration 转换为 RuntimeError
+    let leaked = r#"
+.func leaky()
+    LOAD_FUNC next
+    BUILD_LIST 0
+    GET_ITER
+    CALL 1
+    YIELD_VALUE
+    RETURN
+.end
+    LOAD_FUNC leaky
+    CALL 0
+    GET_ITER
+each:
+    FOR_ITER end
+    POP
+    JUMP each
+end:
+"#;
+    assert!(error(leaked).contains("generator raised StopIteration"));
+
+    match run(
+        ".func plain()\n    LOAD_CONST 1\n    RETURN\n.end\n    LOAD_CONST 1\n    YIELD_VALUE\n",
+    ) {
+        Err(VMError::Verify { reason, .. }) => {
+            assert_eq!(reason, "YIELD_VALUE outside a generator function")
+        }
+        other => panic!("expected a verify error, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn nested_generator_resumes_raise_recursion_error() {
+    // `nest(n)` 的第一个值是 `next(nest(n - 1))`，每层恢复都嵌套一个执行循环
+    let vm = run(r#"
+.global shallow, deep
+.func nest(n)
+    LOAD_LOCAL n
+    JUMP_IF_FALSE bottom
+    LOAD_FUNC next
+    LOAD_FUNC nest
+    LOAD_LOCAL n
+    LOAD_CONST 1
+    SUB
+    CALL 1
+    CALL 1
+    YIELD_VALUE
+    POP
+    LOAD_CONST None
+    RETURN
+bottom:
+    LOAD_CONST "bottom"
+    YIELD_VALUE
+    POP
+    LOAD_CONST None
+    RETURN
+.end
+    LOAD_FUNC next
+    LOAD_FUNC nest
+    LOAD_CONST 10
+    CALL 1
+    CALL 1
+    STORE_GLOBAL shallow
+    TRY_BEGIN
+    LOAD_FUNC next
+    LOAD_FUNC nest
+    LOAD_CONST 2000
+    CALL 1
+    CALL 1
+    STORE_GLOBAL deep
+    TRY_END
+    CATCH_BEGIN RecursionError
+    STORE_GLOBAL deep
+    CATCH_END
+"#)
+    .unwrap();
+    assert_eq!(global(&vm, "shallow"), "'bottom'");
+    assert_eq!(
+        exceptions::describe(&vm.get_global("deep").unwrap()),
+        "RecursionError: maximum recursion depth exceeded"
+    );
+}
@@ -0,0 +1,165 @@
+use aqua_vm::builtins::BuiltinFunction;
+use aqua_vm::iter::Range;
+use aqua_vm::{VMError, Value};
+use std::rc::Rc;
+
+mod common;
+use common::{global, run};
+
+fn range(start: i64, stop: i64, step: i64) -> Value {
+    Value::Range(Rc::new(Range::new(start, stop, step).unwrap()))
+}
+
+#[test]
+fn for_loop_over_large_range_is_lazy() {
+    let vm = run(r#"
+.global total
+    LOAD_CONST 0
+    STORE_GLOBAL total
+    LOAD_FUNC range
+    LOAD_CONST 1000000
+    CALL 1
+    GET_ITER
+loop:
+    FOR_ITER done
+    LOAD_GLOBAL total
+    ADD
+    STORE_GLOBAL total
+    JUMP loop
+done:
+"#)
+    .unwrap();
+    assert_eq!(global(&vm, "total"), "499999500000");
+}
+
+#[test]
+fn iterates_native_values() {
+    let vm = run(r#"
+.global keys, squares
+    BUILD_LIST 0
+    LOAD_CONST "b"
+    LOAD_CONST 1
+    LOAD_CONST "a"
+    LOAD_CONST 2
+    BUILD_DICT 2
+    GET_ITER
+keys:
+    FOR_ITER keys_done
+    LIST_APPEND
+    JUMP keys
+keys_done:
+    STORE_GLOBAL keys
+    BUILD_LIST 0
+    LOAD_FUNC range
+    LOAD_CONST 10
+    LOAD_CONST 0
+    LOAD_CONST -3
+    CALL 3
+    GET_ITER
+squares:
+    FOR_ITER squares_done
+    DUP
+    MUL
+    LIST_APPEND
+    JUMP squares
+squares_done:
+    STORE_GLOBAL squares
+"#)
+    .unwrap();
+    assert_eq!(global(&vm, "keys"), "['b', 'a']");
+    assert_eq!(global(&vm, "squares"), "[100, 49, 16, 1]");
+
+    let chars: Vec<String> = match Value::String("héllo".into()).iter().unwrap() {
+        Value::Iterator(iter) => std::iter::from_fn(|| iter.borrow_mut().next_item().unwrap())
+            .map(|c| c.to_string())
+            .collect(),
+        other => panic!("not an iterator: {}", other.repr()),
+    };
+    assert_eq!(chars, ["h", "é", "l", "l", "o"]);
+}
+
+#[test]
+fn range_behaves_like_python() {
+    let r = range(2, 20, 3);
+    assert_eq!(r.repr(), "range(2, 20, 3)");
+    assert_eq!(range(0, 5, 1).repr(), "range(0, 5)");
+    assert_eq!(r.length().unwrap(), 6);
+    assert_eq!(range(10, 0, -3).length().unwrap(), 4);
+    assert_eq!(range(0, -5, 1).length().unwrap(), 0);
+    assert_eq!(r.get_item(&Value::Int(-1)).unwrap().repr(), "17");
+    assert!(r.contains(&Value::Int(11)).unwrap());
+    assert!(!r.contains(&Value::Int(12)).unwrap());
+    assert!(r.contains(&Value::Float(14.0)).unwrap());
+    assert!(!r.contains(&Value::String("5".into())).unwrap());
+    assert!(range(0, 0, 1) == range(5, 2, 1));
+    assert!(range(1, 2, 1) == range(1, 3, 5));
+    assert_eq!(
+        range(0, 10, 2).hash_key().unwrap(),
+        range(0, 9, 2).hash_key().unwrap()
+    );
+
+    let err = BuiltinFunction::Range
+        .call(&[Value::Int(0), Value::Int(5), Value::Int(0)])
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Value error: range() arg 3 must not be zero"
+    );
+    let err = BuiltinFunction::Range
+        .call(&[Value::Float(1.5)])
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Type error: 'float' object cannot be interpreted as an integer"
+    );
+}
+
+#[test]
+fn next_and_iteration_errors() {
+    let iterator = BuiltinFunction::Iter.call(&[range(0, 2, 1)]).unwrap();
+    assert_eq!(iterator.type_name(), "range_iterator");
+    let next = |default: Option<Value>| {
+        let mut args = vec![iterator.clone()];
+        args.extend(default);
+        BuiltinFunction::Next.call(&args)
+    };
+    assert_eq!(next(None).unwrap().repr(), "0");
+    assert_eq!(next(None).unwrap().repr(), "1");
+    assert_eq!(next(Some(Value::Null)).unwrap().repr(), "None");
+    assert!(matches!(next(None), Err(VMError::StopIteration)));
+
+    let err = run(r#"
+    LOAD_CONST 5
+    GET_ITER
+"#)
+    .err()
+    .unwrap();
+    assert_eq!(err.to_string(), "Type error: 'int' object is not iterable");
+
+    // 迭代期间向字典添加键
+    let err = run(r#"
+.global d
+    LOAD_CONST "a"
+    LOAD_CONST 1
+    BUILD_DICT 1
+    STORE_GLOBAL d
+    LOAD_GLOBAL d
+    GET_ITER
+loop:
+    FOR_ITER done
+    LOAD_GLOBAL d
+    ROT_TWO
+    LOAD_CONST "x"
+    ADD
+    LOAD_CONST 0
+    SET_ITEM
+    JUMP loop
+done:
+"#)
+    .err()
+    .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "Runtime error: dictionary changed size during iteration"
+    );
+}
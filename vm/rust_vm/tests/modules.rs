@@ -0,0 +1,284 @@
+use aqua_vm::exceptions;
+use aqua_vm::vm::VMConfig;
+use aqua_vm::{bytecode, AquaVM, VMError};
+use std::path::{Path, PathBuf};
+
+/// 每个测试使用独立的模块目录
+fn module_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("aqua-modules-{}-{}", std::process::id(), test));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// 把汇编源码写成 `dir` 下的模块文件，`name` 中的点对应子目录
+fn write_module(dir: &Path, name: &str, source: &str) {
+    let path = dir.join(name.replace('.', "/")).with_extension("acode");
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    bytecode::assemble(source).unwrap().write_to(path).unwrap();
+}
+
+fn run(dir: &Path, source: &str) -> Result<AquaVM, VMError> {
+    let config = VMConfig {
+        module_paths: vec![dir.to_path_buf()],
+        ..VMConfig::default()
+    };
+    let mut vm = AquaVM::with_config(config);
+    vm.load_bytecode(&bytecode::assemble(source).unwrap())?;
+    vm.run()?;
+    Ok(vm)
+}
+
+fn global(vm: &AquaVM, name: &str) -> String {
+    vm.get_global(name).unwrap().repr()
+}
+
+/// 顶层代码把 `count` 置零，`bump()` 修改模块自己的全局变量 `count`
+const COUNTER: &str = r#"
+.global count, bump
+.func bump()
+    LOAD_GLOBAL count
+    LOAD_CONST 1
+    ADD
+    STORE_GLOBAL count
+    LOAD_GLOBAL count
+    RETURN
+.end
+    LOAD_CONST 0
+    STORE_GLOBAL count
+    LOAD_FUNC bump
+    STORE_GLOBAL bump
+    HALT
+"#;
+
+#[test]
+fn modules_are_cached_and_keep_their_own_globals() {
+    let dir = module_dir("cache");
+    write_module(&dir, "counter", COUNTER);
+
+    let vm = run(
+        &dir,
+        r#"
+.global first, second, bump, count, result, same
+    LOAD_CONST "counter"
+    IMPORT_MODULE
+    STORE_GLOBAL first
+    LOAD_CONST "counter"
+    LOAD_CONST ("bump",)
+    IMPORT_FROM
+    STORE_GLOBAL bump
+    LOAD_GLOBAL bump
+    CALL 0
+    POP
+    LOAD_CONST "counter"
+    IMPORT_MODULE
+    STORE_GLOBAL second
+    LOAD_GLOBAL second
+    CALL_METHOD bump 0
+    STORE_GLOBAL result
+    LOAD_GLOBAL first
+    LOAD_GLOBAL second
+    EQ
+    STORE_GLOBAL same
+"#,
+    )
+    .unwrap();
+
+    // 两次导入得到同一个模块，顶层代码只执行一次，第二次导入没有把 count 置零
+    assert_eq!(global(&vm, "same"), "True");
+    assert_eq!(global(&vm, "first"), "<module 'counter'>");
+    assert_eq!(global(&vm, "result"), "2");
+    // 导入的函数修改的是定义它的模块的全局变量
+    assert_eq!(global(&vm, "count"), "None");
+
+    // 缓存属于虚拟机，新的虚拟机重新执行模块
+    let vm = run(
+        &dir,
+        r#"
+.global count
+    LOAD_CONST "counter"
+    IMPORT_MODULE
+    GET_ATTR count
+    STORE_GLOBAL count
+"#,
+    )
+    .unwrap();
+    assert_eq!(global(&vm, "count"), "0");
+}
+
+#[test]
+fn from_import_binds_names_in_source_order() {
+    let dir = module_dir("from");
+    write_module(
+        &dir,
+        "consts",
+        r#"
+.global PI, E
+    LOAD_CONST 3
+    STORE_GLOBAL PI
+    LOAD_CONST 2
+    STORE_GLOBAL E
+"#,
+    );
+
+    let vm = run(
+        &dir,
+        r#"
+.global E, PI
+    LOAD_CONST "consts"
+    LOAD_CONST ("E", "PI")
+    IMPORT_FROM
+    STORE_GLOBAL E
+    STORE_GLOBAL PI
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+        (global(&vm, "E"), global(&vm, "PI")),
+        ("2".into(), "3".into())
+    );
+
+    let error = run(
+        &dir,
+        r#"
+    LOAD_CONST "consts"
+    LOAD_CONST ("TAU",)
+    IMPORT_FROM
+    POP
+"#,
+    )
+    .err()
+    .unwrap();
+    assert_eq!(
+        error.to_string(),
+        "Import error: cannot import name 'TAU' from 'consts'"
+    );
+}
+
+#[test]
+fn circular_imports_report_the_chain() {
+    let dir = module_dir("cycle");
+    write_module(
+        &dir,
+        "a",
+        "    LOAD_CONST \"b\"\n    IMPORT_MODULE\n    POP\n",
+    );
+    write_module(
+        &dir,
+        "b",
+        "    LOAD_CONST \"a\"\n    IMPORT_MODULE\n    POP\n",
+    );
+
+    let error = run(&dir, "    LOAD_CONST \"a\"\n    IMPORT_MODULE\n    POP\n")
+        .err()
+        .unwrap();
+    match error {
+        VMError::ImportError(message) => assert_eq!(message, "circular import: a -> b -> a"),
+        other => panic!("expected import error, got {:?}", other),
+    }
+}
+
+#[test]
+fn missing_modules_raise_catchable_errors() {
+    let dir = module_dir("missing");
+    let vm = run(
+        &dir,
+        r#"
+.global ImportError, caught
+    TRY_BEGIN
+    LOAD_CONST "no.such.module"
+    IMPORT_MODULE
+    POP
+    TRY_END
+    CATCH_BEGIN ImportError
+    STORE_GLOBAL caught
+    CATCH_END
+"#,
+    )
+    .unwrap();
+    let caught = vm.get_global("caught").unwrap();
+    assert_eq!(
+        exceptions::describe(&caught),
+        "ModuleNotFoundError: No module named 'no.such.module'"
+    );
+}
+
+#[test]
+fn aqua_path_extends_the_search_path() {
+    let dir = module_dir("env");
+    write_module(&dir, "pkg.util", COUNTER);
+    std::env::set_var(aqua_vm::module::AQUA_PATH, &dir);
+
+    let mut vm = AquaVM::new();
+    let program = r#"
+.global result
+    LOAD_CONST "pkg.util"
+    IMPORT_MODULE
+    CALL_METHOD bump 0
+    STORE_GLOBAL result
+"#;
+    vm.load_bytecode(&bytecode::assemble(program).unwrap())
+        .unwrap();
+    vm.run().unwrap();
+    assert_eq!(global(&vm, "result"), "1");
+}
+
+#[test]
+fn stdlib_path_is_searched_after_module_paths() {
+    let stdlib = module_dir("stdlib");
+    write_module(&stdlib, "counter", COUNTER);
+    write_module(
+        &stdlib,
+        "shadowed",
+        r#"
+.global origin
+    LOAD_CONST "stdlib"
+    STORE_GLOBAL origin
+    HALT
+"#,
+    );
+    let dir = module_dir("stdlib-shadow");
+    write_module(
+        &dir,
+        "shadowed",
+        r#"
+.global origin
+    LOAD_CONST "program"
+    STORE_GLOBAL origin
+    HALT
+"#,
+    );
+
+    let mut vm = AquaVM::with_config(VMConfig {
+        module_paths: vec![dir],
+        stdlib_path: Some(stdlib),
+        ..VMConfig::default()
+    });
+    let program = r#"
+.global result, origin
+    LOAD_CONST "counter"
+    IMPORT_MODULE
+    CALL_METHOD bump 0
+    STORE_GLOBAL result
+    LOAD_CONST "shadowed"
+    IMPORT_MODULE
+    GET_ATTR origin
+    STORE_GLOBAL origin
+    HALT
+"#;
+    vm.load_bytecode(&bytecode::assemble(program).unwrap())
+        .unwrap();
+    vm.run().unwrap();
+    assert_eq!(global(&vm, "result"), "1");
+    assert_eq!(global(&vm, "origin"), "'program'");
+}
+
+#[test]
+fn default_stdlib_is_the_repository_stdlib() {
+    let expected = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../../stdlib")
+        .canonicalize()
+        .unwrap();
+    let found = VMConfig::default().stdlib_path.unwrap();
+    assert_eq!(found.canonicalize().unwrap(), expected);
+    assert!(found.join("math.aqua").is_file());
+}
@@ -0,0 +1,377 @@
+use aqua_vm::{AquaVM, VMError};
+
+mod common;
+use common::{global, run};
+
+/// `Counter` 类：`__init__(self, start)` 保存初值，`add(self, n)` 累加并返回新值
+const COUNTER: &str = r#"
+.global Counter
+.func Counter.__init__(self, start)
+    LOAD_VAR self
+    LOAD_VAR start
+    LOAD_CONST "value"
+    SET_ATTR
+    LOAD_CONST None
+    RETURN
+.end
+.func Counter.add(self, n)
+    LOAD_VAR self
+    LOAD_VAR self
+    GET_ATTR value
+    LOAD_VAR n
+    ADD
+    LOAD_CONST "value"
+    SET_ATTR
+    LOAD_VAR self
+    GET_ATTR value
+    RETURN
+.end
+    LOAD_CONST "__init__"
+    LOAD_CONST "Counter.__init__"
+    LOAD_CONST "add"
+    LOAD_CONST "Counter.add"
+    BUILD_DICT 2
+    CREATE_CLASS Counter
+    STORE_GLOBAL Counter
+"#;
+
+fn run_with_counter(body: &str) -> Result<AquaVM, VMError> {
+    run(&format!("{}{}", COUNTER, body))
+}
+
+#[test]
+fn classes_construct_instances_and_bind_methods() {
+    let vm = run_with_counter(
+        r#"
+.global c, first, second, bound, name, has_add, has_missing
+    LOAD_GLOBAL Counter
+    LOAD_CONST 10
+    CALL 1
+    STORE_GLOBAL c
+    LOAD_GLOBAL c
+    LOAD_CONST 5
+    CALL_METHOD add 1
+    STORE_GLOBAL first
+    LOAD_GLOBAL c
+    GET_ATTR add
+    STORE_GLOBAL bound
+    LOAD_GLOBAL bound
+    LOAD_CONST 7
+    CALL 1
+    STORE_GLOBAL second
+    LOAD_GLOBAL Counter
+    GET_ATTR __name__
+    STORE_GLOBAL name
+    LOAD_GLOBAL c
+    HAS_ATTR add
+    STORE_GLOBAL has_add
+    LOAD_GLOBAL c
+    HAS_ATTR missing
+    STORE_GLOBAL has_missing
+"#,
+    )
+    .unwrap();
+    assert_eq!(global(&vm, "first"), "15");
+    assert_eq!(global(&vm, "second"), "22");
+    assert_eq!(global(&vm, "c"), "<Counter object>");
+    assert_eq!(global(&vm, "name"), "'Counter'");
+    assert_eq!(global(&vm, "has_add"), "True");
+    assert_eq!(global(&vm, "has_missing"), "False");
+    assert!(global(&vm, "bound").starts_with("<bound method Counter.add of"));
+}
+
+#[test]
+fn create_object_and_unbound_class_methods() {
+    let vm = run_with_counter(
+        r#"
+.global c, total
+    LOAD_GLOBAL Counter
+    LOAD_CONST 1
+    CREATE_OBJECT 1
+    STORE_GLOBAL c
+    LOAD_GLOBAL Counter
+    LOAD_GLOBAL c
+    LOAD_CONST 2
+    CALL_METHOD add 2
+    STORE_GLOBAL total
+"#,
+    )
+    .unwrap();
+    assert_eq!(global(&vm, "total"), "3");
+}
+
+#[test]
+fn attribute_errors_match_python() {
+    let error = |body: &str| run_with_counter(body).err().unwrap().to_string();
+
+    assert_eq!(
+        error(
+            r#"
+    LOAD_GLOBAL Counter
+    LOAD_CONST 0
+    CALL 1
+    GET_ATTR size
+"#
+        ),
+        "Attribute error: 'Counter' object has no attribute 'size'"
+    );
+    assert_eq!(
+        error(
+            r#"
+    LOAD_GLOBAL Counter
+    GET_ATTR size
+"#
+        ),
+        "Attribute error: type object 'Counter' has no attribute 'size'"
+    );
+    assert_eq!(
+        error(
+            r#"
+    LOAD_CONST 5
+    CALL_METHOD upper 0
+"#
+        ),
+        "Attribute error: 'int' object has no attribute 'upper'"
+    );
+    assert_eq!(
+        error(
+            r#"
+    LOAD_GLOBAL Counter
+    LOAD_CONST 1
+    LOAD_CONST 2
+    SET_ATTR
+"#
+        ),
+        "Type error: attribute name must be string, not 'int'"
+    );
+    assert_eq!(
+        error(
+            r#"
+    LOAD_GLOBAL Counter
+    LOAD_CONST 1
+    LOAD_CONST "size"
+    SET_ATTR
+"#
+        ),
+        "Type error: cannot set 'size' attribute of immutable type 'Counter'"
+    );
+    assert_eq!(
+        error(
+            r#"
+    LOAD_CONST 5
+    LOAD_CONST 1
+    CALL 0
+"#
+        ),
+        "Type error: 'int' object is not callable"
+    );
+}
+
+#[test]
+fn constructor_arguments_are_checked() {
+    let err = run(r#"
+.global Empty
+    BUILD_DICT 0
+    CREATE_CLASS Empty
+    STORE_GLOBAL Empty
+    LOAD_GLOBAL Empty
+    LOAD_CONST 1
+    CALL 1
+"#)
+    .err()
+    .unwrap();
+    assert_eq!(err.to_string(), "Type error: Empty() takes no arguments");
+
+    let err = run(r#"
+.func Bad.__init__(self)
+    LOAD_CONST 1
+    RETURN
+.end
+.global Bad
+    LOAD_CONST "__init__"
+    LOAD_CONST "Bad.__init__"
+    BUILD_DICT 1
+    CREATE_CLASS Bad
+    STORE_GLOBAL Bad
+    LOAD_GLOBAL Bad
+    CALL 0
+"#)
+    .err()
+    .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "Type error: __init__() should return None, not 'int'"
+    );
+
+    let err = run(r#"
+    LOAD_CONST "run"
+    LOAD_CONST "Missing.run"
+    BUILD_DICT 1
+    CREATE_CLASS Missing
+"#)
+    .err()
+    .unwrap();
+    assert!(matches!(err, VMError::FunctionNotFound(name) if name == "Missing.run"));
+}
+
+#[test]
+fn native_methods_on_builtin_types() {
+    let vm = run(r#"
+.global items, popped, words, joined, value
+    BUILD_LIST 0
+    STORE_GLOBAL items
+    LOAD_GLOBAL items
+    LOAD_CONST 3
+    CALL_METHOD append 1
+    POP
+    LOAD_GLOBAL items
+    LOAD_CONST 1
+    CALL_METHOD append 1
+    POP
+    LOAD_GLOBAL items
+    LOAD_CONST 2
+    CALL_METHOD append 1
+    POP
+    LOAD_GLOBAL items
+    CALL_METHOD sort 0
+    POP
+    LOAD_GLOBAL items
+    CALL_METHOD pop 0
+    STORE_GLOBAL popped
+    LOAD_CONST "a b  c"
+    CALL_METHOD split 0
+    STORE_GLOBAL words
+    LOAD_CONST "-"
+    LOAD_GLOBAL words
+    CALL_METHOD join 1
+    CALL_METHOD upper 0
+    STORE_GLOBAL joined
+    LOAD_CONST "k"
+    LOAD_CONST 1
+    BUILD_DICT 1
+    LOAD_CONST "missing"
+    LOAD_CONST 0
+    CALL_METHOD get 2
+    STORE_GLOBAL value
+"#)
+    .unwrap();
+    assert_eq!(global(&vm, "items"), "[1, 2]");
+    assert_eq!(global(&vm, "popped"), "3");
+    assert_eq!(global(&vm, "words"), "['a', 'b', 'c']");
+    assert_eq!(global(&vm, "joined"), "'A-B-C'");
+    assert_eq!(global(&vm, "value"), "0");
+}
+
+/// `Base.who` 返回 "base"，`Child.who` 通过 `super()` 调用父类实现
+const HIERARCHY: &str = r#"
+.global Base, Child
+.func Base.who(self)
+    LOAD_CONST "base"
+    RETURN
+.end
+.func Child.who(self)
+    LOAD_CONST "child/"
+    LOAD_FUNC super
+    CALL 0
+    CALL_METHOD who 0
+    ADD
+    RETURN
+.end
+    LOAD_CONST "who"
+    LOAD_CONST "Base.who"
+    BUILD_DICT 1
+    CREATE_CLASS Base
+    STORE_GLOBAL Base
+    LOAD_GLOBAL Base
+    LOAD_CONST "who"
+    LOAD_CONST "Child.who"
+    BUILD_DICT 1
+    CREATE_CLASS Child 1
+    STORE_GLOBAL Child
+"#;
+
+#[test]
+fn inheritance_and_super_calls() {
+    let vm = run(&format!(
+        "{}{}",
+        HIERARCHY,
+        r#"
+.global Leaf, who, is_base, is_sub, mro
+    LOAD_GLOBAL Child
+    BUILD_DICT 0
+    CREATE_CLASS Leaf 1
+    STORE_GLOBAL Leaf
+    LOAD_GLOBAL Leaf
+    CALL 0
+    CALL_METHOD who 0
+    STORE_GLOBAL who
+    LOAD_FUNC isinstance
+    LOAD_GLOBAL Leaf
+    CALL 0
+    LOAD_GLOBAL Base
+    CALL 2
+    STORE_GLOBAL is_base
+    LOAD_FUNC issubclass
+    LOAD_GLOBAL Base
+    LOAD_GLOBAL Child
+    CALL 2
+    STORE_GLOBAL is_sub
+    LOAD_GLOBAL Leaf
+    GET_ATTR __mro__
+    STORE_GLOBAL mro
+"#
+    ))
+    .unwrap();
+    assert_eq!(global(&vm, "who"), "'child/base'");
+    assert_eq!(global(&vm, "is_base"), "True");
+    assert_eq!(global(&vm, "is_sub"), "False");
+    assert_eq!(
+        global(&vm, "mro"),
+        "(<class 'Leaf'>, <class 'Child'>, <class 'Base'>)"
+    );
+}
+
+#[test]
+fn inconsistent_hierarchies_are_rejected() {
+    // class Bad(Base, Child)：Base 必须排在它的子类 Child 之后
+    let err = run(&format!(
+        "{}{}",
+        HIERARCHY,
+        r#"
+    LOAD_GLOBAL Base
+    LOAD_GLOBAL Child
+    BUILD_DICT 0
+    CREATE_CLASS Bad 2
+"#
+    ))
+    .err()
+    .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "Type error: Cannot create a consistent method resolution order (MRO) for bases Base, Child"
+    );
+
+    let err = run(r#"
+    LOAD_FUNC super
+    CALL 0
+"#)
+    .err()
+    .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "Runtime error: super(): __class__ cell not found"
+    );
+
+    let err = run(r#"
+    LOAD_FUNC isinstance
+    LOAD_CONST 1
+    LOAD_CONST "int"
+    CALL 2
+"#)
+    .err()
+    .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "Type error: isinstance() arg 2 must be a type, a tuple of types, or a union"
+    );
+}
@@ -0,0 +1,138 @@
+use aqua_vm::{VMError, Value};
+
+mod common;
+use common::{binary, global, run};
+
+fn show(lhs: &str, op: &str, rhs: &str) -> String {
+    binary(lhs, op, rhs).unwrap().repr()
+}
+
+#[test]
+fn arithmetic_follows_python_rules() {
+    assert_eq!(show("7", "MOD", "-3"), "-2");
+    assert_eq!(show("-7", "MOD", "3"), "2");
+    assert_eq!(show("-7.5", "MOD", "2"), "0.5");
+    assert_eq!(show("6.0", "MOD", "-3"), "-0.0");
+    assert_eq!(show("2", "POW", "10"), "1024");
+    assert_eq!(show("2", "POW", "-1"), "0.5");
+    assert_eq!(show("2.0", "POW", "3"), "8.0");
+    assert!(matches!(
+        binary("1", "MOD", "0"),
+        Err(VMError::DivisionByZero)
+    ));
+    assert!(matches!(
+        binary("0", "POW", "-1"),
+        Err(VMError::DivisionByZero)
+    ));
+
+    assert!(matches!(
+        binary("9223372036854775807", "ADD", "1"),
+        Err(VMError::OverflowError(message)) if message == "integer overflow"
+    ));
+
+    let error = binary("\"a\"", "MOD", "1").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Type error: unsupported operand type(s) for %: 'str' and 'int'"
+    );
+}
+
+#[test]
+fn string_repetition_is_bounded() {
+    assert_eq!(show("\"ab\"", "MUL", "3"), "'ababab'");
+    assert_eq!(show("-2", "MUL", "\"ab\""), "''");
+    assert!(matches!(
+        binary("\"ab\"", "MUL", "4611686018427387904"),
+        Err(VMError::OverflowError(message)) if message == "repeated string is too long"
+    ));
+    assert!(matches!(
+        binary("\"ab\"", "MUL", "1099511627776"),
+        Err(VMError::MemoryError)
+    ));
+
+    // 两种错误都可以被脚本捕获
+    let source = r#"
+.global caught
+    TRY_BEGIN
+    LOAD_CONST "ab"
+    LOAD_CONST 1099511627776
+    MUL
+    POP
+    TRY_END
+    CATCH_BEGIN MemoryError
+    POP
+    LOAD_CONST "MemoryError"
+    STORE_GLOBAL caught
+    CATCH_END
+"#;
+    let vm = run(source).unwrap();
+    assert_eq!(global(&vm, "caught"), "'MemoryError'");
+}
+
+#[test]
+fn comparisons_promote_numbers_and_order_strings() {
+    assert_eq!(show("1", "EQ", "1.0"), "True");
+    assert_eq!(show("true", "EQ", "1"), "True");
+    assert_eq!(show("\"1\"", "EQ", "1"), "False");
+    assert_eq!(show("\"1\"", "NE", "1"), "True");
+    assert_eq!(show("1", "LT", "1.5"), "True");
+    assert_eq!(show("\"apple\"", "LT", "\"banana\""), "True");
+    assert_eq!(show("\"b\"", "GE", "\"ab\""), "True");
+    assert_eq!(show("2", "LE", "2"), "True");
+    assert_eq!(show("3", "GT", "4"), "False");
+
+    let error = binary("\"a\"", "LT", "1").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Type error: '<' not supported between instances of 'str' and 'int'"
+    );
+}
+
+#[test]
+fn membership_and_logic() {
+    assert_eq!(show("\"ell\"", "IN", "\"hello\""), "True");
+    assert_eq!(show("\"z\"", "IN", "\"hello\""), "False");
+    let error = binary("1", "IN", "\"hello\"").unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("requires string as left operand, not int"));
+    let error = binary("1", "IN", "2").unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("argument of type 'int' is not iterable"));
+
+    assert_eq!(show("0", "AND", "\"x\""), "0");
+    assert_eq!(show("2", "AND", "\"x\""), "'x'");
+    assert_eq!(show("\"\"", "OR", "3"), "3");
+    assert_eq!(show("null", "OR", "null"), "None");
+}
+
+#[test]
+fn short_circuit_skips_right_operand() {
+    // 右侧若被求值，会因为调用未定义的函数而失败
+    let source = r#"
+.global a, b, c
+    LOAD_CONST 0
+    JUMP_IF_FALSE_OR_POP and_end
+    LOAD_CONST "missing"
+    CALL 0
+and_end:
+    STORE_VAR a
+    LOAD_CONST "yes"
+    JUMP_IF_TRUE_OR_POP or_end
+    LOAD_CONST "missing"
+    CALL 0
+or_end:
+    STORE_VAR b
+    LOAD_CONST 1
+    JUMP_IF_FALSE_OR_POP c_end
+    LOAD_CONST 2
+    NOT
+c_end:
+    STORE_VAR c
+"#;
+    let vm = run(source).unwrap();
+    assert_eq!(vm.get_global("a"), Some(Value::Int(0)));
+    assert_eq!(global(&vm, "b"), "'yes'");
+    assert_eq!(vm.get_global("c"), Some(Value::Bool(false)));
+}
@@ -0,0 +1,115 @@
+use aqua_vm::package::Package;
+use aqua_vm::{bytecode, AquaVM, VMError};
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+const MANIFEST: &str = r#"{
+    "name": "demo",
+    "version": "1.2.0",
+    "main": "main.acode",
+    "aqua_version": "1.0",
+    "dependencies": []
+}"#;
+
+/// 由 (包内路径, 内容) 构造 zip 数据；`.acode` 条目的内容是汇编源码
+fn archive(entries: &[(&str, &str)]) -> Cursor<Vec<u8>> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for &(name, content) in entries {
+        writer
+            .start_file(name, SimpleFileOptions::default())
+            .unwrap();
+        let data = match name.strip_suffix(".acode") {
+            Some(_) => bytecode::assemble(content)
+                .unwrap()
+                .to_acode_bytes()
+                .unwrap(),
+            None => content.as_bytes().to_vec(),
+        };
+        writer.write_all(&data).unwrap();
+    }
+    let mut cursor = writer.finish().unwrap();
+    cursor.set_position(0);
+    cursor
+}
+
+fn package_error(entries: &[(&str, &str)]) -> String {
+    match Package::read(archive(entries)) {
+        Err(VMError::Package(message)) => message,
+        other => panic!(
+            "expected package error, got {:?}",
+            other.map(|p| p.manifest)
+        ),
+    }
+}
+
+const MAIN: &str = r#"
+.global total
+    LOAD_CONST "lib"
+    LOAD_CONST ("base",)
+    IMPORT_FROM
+    LOAD_CONST "pkg.util"
+    IMPORT_MODULE
+    GET_ATTR step
+    ADD
+    STORE_GLOBAL total
+"#;
+
+#[test]
+fn runs_main_with_bundled_modules() {
+    let package = Package::read(archive(&[
+        ("manifest.json", MANIFEST),
+        ("main.acode", MAIN),
+        (
+            "lib.acode",
+            ".global base\n    LOAD_CONST 40\n    STORE_GLOBAL base\n",
+        ),
+        (
+            "pkg/util.acode",
+            ".global step\n    LOAD_CONST 2\n    STORE_GLOBAL step\n",
+        ),
+        ("README.txt", "not a module"),
+    ]))
+    .unwrap();
+
+    assert_eq!(package.manifest.name, "demo");
+    let names: Vec<_> = package
+        .modules
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect();
+    assert_eq!(names, ["lib", "pkg.util"]);
+
+    let mut vm = AquaVM::new();
+    vm.load_package(&package).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.get_global("total").unwrap().repr(), "42");
+}
+
+#[test]
+fn rejects_invalid_packages() {
+    let why = package_error(&[("manifest.json", r#"{"name": "demo", "main": "main.acode"}"#)]);
+    assert!(why.contains("missing field `version`"), "{}", why);
+
+    let manifest = MANIFEST.replace(r#""aqua_version": "1.0""#, r#""aqua_version": "^2.1""#);
+    let why = package_error(&[("manifest.json", &manifest), ("main.acode", MAIN)]);
+    assert_eq!(
+        why,
+        "package 'demo' requires AquaScript ^2.1, but this VM implements 1.0.0"
+    );
+
+    let manifest = MANIFEST.replace("1.2.0", "1.2");
+    let why = package_error(&[("manifest.json", &manifest), ("main.acode", MAIN)]);
+    assert!(why.contains("not a semantic version"), "{}", why);
+
+    let why = package_error(&[("manifest.json", MANIFEST)]);
+    assert_eq!(why, "missing main.acode in package");
+
+    match Package::read(Cursor::new(b"plain text".to_vec())) {
+        Err(VMError::Package(why)) => assert!(why.contains("not a valid .apack"), "{}", why),
+        other => panic!(
+            "expected package error, got {:?}",
+            other.map(|p| p.manifest)
+        ),
+    }
+}
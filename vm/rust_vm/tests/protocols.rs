@@ -0,0 +1,453 @@
+use aqua_vm::{exceptions, AquaVM, VMError};
+
+mod common;
+use common::{global, run};
+
+/// `Money(cents)`：支持 `+`（含 `int + Money`）、`==`、`<`、`repr()` 和哈希
+const MONEY: &str = r#"
+.global Money
+.func Money.__init__(self, cents)
+    LOAD_VAR self
+    LOAD_VAR cents
+    LOAD_CONST "cents"
+    SET_ATTR
+    LOAD_CONST None
+    RETURN
+.end
+.func Money.__add__(self, other)
+    LOAD_GLOBAL Money
+    LOAD_VAR self
+    GET_ATTR cents
+    LOAD_VAR other
+    GET_ATTR cents
+    ADD
+    CALL 1
+    RETURN
+.end
+.func Money.__radd__(self, other)
+    LOAD_GLOBAL Money
+    LOAD_VAR other
+    LOAD_VAR self
+    GET_ATTR cents
+    ADD
+    CALL 1
+    RETURN
+.end
+.func Money.__eq__(self, other)
+    LOAD_VAR self
+    GET_ATTR cents
+    LOAD_VAR other
+    GET_ATTR cents
+    EQ
+    RETURN
+.end
+.func Money.__lt__(self, other)
+    LOAD_VAR self
+    GET_ATTR cents
+    LOAD_VAR other
+    GET_ATTR cents
+    LT
+    RETURN
+.end
+.func Money.__hash__(self)
+    LOAD_VAR self
+    GET_ATTR cents
+    RETURN
+.end
+.func Money.__repr__(self)
+    LOAD_CONST "Money("
+    LOAD_FUNC str
+    LOAD_VAR self
+    GET_ATTR cents
+    CALL 1
+    ADD
+    LOAD_CONST ")"
+    ADD
+    RETURN
+.end
+    LOAD_CONST "__init__"
+    LOAD_CONST "Money.__init__"
+    LOAD_CONST "__add__"
+    LOAD_CONST "Money.__add__"
+    LOAD_CONST "__radd__"
+    LOAD_CONST "Money.__radd__"
+    LOAD_CONST "__eq__"
+    LOAD_CONST "Money.__eq__"
+    LOAD_CONST "__lt__"
+    LOAD_CONST "Money.__lt__"
+    LOAD_CONST "__hash__"
+    LOAD_CONST "Money.__hash__"
+    LOAD_CONST "__repr__"
+    LOAD_CONST "Money.__repr__"
+    BUILD_DICT 7
+    CREATE_CLASS Money
+    STORE_GLOBAL Money
+"#;
+
+fn run_with_money(body: &str) -> Result<AquaVM, VMError> {
+    run(&format!("{}{}", MONEY, body))
+}
+
+#[test]
+fn operators_dispatch_to_dunder_methods() {
+    let vm = run_with_money(
+        r#"
+.global sum, reflected, eq, ne, gt, sorted, text
+    LOAD_GLOBAL Money
+    LOAD_CONST 5
+    CALL 1
+    LOAD_GLOBAL Money
+    LOAD_CONST 7
+    CALL 1
+    ADD
+    STORE_GLOBAL sum
+    LOAD_CONST 3
+    LOAD_GLOBAL Money
+    LOAD_CONST 4
+    CALL 1
+    ADD
+    STORE_GLOBAL reflected
+    LOAD_GLOBAL sum
+    LOAD_GLOBAL Money
+    LOAD_CONST 12
+    CALL 1
+    EQ
+    STORE_GLOBAL eq
+    LOAD_GLOBAL sum
+    LOAD_GLOBAL Money
+    LOAD_CONST 12
+    CALL 1
+    NE
+    STORE_GLOBAL ne
+    LOAD_GLOBAL sum
+    LOAD_GLOBAL reflected
+    GT
+    STORE_GLOBAL gt
+    LOAD_GLOBAL sum
+    LOAD_GLOBAL reflected
+    LOAD_GLOBAL Money
+    LOAD_CONST 1
+    CALL 1
+    BUILD_LIST 3
+    STORE_GLOBAL sorted
+    LOAD_GLOBAL sorted
+    CALL_METHOD sort 0
+    POP
+    LOAD_FUNC repr
+    LOAD_GLOBAL sorted
+    CALL 1
+    STORE_GLOBAL text
+"#,
+    )
+    .unwrap();
+    assert_eq!(global(&vm, "eq"), "True");
+    // 未定义 __ne__ 时对 __eq__ 取反
+    assert_eq!(global(&vm, "ne"), "False");
+    // 未定义 __gt__ 时使用右操作数的 __lt__
+    assert_eq!(global(&vm, "gt"), "True");
+    assert_eq!(global(&vm, "text"), "'[Money(1), Money(7), Money(12)]'");
+}
+
+#[test]
+fn instances_as_dict_keys_and_containers() {
+    let vm = run_with_money(
+        r#"
+.global prices, found, member, hashed
+    LOAD_GLOBAL Money
+    LOAD_CONST 100
+    CALL 1
+    LOAD_CONST "dollar"
+    BUILD_DICT 1
+    STORE_GLOBAL prices
+    LOAD_GLOBAL prices
+    LOAD_GLOBAL Money
+    LOAD_CONST 100
+    CALL 1
+    GET_ITEM
+    STORE_GLOBAL found
+    LOAD_GLOBAL Money
+    LOAD_CONST 100
+    CALL 1
+    LOAD_GLOBAL prices
+    IN
+    LOAD_GLOBAL Money
+    LOAD_CONST 2
+    CALL 1
+    LOAD_GLOBAL Money
+    LOAD_CONST 2
+    CALL 1
+    BUILD_TUPLE 1
+    IN
+    BUILD_TUPLE 2
+    STORE_GLOBAL member
+    LOAD_FUNC hash
+    LOAD_GLOBAL Money
+    LOAD_CONST 42
+    CALL 1
+    CALL 1
+    STORE_GLOBAL hashed
+"#,
+    )
+    .unwrap();
+    assert_eq!(global(&vm, "found"), "'dollar'");
+    assert_eq!(global(&vm, "member"), "(True, True)");
+    assert_eq!(global(&vm, "hashed"), "42");
+
+    let err = run_with_money(
+        r#"
+.global prices
+    BUILD_DICT 0
+    STORE_GLOBAL prices
+    LOAD_GLOBAL prices
+    LOAD_GLOBAL Money
+    LOAD_CONST 3
+    CALL 1
+    GET_ITEM
+"#,
+    )
+    .err()
+    .unwrap();
+    assert_eq!(err.to_string(), "Key error: Money(3)");
+}
+
+/// `Deck`：`__len__`、`__getitem__`/`__setitem__`、`__contains__`、`__str__`，
+/// 以及基于内置迭代器的 `__iter__`/`__next__`
+const DECK: &str = r#"
+.global Deck
+.func Deck.__init__(self)
+    LOAD_VAR self
+    LOAD_CONST 1
+    LOAD_CONST 2
+    LOAD_CONST 3
+    BUILD_LIST 3
+    LOAD_CONST "cards"
+    SET_ATTR
+    LOAD_CONST None
+    RETURN
+.end
+.func Deck.__len__(self)
+    LOAD_VAR self
+    GET_ATTR cards
+    LEN
+    RETURN
+.end
+.func Deck.__getitem__(self, i)
+    LOAD_VAR self
+    GET_ATTR cards
+    LOAD_VAR i
+    GET_ITEM
+    RETURN
+.end
+.func Deck.__setitem__(self, i, card)
+    LOAD_VAR self
+    GET_ATTR cards
+    LOAD_VAR i
+    LOAD_VAR card
+    SET_ITEM
+    LOAD_CONST None
+    RETURN
+.end
+.func Deck.__contains__(self, card)
+    LOAD_VAR card
+    LOAD_VAR self
+    GET_ATTR cards
+    IN
+    RETURN
+.end
+.func Deck.__str__(self)
+    LOAD_CONST "Deck"
+    LOAD_FUNC str
+    LOAD_VAR self
+    GET_ATTR cards
+    CALL 1
+    ADD
+    RETURN
+.end
+.func Deck.__iter__(self)
+    LOAD_VAR self
+    LOAD_FUNC iter
+    LOAD_VAR self
+    GET_ATTR cards
+    CALL 1
+    LOAD_CONST "position"
+    SET_ATTR
+    LOAD_VAR self
+    RETURN
+.end
+.func Deck.__next__(self)
+    LOAD_FUNC next
+    LOAD_VAR self
+    GET_ATTR position
+    CALL 1
+    RETURN
+.end
+    LOAD_CONST "__init__"
+    LOAD_CONST "Deck.__init__"
+    LOAD_CONST "__len__"
+    LOAD_CONST "Deck.__len__"
+    LOAD_CONST "__getitem__"
+    LOAD_CONST "Deck.__getitem__"
+    LOAD_CONST "__setitem__"
+    LOAD_CONST "Deck.__setitem__"
+    LOAD_CONST "__contains__"
+    LOAD_CONST "Deck.__contains__"
+    LOAD_CONST "__str__"
+    LOAD_CONST "Deck.__str__"
+    LOAD_CONST "__iter__"
+    LOAD_CONST "Deck.__iter__"
+    LOAD_CONST "__next__"
+    LOAD_CONST "Deck.__next__"
+    BUILD_DICT 8
+    CREATE_CLASS Deck
+    STORE_GLOBAL Deck
+"#;
+
+#[test]
+fn container_protocols_and_iteration() {
+    let vm = run(&format!(
+        "{}{}",
+        DECK,
+        r#"
+.global deck, size, second, has, text, total
+    LOAD_GLOBAL Deck
+    CALL 0
+    STORE_GLOBAL deck
+    LOAD_GLOBAL deck
+    LOAD_CONST 0
+    LOAD_CONST 10
+    SET_ITEM
+    LOAD_FUNC len
+    LOAD_GLOBAL deck
+    CALL 1
+    STORE_GLOBAL size
+    LOAD_GLOBAL deck
+    LOAD_CONST 1
+    GET_ITEM
+    STORE_GLOBAL second
+    LOAD_CONST 10
+    LOAD_GLOBAL deck
+    IN
+    STORE_GLOBAL has
+    LOAD_GLOBAL deck
+    FORMAT_VALUE
+    STORE_GLOBAL text
+    LOAD_CONST 0
+    STORE_GLOBAL total
+    LOAD_GLOBAL deck
+    GET_ITER
+loop:
+    FOR_ITER done
+    LOAD_GLOBAL total
+    ADD
+    STORE_GLOBAL total
+    JUMP loop
+done:
+"#
+    ))
+    .unwrap();
+    assert_eq!(global(&vm, "size"), "3");
+    assert_eq!(global(&vm, "second"), "2");
+    assert_eq!(global(&vm, "has"), "True");
+    assert_eq!(global(&vm, "text"), "'Deck[10, 2, 3]'");
+    assert_eq!(global(&vm, "total"), "15");
+}
+
+#[test]
+fn missing_or_invalid_dunder_methods() {
+    let error = |body: &str| run(&format!("{}{}", DECK, body)).err().unwrap().to_string();
+    assert_eq!(
+        error(
+            r#"
+    LOAD_GLOBAL Deck
+    CALL 0
+    LOAD_CONST 1
+    SUB
+"#
+        ),
+        "Type error: unsupported operand type(s) for -: 'Deck' and 'int'"
+    );
+    assert_eq!(
+        error(
+            r#"
+    LOAD_GLOBAL Deck
+    CALL 0
+    LOAD_GLOBAL Deck
+    CALL 0
+    LT
+"#
+        ),
+        "Type error: '<' not supported between instances of 'Deck' and 'Deck'"
+    );
+
+    let err = run(r#"
+.global Bad
+.func Bad.__str__(self)
+    LOAD_CONST 1
+    RETURN
+.end
+    LOAD_CONST "__str__"
+    LOAD_CONST "Bad.__str__"
+    BUILD_DICT 1
+    CREATE_CLASS Bad
+    STORE_GLOBAL Bad
+    LOAD_FUNC str
+    LOAD_GLOBAL Bad
+    CALL 0
+    CALL 1
+"#)
+    .err()
+    .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "Type error: __str__ returned non-string (type int)"
+    );
+}
+
+#[test]
+fn nested_dunder_calls_raise_recursion_error() {
+    // `Deep() + n` 在 `__add__` 中计算 `self + (n - 1)`，每层都嵌套一个执行循环
+    let deep = |n: usize| {
+        format!(
+            r#"
+.global Deep, result
+.func Deep.__add__(self, n)
+    LOAD_VAR n
+    JUMP_IF_FALSE done
+    LOAD_VAR self
+    LOAD_VAR n
+    LOAD_CONST 1
+    SUB
+    ADD
+    RETURN
+done:
+    LOAD_CONST "bottom"
+    RETURN
+.end
+    LOAD_CONST "__add__"
+    LOAD_CONST "Deep.__add__"
+    BUILD_DICT 1
+    CREATE_CLASS Deep
+    STORE_GLOBAL Deep
+    TRY_BEGIN
+    LOAD_GLOBAL Deep
+    CALL 0
+    LOAD_CONST {}
+    ADD
+    STORE_GLOBAL result
+    TRY_END
+    CATCH_BEGIN RecursionError
+    STORE_GLOBAL result
+    CATCH_END
+"#,
+            n
+        )
+    };
+    let vm = run(&deep(10)).unwrap();
+    assert_eq!(global(&vm, "result"), "'bottom'");
+
+    let vm = run(&deep(2000)).unwrap();
+    assert_eq!(
+        exceptions::describe(&vm.get_global("result").unwrap()),
+        "RecursionError: maximum recursion depth exceeded"
+    );
+}
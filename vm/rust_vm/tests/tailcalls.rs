@@ -0,0 +1,132 @@
+use aqua_vm::vm::VMConfig;
+use aqua_vm::{bytecode, AquaVM, VMError, Value};
+
+fn run(source: &str, max_call_depth: usize) -> Result<AquaVM, VMError> {
+    let mut vm = AquaVM::with_config(VMConfig {
+        max_call_depth,
+        ..VMConfig::default()
+    });
+    vm.load_bytecode(&bytecode::assemble(source)?)?;
+    vm.run()?;
+    Ok(vm)
+}
+
+/// `count(n, total)`：尾递归地把 1..=n 累加到 total 上
+const COUNT: &str = r#"
+.func count(n, total)
+    LOAD_LOCAL n
+    JUMP_IF_FALSE done
+    LOAD_FUNC count
+    LOAD_LOCAL n
+    LOAD_CONST 1
+    SUB
+    LOAD_LOCAL total
+    LOAD_LOCAL n
+    ADD
+    CALL 2
+    RETURN
+done:
+    LOAD_LOCAL total
+    RETURN
+.end
+"#;
+
+#[test]
+fn tail_recursion_runs_in_constant_call_depth() {
+    let vm = run(
+        &format!(
+            "{}{}",
+            COUNT,
+            r#"
+.global sum
+    LOAD_FUNC count
+    LOAD_CONST 100000
+    LOAD_CONST 0
+    CALL 2
+    STORE_GLOBAL sum
+"#
+        ),
+        50,
+    )
+    .unwrap();
+    assert!(matches!(vm.get_global("sum"), Some(Value::Int(5000050000))));
+    assert_eq!(vm.get_stats().peak_call_stack_depth, 2);
+    assert_eq!(vm.get_stats().tail_calls, 100000);
+}
+
+#[test]
+fn tracebacks_mark_elided_tail_calls() {
+    let vm = run(
+        r#"
+.global error
+.func fail(n)
+    LOAD_LOCAL n
+    JUMP_IF_FALSE done
+    LOAD_FUNC fail
+    LOAD_LOCAL n
+    LOAD_CONST 1
+    SUB
+    CALL 1
+    RETURN
+done:
+    LOAD_CONST 1
+    LOAD_LOCAL n
+    DIV
+    RETURN
+.end
+    TRY_BEGIN
+    LOAD_FUNC fail
+    LOAD_CONST 3
+    CALL 1
+    POP
+    TRY_END
+    CATCH_BEGIN ZeroDivisionError
+    STORE_GLOBAL error
+    CATCH_END
+"#,
+        1000,
+    )
+    .unwrap();
+    match vm.get_global("error").unwrap() {
+        Value::Instance(instance) => assert_eq!(
+            instance.attribute("traceback").unwrap().repr(),
+            "['<main> at pc 3', '(3 tail calls elided)', 'fail at pc 10']"
+        ),
+        other => panic!("expected an instance, got {:?}", other),
+    }
+}
+
+#[test]
+fn calls_inside_try_keep_their_frames() {
+    let source = r#"
+.func guarded(n)
+    TRY_BEGIN
+    LOAD_FUNC guarded
+    LOAD_LOCAL n
+    CALL 1
+    RETURN
+    TRY_END
+    CATCH_BEGIN ValueError
+    POP
+    CATCH_END
+    LOAD_CONST None
+    RETURN
+.end
+    LOAD_FUNC guarded
+    LOAD_CONST 0
+    CALL 1
+"#;
+    // 改写只看指令序列，try 中的调用同样被改写，由虚拟机在运行时退回普通调用
+    let mut instructions = bytecode::assemble(source).unwrap().functions["guarded"]
+        .instructions
+        .clone();
+    bytecode::rewrite_tail_calls(&mut instructions);
+    assert_eq!(instructions[3].opcode, bytecode::OpCode::TailCall);
+
+    let error = run(source, 50).err().expect("the recursion is not elided");
+    assert!(
+        error.to_string().contains("Call stack overflow"),
+        "{}",
+        error
+    );
+}
@@ -0,0 +1,63 @@
+use aqua_vm::Value;
+
+mod common;
+use common::run;
+
+#[test]
+fn functions_read_and_write_globals_explicitly() {
+    let vm = run(r#"
+.global counter, total
+.func bump(step)
+.local counter
+    ; 局部变量与全局变量同名，互不影响
+    LOAD_CONST 100
+    STORE_LOCAL counter
+    LOAD_GLOBAL counter
+    LOAD_LOCAL step
+    ADD
+    STORE_GLOBAL counter
+    LOAD_LOCAL counter
+    RETURN
+.end
+    LOAD_CONST 1
+    STORE_GLOBAL counter
+    LOAD_FUNC bump
+    LOAD_CONST 5
+    CALL 1
+    POP
+    LOAD_FUNC bump
+    LOAD_CONST 10
+    CALL 1
+    STORE_GLOBAL total
+"#)
+    .unwrap();
+    assert_eq!(vm.get_global("counter"), Some(Value::Int(16)));
+    assert_eq!(vm.get_global("total"), Some(Value::Int(100)));
+}
+
+#[test]
+fn stack_shuffling() {
+    let vm = run(r#"
+.global a, b, c, d
+    LOAD_CONST 1
+    LOAD_CONST 2
+    LOAD_CONST 3
+    ROT_THREE
+    ; 栈为 (3, 1, 2)
+    STORE_GLOBAL c
+    STORE_GLOBAL b
+    STORE_GLOBAL a
+    LOAD_CONST "x"
+    LOAD_CONST "y"
+    ROT_TWO
+    STORE_GLOBAL d
+    DUP
+    ADD
+    STORE_GLOBAL a
+"#)
+    .unwrap();
+    assert_eq!(vm.get_global("a").unwrap().repr(), "'yy'");
+    assert_eq!(vm.get_global("b"), Some(Value::Int(1)));
+    assert_eq!(vm.get_global("c"), Some(Value::Int(2)));
+    assert_eq!(vm.get_global("d").unwrap().repr(), "'x'");
+}
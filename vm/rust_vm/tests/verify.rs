@@ -0,0 +1,266 @@
+use aqua_vm::bytecode::{self, Bytecode, ExceptionTable, Instruction, OpCode};
+use aqua_vm::function::Function;
+use aqua_vm::{VMError, Value};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+mod common;
+use common::{load, load_bytecode};
+
+fn program(instructions: Vec<(OpCode, u32)>) -> Bytecode {
+    Bytecode {
+        constants: vec![Value::Int(1), Value::Int(2)],
+        global_vars: HashMap::from([("x".to_string(), 0)]),
+        instructions: instructions
+            .into_iter()
+            .map(|(op, arg)| Instruction::new(op, arg))
+            .collect(),
+        ..Bytecode::default()
+    }
+}
+
+/// 单个参数 `a` 的函数
+fn function(name: &str, instructions: Vec<(OpCode, u32)>) -> Function {
+    Function {
+        name: name.to_string(),
+        parameters: vec!["a".to_string()],
+        kwonly_count: 0,
+        varargs: false,
+        varkw: false,
+        defaults: Vec::new(),
+        kwdefaults: HashMap::new(),
+        generator: false,
+        coroutine: false,
+        instructions: instructions
+            .into_iter()
+            .map(|(op, arg)| Instruction::new(op, arg))
+            .collect(),
+        local_vars: HashMap::from([("a".to_string(), 0)]),
+        cell_vars: Vec::new(),
+        free_vars: Vec::new(),
+        handlers: ExceptionTable::default(),
+        module: 0,
+        id: 0,
+    }
+}
+
+fn reason(bytecode: &Bytecode) -> (String, usize, String) {
+    match bytecode::verify(bytecode) {
+        Err(VMError::Verify {
+            function,
+            pc,
+            reason,
+        }) => (function, pc, reason),
+        other => panic!("expected verify error, got {:?}", other),
+    }
+}
+
+#[test]
+fn accepts_well_formed_program() {
+    let bytecode = program(vec![
+        (OpCode::LoadConst, 0),
+        (OpCode::LoadConst, 1),
+        (OpCode::Lt, 0),
+        (OpCode::JumpIfFalse, 6),
+        (OpCode::LoadConst, 0),
+        (OpCode::StoreGlobal, 0),
+        (OpCode::Halt, 0),
+    ]);
+    bytecode::verify(&bytecode).unwrap();
+}
+
+#[test]
+fn rejects_bad_indices_and_targets() {
+    let (_, pc, why) = reason(&program(vec![(OpCode::LoadConst, 7)]));
+    assert_eq!(pc, 0);
+    assert!(why.contains("constant index 7"), "{}", why);
+
+    let (_, _, why) = reason(&program(vec![
+        (OpCode::LoadConst, 0),
+        (OpCode::StoreGlobal, 3),
+    ]));
+    assert!(why.contains("global index 3"), "{}", why);
+
+    let (_, _, why) = reason(&program(vec![(OpCode::LoadLocal, 0)]));
+    assert!(why.contains("outside a function"), "{}", why);
+
+    let (_, pc, why) = reason(&program(vec![(OpCode::Jump, 99)]));
+    assert_eq!(pc, 0);
+    assert!(why.contains("jump target 99"), "{}", why);
+}
+
+#[test]
+fn rejects_underflow_and_inconsistent_merges() {
+    let (_, pc, why) = reason(&program(vec![(OpCode::LoadConst, 0), (OpCode::Add, 0)]));
+    assert_eq!(pc, 1);
+    assert!(why.contains("underflow"), "{}", why);
+
+    // 一条路径多留下一个值，汇合到同一位置
+    let (_, pc, why) = reason(&program(vec![
+        (OpCode::LoadConst, 0),
+        (OpCode::LoadConst, 0),
+        (OpCode::JumpIfTrue, 4),
+        (OpCode::Pop, 0),
+        (OpCode::Halt, 0),
+    ]));
+    assert_eq!(pc, 4);
+    assert!(why.contains("inconsistent stack height"), "{}", why);
+}
+
+#[test]
+fn checks_function_bodies() {
+    let mut bytecode = program(vec![(OpCode::Halt, 0)]);
+    bytecode.functions.insert(
+        "f".to_string(),
+        function("f", vec![(OpCode::LoadLocal, 0), (OpCode::StoreLocal, 1)]),
+    );
+
+    let (function, pc, why) = reason(&bytecode);
+    assert_eq!(function, "f");
+    assert_eq!(pc, 1);
+    assert!(why.contains("local index 1"), "{}", why);
+
+    // 校验失败时 load_bytecode 直接拒绝程序
+    assert!(matches!(
+        load_bytecode(&bytecode),
+        Err(VMError::Verify { .. })
+    ));
+}
+
+#[test]
+fn builds_exception_tables_and_rejects_broken_try() {
+    // try { x = 1 } catch { pop } finally {}，进入语句时栈上留有一个值
+    let bytecode = program(vec![
+        (OpCode::LoadConst, 0),
+        (OpCode::TryBegin, 0),
+        (OpCode::LoadConst, 0),
+        (OpCode::StoreGlobal, 0),
+        (OpCode::TryEnd, 0),
+        (OpCode::CatchBegin, bytecode::CATCH_ALL),
+        (OpCode::Pop, 0),
+        (OpCode::CatchEnd, 0),
+        (OpCode::FinallyBegin, 0),
+        (OpCode::FinallyEnd, 0),
+        (OpCode::Pop, 0),
+    ]);
+    let analysis = bytecode::analyze(&bytecode).unwrap();
+    let [handler] = analysis.main.handlers() else {
+        panic!("expected one handler, got {:?}", analysis.main);
+    };
+    assert_eq!((handler.start, handler.try_end, handler.end), (1, 4, 10));
+    assert_eq!(handler.catches[0].start, 5);
+    assert_eq!(handler.catches[0].exception_type, None);
+    assert_eq!(handler.finally, Some(8));
+    assert_eq!(handler.depth, 1);
+    assert_eq!(analysis.main.innermost(0), None);
+    assert_eq!(analysis.main.innermost(7), Some(0));
+    assert_eq!(analysis.main.innermost(10), None);
+
+    let (_, pc, why) = reason(&program(vec![(OpCode::TryBegin, 0), (OpCode::TryEnd, 0)]));
+    assert_eq!(pc, 1);
+    assert!(why.contains("without catch or finally"), "{}", why);
+
+    let (_, pc, why) = reason(&program(vec![(OpCode::TryBegin, 0), (OpCode::Halt, 0)]));
+    assert_eq!(pc, 2);
+    assert!(why.contains("missing TRY_END"), "{}", why);
+
+    let (_, pc, why) = reason(&program(vec![(OpCode::CatchEnd, 0)]));
+    assert_eq!(pc, 0);
+    assert!(why.contains("unexpected CATCH_END"), "{}", why);
+
+    let (_, _, why) = reason(&program(vec![
+        (OpCode::TryBegin, 0),
+        (OpCode::TryEnd, 0),
+        (OpCode::CatchBegin, 1),
+        (OpCode::Pop, 0),
+        (OpCode::CatchEnd, 0),
+    ]));
+    assert!(why.contains("exception type is a int"), "{}", why);
+}
+
+#[test]
+fn rejects_halt_inside_functions() {
+    // __str__ 在 FORMAT_VALUE 中被嵌套调用，HALT 会清空外层的调用帧
+    let error = load(
+        r#"
+.func Box.__str__(self)
+    HALT
+.end
+    LOAD_CONST "__str__"
+    LOAD_FUNC Box.__str__
+    BUILD_DICT 1
+    CREATE_CLASS Box
+    CREATE_OBJECT 0
+    FORMAT_VALUE ">4"
+    POP
+"#,
+    )
+    .err()
+    .unwrap();
+    assert_eq!(
+        error.to_string(),
+        "Verification failed in Box.__str__ at pc 0: HALT outside the main program, use RETURN"
+    );
+}
+
+#[test]
+fn deeply_nested_try_statements_are_scanned_without_recursion() {
+    const DEPTH: usize = 200_000;
+    let mut instructions = vec![(OpCode::TryBegin, 0); DEPTH];
+    instructions.push((OpCode::LoadConst, 0));
+    instructions.push((OpCode::StoreGlobal, 0));
+    for _ in 0..DEPTH {
+        instructions.extend([
+            (OpCode::TryEnd, 0),
+            (OpCode::FinallyBegin, 0),
+            (OpCode::FinallyEnd, 0),
+        ]);
+    }
+    let bytecode = program(instructions);
+
+    let analysis = bytecode::analyze(&bytecode).unwrap();
+    let table = &analysis.main;
+    assert_eq!(table.handlers().len(), DEPTH);
+    assert_eq!(table.innermost(DEPTH), Some(DEPTH - 1));
+    assert_eq!(table.enclosing(DEPTH).count(), DEPTH);
+    assert_eq!(table[DEPTH - 1].parent, Some(DEPTH - 2));
+    assert_eq!(table[0].end, bytecode.instructions.len());
+
+    let mut vm = load_bytecode(&bytecode).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.get_global("x").unwrap().to_string(), "1");
+
+    // 缺少结束指令时报告最内层的语句
+    let mut unclosed = bytecode.clone();
+    unclosed.instructions.truncate(DEPTH + 2);
+    let (_, pc, why) = reason(&unclosed);
+    assert_eq!((pc, why.as_str()), (DEPTH + 2, "missing TRY_END"));
+}
+
+#[test]
+fn checks_nested_code_constants() {
+    // v2 文件中的代码对象常量越界访问常量池
+    let bad = function("inner", vec![(OpCode::LoadConst, 99), (OpCode::Return, 0)]);
+    let mut bytecode = program(vec![(OpCode::LoadFunc, 2), (OpCode::Pop, 0)]);
+    bytecode.constants.push(Value::Code(Rc::new(bad.clone())));
+    let decoded = Bytecode::from_acode_bytes(&bytecode.to_acode_bytes().unwrap()).unwrap();
+    let (function_name, pc, why) = reason(&decoded);
+    assert_eq!((function_name.as_str(), pc), ("inner", 0));
+    assert!(why.contains("constant index 99"), "{}", why);
+    assert!(matches!(
+        load_bytecode(&decoded),
+        Err(VMError::Verify { .. })
+    ));
+
+    // 默认值中的代码对象同样要校验，包括具名函数的默认值
+    let mut outer = function("outer", vec![(OpCode::LoadLocal, 0), (OpCode::Return, 0)]);
+    outer
+        .defaults
+        .push(Value::Tuple(vec![Value::Code(Rc::new(bad))].into()));
+    let mut bytecode = program(vec![(OpCode::Halt, 0)]);
+    bytecode.constants.push(Value::Code(Rc::new(outer.clone())));
+    assert_eq!(reason(&bytecode).0, "inner");
+    let mut bytecode = program(vec![(OpCode::Halt, 0)]);
+    bytecode.functions.insert("outer".to_string(), outer);
+    assert_eq!(reason(&bytecode).0, "inner");
+}